@@ -0,0 +1,299 @@
+//! Criterion benchmarks comparing the PtrHash oracle against simple baselines
+//!
+//! Baselines:
+//! - `FxHashMap<BlockHash, u32>`: the obvious "just use a hash map" approach
+//! - Sorted `Vec<(BlockHash, u32)>` + binary search: the obvious compact approach
+//!
+//! Uses `assets/prebip34.txt` when present, otherwise a synthetic dataset of the same size.
+//!
+//! Run with: cargo bench --features generate
+//! Add `--features generate,fmph` to also benchmark the FMPH backend.
+//! The `ptrhash_vec_remap` entries use a plain `Vec<u32>` remap table instead
+//! of the default Elias-Fano one.
+//! The `cache` group runs the same oracle with and without `with_cache` on a
+//! hot set of 16 hashes, where the cache helps, and on the spread-out batch,
+//! where nearly every lookup misses and the cache only costs.
+//! The `load/heights` entry times decoding the packed heights alone.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+#[cfg(feature = "fmph")]
+use height_oracle::phf::PhfBackend;
+use height_oracle::phf::RemapEncoding;
+use height_oracle::{BlockHash, BuildOptions, HeightOracle, PhfConfig, BIP34_ACTIVATION_HEIGHT};
+use rustc_hash::FxHashMap;
+
+/// Number of lookups per iteration in the batch benchmarks
+const BATCH_SIZE: usize = 1024;
+
+/// Load the real dataset if available, otherwise generate pseudo-random hashes
+fn load_dataset() -> (Vec<BlockHash>, Vec<u32>) {
+    if let Ok(content) = std::fs::read_to_string("assets/prebip34.txt") {
+        let mut hashes = Vec::new();
+        let mut heights = Vec::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line == "x" {
+                continue;
+            }
+            hashes.push(height_oracle::parse_block_hash(line).expect("Invalid hash in dataset"));
+            heights.push(line_number as u32);
+        }
+        return (hashes, heights);
+    }
+
+    // SplitMix64 so runs are reproducible without pulling in a RNG crate
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+
+    let n = BIP34_ACTIVATION_HEIGHT as usize;
+    let mut hashes = Vec::with_capacity(n);
+    for _ in 0..n {
+        let mut hash = [0u8; 32];
+        for chunk in hash.chunks_mut(8) {
+            chunk.copy_from_slice(&next().to_le_bytes());
+        }
+        hashes.push(BlockHash::from(hash));
+    }
+    (hashes, (0..n as u32).collect())
+}
+
+/// Build the oracle with a plain `Vec<u32>` PtrHash remap table
+fn build_vec_remap(hashes: &[BlockHash], heights: &[u32]) -> HeightOracle {
+    let options = BuildOptions::new().phf_config(PhfConfig::new().remap(RemapEncoding::Vec));
+    HeightOracle::from_entries_with_options(hashes, heights, &options).unwrap()
+}
+
+/// Build the oracle with the FMPH backend instead of PtrHash
+#[cfg(feature = "fmph")]
+fn build_fmph(hashes: &[BlockHash], heights: &[u32]) -> HeightOracle {
+    let options = BuildOptions::new().phf_config(PhfConfig::new().backend(PhfBackend::Fmph));
+    HeightOracle::from_entries_with_options(hashes, heights, &options).unwrap()
+}
+
+fn build_hashmap(hashes: &[BlockHash], heights: &[u32]) -> FxHashMap<BlockHash, u32> {
+    hashes
+        .iter()
+        .copied()
+        .zip(heights.iter().copied())
+        .collect()
+}
+
+fn build_sorted(hashes: &[BlockHash], heights: &[u32]) -> Vec<(BlockHash, u32)> {
+    let mut sorted: Vec<(BlockHash, u32)> = hashes
+        .iter()
+        .copied()
+        .zip(heights.iter().copied())
+        .collect();
+    sorted.sort_unstable_by_key(|(hash, _)| *hash);
+    sorted
+}
+
+fn sorted_lookup(sorted: &[(BlockHash, u32)], block_hash: &BlockHash) -> u32 {
+    match sorted.binary_search_by(|(hash, _)| hash.cmp(block_hash)) {
+        Ok(i) => sorted[i].1,
+        Err(_) => 0,
+    }
+}
+
+/// Every 223rd key, so lookups are spread over the whole key set
+fn query_batch(hashes: &[BlockHash]) -> Vec<BlockHash> {
+    (0..BATCH_SIZE)
+        .map(|i| hashes[(i * 223) % hashes.len()])
+        .collect()
+}
+
+fn bench_single_lookup(c: &mut Criterion) {
+    let (hashes, heights) = load_dataset();
+    let oracle = HeightOracle::from_entries(&hashes, &heights).unwrap();
+    let map = build_hashmap(&hashes, &heights);
+    let sorted = build_sorted(&hashes, &heights);
+    let probe = hashes[hashes.len() / 2];
+
+    let mut group = c.benchmark_group("single_lookup");
+    group.bench_function("ptrhash", |b| {
+        b.iter(|| oracle.get_height_unchecked(black_box(&probe)))
+    });
+    let vec_remap = build_vec_remap(&hashes, &heights);
+    group.bench_function("ptrhash_vec_remap", |b| {
+        b.iter(|| vec_remap.get_height_unchecked(black_box(&probe)))
+    });
+    #[cfg(feature = "fmph")]
+    {
+        let fmph = build_fmph(&hashes, &heights);
+        group.bench_function("fmph", |b| {
+            b.iter(|| fmph.get_height_unchecked(black_box(&probe)))
+        });
+    }
+    group.bench_function("fxhashmap", |b| b.iter(|| map.get(black_box(&probe))));
+    group.bench_function("binary_search", |b| {
+        b.iter(|| sorted_lookup(&sorted, black_box(&probe)))
+    });
+    group.finish();
+}
+
+fn bench_batch_throughput(c: &mut Criterion) {
+    let (hashes, heights) = load_dataset();
+    let oracle = HeightOracle::from_entries(&hashes, &heights).unwrap();
+    let map = build_hashmap(&hashes, &heights);
+    let sorted = build_sorted(&hashes, &heights);
+    let queries = query_batch(&hashes);
+
+    let mut group = c.benchmark_group("batch_throughput");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.bench_function("ptrhash", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|hash| oracle.get_height_unchecked(hash))
+                .fold(0u32, u32::wrapping_add)
+        })
+    });
+    let vec_remap = build_vec_remap(&hashes, &heights);
+    group.bench_function("ptrhash_vec_remap", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|hash| vec_remap.get_height_unchecked(hash))
+                .fold(0u32, u32::wrapping_add)
+        })
+    });
+    #[cfg(feature = "fmph")]
+    {
+        let fmph = build_fmph(&hashes, &heights);
+        group.bench_function("fmph", |b| {
+            b.iter(|| {
+                queries
+                    .iter()
+                    .map(|hash| fmph.get_height_unchecked(hash))
+                    .fold(0u32, u32::wrapping_add)
+            })
+        });
+    }
+    group.bench_function("fxhashmap", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|hash| map.get(hash).copied().unwrap_or(0))
+                .fold(0u32, u32::wrapping_add)
+        })
+    });
+    group.bench_function("binary_search", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|hash| sorted_lookup(&sorted, hash))
+                .fold(0u32, u32::wrapping_add)
+        })
+    });
+    group.finish();
+}
+
+/// Number of distinct hashes in the cache benchmark's hot set
+const HOT_SET_SIZE: usize = 16;
+
+fn bench_cache(c: &mut Criterion) {
+    let (hashes, heights) = load_dataset();
+    let uncached = HeightOracle::from_entries(&hashes, &heights).unwrap();
+    let cached = uncached.clone().with_cache(1024);
+    let spread = query_batch(&hashes);
+    let hot: Vec<BlockHash> = spread
+        .iter()
+        .take(HOT_SET_SIZE)
+        .copied()
+        .cycle()
+        .take(BATCH_SIZE)
+        .collect();
+
+    let mut group = c.benchmark_group("cache");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    for (workload, queries) in [("hot", &hot), ("spread", &spread)] {
+        for (name, oracle) in [("uncached", &uncached), ("cached_1024", &cached)] {
+            group.bench_function(format!("{workload}/{name}"), |b| {
+                b.iter(|| {
+                    queries
+                        .iter()
+                        .map(|hash| oracle.get_height_unchecked(hash))
+                        .fold(0u32, u32::wrapping_add)
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let (hashes, heights) = load_dataset();
+
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+    group.bench_function("ptrhash", |b| {
+        b.iter(|| HeightOracle::from_entries(black_box(&hashes), black_box(&heights)).unwrap())
+    });
+    #[cfg(feature = "fmph")]
+    group.bench_function("fmph", |b| {
+        b.iter(|| build_fmph(black_box(&hashes), black_box(&heights)))
+    });
+    group.bench_function("fxhashmap", |b| {
+        b.iter(|| build_hashmap(black_box(&hashes), black_box(&heights)))
+    });
+    group.bench_function("binary_search", |b| {
+        b.iter(|| build_sorted(black_box(&hashes), black_box(&heights)))
+    });
+    group.finish();
+}
+
+fn bench_load(c: &mut Criterion) {
+    let (hashes, heights) = load_dataset();
+    let oracle = HeightOracle::from_entries(&hashes, &heights).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let ptrhash_path = dir.path().join("phash.ptrh.dat");
+    let heights_path = dir.path().join("heights.u18packed.dat");
+    oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+
+    let mut group = c.benchmark_group("load");
+    group.sample_size(20);
+    group.bench_function("ptrhash", |b| {
+        b.iter(|| HeightOracle::load_from_paths(&ptrhash_path, &heights_path).unwrap())
+    });
+
+    let mut packed = Vec::new();
+    height_oracle::packing::serialize_heights(&heights, &mut packed).unwrap();
+    group.throughput(Throughput::Elements(heights.len() as u64));
+    group.bench_function("heights", |b| {
+        b.iter(|| {
+            height_oracle::packing::deserialize_heights(black_box(packed.as_slice())).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let (hashes, _) = load_dataset();
+    let hex: Vec<String> = query_batch(&hashes).iter().map(|h| h.to_string()).collect();
+    let hex: Vec<&str> = hex.iter().map(String::as_str).collect();
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.bench_function("bulk", |b| {
+        b.iter(|| height_oracle::parse_block_hashes_bulk(black_box(&hex)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_single_lookup,
+    bench_batch_throughput,
+    bench_cache,
+    bench_build,
+    bench_load,
+    bench_parse
+);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+/*
+ * C interface to the embedded height oracle (cargo feature "ffi").
+ *
+ * Link against the cdylib built with:
+ *   cargo build --release --features ffi
+ *
+ * Every function returns a HEIGHT_ORACLE_* status code and writes results
+ * through out pointers. Block hashes are 32 bytes in network byte order, or
+ * 64 reverse-hex characters. Lookups are unchecked: hashes outside the
+ * pre-BIP34 domain return the height of some other block.
+ */
+#ifndef HEIGHT_ORACLE_H
+#define HEIGHT_ORACLE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HEIGHT_ORACLE_OK 0
+#define HEIGHT_ORACLE_NULL_POINTER 1
+#define HEIGHT_ORACLE_INVALID_HASH 2
+#define HEIGHT_ORACLE_UNSUPPORTED_NETWORK 3
+#define HEIGHT_ORACLE_LOAD_FAILED 4
+
+#define HEIGHT_ORACLE_MAINNET 0
+#define HEIGHT_ORACLE_TESTNET3 1
+
+typedef struct HeightOracleHandle HeightOracleHandle;
+
+/* Load the embedded oracle for a network into a new handle. */
+int32_t height_oracle_open(uint32_t network, HeightOracleHandle **out);
+
+/* Free a handle; NULL is ignored. */
+void height_oracle_free(HeightOracleHandle *handle);
+
+/* Number of blocks in the oracle, 0 for NULL. */
+uint64_t height_oracle_len(const HeightOracleHandle *handle);
+
+/* Height of one 32-byte block hash. */
+int32_t height_oracle_get_height(const HeightOracleHandle *handle,
+                                 const uint8_t *hash,
+                                 uint32_t *out);
+
+/* Height of one NUL-terminated reverse-hex block hash. */
+int32_t height_oracle_get_height_hex(const HeightOracleHandle *handle,
+                                     const char *hash_hex,
+                                     uint32_t *out);
+
+/* Heights of count hashes stored back to back (32 * count bytes). */
+int32_t height_oracle_get_heights(const HeightOracleHandle *handle,
+                                  const uint8_t *hashes,
+                                  size_t count,
+                                  uint32_t *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* HEIGHT_ORACLE_H */
@@ -0,0 +1,189 @@
+// .NET bindings for the embedded height oracle over its C interface
+// (cargo feature "ffi", declared in bindings/c/height_oracle.h).
+//
+// Build the native library with `cargo build --release --features ffi` and
+// ship libheight_oracle.so / height_oracle.dll / libheight_oracle.dylib next
+// to the application. Block hashes are 32 bytes in network byte order, the
+// layout of NBitcoin's uint256.ToBytes().
+
+using System;
+using System.Runtime.InteropServices;
+
+namespace HeightOracle
+{
+    /// <summary>Networks with embedded oracle assets.</summary>
+    public enum OracleNetwork : uint
+    {
+        Mainnet = 0,
+        Testnet3 = 1,
+    }
+
+    /// <summary>A native call failed; <see cref="Status"/> is the HEIGHT_ORACLE_* code.</summary>
+    public sealed class HeightOracleException : Exception
+    {
+        public int Status { get; }
+
+        internal HeightOracleException(int status)
+            : base(Describe(status))
+        {
+            Status = status;
+        }
+
+        private static string Describe(int status) => status switch
+        {
+            NativeMethods.NullPointer => "A required argument was null",
+            NativeMethods.InvalidHash => "Block hash must be 64 hex characters",
+            NativeMethods.UnsupportedNetwork => "No embedded oracle for this network in the native library",
+            NativeMethods.LoadFailed => "The embedded oracle assets failed to load",
+            _ => $"Height oracle call failed with status {status}",
+        };
+    }
+
+    /// <summary>Owns a native oracle handle and frees it exactly once.</summary>
+    internal sealed class OracleSafeHandle : SafeHandle
+    {
+        public OracleSafeHandle()
+            : base(IntPtr.Zero, ownsHandle: true)
+        {
+        }
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            NativeMethods.height_oracle_free(handle);
+            return true;
+        }
+    }
+
+    internal static class NativeMethods
+    {
+        private const string Library = "height_oracle";
+
+        public const int Ok = 0;
+        public const int NullPointer = 1;
+        public const int InvalidHash = 2;
+        public const int UnsupportedNetwork = 3;
+        public const int LoadFailed = 4;
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern int height_oracle_open(uint network, out OracleSafeHandle handle);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern void height_oracle_free(IntPtr handle);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern ulong height_oracle_len(OracleSafeHandle handle);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern unsafe int height_oracle_get_height(
+            OracleSafeHandle handle, byte* hash, out uint height);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern int height_oracle_get_height_hex(
+            OracleSafeHandle handle,
+            [MarshalAs(UnmanagedType.LPUTF8Str)] string hashHex,
+            out uint height);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        public static extern unsafe int height_oracle_get_heights(
+            OracleSafeHandle handle, byte* hashes, UIntPtr count, uint* heights);
+    }
+
+    /// <summary>
+    /// Block hash to height lookups for pre-BIP34 blocks.
+    /// </summary>
+    /// <remarks>
+    /// Lookups are unchecked: hashes outside the pre-BIP34 domain return the
+    /// height of some other block. Instances are thread-safe; dispose them to
+    /// free the native tables.
+    /// </remarks>
+    public sealed class Oracle : IDisposable
+    {
+        private const int HashSize = 32;
+
+        private readonly OracleSafeHandle _handle;
+
+        private Oracle(OracleSafeHandle handle)
+        {
+            _handle = handle;
+        }
+
+        /// <summary>Load the embedded oracle for <paramref name="network"/>.</summary>
+        public static Oracle Open(OracleNetwork network = OracleNetwork.Mainnet)
+        {
+            Check(NativeMethods.height_oracle_open((uint)network, out var handle));
+            return new Oracle(handle);
+        }
+
+        /// <summary>Number of blocks in the oracle.</summary>
+        public long Count => (long)NativeMethods.height_oracle_len(_handle);
+
+        /// <summary>Height of a 32-byte block hash in network byte order.</summary>
+        public unsafe uint GetHeight(ReadOnlySpan<byte> hash)
+        {
+            if (hash.Length != HashSize)
+            {
+                throw new ArgumentException("Block hash must be 32 bytes", nameof(hash));
+            }
+            uint height;
+            fixed (byte* hashPtr = hash)
+            {
+                Check(NativeMethods.height_oracle_get_height(_handle, hashPtr, out height));
+            }
+            return height;
+        }
+
+        /// <summary>Height of a block hash in reverse hex, as block explorers show it.</summary>
+        public uint GetHeight(string hashHex)
+        {
+            if (hashHex is null)
+            {
+                throw new ArgumentNullException(nameof(hashHex));
+            }
+            Check(NativeMethods.height_oracle_get_height_hex(_handle, hashHex, out var height));
+            return height;
+        }
+
+        /// <summary>
+        /// Heights of hashes stored back to back (32 bytes each), written to
+        /// <paramref name="heights"/>, which needs one slot per hash.
+        /// </summary>
+        public unsafe void GetHeights(ReadOnlySpan<byte> hashes, Span<uint> heights)
+        {
+            if (hashes.Length % HashSize != 0)
+            {
+                throw new ArgumentException("Hashes must be 32 bytes each", nameof(hashes));
+            }
+            int count = hashes.Length / HashSize;
+            if (heights.Length < count)
+            {
+                throw new ArgumentException($"Need room for {count} heights", nameof(heights));
+            }
+            fixed (byte* hashesPtr = hashes)
+            fixed (uint* heightsPtr = heights)
+            {
+                Check(NativeMethods.height_oracle_get_heights(
+                    _handle, hashesPtr, (UIntPtr)count, heightsPtr));
+            }
+        }
+
+        /// <summary>Heights of hashes stored back to back (32 bytes each).</summary>
+        public uint[] GetHeights(ReadOnlySpan<byte> hashes)
+        {
+            var heights = new uint[hashes.Length / HashSize];
+            GetHeights(hashes, heights);
+            return heights;
+        }
+
+        public void Dispose() => _handle.Dispose();
+
+        private static void Check(int status)
+        {
+            if (status != NativeMethods.Ok)
+            {
+                throw new HeightOracleException(status);
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Looks up block heights given as reverse-hex arguments.
+//
+//	go run ./example 000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f
+package main
+
+import (
+	"encoding/hex"
+	"fmt"
+	"log"
+	"os"
+
+	heightoracle "github.com/bayernatoor/height-oracle/bindings/go"
+)
+
+// parseHash decodes a block hash as explorers show it into network byte order.
+func parseHash(s string) ([heightoracle.HashSize]byte, error) {
+	var hash [heightoracle.HashSize]byte
+	decoded, err := hex.DecodeString(s)
+	if err != nil {
+		return hash, err
+	}
+	if len(decoded) != heightoracle.HashSize {
+		return hash, fmt.Errorf("block hash must be 64 hex characters: %s", s)
+	}
+	for i, b := range decoded {
+		hash[heightoracle.HashSize-1-i] = b
+	}
+	return hash, nil
+}
+
+func main() {
+	if err := heightoracle.Init(); err != nil {
+		log.Fatal(err)
+	}
+
+	hashes := make([][heightoracle.HashSize]byte, 0, len(os.Args)-1)
+	for _, arg := range os.Args[1:] {
+		hash, err := parseHash(arg)
+		if err != nil {
+			log.Fatal(err)
+		}
+		hashes = append(hashes, hash)
+	}
+
+	heights := make([]uint32, len(hashes))
+	if err := heightoracle.Heights(hashes, heights); err != nil {
+		log.Fatal(err)
+	}
+	for i, arg := range os.Args[1:] {
+		fmt.Printf("%s\t%d\n", arg, heights[i])
+	}
+}
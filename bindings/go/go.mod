@@ -0,0 +1,3 @@
+module github.com/bayernatoor/height-oracle/bindings/go
+
+go 1.21
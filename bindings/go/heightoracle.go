@@ -0,0 +1,75 @@
+// Package heightoracle links the embedded height oracle statically through
+// cgo, for block hash to height lookups of pre-BIP34 mainnet blocks.
+//
+// Build the static library first:
+//
+//	cargo build --release --manifest-path bindings/go/Cargo.toml
+//
+// Lookups are unchecked: hashes outside the pre-BIP34 domain return the
+// height of some other block.
+package heightoracle
+
+/*
+#cgo CFLAGS: -I${SRCDIR}
+#cgo LDFLAGS: -L${SRCDIR}/target/release -lheight_oracle_go
+#cgo linux LDFLAGS: -lm -ldl -lpthread
+#cgo darwin LDFLAGS: -framework CoreFoundation
+#include "heightoracle.h"
+*/
+import "C"
+
+import (
+	"errors"
+	"fmt"
+	"unsafe"
+)
+
+// HashSize is the length of a block hash in bytes.
+const HashSize = 32
+
+// ErrNotInitialized is returned by lookups made before a successful Init.
+var ErrNotInitialized = errors.New("heightoracle: Init has not succeeded")
+
+func statusError(status C.int32_t) error {
+	switch status {
+	case C.HEIGHT_ORACLE_OK:
+		return nil
+	case C.HEIGHT_ORACLE_NOT_INITIALIZED:
+		return ErrNotInitialized
+	case C.HEIGHT_ORACLE_LOAD_FAILED:
+		return errors.New("heightoracle: embedded assets failed to load")
+	default:
+		return fmt.Errorf("heightoracle: call failed with status %d", int32(status))
+	}
+}
+
+// Init loads the embedded oracle. Call it once at startup; later calls are
+// no-ops.
+func Init() error {
+	return statusError(C.height_oracle_init())
+}
+
+// Height returns the height of a block hash in network byte order, the
+// layout of btcd's chainhash.Hash.
+func Height(hash *[HashSize]byte) (uint32, error) {
+	var height C.uint32_t
+	status := C.height_oracle_lookup((*C.uint8_t)(unsafe.Pointer(&hash[0])), &height)
+	return uint32(height), statusError(status)
+}
+
+// Heights looks up hashes in one batched call, writing one height per hash
+// to heights, which must be at least as long as hashes.
+func Heights(hashes [][HashSize]byte, heights []uint32) error {
+	if len(heights) < len(hashes) {
+		return fmt.Errorf("heightoracle: need room for %d heights, have %d", len(hashes), len(heights))
+	}
+	if len(hashes) == 0 {
+		return nil
+	}
+	status := C.height_oracle_lookup_batch(
+		(*C.uint8_t)(unsafe.Pointer(&hashes[0][0])),
+		C.size_t(len(hashes)),
+		(*C.uint32_t)(unsafe.Pointer(&heights[0])),
+	)
+	return statusError(status)
+}
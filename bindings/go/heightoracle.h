@@ -0,0 +1,41 @@
+/*
+ * Static library C ABI over the embedded height oracle (bindings/go).
+ *
+ * Build libheight_oracle_go.a with:
+ *   cargo build --release --manifest-path bindings/go/Cargo.toml
+ *
+ * Call height_oracle_init once; lookups after that never allocate. Block
+ * hashes are 32 bytes in network byte order. Lookups are unchecked: hashes
+ * outside the pre-BIP34 domain return the height of some other block.
+ */
+#ifndef HEIGHTORACLE_H
+#define HEIGHTORACLE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HEIGHT_ORACLE_OK 0
+#define HEIGHT_ORACLE_NULL_POINTER 1
+#define HEIGHT_ORACLE_LOAD_FAILED 4
+#define HEIGHT_ORACLE_NOT_INITIALIZED 5
+
+/* Load the embedded mainnet oracle; later calls are no-ops. */
+int32_t height_oracle_init(void);
+
+/* Height of one 32-byte block hash. */
+int32_t height_oracle_lookup(const uint8_t *hash, uint32_t *out);
+
+/* Heights of count hashes stored back to back (32 * count bytes). */
+int32_t height_oracle_lookup_batch(const uint8_t *hashes,
+                                   size_t count,
+                                   uint32_t *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* HEIGHTORACLE_H */
@@ -0,0 +1,87 @@
+//! Static library C ABI for Go (cgo) and other static linkers
+//!
+//! Three functions over the global embedded mainnet oracle, declared in
+//! `heightoracle.h`. [`height_oracle_init`] loads the embedded assets once;
+//! after that, lookups only read the loaded tables and never allocate.
+//! Block hashes are 32 bytes in network byte order (btcd's `chainhash.Hash`
+//! layout). Lookups are unchecked: hashes outside the pre-BIP34 domain return
+//! the height of some other block.
+
+use height_oracle::{BlockHash, HeightLookup, HeightOracleEmbedded};
+use std::sync::OnceLock;
+
+/// Success
+pub const HEIGHT_ORACLE_OK: i32 = 0;
+/// A required pointer argument was null
+pub const HEIGHT_ORACLE_NULL_POINTER: i32 = 1;
+/// The embedded assets failed to load
+pub const HEIGHT_ORACLE_LOAD_FAILED: i32 = 4;
+/// A lookup was made before a successful [`height_oracle_init`]
+pub const HEIGHT_ORACLE_NOT_INITIALIZED: i32 = 5;
+
+static ORACLE: OnceLock<HeightOracleEmbedded> = OnceLock::new();
+
+/// Load the embedded oracle; later calls are no-ops
+///
+/// Safe to call from several threads at once.
+#[no_mangle]
+pub extern "C" fn height_oracle_init() -> i32 {
+    if ORACLE.get().is_some() {
+        return HEIGHT_ORACLE_OK;
+    }
+    match height_oracle::embedded::global() {
+        Ok(oracle) => {
+            let _ = ORACLE.set(oracle);
+            HEIGHT_ORACLE_OK
+        }
+        Err(_) => HEIGHT_ORACLE_LOAD_FAILED,
+    }
+}
+
+/// Look up the height of a 32-byte block hash in network byte order (unchecked)
+///
+/// # Safety
+///
+/// `hash` must be null or valid for reading 32 bytes, and `out` null or valid
+/// for writing a `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_lookup(hash: *const u8, out: *mut u32) -> i32 {
+    if hash.is_null() || out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    let Some(oracle) = ORACLE.get() else {
+        return HEIGHT_ORACLE_NOT_INITIALIZED;
+    };
+    let block_hash = BlockHash::from_byte_array(*hash.cast::<[u8; 32]>());
+    *out = oracle.get_height_unchecked(block_hash);
+    HEIGHT_ORACLE_OK
+}
+
+/// Look up `count` block hashes stored back to back (32 bytes each, network
+/// byte order), writing `count` heights to `out` (unchecked)
+///
+/// # Safety
+///
+/// `hashes` must be null or valid for reading `32 * count` bytes, and `out`
+/// null or valid for writing `count` `u32`s.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_lookup_batch(
+    hashes: *const u8,
+    count: usize,
+    out: *mut u32,
+) -> i32 {
+    let Some(oracle) = ORACLE.get() else {
+        return HEIGHT_ORACLE_NOT_INITIALIZED;
+    };
+    if count == 0 {
+        return HEIGHT_ORACLE_OK;
+    }
+    if hashes.is_null() || out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    // BlockHash is a transparent wrapper around [u8; 32], alignment 1
+    let block_hashes = std::slice::from_raw_parts(hashes.cast::<BlockHash>(), count);
+    let heights = std::slice::from_raw_parts_mut(out, count);
+    oracle.get_heights_unchecked(block_hashes, heights);
+    HEIGHT_ORACLE_OK
+}
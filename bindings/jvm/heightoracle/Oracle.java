@@ -0,0 +1,30 @@
+// JVM bindings for the embedded height oracle (cargo feature "jni").
+//
+// Build the native library with `cargo build --release --features jni` and
+// put libheight_oracle.so / height_oracle.dll / libheight_oracle.dylib on
+// java.library.path. Block hashes are 32 bytes in the order bitcoinj's
+// Sha256Hash.getBytes() returns; the native side reverses them into network
+// byte order.
+package heightoracle;
+
+/**
+ * Block hash to height lookups for pre-BIP34 mainnet blocks.
+ *
+ * <p>Lookups are unchecked: hashes outside the pre-BIP34 domain return the
+ * height of some other block. The oracle is loaded on the first call and
+ * shared by all threads; a load failure throws {@link IllegalStateException}.
+ */
+public final class Oracle {
+    static {
+        System.loadLibrary("height_oracle");
+    }
+
+    private Oracle() {
+    }
+
+    /** Height of one 32-byte block hash. */
+    public static native int guessHeight(byte[] hash);
+
+    /** Heights of hashes stored back to back (32 bytes each), one per hash. */
+    public static native int[] guessHeights(byte[] hashes);
+}
@@ -0,0 +1,210 @@
+//! Build script
+//!
+//! - Always: picks the directory embedded assets are read from, `ORACLE_ASSET_DIR`
+//!   if set and the crate's own `assets/` otherwise, and passes it to the crate
+//!   as `HEIGHT_ORACLE_ASSET_DIR` for `include_bytes!`, and turns each asset an
+//!   enabled embedded feature needs but the directory lacks into a
+//!   `compile_error!` that names the command generating it.
+//! - Feature grpc: generates the gRPC service stubs from a Rust description of
+//!   `proto/height_oracle.proto`, so that building does not require `protoc`.
+//!   `GetVerifiedHeight` is only generated with feature verify as well.
+//! - Feature embedded-zstd: compresses the assets into the container embedded by
+//!   `HeightOracleEmbedded::load_embedded`.
+
+/// Environment variable overriding the directory embedded assets are read from
+const ASSET_DIR_VAR: &str = "ORACLE_ASSET_DIR";
+
+/// Embedded assets that are not shipped in `assets/`: the feature compiling
+/// each one in, its path under the asset directory and how to generate it
+const GENERATED_ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "embedded-testnet3",
+        "testnet3/phash.ptrh.dat",
+        TESTNET3_COMMAND,
+    ),
+    (
+        "embedded-testnet3",
+        "testnet3/heights.u18packed.dat",
+        TESTNET3_COMMAND,
+    ),
+    (
+        "embedded-timestamps",
+        "timestamps.bitpacked.dat",
+        TIMESTAMPS_COMMAND,
+    ),
+    (
+        "embedded-chronology",
+        "chronology.days.dat",
+        TIMESTAMPS_COMMAND,
+    ),
+    ("embedded-filter", "prebip34.xor8.dat", FILTER_COMMAND),
+    (
+        "embedded-chainwork",
+        "chainwork.runs.dat",
+        CHAINWORK_COMMAND,
+    ),
+    ("embedded-headers", "headers.raw80.dat", HEADERS_COMMAND),
+    (
+        "embedded-difficulty",
+        "difficulty.bits.dat",
+        TIMESTAMPS_COMMAND,
+    ),
+];
+
+const TESTNET3_COMMAND: &str = "fetch from a testnet3 node with \
+    `cargo run --example fetch_prebip34 --features fetch -- --network testnet3`, \
+    run `cargo run --features generate --release` and move phash.ptrh.dat and \
+    heights.u18packed.dat into assets/testnet3/";
+
+const HEADERS_COMMAND: &str = "fetch raw headers with \
+    `cargo run --example fetch_prebip34 --features fetch -- --headers-output assets/headers.bin` \
+    and run `cargo run --features generate --release -- --headers-from assets/headers.bin`";
+
+const CHAINWORK_COMMAND: &str = "run \
+    `cargo run --example fetch_prebip34 --features fetch -- --chainwork-output assets/chainwork.runs.dat`";
+
+const FILTER_COMMAND: &str = "run `cargo run --features generate --release -- --filter`";
+
+const TIMESTAMPS_COMMAND: &str = "fetch raw headers with \
+    `cargo run --example fetch_prebip34 --features fetch -- --headers-output assets/headers.bin` \
+    and run `cargo run --features generate --release -- --timestamps-from assets/headers.bin`";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let asset_dir = asset_dir();
+    println!(
+        "cargo:rustc-env=HEIGHT_ORACLE_ASSET_DIR={}",
+        asset_dir.display()
+    );
+
+    check_generated_assets(&asset_dir);
+
+    #[cfg(feature = "grpc")]
+    generate_grpc_service();
+
+    #[cfg(feature = "embedded-zstd")]
+    compress_embedded_assets(&asset_dir);
+}
+
+/// `ORACLE_ASSET_DIR` if set, otherwise the bundled `assets/` directory
+fn asset_dir() -> std::path::PathBuf {
+    println!("cargo:rerun-if-env-changed={ASSET_DIR_VAR}");
+    match std::env::var_os(ASSET_DIR_VAR) {
+        Some(dir) => {
+            let dir = std::path::PathBuf::from(dir);
+            // Relative paths would resolve against this crate, not the consumer
+            assert!(
+                dir.is_absolute(),
+                "{ASSET_DIR_VAR} must be an absolute path, got {} \
+                 (in .cargo/config.toml, set it under [env] with relative = true)",
+                dir.display()
+            );
+            assert!(
+                dir.is_dir(),
+                "{ASSET_DIR_VAR} is not a directory: {}",
+                dir.display()
+            );
+            dir
+        }
+        None => std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("assets"),
+    }
+}
+
+/// Write `OUT_DIR/missing_assets.rs`, with a `compile_error!` for each of the
+/// [`GENERATED_ASSETS`] of an enabled feature that `asset_dir` lacks
+///
+/// Without it, a missing asset fails the build inside `include_bytes!` with no
+/// hint of where the file comes from.
+fn check_generated_assets(asset_dir: &std::path::Path) {
+    let mut errors = String::new();
+    for &(feature, name, command) in GENERATED_ASSETS {
+        let feature_var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+        if std::env::var_os(feature_var).is_none() {
+            continue;
+        }
+        let path = asset_dir.join(name);
+        println!("cargo:rerun-if-changed={}", path.display());
+        if !path.is_file() {
+            let message = format!(
+                "feature `{feature}` embeds {}, which does not exist; to generate it, {command}",
+                path.display()
+            );
+            errors.push_str(&format!("compile_error!({message:?});\n"));
+        }
+    }
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("missing_assets.rs"), errors)
+        .expect("Failed to write missing asset checks");
+}
+
+/// Write `OUT_DIR/oracle.hoc.zst` (same layout as `height_oracle::container`)
+#[cfg(feature = "embedded-zstd")]
+fn compress_embedded_assets(asset_dir: &std::path::Path) {
+    const CONTAINER_MAGIC: &[u8; 4] = b"HORC";
+    const ZSTD_LEVEL: i32 = 19;
+
+    let read = |name: &str| {
+        let path = asset_dir.join(name);
+        println!("cargo:rerun-if-changed={}", path.display());
+        std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()))
+    };
+    let ptrhash_data = read("phash.ptrh.dat");
+    let heights_data = read("heights.u18packed.dat");
+
+    let mut container = Vec::new();
+    container.extend_from_slice(CONTAINER_MAGIC);
+    container.extend_from_slice(&(ptrhash_data.len() as u64).to_le_bytes());
+    container.extend_from_slice(&ptrhash_data);
+    container.extend_from_slice(&heights_data);
+
+    let compressed =
+        zstd::bulk::compress(&container, ZSTD_LEVEL).expect("Failed to compress assets");
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("oracle.hoc.zst"), compressed)
+        .expect("Failed to write compressed container");
+}
+
+#[cfg(feature = "grpc")]
+fn generate_grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    println!("cargo:rerun-if-changed=proto/height_oracle.proto");
+
+    let builder = Service::builder()
+        .name("Oracle")
+        .package("height_oracle.v1")
+        .method(
+            Method::builder()
+                .name("get_height")
+                .route_name("GetHeight")
+                .input_type("crate::grpc::GetHeightRequest")
+                .output_type("crate::grpc::GetHeightResponse")
+                .codec_path("tonic_prost::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("get_heights")
+                .route_name("GetHeights")
+                .input_type("crate::grpc::GetHeightRequest")
+                .output_type("crate::grpc::GetHeightResponse")
+                .codec_path("tonic_prost::ProstCodec")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        );
+    #[cfg(feature = "verify")]
+    let builder = builder.method(
+        Method::builder()
+            .name("get_verified_height")
+            .route_name("GetVerifiedHeight")
+            .input_type("crate::grpc::GetHeightRequest")
+            .output_type("crate::grpc::GetVerifiedHeightResponse")
+            .codec_path("tonic_prost::ProstCodec")
+            .build(),
+    );
+    let service = builder.build();
+
+    Builder::new().compile(&[service]);
+}
@@ -0,0 +1,66 @@
+//! `--verify`: cross-check looked-up heights with bitcoind
+//!
+//! The RPC handling is the library's `verify::RpcCrossChecker`, driven on a
+//! current-thread runtime. It and its HTTP stack are only built with the
+//! optional `verify` feature, so the default binary stays small.
+
+use height_oracle::BlockHash;
+
+/// Default bitcoind RPC endpoint
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8332";
+
+#[cfg(feature = "verify")]
+pub struct Verifier {
+    checker: height_oracle::verify::RpcCrossChecker,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// Stands in for the verifier when it is compiled out; never constructed
+#[cfg(not(feature = "verify"))]
+pub enum Verifier {}
+
+impl Verifier {
+    /// Verifier for `http://[user:pass@]host[:port][/path]`
+    ///
+    /// Credentials from a bitcoind cookie file take precedence over ones in the URL.
+    #[cfg(feature = "verify")]
+    pub fn new(url: &str, cookie_path: Option<&str>) -> Result<Self, String> {
+        use height_oracle::fetch::rpc::{read_cookie, RpcConfig};
+
+        let mut config = RpcConfig::from_url(url).map_err(|e| format!("{e:#}"))?;
+        if let Some(path) = cookie_path {
+            (config.user, config.pass) = read_cookie(path).map_err(|e| format!("{e:#}"))?;
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to start RPC runtime: {e}"))?;
+        Ok(Self {
+            checker: height_oracle::verify::RpcCrossChecker::from_config(&config),
+            runtime,
+        })
+    }
+
+    #[cfg(not(feature = "verify"))]
+    pub fn new(_url: &str, _cookie_path: Option<&str>) -> Result<Self, String> {
+        Err("--verify is not available: rebuild delphi with `--features verify`".to_string())
+    }
+
+    /// Whether the node has each block at its looked-up height
+    #[cfg(feature = "verify")]
+    pub fn confirm(&self, lookups: &[(BlockHash, u32)]) -> Result<Vec<bool>, String> {
+        let confirmations = self
+            .runtime
+            .block_on(self.checker.confirm_batch(lookups))
+            .map_err(|e| format!("{e:#}"))?;
+        Ok(confirmations
+            .iter()
+            .map(|confirmation| confirmation.is_confirmed())
+            .collect())
+    }
+
+    #[cfg(not(feature = "verify"))]
+    pub fn confirm(&self, _lookups: &[(BlockHash, u32)]) -> Result<Vec<bool>, String> {
+        match *self {}
+    }
+}
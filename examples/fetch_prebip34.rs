@@ -1,58 +1,45 @@
-use std::collections::HashMap;
 use std::env;
-use std::fs::{create_dir_all, File};
-use std::io::Write;
 use std::path::PathBuf;
 
-use height_oracle::BIP34_ACTIVATION_HEIGHT;
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize)]
-struct JsonRpcRequest<'a, T> {
-    jsonrpc: &'a str,
-    #[serde(rename = "id")]
-    _id: String,
-    method: &'a str,
-    params: T,
-}
-
-#[derive(Deserialize)]
-struct JsonRpcResponse<T> {
-    result: Option<T>,
-    error: Option<JsonRpcError>,
-    #[serde(rename = "id")]
-    _id: serde_json::Value,
-}
-
-#[derive(Deserialize)]
-struct JsonRpcError {
-    code: i64,
-    message: String,
-}
+use height_oracle::fetch::ordered::PartialDataset;
+use height_oracle::fetch::{
+    self, esplora::EsploraClient, p2p::P2pClient, rest::RestClient, rpc::RpcClient, Backend,
+    FetchCheckpoint, FetchConfig, FetchFailure, ThrottleOptions,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Defaults
-    let mut rpc_url = String::from("http://127.0.0.1:8332");
+    let mut backend_name = String::from("rpc");
+    let mut url: Option<String> = None;
     let mut rpc_user: Option<String> = env::var("BTC_RPC_USER").ok();
     let mut rpc_pass: Option<String> = env::var("BTC_RPC_PASS").ok();
     let mut cookie_path: Option<PathBuf> = None;
-    let mut concurrency: usize = 32;
-
-    let default_end: u32 = BIP34_ACTIVATION_HEIGHT - 1; // inclusive end height
-    let mut start_height: u32 = 0;
-    let mut end_height: u32 = default_end;
-
-    let mut output_path = PathBuf::from("assets/prebip34.txt");
+    let mut rate_limit: Option<f64> = None;
+    let mut config = FetchConfig::default();
+    let mut end_height: Option<u32> = None;
+    let mut batch_size: Option<usize> = None;
+
+    let mut output_path: Option<PathBuf> = None;
+    let mut output_format = String::from("txt");
+    let mut headers_output: Option<PathBuf> = None;
+    let mut chainwork_output: Option<PathBuf> = None;
+    let mut filter_headers_output: Option<PathBuf> = None;
+    let mut keep_v2_hashes = false;
+    let mut resume = false;
 
     // Parse simple CLI flags
     let mut args = env::args().skip(1);
     while let Some(arg) = args.next() {
         match arg.as_str() {
-            "--rpc-url" => {
+            "--backend" => {
+                if let Some(v) = args.next() {
+                    backend_name = v;
+                }
+            }
+            "--url" | "--rpc-url" => {
                 if let Some(v) = args.next() {
-                    rpc_url = v;
+                    url = Some(v);
                 }
             }
             "--rpc-user" => {
@@ -70,223 +57,293 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     cookie_path = Some(PathBuf::from(v));
                 }
             }
+            "--rate-limit" => {
+                if let Some(v) = args.next() {
+                    rate_limit = v.parse().ok().or(rate_limit);
+                }
+            }
             "--concurrency" => {
                 if let Some(v) = args.next() {
-                    concurrency = v.parse().unwrap_or(concurrency);
+                    config.concurrency = v.parse().unwrap_or(config.concurrency);
                 }
             }
             "--start-height" => {
                 if let Some(v) = args.next() {
-                    start_height = v.parse().unwrap_or(start_height);
+                    config.start_height = v.parse().unwrap_or(config.start_height);
                 }
             }
             "--end-height" => {
                 if let Some(v) = args.next() {
-                    end_height = v.parse().unwrap_or(end_height);
+                    end_height = v.parse().ok().or(end_height);
+                }
+            }
+            "--network" => {
+                if let Some(v) = args.next() {
+                    config.network = match v.parse() {
+                        Ok(network) => network,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    };
                 }
             }
             "--output" => {
                 if let Some(v) = args.next() {
-                    output_path = PathBuf::from(v);
+                    output_path = Some(PathBuf::from(v));
+                }
+            }
+            "--output-format" => {
+                if let Some(v) = args.next() {
+                    output_format = v;
+                }
+            }
+            "--headers-output" => {
+                if let Some(v) = args.next() {
+                    headers_output = Some(PathBuf::from(v));
                 }
             }
+            "--chainwork-output" => {
+                if let Some(v) = args.next() {
+                    chainwork_output = Some(PathBuf::from(v));
+                }
+            }
+            "--filter-headers-output" => {
+                if let Some(v) = args.next() {
+                    filter_headers_output = Some(PathBuf::from(v));
+                }
+            }
+            "--batch-size" => {
+                if let Some(v) = args.next() {
+                    batch_size = v.parse().ok().or(batch_size);
+                }
+            }
+            "--keep-v2-hashes" => keep_v2_hashes = true,
+            "--resume" => resume = true,
             _ => {}
         }
     }
 
-    if end_height < start_height {
+    // Default to the whole pre-BIP34 range of the chosen network
+    config.end_height = end_height.unwrap_or_else(|| config.network.bip34_activation_height() - 1);
+    if config.end_height < config.start_height {
         eprintln!("end-height must be >= start-height");
         std::process::exit(1);
     }
-
-    // If user/pass not provided, try cookie at default path
-    if rpc_user.is_none() || rpc_pass.is_none() {
-        let default_cookie = dirs::home_dir().map(|h| h.join(".bitcoin/.cookie"));
-        let cookie_file = cookie_path.or(default_cookie);
-        if let Some(path) = cookie_file {
-            if path.exists() {
-                let content = std::fs::read_to_string(&path)?;
-                // cookie content is "user:password"
-                if let Some((u, p)) = content.trim().split_once(':') {
-                    rpc_user = Some(u.to_string());
-                    rpc_pass = Some(p.to_string());
-                }
-            }
-        }
+    if filter_headers_output.is_some() && backend_name != "rpc" {
+        eprintln!("--filter-headers-output needs the rpc backend (getblockfilter)");
+        std::process::exit(1);
     }
 
-    let (rpc_user, rpc_pass) = match (rpc_user, rpc_pass) {
-        (Some(u), Some(p)) => (u, p),
-        _ => {
-            eprintln!("Missing RPC credentials. Provide --rpc-user/--rpc-pass, set BTC_RPC_USER/BTC_RPC_PASS, or ensure ~/.bitcoin/.cookie exists.");
+    let binary_output = match output_format.as_str() {
+        "txt" => false,
+        "bin" => true,
+        other => {
+            eprintln!(
+                "Unknown output format '{}'. Expected 'txt' or 'bin'.",
+                other
+            );
             std::process::exit(1);
         }
     };
-
-    // HTTP client
-    let client = reqwest::Client::builder().build()?;
-
-    let total: u64 = (end_height as u64) - (start_height as u64) + 1;
-    println!(
-        "Fetching pre-BIP34 block hashes: heights {}..={} ({} blocks) with concurrency={}",
-        start_height, end_height, total, concurrency
-    );
-    println!("RPC URL: {}", rpc_url);
-
-    // Prepare heights list
-    let heights: Vec<u32> = (start_height..=end_height).collect();
-
-    use futures::{stream, StreamExt};
-
-    #[derive(Clone)]
-    struct Auth {
-        user: String,
-        pass: String,
-    }
-    let auth = Auth {
-        user: rpc_user,
-        pass: rpc_pass,
+    let output_path =
+        output_path.unwrap_or_else(|| PathBuf::from(format!("assets/prebip34.{}", output_format)));
+
+    // bitcoind backends: adapt to work queue rejections below this ceiling
+    let throttle = ThrottleOptions {
+        max_in_flight: config.concurrency,
+        requests_per_second: rate_limit.unwrap_or(0.0),
+        ..Default::default()
     };
 
-    // Concurrently fetch hashes
-    let results = stream::iter(heights.clone())
-        .map(|h| {
-            let client = client.clone();
-            let url = rpc_url.clone();
-            let auth = auth.clone();
-            async move {
-                // Build JSON-RPC request
-                let req_body = JsonRpcRequest {
-                    jsonrpc: "1.0",
-                    _id: format!("getblockhash-{}", h),
-                    method: "getblockhash",
-                    params: vec![serde_json::Value::from(h as u64)],
-                };
-
-                // Send request with basic auth
-                let resp = client
-                    .post(&url)
-                    .basic_auth(&auth.user, Some(&auth.pass))
-                    .json(&req_body)
-                    .send()
-                    .await;
-
-                let resp = match resp {
-                    Ok(r) => r,
-                    Err(e) => return Err((h, format!("request error: {}", e))),
-                };
-
-                let status = resp.status();
-                let text = resp.text().await.map_err(|e| (h, e.to_string()))?;
-                if !status.is_success() {
-                    return Err((h, format!("HTTP {}: {}", status, text)));
-                }
-
-                let parsed: JsonRpcResponse<String> = serde_json::from_str(&text)
-                    .map_err(|e| (h, format!("decode error: {} - body: {}", e, text)))?;
-
-                if let Some(err) = parsed.error {
-                    return Err((h, format!("RPC error {}: {}", err.code, err.message)));
-                }
-                let hash = parsed
-                    .result
-                    .ok_or_else(|| (h, String::from("missing result")))?;
-
-                // Fetch block header to inspect version. If version == 2, write a placeholder 'x'
-                let header_req = JsonRpcRequest {
-                    jsonrpc: "1.0",
-                    _id: format!("getblockheader-{}", h),
-                    method: "getblockheader",
-                    params: vec![
-                        serde_json::Value::from(hash.clone()),
-                        serde_json::Value::from(true),
-                    ],
-                };
-
-                let resp2 = client
-                    .post(&url)
-                    .basic_auth(&auth.user, Some(&auth.pass))
-                    .json(&header_req)
-                    .send()
-                    .await;
-
-                let resp2 = match resp2 {
-                    Ok(r) => r,
-                    Err(e) => return Err((h, format!("header request error: {}", e))),
-                };
-
-                let status2 = resp2.status();
-                let text2 = resp2.text().await.map_err(|e| (h, e.to_string()))?;
-                if !status2.is_success() {
-                    return Err((h, format!("HTTP {}: {}", status2, text2)));
+    let backend = match backend_name.as_str() {
+        "rpc" => {
+            // If user/pass not provided, try cookie at default path
+            if rpc_user.is_none() || rpc_pass.is_none() {
+                let default_cookie = dirs::home_dir().map(|h| h.join(".bitcoin/.cookie"));
+                if let Some(path) = cookie_path.or(default_cookie) {
+                    if path.exists() {
+                        let (u, p) = fetch::rpc::read_cookie(&path)?;
+                        rpc_user = Some(u);
+                        rpc_pass = Some(p);
+                    }
                 }
+            }
 
-                let parsed2: JsonRpcResponse<serde_json::Value> = serde_json::from_str(&text2)
-                    .map_err(|e| (h, format!("decode header error: {} - body: {}", e, text2)))?;
-
-                if let Some(err) = parsed2.error {
-                    return Err((h, format!("RPC header error {}: {}", err.code, err.message)));
+            let (rpc_user, rpc_pass) = match (rpc_user, rpc_pass) {
+                (Some(u), Some(p)) => (u, p),
+                _ => {
+                    eprintln!("Missing RPC credentials. Provide --rpc-user/--rpc-pass, set BTC_RPC_USER/BTC_RPC_PASS, or ensure ~/.bitcoin/.cookie exists.");
+                    std::process::exit(1);
                 }
+            };
 
-                let use_x = parsed2
-                    .result
-                    .as_ref()
-                    .and_then(|v| v.get("version"))
-                    .and_then(|ver| ver.as_i64())
-                    .map(|ver| ver == 2)
-                    .unwrap_or(false);
+            config.batch_size = batch_size.unwrap_or(config.batch_size);
+            let url = url.unwrap_or_else(|| fetch::rpc::DEFAULT_RPC_URL.to_string());
+            println!("RPC URL: {}", url);
+            Backend::Rpc(RpcClient::new(url, rpc_user, rpc_pass).with_throttle(throttle))
+        }
+        "rest" => {
+            // Headers come in ranges, so batch as much as one request allows by default
+            config.batch_size = batch_size.unwrap_or(fetch::rest::MAX_BATCH_SIZE);
+            let url = url.unwrap_or_else(|| fetch::rest::DEFAULT_REST_URL.to_string());
+            println!("REST URL: {}", url);
+            Backend::Rest(RestClient::new(url).with_throttle(throttle))
+        }
+        "esplora" => {
+            let url = url.unwrap_or_else(|| fetch::esplora::DEFAULT_ESPLORA_URL.to_string());
+            let rate_limit = rate_limit.unwrap_or(10.0);
+            println!("Esplora URL: {} (max {} requests/s)", url, rate_limit);
+            Backend::Esplora(EsploraClient::new(url, rate_limit))
+        }
+        "p2p" => {
+            let addr = url.unwrap_or_else(|| fetch::p2p::DEFAULT_P2P_ADDR.to_string());
+            println!("P2P peer: {}", addr);
+            Backend::P2p(P2pClient::new(addr))
+        }
+        other => {
+            eprintln!(
+                "Unknown backend '{}'. Expected 'rpc', 'rest', 'esplora' or 'p2p'.",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
 
-                if use_x {
-                    Ok::<(u32, String), (u32, String)>((h, "x".to_string()))
-                } else {
-                    Ok::<(u32, String), (u32, String)>((h, hash))
-                }
-            }
-        })
-        .buffer_unordered(concurrency)
-        .collect::<Vec<_>>()
-        .await;
+    println!(
+        "Fetching pre-BIP34 {} block hashes: heights {}..={} ({} blocks) with concurrency={}",
+        config.network,
+        config.start_height,
+        config.end_height,
+        config.total(),
+        config.concurrency
+    );
 
-    // Partition successes and failures
-    let mut by_height: HashMap<u32, String> = HashMap::with_capacity(results.len());
-    let mut failures: Vec<(u32, String)> = Vec::new();
-    for r in results {
-        match r {
-            Ok((h, hash)) => {
-                by_height.insert(h, hash);
-            }
-            Err((h, err)) => {
-                failures.push((h, err));
+    // A plain text dataset is written in height order as blocks arrive, so
+    // memory stays flat and an interrupted run leaves a prefix to resume from
+    if !binary_output
+        && headers_output.is_none()
+        && chainwork_output.is_none()
+        && filter_headers_output.is_none()
+    {
+        let mut dataset = if resume {
+            PartialDataset::resume(&output_path, keep_v2_hashes)?
+        } else {
+            PartialDataset::create(&output_path, keep_v2_hashes)?
+        };
+        if dataset.lines() > 0 {
+            println!(
+                "Resuming after {} lines already written to {}.partial",
+                dataset.lines(),
+                output_path.display()
+            );
+        }
+        config.start_height += dataset.lines() as u32;
+
+        if config.start_height <= config.end_height {
+            let failures =
+                fetch::fetch_blocks_ordered(&backend, &config, |block| dataset.write(block))
+                    .await?;
+            dataset.flush()?;
+            if !failures.is_empty() {
+                report_failures(&backend, &failures);
+                eprintln!(
+                    "The {} lines before the first failure were saved to {}.partial; add --resume to continue from there.",
+                    dataset.lines(),
+                    output_path.display()
+                );
+                std::process::exit(1);
             }
         }
+
+        let lines = dataset.lines();
+        dataset.finish()?;
+        println!("Wrote {} records to {}", lines, output_path.display());
+        println!("Done.");
+        return Ok(());
     }
 
-    if !failures.is_empty() {
+    // Other outputs need every block at the end; progress is checkpointed next
+    // to the output so a failed run can be resumed
+    let state_path = PathBuf::from(format!("{}.fetch-state", output_path.display()));
+    let mut checkpoint = if resume {
+        let checkpoint = FetchCheckpoint::resume(&state_path)?;
+        let ranges = checkpoint.completed_ranges();
+        println!(
+            "Resuming from {}: {} blocks already fetched in {} range(s)",
+            state_path.display(),
+            checkpoint.completed().len(),
+            ranges.len()
+        );
+        checkpoint
+    } else {
+        FetchCheckpoint::create(&state_path)?
+    };
+
+    let outcome = fetch::fetch_blocks_resumable(&backend, &config, &mut checkpoint).await?;
+
+    if !outcome.failures.is_empty() {
+        report_failures(&backend, &outcome.failures);
         eprintln!(
-            "Failed to fetch {} heights (showing up to 10):",
-            failures.len()
+            "Progress was saved to {}; add --resume to continue without refetching.",
+            checkpoint.path().display()
         );
-        for (i, (h, err)) in failures.iter().take(10).enumerate() {
-            eprintln!("  {}. height {}: {}", i + 1, h, err);
-        }
-        eprintln!("You can re-run with a lower --concurrency or check your node's rpcworkqueue/rpcthreads settings.");
         std::process::exit(1);
     }
 
-    // Ensure assets directory exists
-    if let Some(parent) = output_path.parent() {
-        create_dir_all(parent)?;
-    }
-
     // Write in order
-    let mut file = File::create(&output_path)?;
-    for h in start_height..=end_height {
-        let hash = by_height.get(&h).expect("missing height in map");
-        writeln!(file, "{}", hash)?;
+    if binary_output {
+        fetch::write_dataset_bin(&outcome.blocks, &output_path, keep_v2_hashes)?;
+    } else {
+        fetch::write_dataset(&outcome.blocks, &output_path, keep_v2_hashes)?;
     }
 
-    println!("Wrote {} lines to {}", total, output_path.display());
+    println!(
+        "Wrote {} records to {}",
+        outcome.blocks.len(),
+        output_path.display()
+    );
+
+    if let Some(headers_output) = headers_output {
+        fetch::write_headers(&outcome.blocks, &headers_output)?;
+        println!(
+            "Wrote {} headers to {}",
+            outcome.blocks.len(),
+            headers_output.display()
+        );
+    }
+    if let Some(chainwork_output) = chainwork_output {
+        fetch::write_chainwork(&outcome.blocks, &chainwork_output)?;
+        println!("Wrote chainwork table to {}", chainwork_output.display());
+    }
+    if let (Some(filter_headers_output), Backend::Rpc(client)) = (filter_headers_output, &backend) {
+        let filter_headers = fetch::fetch_filter_headers(client, &outcome.blocks).await?;
+        fetch::write_filter_headers(&filter_headers, &filter_headers_output)?;
+        println!(
+            "Wrote {} filter headers to {}",
+            filter_headers.len(),
+            filter_headers_output.display()
+        );
+    }
+    checkpoint.remove()?;
     println!("Done.");
 
     Ok(())
 }
+
+/// Print the first failures and a backend-specific hint
+fn report_failures(backend: &Backend, failures: &[FetchFailure]) {
+    eprintln!(
+        "Failed to fetch {} heights (showing up to 10):",
+        failures.len()
+    );
+    for (i, failure) in failures.iter().take(10).enumerate() {
+        eprintln!("  {}. height {}: {}", i + 1, failure.height, failure.error);
+    }
+    match backend {
+        Backend::Rpc(_) => eprintln!("Work queue rejections are already retried; you can re-run with a lower --concurrency or --rate-limit, or check your node's rpcworkqueue/rpcthreads settings."),
+        Backend::Rest(_) => eprintln!("You can re-run with a lower --concurrency, --batch-size or --rate-limit, and check that bitcoind runs with -rest=1."),
+        Backend::Esplora(_) => eprintln!("You can re-run with a lower --concurrency or --rate-limit."),
+        Backend::P2p(_) => eprintln!("You can re-run with --resume, or pick another peer with --url host:port."),
+    }
+}
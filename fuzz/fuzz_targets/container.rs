@@ -0,0 +1,15 @@
+//! Single-file containers from arbitrary bytes, compressed or not
+
+#![no_main]
+
+use height_oracle::container;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(data) = container::decompress_if_needed(data) else {
+        return;
+    };
+    if let Ok((ptrhash, heights)) = container::decode(&data) {
+        assert_eq!(container::encode(ptrhash, heights), *data);
+    }
+});
@@ -0,0 +1,35 @@
+//! Packed heights and version flags from arbitrary bytes must error, not panic
+//! or allocate for a length the input cannot back
+
+#![no_main]
+
+use height_oracle::packing::{
+    deserialize_heights, deserialize_heights_with_mode, serialize_heights, split_trailer,
+    TrailerMode, VersionFlags, MAX_HEIGHT,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(heights) = deserialize_heights(data) {
+        assert!(heights.len() <= data.len().saturating_sub(5) / 9 * 4);
+        assert!(heights.iter().all(|&height| height <= MAX_HEIGHT));
+
+        // Whatever was read round-trips
+        let mut buffer = Vec::new();
+        serialize_heights(&heights, &mut buffer).unwrap();
+        assert_eq!(deserialize_heights(buffer.as_slice()).unwrap(), heights);
+    }
+
+    // Lenient reading agrees with strict reading of the part split_trailer cuts off
+    let mut reader = data;
+    if let Ok(heights) = deserialize_heights_with_mode(&mut reader, TrailerMode::Lenient) {
+        let (head, trailer) = split_trailer(data).unwrap();
+        assert_eq!(trailer, reader);
+        assert_eq!(deserialize_heights(head).unwrap(), heights);
+    }
+
+    if let Ok(flags) = VersionFlags::deserialize(data) {
+        assert!(flags.len().div_ceil(8) <= data.len().saturating_sub(4));
+        let _ = flags.count_v2();
+    }
+});
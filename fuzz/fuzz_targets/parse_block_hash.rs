@@ -0,0 +1,16 @@
+//! Block hash parsing must reject bad input cleanly and round-trip good input
+
+#![no_main]
+
+use height_oracle::parse_block_hash;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(hash) = parse_block_hash(input) {
+        let hex = input.strip_prefix("0x").unwrap_or(input);
+        assert!(hash.to_string().eq_ignore_ascii_case(hex));
+    }
+});
@@ -0,0 +1,132 @@
+//! Known pre-BIP34 mainnet oddities
+//!
+//! Before BIP34 put the height in the coinbase, two coinbase transactions were
+//! mined twice with identical txids: block 91,842 repeats the coinbase of
+//! 91,812 and block 91,880 repeats that of 91,722. The later outputs overwrote
+//! the earlier ones, so each txid refers to two blocks and only one spendable
+//! output. Anyone mapping coinbase txids to heights through this oracle should
+//! expect two heights for these txids. BIP30 exempts the two later blocks
+//! (see [`crate::consensus::BIP30_EXCEPTION_HEIGHTS`]).
+//!
+//! Hashes are in display (reversed hex) order, like [`crate::Network::genesis_hash`].
+
+use crate::BlockHash;
+
+/// A coinbase transaction mined in two blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateCoinbase {
+    /// Txid shared by both coinbases
+    pub txid: &'static str,
+    /// Height of the block that first had the coinbase
+    pub original_height: u32,
+    /// Hash of that block
+    pub original_block_hash: &'static str,
+    /// Height of the block that repeated it (a BIP30 exception)
+    pub duplicate_height: u32,
+    /// Hash of that block
+    pub duplicate_block_hash: &'static str,
+}
+
+impl DuplicateCoinbase {
+    /// Both heights whose coinbase has [`Self::txid`], earlier first
+    pub const fn heights(&self) -> [u32; 2] {
+        [self.original_height, self.duplicate_height]
+    }
+
+    /// Both (height, block hash) pairs, earlier first
+    pub const fn blocks(&self) -> [(u32, &'static str); 2] {
+        [
+            (self.original_height, self.original_block_hash),
+            (self.duplicate_height, self.duplicate_block_hash),
+        ]
+    }
+}
+
+/// The mainnet duplicate coinbases, in order of the duplicate height
+pub const DUPLICATE_COINBASES: [DuplicateCoinbase; 2] = [
+    DuplicateCoinbase {
+        txid: "d5d27987d2a3dfc724e359870c6644b40e497bdc0589a033220fe15429d88599",
+        original_height: 91_812,
+        original_block_hash: "00000000000af0aed4792b1acee3d966af36cf5def14935db8de83d6f9306f2f",
+        duplicate_height: 91_842,
+        duplicate_block_hash: "00000000000a4d0a398161ffc163c503763b1f4360639393e0e4c8e300e0caec",
+    },
+    DuplicateCoinbase {
+        txid: "e3bf3d07d4b0375638d5f1db5255fe07ba2c4cb067cd81b84ee974b6585fb468",
+        original_height: 91_722,
+        original_block_hash: "00000000000271a2dc26e7667f8419f2e15416dc6955e5a6c6cdf3f2574dd08e",
+        duplicate_height: 91_880,
+        duplicate_block_hash: "00000000000743f190a18c5577a3c2d2a1f610ae9601ac046a38084ccb7cd721",
+    },
+];
+
+/// The duplicate coinbase whose original or duplicate block is at `height`
+pub fn duplicate_coinbase_at(height: u32) -> Option<&'static DuplicateCoinbase> {
+    DUPLICATE_COINBASES
+        .iter()
+        .find(|duplicate| duplicate.heights().contains(&height))
+}
+
+/// The duplicate coinbase of the block with hash `block_hash`
+pub fn duplicate_coinbase_of(block_hash: &BlockHash) -> Option<&'static DuplicateCoinbase> {
+    let hex = block_hash.to_string();
+    DUPLICATE_COINBASES.iter().find(|duplicate| {
+        duplicate
+            .blocks()
+            .iter()
+            .any(|(_, hash)| hash.eq_ignore_ascii_case(&hex))
+    })
+}
+
+/// Both heights of a coinbase `txid` (display hex) that was mined twice
+///
+/// `None` for every other txid, which belongs to a single block.
+pub fn heights_for_coinbase_txid(txid: &str) -> Option<[u32; 2]> {
+    DUPLICATE_COINBASES
+        .iter()
+        .find(|duplicate| duplicate.txid.eq_ignore_ascii_case(txid))
+        .map(DuplicateCoinbase::heights)
+}
+
+/// Whether the coinbase of the block at `height` also appears at another height
+pub fn has_duplicate_coinbase(height: u32) -> bool {
+    duplicate_coinbase_at(height).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_coinbases() {
+        let duplicates: Vec<u32> = DUPLICATE_COINBASES
+            .iter()
+            .map(|duplicate| duplicate.duplicate_height)
+            .collect();
+        assert_eq!(duplicates, crate::consensus::BIP30_EXCEPTION_HEIGHTS);
+        for duplicate in &DUPLICATE_COINBASES {
+            assert!(duplicate.original_height < duplicate.duplicate_height);
+            assert!(crate::consensus::is_prebip34(duplicate.duplicate_height));
+            for (_, hash) in duplicate.blocks() {
+                assert!(crate::parse_block_hash(hash).is_ok());
+            }
+        }
+
+        assert_eq!(duplicate_coinbase_at(91_812), duplicate_coinbase_at(91_842));
+        assert!(has_duplicate_coinbase(91_722) && !has_duplicate_coinbase(91_723));
+        assert_eq!(
+            heights_for_coinbase_txid(
+                "E3BF3D07D4B0375638D5F1DB5255FE07BA2C4CB067CD81B84EE974B6585FB468"
+            ),
+            Some([91_722, 91_880])
+        );
+        assert_eq!(heights_for_coinbase_txid(&"00".repeat(32)), None);
+
+        let block_hash: BlockHash = DUPLICATE_COINBASES[0].duplicate_block_hash.parse().unwrap();
+        assert_eq!(
+            duplicate_coinbase_of(&block_hash),
+            Some(&DUPLICATE_COINBASES[0])
+        );
+        assert_eq!(duplicate_coinbase_of(&BlockHash::from([0; 32])), None);
+    }
+}
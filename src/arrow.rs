@@ -0,0 +1,121 @@
+//! Arrow integration (Feature: arrow)
+//!
+//! Enriches block datasets in DataFusion or Polars pipelines a whole column
+//! at a time: a `FixedSizeBinary(32)` column of block hashes in network byte
+//! order goes in, a `UInt32` column of heights comes out. The hash column's
+//! values buffer is read in place by the batched lookup, see
+//! [`crate::HeightLookup::get_heights_columnar`].
+//!
+//! Null hashes give null heights. [`heights`] is unchecked like the other
+//! lookups; [`heights_checked`] also nulls the entries a
+//! [`BlockHeightProvider`] rejects.
+
+use crate::provider::BlockHeightProvider;
+use crate::{BlockHash, HeightLookup};
+use arrow_array::{Array, FixedSizeBinaryArray, UInt32Array};
+use arrow_schema::ArrowError;
+
+/// The hashes of `column`, including the bytes under null entries
+fn block_hashes(column: &FixedSizeBinaryArray) -> Result<&[BlockHash], ArrowError> {
+    if column.value_length() != 32 {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Block hashes must be FixedSizeBinary(32), not FixedSizeBinary({})",
+            column.value_length()
+        )));
+    }
+    Ok(BlockHash::slice_from_bytes(column.value_data())
+        .expect("FixedSizeBinary(32) values are whole hashes"))
+}
+
+/// Heights of a `FixedSizeBinary(32)` column of block hashes (unchecked)
+///
+/// Hashes outside the oracle's domain get the height of some other block.
+/// Fails only if the column is not `FixedSizeBinary(32)`.
+pub fn heights<O: HeightLookup + ?Sized>(
+    oracle: &O,
+    column: &FixedSizeBinaryArray,
+) -> Result<UInt32Array, ArrowError> {
+    let block_hashes = block_hashes(column)?;
+    let mut heights = vec![0; block_hashes.len()];
+    oracle.get_heights_unchecked(block_hashes, &mut heights);
+    Ok(UInt32Array::new(heights.into(), column.nulls().cloned()))
+}
+
+/// Heights of a `FixedSizeBinary(32)` column, null where `provider` rejects the hash
+///
+/// How many hashes outside the dataset are caught depends on the provider;
+/// wrap the oracle in a [`crate::FilteredProvider`] to catch most of them.
+pub fn heights_checked<P: BlockHeightProvider + ?Sized>(
+    provider: &P,
+    column: &FixedSizeBinaryArray,
+) -> Result<UInt32Array, ArrowError> {
+    let block_hashes = block_hashes(column)?;
+    let mut heights = vec![None; block_hashes.len()];
+    provider.block_heights(block_hashes, &mut heights);
+    if let Some(nulls) = column.nulls() {
+        for (height, valid) in heights.iter_mut().zip(nulls.iter()) {
+            if !valid {
+                *height = None;
+            }
+        }
+    }
+    Ok(UInt32Array::from(heights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Height is the first byte of the hash; rejects odd heights when checked
+    struct FirstByte;
+
+    impl HeightLookup for FirstByte {
+        fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+            block_hash.as_bytes()[0] as u32
+        }
+
+        fn len(&self) -> usize {
+            256
+        }
+    }
+
+    impl BlockHeightProvider for FirstByte {
+        fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+            Some(self.get_height_unchecked(block_hash)).filter(|height| height % 2 == 0)
+        }
+    }
+
+    #[test]
+    fn test_heights() {
+        let column = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+            [Some([1; 32]), None, Some([4; 32]), Some([7; 32])].into_iter(),
+            32,
+        )
+        .unwrap();
+
+        let unchecked = heights(&FirstByte, &column).unwrap();
+        assert_eq!(
+            unchecked.iter().collect::<Vec<_>>(),
+            [Some(1), None, Some(4), Some(7)]
+        );
+        let checked = heights_checked(&FirstByte, &column).unwrap();
+        assert_eq!(
+            checked.iter().collect::<Vec<_>>(),
+            [None, None, Some(4), None]
+        );
+
+        // A sliced column only looks up its own hashes
+        let sliced = column.slice(2, 2);
+        assert_eq!(
+            heights(&FirstByte, &sliced)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            [Some(4), Some(7)]
+        );
+
+        let short = FixedSizeBinaryArray::try_from_iter([[0u8; 20]].into_iter()).unwrap();
+        assert!(heights(&FirstByte, &short).is_err());
+        assert!(heights_checked(&FirstByte, &short).is_err());
+    }
+}
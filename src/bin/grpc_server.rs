@@ -0,0 +1,140 @@
+//! gRPC server for the embedded oracle (Feature: grpc)
+//!
+//! Usage: height-oracle-grpc [--listen <addr>] [--timeout-ms <ms>] [--metrics-listen <addr>]
+//!
+//! With `--metrics-listen`, Prometheus metrics are served over plain HTTP at `/metrics`.
+//!
+//! With the `verify` feature, `--verify-rpc-url <url> [--verify-rpc-cookie <path>]`
+//! enables `GetVerifiedHeight`, which confirms heights with that bitcoind node.
+
+#[cfg(feature = "verify")]
+use height_oracle::fetch::rpc::{read_cookie, RpcConfig};
+use height_oracle::grpc::OracleService;
+use height_oracle::metrics::Metrics;
+#[cfg(feature = "verify")]
+use height_oracle::verify::RpcCrossChecker;
+use height_oracle::HeightOracleEmbedded;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:50051";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut listen: SocketAddr = DEFAULT_LISTEN_ADDR.parse()?;
+    let mut timeout: Option<Duration> = None;
+    let mut metrics_listen: Option<SocketAddr> = None;
+    #[cfg(feature = "verify")]
+    let mut verify_rpc_url: Option<String> = None;
+    #[cfg(feature = "verify")]
+    let mut verify_rpc_cookie: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let value = args.next().ok_or("--listen requires an address")?;
+                listen = value.parse()?;
+            }
+            "--timeout-ms" => {
+                let value = args.next().ok_or("--timeout-ms requires a value")?;
+                timeout = Some(Duration::from_millis(value.parse()?));
+            }
+            "--metrics-listen" => {
+                let value = args.next().ok_or("--metrics-listen requires an address")?;
+                metrics_listen = Some(value.parse()?);
+            }
+            #[cfg(feature = "verify")]
+            "--verify-rpc-url" => {
+                verify_rpc_url = Some(args.next().ok_or("--verify-rpc-url requires a URL")?);
+            }
+            #[cfg(feature = "verify")]
+            "--verify-rpc-cookie" => {
+                verify_rpc_cookie = Some(args.next().ok_or("--verify-rpc-cookie requires a path")?);
+            }
+            other => return Err(format!("Unknown argument: {other}").into()),
+        }
+    }
+
+    let started = Instant::now();
+    let oracle = HeightOracleEmbedded::load_embedded();
+    let metrics = Arc::new(Metrics::new());
+    metrics.record_init(started.elapsed(), oracle.len());
+    println!("Loaded oracle with {} entries", oracle.len());
+
+    if let Some(addr) = metrics_listen {
+        let listener = TcpListener::bind(addr).await?;
+        println!("Serving metrics on http://{addr}/metrics");
+        tokio::spawn(serve_metrics(listener, Arc::clone(&metrics)));
+    }
+
+    // Clients' grpc-timeout deadlines are always honored; --timeout-ms caps them server-side
+    let mut server = tonic::transport::Server::builder();
+    if let Some(timeout) = timeout {
+        server = server.timeout(timeout);
+    }
+
+    #[allow(unused_mut)]
+    let mut service = OracleService::new(oracle).with_metrics(metrics);
+    #[cfg(feature = "verify")]
+    if let Some(url) = verify_rpc_url {
+        let mut config = RpcConfig::from_url(&url)?;
+        if let Some(cookie) = verify_rpc_cookie {
+            (config.user, config.pass) = read_cookie(cookie)?;
+        }
+        println!("Verifying heights against {}", config.url);
+        service = service.with_cross_checker(Arc::new(RpcCrossChecker::from_config(&config)));
+    }
+
+    println!("Serving height_oracle.v1.Oracle on {listen}");
+    server
+        .add_service(service.into_server())
+        .serve(listen)
+        .await?;
+
+    Ok(())
+}
+
+/// Pause after a failed accept before trying again
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Answer `GET /metrics` with the Prometheus text format and anything else with 404
+async fn serve_metrics(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                // Usually out of file descriptors; retrying at once would spin
+                eprintln!("metrics: accept failed: {e}");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            // Only the request line matters; one read is enough for scrapers' small requests
+            let mut request = [0u8; 1024];
+            let Ok(n) = stream.read(&mut request).await else {
+                return;
+            };
+            let request_line = request[..n].split(|&b| b == b'\r' || b == b'\n').next();
+
+            let response = match request_line {
+                Some(line) if line.starts_with(b"GET /metrics ") => {
+                    let body = metrics.render();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
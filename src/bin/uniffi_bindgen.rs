@@ -0,0 +1,5 @@
+//! Kotlin/Swift binding generator for the UniFFI exports (Feature: uniffi-bindgen)
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
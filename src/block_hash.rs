@@ -0,0 +1,334 @@
+//! Block hash newtype
+//!
+//! Wraps the 32 hash bytes in network byte order (the order they appear in block
+//! headers and P2P messages). The RPC/explorer representation is the reversed hex
+//! string, which is what `Display` and `FromStr` use.
+//!
+//! Mixing up the two byte orders is the easiest mistake to make with raw
+//! `[u8; 32]` values. [`NetworkOrderedHash`] names `BlockHash` by its byte order,
+//! and [`DisplayOrderedHash`] holds the bytes in the order they are displayed.
+//! Converting between them reverses the bytes. The lookups take `&BlockHash`
+//! only, so neither raw bytes nor a display-ordered hash reach them without an
+//! explicit conversion that states the byte order.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Nibble value of each ASCII byte; 0xff for non-hex bytes
+const HEX_NIBBLES: [u8; 256] = {
+    let mut table = [0xff; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    table
+};
+
+/// Decode 64 reverse-hex characters to network byte order
+///
+/// Table-driven and branch-free per byte; invalid characters are collected in
+/// one mask and checked once at the end. `None` if any character is not hex.
+pub(crate) const fn decode_reverse_hex(hex: &[u8; 64]) -> Option<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    let mut invalid = 0u8;
+    let mut i = 0;
+    while i < 32 {
+        let hi = HEX_NIBBLES[hex[2 * i] as usize];
+        let lo = HEX_NIBBLES[hex[2 * i + 1] as usize];
+        invalid |= hi | lo;
+        bytes[31 - i] = (hi << 4) | (lo & 0x0f);
+        i += 1;
+    }
+    if invalid & 0xf0 != 0 {
+        return None;
+    }
+    Some(bytes)
+}
+
+/// Bitcoin block hash in network byte order
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct BlockHash([u8; 32]);
+
+impl BlockHash {
+    /// Wrap raw bytes that are already in network byte order
+    pub const fn from_byte_array(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Wrap bytes in network byte order, as in headers, P2P messages and
+    /// Bitcoin Core's LevelDB keys (same as [`Self::from_byte_array`])
+    pub const fn from_network_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Reverse bytes in display order, as decoded from RPC or explorer hex
+    pub const fn from_display_bytes(bytes: [u8; 32]) -> Self {
+        Self(reversed(bytes))
+    }
+
+    /// Raw bytes in network byte order
+    pub const fn to_byte_array(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Borrow the raw bytes in network byte order
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// View a slice of hashes as a slice of raw byte arrays (no copy)
+    pub fn as_byte_arrays(hashes: &[BlockHash]) -> &[[u8; 32]] {
+        // SAFETY: BlockHash is #[repr(transparent)] over [u8; 32]
+        unsafe { std::slice::from_raw_parts(hashes.as_ptr().cast(), hashes.len()) }
+    }
+
+    /// View concatenated 32-byte hashes as a slice of hashes (no copy)
+    ///
+    /// `None` if the length is not a multiple of 32.
+    pub fn slice_from_bytes(bytes: &[u8]) -> Option<&[BlockHash]> {
+        if !bytes.len().is_multiple_of(32) {
+            return None;
+        }
+        // SAFETY: BlockHash is #[repr(transparent)] over [u8; 32], so it has
+        // alignment 1 and any 32 bytes are a valid value
+        Some(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), bytes.len() / 32) })
+    }
+}
+
+/// [`BlockHash`] named by its byte order, the counterpart of [`DisplayOrderedHash`]
+pub type NetworkOrderedHash = BlockHash;
+
+/// Block hash bytes in display order (as shown by bitcoind RPC and block explorers)
+///
+/// The reverse of [`NetworkOrderedHash`]; convert with `From`/`Into`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct DisplayOrderedHash([u8; 32]);
+
+impl DisplayOrderedHash {
+    /// Wrap raw bytes that are already in display order
+    pub const fn from_byte_array(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw bytes in display order
+    pub const fn to_byte_array(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Borrow the raw bytes in display order
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+const fn reversed(mut bytes: [u8; 32]) -> [u8; 32] {
+    let mut i = 0;
+    while i < 16 {
+        let byte = bytes[i];
+        bytes[i] = bytes[31 - i];
+        bytes[31 - i] = byte;
+        i += 1;
+    }
+    bytes
+}
+
+impl From<DisplayOrderedHash> for NetworkOrderedHash {
+    fn from(hash: DisplayOrderedHash) -> Self {
+        Self(reversed(hash.0))
+    }
+}
+
+impl From<&DisplayOrderedHash> for NetworkOrderedHash {
+    fn from(hash: &DisplayOrderedHash) -> Self {
+        Self::from(*hash)
+    }
+}
+
+impl From<NetworkOrderedHash> for DisplayOrderedHash {
+    fn from(hash: NetworkOrderedHash) -> Self {
+        Self(reversed(hash.0))
+    }
+}
+
+impl From<&NetworkOrderedHash> for DisplayOrderedHash {
+    fn from(hash: &NetworkOrderedHash) -> Self {
+        Self::from(*hash)
+    }
+}
+
+impl fmt::Display for DisplayOrderedHash {
+    /// Hex in byte order, the same string as the [`BlockHash`] it converts to
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for DisplayOrderedHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DisplayOrderedHash({self})")
+    }
+}
+
+impl FromStr for DisplayOrderedHash {
+    type Err = String;
+
+    /// Parse the usual block hash hex (see [`crate::parse_block_hash`])
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_block_hash(s).map(Self::from)
+    }
+}
+
+impl From<[u8; 32]> for BlockHash {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Bytes in network byte order; fails unless the slice is exactly 32 bytes
+impl TryFrom<&[u8]> for BlockHash {
+    type Error = std::array::TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; 32]>::try_from(bytes).map(Self)
+    }
+}
+
+impl From<BlockHash> for [u8; 32] {
+    fn from(hash: BlockHash) -> Self {
+        hash.0
+    }
+}
+
+impl AsRef<[u8]> for BlockHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8; 32]> for BlockHash {
+    fn as_ref(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for BlockHash {
+    /// Reverse hex, as shown by bitcoind RPC and block explorers
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter().rev() {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BlockHash({self})")
+    }
+}
+
+impl FromStr for BlockHash {
+    type Err = String;
+
+    /// Parse reverse hex (see [`crate::parse_block_hash`])
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_block_hash(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BlockHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Reverse hex for human-readable formats, raw network-order bytes otherwise
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BlockHash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+            s.parse().map_err(serde::de::Error::custom)
+        } else {
+            <[u8; 32]>::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    #[test]
+    fn test_display_from_str_roundtrip() {
+        let hash: BlockHash = GENESIS.parse().unwrap();
+        assert_eq!(hash.to_string(), GENESIS);
+        assert_eq!(hash.as_bytes()[0], 0x6f);
+        assert_eq!(format!("{hash:?}"), format!("BlockHash({GENESIS})"));
+    }
+
+    #[test]
+    fn test_byte_array_conversions() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        let hash = BlockHash::from(bytes);
+        assert_eq!(<[u8; 32]>::from(hash), bytes);
+        assert_eq!(BlockHash::as_byte_arrays(&[hash]), &[bytes]);
+
+        assert_eq!(BlockHash::from_network_bytes(bytes), hash);
+        let display = BlockHash::from_display_bytes(bytes);
+        assert_eq!(display.as_bytes()[31], 1);
+        assert_eq!(DisplayOrderedHash::from(display).to_byte_array(), bytes);
+
+        assert_eq!(BlockHash::try_from(&bytes[..]).ok(), Some(hash));
+        assert!(BlockHash::try_from(&bytes[..31]).is_err());
+        assert!(BlockHash::try_from(&[0u8; 33][..]).is_err());
+
+        let column = [bytes, [2; 32]].concat();
+        assert_eq!(
+            BlockHash::slice_from_bytes(&column),
+            Some(&[hash, BlockHash::from([2; 32])][..])
+        );
+        assert_eq!(BlockHash::slice_from_bytes(&column[1..]), None);
+        assert_eq!(BlockHash::slice_from_bytes(&[]), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_display_ordered_hash() {
+        let network: NetworkOrderedHash = GENESIS.parse().unwrap();
+        let display = DisplayOrderedHash::from(network);
+        assert_eq!(display.to_string(), GENESIS);
+        assert_eq!(display.as_bytes()[31], 0x6f);
+        assert_eq!(display, GENESIS.parse().unwrap());
+        assert_eq!(NetworkOrderedHash::from(display), network);
+        assert_eq!(NetworkOrderedHash::from(&display), network);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_json() {
+        let hash: BlockHash = GENESIS.parse().unwrap();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{GENESIS}\""));
+        assert_eq!(serde_json::from_str::<BlockHash>(&json).unwrap(), hash);
+    }
+}
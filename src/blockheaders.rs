@@ -0,0 +1,281 @@
+//! Block header oracle
+//!
+//! Maps pre-BIP34 block hashes to their full 80-byte headers, so SPV tools
+//! replaying early chain history can materialize headers without a node. Like
+//! [`crate::timestamps`], the headers are a side table indexed by the height
+//! oracle's perfect hash, so a [`HeaderOracle`] reuses the PtrHash asset and
+//! only adds [`crate::HEADERS_FILE_NAME`] (about 18 MB on mainnet).
+//!
+//! Asset format: [num_entries: u32][num_entries * 80-byte raw headers]
+//!
+//! The headers are stored as-is, so the asset can be memory-mapped or
+//! compiled in and read in place instead of being copied at load time.
+
+use crate::format::{self, AssetKind};
+use crate::header::{BlockHeader, HEADER_SIZE};
+use crate::phf::Phf;
+use crate::BlockHash;
+use std::io::{self, Write};
+#[cfg(feature = "embedded-headers")]
+use std::sync::OnceLock;
+
+/// Length of the entry count before the headers
+const COUNT_LEN: usize = 4;
+
+// Embedded headers asset at compile time
+#[cfg(feature = "embedded-headers")]
+const HEADERS_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/headers.raw80.dat"
+));
+
+/// Serialize headers as a count followed by the raw headers
+pub fn serialize_headers<W: Write>(headers: &[[u8; HEADER_SIZE]], mut writer: W) -> io::Result<()> {
+    writer.write_all(&(headers.len() as u32).to_le_bytes())?;
+    for header in headers {
+        writer.write_all(header)?;
+    }
+    Ok(())
+}
+
+/// Check a serialized headers asset and return its entry count
+fn validate_headers(data: &[u8]) -> io::Result<usize> {
+    let count = data
+        .first_chunk::<COUNT_LEN>()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Headers asset is empty"))?;
+    let len = u32::from_le_bytes(*count) as usize;
+    let expected = (len as u64) * HEADER_SIZE as u64 + COUNT_LEN as u64;
+    if data.len() as u64 != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Headers asset is {} bytes but {len} headers need {expected}",
+                data.len()
+            ),
+        ));
+    }
+    Ok(len)
+}
+
+/// Where the serialized headers live
+enum HeaderBytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "embedded-headers")]
+    Static(&'static [u8]),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl std::ops::Deref for HeaderBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data,
+            #[cfg(feature = "embedded-headers")]
+            Self::Static(data) => data,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+/// Block hash → raw 80-byte header lookups for pre-BIP34 blocks
+pub struct HeaderOracle {
+    phash: Phf,
+    data: HeaderBytes,
+    len: usize,
+}
+
+impl HeaderOracle {
+    fn assemble(phash: Phf, data: HeaderBytes) -> io::Result<Self> {
+        let len = validate_headers(&data)?;
+        phash.check_table_len(len, "headers")?;
+        Ok(Self { phash, data, len })
+    }
+
+    /// Load from in-memory asset bytes (the height oracle's PtrHash asset + headers asset)
+    ///
+    /// Copies the headers; see [`Self::from_files_mmap`] to read them in place.
+    pub fn from_bytes(ptrhash_data: &[u8], headers_data: &[u8]) -> io::Result<Self> {
+        let phash = Phf::read(format::strip_header(ptrhash_data, AssetKind::PtrHash)?)?;
+        Self::assemble(phash, HeaderBytes::Owned(headers_data.to_vec()))
+    }
+
+    /// Load from asset files on disk
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_files<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        headers_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let phash = Phf::read(format::strip_header(&ptrhash_data, AssetKind::PtrHash)?)?;
+        Self::assemble(phash, HeaderBytes::Owned(std::fs::read(headers_path)?))
+    }
+
+    /// Load with the headers asset memory-mapped instead of read (Feature: mmap)
+    ///
+    /// Only the headers that lookups touch are paged in, and processes mapping
+    /// the same file share them.
+    #[cfg(feature = "mmap")]
+    pub fn from_files_mmap<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        headers_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let phash = Phf::read(format::strip_header(&ptrhash_data, AssetKind::PtrHash)?)?;
+        let file = std::fs::File::open(headers_path)?;
+        // SAFETY: assets are replaced by rename, never rewritten in place
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::assemble(phash, HeaderBytes::Mapped(map))
+    }
+
+    /// Load from the embedded PtrHash and headers assets (Feature: embedded-headers)
+    ///
+    /// The headers are read from the binary in place, not copied.
+    #[cfg(feature = "embedded-headers")]
+    pub fn try_load_embedded() -> io::Result<Self> {
+        Self::assemble(
+            crate::embedded::embedded_phf()?,
+            HeaderBytes::Static(HEADERS_DATA),
+        )
+    }
+
+    /// Raw header at perfect-hash `index`
+    fn raw_at(&self, index: usize) -> [u8; HEADER_SIZE] {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        let start = COUNT_LEN + index * HEADER_SIZE;
+        self.data[start..start + HEADER_SIZE]
+            .try_into()
+            .expect("slice is HEADER_SIZE bytes")
+    }
+
+    /// Look up the raw 80-byte header for a block hash (unchecked)
+    ///
+    /// Like the height lookups, hashes outside the dataset return the header of
+    /// some other block.
+    pub fn get_raw_header_unchecked(&self, block_hash: &BlockHash) -> [u8; HEADER_SIZE] {
+        self.raw_at(self.phash.index(block_hash.as_bytes()))
+    }
+
+    /// Look up and parse the header for a block hash (unchecked)
+    pub fn get_header_unchecked(&self, block_hash: &BlockHash) -> BlockHeader {
+        BlockHeader::parse(&self.get_raw_header_unchecked(block_hash))
+    }
+
+    /// Raw header for a block hash, or `None` if the hash is not in the dataset (Feature: sha256d)
+    ///
+    /// Unlike the height lookups this one can be checked: the stored header is
+    /// hashed and compared with `block_hash`.
+    #[cfg(feature = "sha256d")]
+    pub fn get_raw_header(&self, block_hash: &BlockHash) -> Option<[u8; HEADER_SIZE]> {
+        let raw = self.get_raw_header_unchecked(block_hash);
+        (crate::header::block_hash(&raw) == *block_hash).then_some(raw)
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Global singleton for the embedded header oracle
+#[cfg(feature = "embedded-headers")]
+static EMBEDDED_HEADERS: OnceLock<HeaderOracle> = OnceLock::new();
+
+/// Global raw header lookup for the embedded oracle (Feature: embedded-headers)
+#[cfg(feature = "embedded-headers")]
+pub fn guess_header_prebip34block_unchecked(block_hash: &BlockHash) -> [u8; HEADER_SIZE] {
+    EMBEDDED_HEADERS
+        .get_or_init(|| HeaderOracle::try_load_embedded().expect("Failed to load embedded headers"))
+        .get_raw_header_unchecked(block_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_asset() {
+        let headers = [[1; HEADER_SIZE], [2; HEADER_SIZE]];
+        let mut data = Vec::new();
+        serialize_headers(&headers, &mut data).unwrap();
+        assert_eq!(data.len(), COUNT_LEN + 2 * HEADER_SIZE);
+        assert_eq!(validate_headers(&data).unwrap(), 2);
+
+        assert!(validate_headers(&data[..data.len() - 1]).is_err());
+        assert!(validate_headers(&data[..2]).is_err());
+        data.push(0);
+        assert!(validate_headers(&data).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_header_oracle() {
+        // Headers differing only in the nonce, indexed by height
+        let headers: Vec<[u8; HEADER_SIZE]> = (0..5u8)
+            .map(|nonce| {
+                let mut raw = [0; HEADER_SIZE];
+                raw[76] = nonce;
+                raw
+            })
+            .collect();
+        let block_hashes: Vec<BlockHash> = headers.iter().map(crate::header::block_hash).collect();
+        let heights: Vec<u32> = (0..5).collect();
+        let oracle = crate::generate::test_support::test_oracle(&block_hashes, &heights);
+        let dataset = crate::Dataset {
+            block_hashes: block_hashes.clone(),
+            heights,
+            v2_flags: vec![false; 5],
+            placeholders: 0,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        let headers_path = dir.path().join(crate::HEADERS_FILE_NAME);
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        oracle
+            .save_headers(
+                &dataset,
+                &headers,
+                &headers_path,
+                &crate::SaveOptions::new(),
+            )
+            .unwrap();
+        let ptrhash_data = std::fs::read(&ptrhash_path).unwrap();
+        let headers_data = std::fs::read(&headers_path).unwrap();
+
+        let header_oracle = HeaderOracle::from_bytes(&ptrhash_data, &headers_data).unwrap();
+        assert_eq!(header_oracle.len(), 5);
+        for (block_hash, raw) in block_hashes.iter().zip(&headers) {
+            assert_eq!(header_oracle.get_raw_header_unchecked(block_hash), *raw);
+            assert_eq!(header_oracle.get_raw_header(block_hash), Some(*raw));
+            assert_eq!(
+                header_oracle.get_header_unchecked(block_hash),
+                BlockHeader::parse(raw)
+            );
+        }
+        assert_eq!(
+            header_oracle.get_raw_header(&BlockHash::from([9; 32])),
+            None
+        );
+
+        // Headers that do not hash to the dataset's hashes are refused
+        let mut swapped = headers.clone();
+        swapped.swap(0, 1);
+        assert!(oracle
+            .save_headers(
+                &dataset,
+                &swapped,
+                dir.path().join("x"),
+                &crate::SaveOptions::new()
+            )
+            .is_err());
+        assert!(HeaderOracle::from_bytes(&ptrhash_data, &headers_data[..84]).is_err());
+    }
+}
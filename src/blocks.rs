@@ -0,0 +1,338 @@
+//! Offline ingestion from Bitcoin Core block files (Feature: generate)
+//!
+//! Scans the `blk*.dat` files of a Bitcoin Core `blocks/` directory, links the
+//! headers into a tree rooted at genesis and returns the main-chain hashes below
+//! BIP34 activation, without an RPC connection. This allows building assets on
+//! an air-gapped machine from a copy of the block files.
+//!
+//! Each file is a sequence of `magic[4] | size[4] | block[size]` records, stored
+//! in arrival order rather than height order. Since Bitcoin Core 28 the files may
+//! be XOR-obfuscated with the 8-byte key in `xor.dat`.
+
+use crate::hashdump::{HashDump, HashRecord};
+use crate::header::{self, BlockHeader, HEADER_SIZE};
+use crate::{BlockHash, BIP34_ACTIVATION_HEIGHT};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Network magic of mainnet block file records
+pub const MAINNET_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+/// Blocks the best chain must extend past the last pre-BIP34 height before scanning stops
+///
+/// Stale forks are far shorter than this, so the best chain at that point
+/// already agrees with the final main chain on every pre-BIP34 height.
+pub const MIN_CONFIRMATIONS: u32 = 1_000;
+
+struct IndexedBlock {
+    prev_blockhash: BlockHash,
+    version: i32,
+    height: Option<u32>,
+}
+
+/// Block headers linked by their previous-block hash
+///
+/// Headers can be inserted in any order. A header gets a height once its
+/// parent has one, with genesis (all-zero previous hash) at height 0.
+#[derive(Default)]
+pub struct HeaderIndex {
+    blocks: HashMap<BlockHash, IndexedBlock>,
+    /// Children waiting for their parent to get a height, by parent hash
+    orphans: HashMap<BlockHash, Vec<BlockHash>>,
+    tip: Option<(u32, BlockHash)>,
+}
+
+impl HeaderIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a raw header and return its hash; duplicates are ignored
+    pub fn insert(&mut self, raw: &[u8; HEADER_SIZE]) -> BlockHash {
+        let hash = header::block_hash(raw);
+        if self.blocks.contains_key(&hash) {
+            return hash;
+        }
+
+        let parsed = BlockHeader::parse(raw);
+        let prev = parsed.prev_blockhash;
+        let height = if prev == BlockHash::default() {
+            Some(0)
+        } else {
+            self.blocks
+                .get(&prev)
+                .and_then(|parent| parent.height)
+                .map(|height| height + 1)
+        };
+        self.blocks.insert(
+            hash,
+            IndexedBlock {
+                prev_blockhash: prev,
+                version: parsed.version,
+                height: None,
+            },
+        );
+
+        match height {
+            Some(height) => self.connect(hash, height),
+            None => self.orphans.entry(prev).or_default().push(hash),
+        }
+        hash
+    }
+
+    /// Assign `height` to `hash` and to every waiting descendant
+    fn connect(&mut self, hash: BlockHash, height: u32) {
+        let mut stack = vec![(hash, height)];
+        while let Some((hash, height)) = stack.pop() {
+            if let Some(block) = self.blocks.get_mut(&hash) {
+                block.height = Some(height);
+            }
+            if self.tip.is_none_or(|(best, _)| height > best) {
+                self.tip = Some((height, hash));
+            }
+            if let Some(children) = self.orphans.remove(&hash) {
+                stack.extend(children.into_iter().map(|child| (child, height + 1)));
+            }
+        }
+    }
+
+    /// Number of distinct headers inserted
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Check if no headers were inserted
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Height of the highest header connected to genesis
+    pub fn best_height(&self) -> Option<u32> {
+        self.tip.map(|(height, _)| height)
+    }
+
+    /// Dataset records for heights `0..end_height` of the best chain
+    ///
+    /// The best chain is the one reaching the greatest height. Blocks with
+    /// version >= 2 become placeholders unless `keep_v2_hashes` is set, in
+    /// which case they are kept and flagged.
+    pub fn main_chain(&self, end_height: u32, keep_v2_hashes: bool) -> Result<Vec<HashRecord>> {
+        let (tip_height, tip) = self.tip.context("No header connects to genesis")?;
+        anyhow::ensure!(
+            end_height == 0 || tip_height >= end_height - 1,
+            "Best chain only reaches height {tip_height}, need {}",
+            end_height - 1
+        );
+
+        let mut records = vec![HashRecord::Placeholder; end_height as usize];
+        let (mut height, mut hash) = (tip_height, tip);
+        loop {
+            let block = &self.blocks[&hash];
+            if height < end_height {
+                let is_v2 = block.version >= 2;
+                if !is_v2 || keep_v2_hashes {
+                    records[height as usize] = HashRecord::Hash {
+                        block_hash: hash,
+                        is_v2,
+                    };
+                }
+            }
+            if height == 0 {
+                break;
+            }
+            height -= 1;
+            hash = block.prev_blockhash;
+        }
+        Ok(records)
+    }
+}
+
+/// Read the pre-BIP34 main chain from a Bitcoin Core `blocks/` directory
+///
+/// Files are scanned in name order until the best chain has
+/// [`MIN_CONFIRMATIONS`] blocks past the last pre-BIP34 height, so a fully
+/// synced node only needs its first few hundred files read.
+pub fn read_blocks_dir<P: AsRef<Path>>(dir: P, keep_v2_hashes: bool) -> Result<HashDump> {
+    let dir = dir.as_ref();
+    let files = blk_files(dir)?;
+    anyhow::ensure!(
+        !files.is_empty(),
+        "No blk*.dat files found in {}",
+        dir.display()
+    );
+    let xor_key = read_xor_key(dir)?;
+
+    let mut index = HeaderIndex::new();
+    let enough = BIP34_ACTIVATION_HEIGHT - 1 + MIN_CONFIRMATIONS;
+    for path in &files {
+        scan_blk_file(path, xor_key, &mut index)
+            .with_context(|| format!("Failed to scan {}", path.display()))?;
+        if index.best_height() >= Some(enough) {
+            break;
+        }
+    }
+
+    let records = index
+        .main_chain(BIP34_ACTIVATION_HEIGHT, keep_v2_hashes)
+        .with_context(|| format!("Incomplete block files in {}", dir.display()))?;
+    Ok(HashDump {
+        start_height: 0,
+        records,
+    })
+}
+
+/// `blk?????.dat` files in `dir`, sorted by name
+fn blk_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.context("Failed to read directory entry")?.path();
+        let is_blk = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("blk")?.strip_suffix(".dat"))
+            .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()));
+        if is_blk {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Obfuscation key from `xor.dat`, all zeros (no-op) when absent
+fn read_xor_key(dir: &Path) -> Result<[u8; 8]> {
+    let path = dir.join("xor.dat");
+    if !path.exists() {
+        return Ok([0; 8]);
+    }
+    let data =
+        std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    data.as_slice()
+        .try_into()
+        .ok()
+        .with_context(|| format!("{} must hold 8 bytes, found {}", path.display(), data.len()))
+}
+
+/// Add the header of every block in one `blk*.dat` file to `index`
+fn scan_blk_file(path: &Path, xor_key: [u8; 8], index: &mut HeaderIndex) -> Result<()> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut pos = 0u64;
+
+    // De-obfuscate `buf`, read from file offset `pos`
+    let xor = |buf: &mut [u8], pos: u64| {
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte ^= xor_key[(pos as usize + i) % xor_key.len()];
+        }
+    };
+
+    loop {
+        let mut prefix = [0u8; 8];
+        match reader.read_exact(&mut prefix) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("Failed to read record"),
+        }
+        xor(&mut prefix, pos);
+
+        // Files are preallocated; the unused tail is zero-filled
+        if prefix[..4] == [0; 4] {
+            break;
+        }
+        anyhow::ensure!(
+            prefix[..4] == MAINNET_MAGIC,
+            "Unexpected network magic {:02x?} at offset {pos}",
+            &prefix[..4]
+        );
+        let size = u32::from_le_bytes([prefix[4], prefix[5], prefix[6], prefix[7]]) as u64;
+        anyhow::ensure!(
+            size >= HEADER_SIZE as u64,
+            "Block at offset {pos} is only {size} bytes"
+        );
+
+        let mut raw = [0u8; HEADER_SIZE];
+        reader
+            .read_exact(&mut raw)
+            .with_context(|| format!("Truncated block at offset {pos}"))?;
+        xor(&mut raw, pos + 8);
+        index.insert(&raw);
+
+        reader
+            .seek_relative((size - HEADER_SIZE as u64) as i64)
+            .context("Failed to skip block body")?;
+        pos += 8 + size;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const GENESIS_HEADER_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const BLOCK_1_HEADER_HEX: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    fn header(hex_str: &str) -> [u8; HEADER_SIZE] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    /// Block file record with a dummy body after the header
+    fn record(raw: &[u8; HEADER_SIZE]) -> Vec<u8> {
+        let body = [0xaa; 5];
+        let mut out = MAINNET_MAGIC.to_vec();
+        out.extend_from_slice(&((HEADER_SIZE + body.len()) as u32).to_le_bytes());
+        out.extend_from_slice(raw);
+        out.extend_from_slice(&body);
+        out
+    }
+
+    #[test]
+    fn test_out_of_order_blocks_file() {
+        let genesis = header(GENESIS_HEADER_HEX);
+        let block_1 = header(BLOCK_1_HEADER_HEX);
+
+        // Child before parent, a duplicate, then zero padding, all obfuscated
+        let xor_key = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut data = [record(&block_1), record(&genesis), record(&block_1)].concat();
+        data.extend_from_slice(&[0; 16]);
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte ^= xor_key[i % 8];
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::File::create(dir.path().join("blk00000.dat"))
+            .unwrap()
+            .write_all(&data)
+            .unwrap();
+        std::fs::write(dir.path().join("xor.dat"), xor_key).unwrap();
+        std::fs::write(dir.path().join("rev00000.dat"), b"ignored").unwrap();
+
+        let files = blk_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 1);
+        let mut index = HeaderIndex::new();
+        scan_blk_file(&files[0], read_xor_key(dir.path()).unwrap(), &mut index).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.best_height(), Some(1));
+
+        let records = index.main_chain(2, false).unwrap();
+        assert_eq!(
+            records[1],
+            HashRecord::Hash {
+                block_hash: crate::parse_block_hash(
+                    "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+                )
+                .unwrap(),
+                is_v2: false,
+            }
+        );
+        assert!(index.main_chain(3, false).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Optional direct-mapped cache in front of the perfect hash
+//!
+//! Every oracle can be given a small cache with `with_cache(entries)`. A
+//! lookup first checks the one slot picked by the low bits of the hash and,
+//! on a match, returns the height without touching the perfect hash or the
+//! heights table; on a miss it looks the hash up as usual and stores the
+//! result in that slot. Workloads that keep asking for a small hot set (the
+//! tip, the genesis block, a few checkpoints) skip the PHF evaluation and the
+//! random heights read; uniformly spread lookups only pay for the extra
+//! compare and store, so the cache is off by default.
+//!
+//! Each slot holds the full hash, so a hit is always the height of that hash.
+//! Slots are guarded by a sequence number: a reader that races a writer sees a
+//! miss rather than a torn entry. Clones of an oracle share its cache.
+
+use crate::BlockHash;
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+
+/// One cache slot: a block hash and its height
+#[derive(Default)]
+struct Slot {
+    /// Even when the slot is stable, odd while it is written, 0 when empty
+    sequence: AtomicU32,
+    height: AtomicU32,
+    hash: [AtomicU64; 4],
+}
+
+/// Direct-mapped block hash -> height cache
+pub(crate) struct LookupCache {
+    slots: Box<[Slot]>,
+    mask: usize,
+}
+
+impl LookupCache {
+    /// A cache of `entries` slots, rounded up to a power of two
+    pub(crate) fn new(entries: usize) -> Self {
+        let entries = entries.max(1).next_power_of_two();
+        Self {
+            slots: (0..entries).map(|_| Slot::default()).collect(),
+            mask: entries - 1,
+        }
+    }
+
+    /// Number of slots
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Heap bytes of the slots
+    pub(crate) fn heap_bytes(&self) -> usize {
+        std::mem::size_of_val(&*self.slots)
+    }
+
+    fn words(block_hash: &BlockHash) -> [u64; 4] {
+        let bytes = block_hash.as_bytes();
+        std::array::from_fn(|i| u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap()))
+    }
+
+    /// The slot for `words`, picked by the low bits of the leading word
+    ///
+    /// In network order the leading bytes of a block hash are the random ones;
+    /// the proof-of-work zeros are at the end.
+    fn slot(&self, words: &[u64; 4]) -> &Slot {
+        &self.slots[words[0] as usize & self.mask]
+    }
+
+    /// Cached height of `block_hash`, if its slot holds it
+    #[inline]
+    pub(crate) fn get(&self, block_hash: &BlockHash) -> Option<u32> {
+        let words = Self::words(block_hash);
+        let slot = self.slot(&words);
+        let sequence = slot.sequence.load(Ordering::Acquire);
+        if sequence == 0 || sequence % 2 == 1 {
+            return None;
+        }
+        let matches = slot
+            .hash
+            .iter()
+            .zip(&words)
+            .all(|(stored, &word)| stored.load(Ordering::Relaxed) == word);
+        let height = slot.height.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        (matches && slot.sequence.load(Ordering::Relaxed) == sequence).then_some(height)
+    }
+
+    /// Store `height` for `block_hash`, evicting whatever shared its slot
+    ///
+    /// Gives up if another thread is writing the slot; the next miss retries.
+    #[inline]
+    pub(crate) fn insert(&self, block_hash: &BlockHash, height: u32) {
+        let words = Self::words(block_hash);
+        let slot = self.slot(&words);
+        let sequence = slot.sequence.load(Ordering::Relaxed);
+        if sequence % 2 == 1
+            || slot
+                .sequence
+                .compare_exchange(sequence, sequence + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        fence(Ordering::Release);
+        for (stored, word) in slot.hash.iter().zip(words) {
+            stored.store(word, Ordering::Relaxed);
+        }
+        slot.height.store(height, Ordering::Relaxed);
+        // Skip 0 on wrap-around, it marks an empty slot
+        let next = match sequence.wrapping_add(2) {
+            0 => 2,
+            next => next,
+        };
+        slot.sequence.store(next, Ordering::Release);
+    }
+}
+
+impl std::fmt::Debug for LookupCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LookupCache")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+/// `lookup()` through `cache` if there is one; found heights are cached
+#[inline]
+pub(crate) fn cached_opt(
+    cache: Option<&LookupCache>,
+    block_hash: &BlockHash,
+    lookup: impl FnOnce() -> Option<u32>,
+) -> Option<u32> {
+    let Some(cache) = cache else {
+        return lookup();
+    };
+    if let Some(height) = cache.get(block_hash) {
+        return Some(height);
+    }
+    let height = lookup();
+    if let Some(height) = height {
+        cache.insert(block_hash, height);
+    }
+    height
+}
+
+/// Like [`cached_opt`], for lookups that always yield a height
+#[inline]
+pub(crate) fn cached(
+    cache: Option<&LookupCache>,
+    block_hash: &BlockHash,
+    lookup: impl FnOnce() -> u32,
+) -> u32 {
+    let Some(cache) = cache else {
+        return lookup();
+    };
+    if let Some(height) = cache.get(block_hash) {
+        return height;
+    }
+    let height = lookup();
+    cache.insert(block_hash, height);
+    height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_cache() {
+        let cache = LookupCache::new(3);
+        assert_eq!(cache.capacity(), 4);
+        assert_eq!(LookupCache::new(0).capacity(), 1);
+
+        // An empty slot misses, even for the all-zero hash
+        let zero = BlockHash::from([0; 32]);
+        assert_eq!(cache.get(&zero), None);
+        cache.insert(&zero, 7);
+        assert_eq!(cache.get(&zero), Some(7));
+
+        // Hashes sharing a slot evict each other and never alias
+        let mut bytes = [0; 32];
+        bytes[0] = 4;
+        let colliding = BlockHash::from(bytes);
+        assert_eq!(cache.get(&colliding), None);
+        cache.insert(&colliding, 9);
+        assert_eq!(cache.get(&colliding), Some(9));
+        assert_eq!(cache.get(&zero), None);
+
+        let mut calls = 0;
+        let mut lookup = |height| {
+            calls += 1;
+            height
+        };
+        assert_eq!(cached_opt(Some(&cache), &zero, || lookup(None)), None);
+        assert_eq!(cached(Some(&cache), &zero, || lookup(Some(3)).unwrap()), 3);
+        assert_eq!(cached_opt(Some(&cache), &zero, || lookup(None)), Some(3));
+        assert_eq!(cached(None, &zero, || lookup(Some(5)).unwrap()), 5);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let cache = LookupCache::new(8);
+        let hashes: Vec<BlockHash> = (0..64u8).map(|i| BlockHash::from([i; 32])).collect();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1_000 {
+                        for (height, block_hash) in hashes.iter().enumerate() {
+                            cache.insert(block_hash, height as u32);
+                            if let Some(cached) = cache.get(block_hash) {
+                                assert_eq!(cached, height as u32);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
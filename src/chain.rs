@@ -0,0 +1,178 @@
+//! Chain-linkage verification (Feature: sha256d)
+//!
+//! Checks a dataset against the raw headers of the same height range before the
+//! oracle is built: every header must hash to the dataset's hash at that height,
+//! link to the previous header, and meet its own proof-of-work target. This catches
+//! corrupted or reordered input files that would otherwise build a silently wrong oracle.
+
+use crate::header::{self, BlockHeader, HEADER_SIZE};
+use crate::BlockHash;
+use std::fmt;
+
+/// Why a dataset failed chain verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// Dataset and header list cover a different number of heights
+    LengthMismatch { dataset: usize, headers: usize },
+    /// The header at `height` does not hash to the dataset entry
+    HashMismatch {
+        height: u32,
+        expected: BlockHash,
+        actual: BlockHash,
+    },
+    /// The header at `height` does not reference the previous header's hash
+    BrokenLink { height: u32 },
+    /// The header hash at `height` is above its own `bits` target
+    InsufficientWork { height: u32 },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::LengthMismatch { dataset, headers } => write!(
+                f,
+                "Dataset has {dataset} entries but {headers} headers were provided"
+            ),
+            ChainError::HashMismatch {
+                height,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Header at height {height} hashes to {actual}, dataset has {expected}"
+            ),
+            ChainError::BrokenLink { height } => write!(
+                f,
+                "Header at height {height} does not link to the previous block"
+            ),
+            ChainError::InsufficientWork { height } => write!(
+                f,
+                "Header at height {height} does not meet its proof-of-work target"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Verify `dataset` (one entry per height, `None` for placeholders) against `headers`
+///
+/// Both slices start at `start_height`. Placeholder entries are still linked and
+/// PoW-checked through their header; only the hash comparison is skipped.
+pub fn verify_chain(
+    dataset: &[Option<BlockHash>],
+    headers: &[[u8; HEADER_SIZE]],
+    start_height: u32,
+) -> Result<(), ChainError> {
+    if dataset.len() != headers.len() {
+        return Err(ChainError::LengthMismatch {
+            dataset: dataset.len(),
+            headers: headers.len(),
+        });
+    }
+
+    let mut prev_hash: Option<BlockHash> = None;
+    for (i, (entry, raw)) in dataset.iter().zip(headers.iter()).enumerate() {
+        let height = start_height + i as u32;
+        let parsed = BlockHeader::parse(raw);
+        let hash = header::block_hash(raw);
+
+        if let Some(expected) = entry {
+            if *expected != hash {
+                return Err(ChainError::HashMismatch {
+                    height,
+                    expected: *expected,
+                    actual: hash,
+                });
+            }
+        }
+
+        if let Some(prev_hash) = prev_hash {
+            if parsed.prev_blockhash != prev_hash {
+                return Err(ChainError::BrokenLink { height });
+            }
+        }
+
+        if !header::hash_meets_target(&hash, &parsed.target()) {
+            return Err(ChainError::InsufficientWork { height });
+        }
+
+        prev_hash = Some(hash);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_HEADER_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const BLOCK_1_HEADER_HEX: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    fn header(hex_str: &str) -> [u8; HEADER_SIZE] {
+        let mut raw = [0u8; HEADER_SIZE];
+        for (i, byte) in raw.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex_str[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        raw
+    }
+
+    fn first_two_blocks() -> (Vec<Option<BlockHash>>, Vec<[u8; HEADER_SIZE]>) {
+        let dataset = vec![
+            Some(
+                crate::parse_block_hash(
+                    "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+                )
+                .unwrap(),
+            ),
+            Some(
+                crate::parse_block_hash(
+                    "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+                )
+                .unwrap(),
+            ),
+        ];
+        let headers = vec![header(GENESIS_HEADER_HEX), header(BLOCK_1_HEADER_HEX)];
+        (dataset, headers)
+    }
+
+    #[test]
+    fn test_valid_chain() {
+        let (dataset, headers) = first_two_blocks();
+        assert_eq!(verify_chain(&dataset, &headers, 0), Ok(()));
+
+        // Placeholders skip only the hash comparison
+        assert_eq!(verify_chain(&[None, None], &headers, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_reordered_chain() {
+        let (mut dataset, mut headers) = first_two_blocks();
+        dataset.reverse();
+        headers.reverse();
+        assert_eq!(
+            verify_chain(&dataset, &headers, 0),
+            Err(ChainError::BrokenLink { height: 1 })
+        );
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let (mut dataset, headers) = first_two_blocks();
+        dataset.swap(0, 1);
+        assert!(matches!(
+            verify_chain(&dataset, &headers, 0),
+            Err(ChainError::HashMismatch { height: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_insufficient_work() {
+        let (dataset, mut headers) = first_two_blocks();
+        // Tamper with the nonce: hash changes and no longer meets the target
+        headers[0][76] ^= 0xff;
+        assert!(verify_chain(&[None], &headers[..1], 0).is_err());
+        assert!(verify_chain(&dataset, &headers, 0).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Height → cumulative chainwork
+//!
+//! SPV-style verifiers comparing the work claimed by a historical fork need the
+//! main chain's total work at a given height. A [`Chainwork`] table answers that
+//! offline. It is built from raw headers by the fetcher (`--chainwork-output`).
+//!
+//! Cumulative work is monotone, and a block's own work only changes at a
+//! difficulty retarget, so the table stores one run per difficulty period
+//! rather than one value per block: a few KB for the whole pre-BIP34 chain.
+//!
+//! Asset format: [num_blocks: u32][num_runs: u32][per run: start: u32, work_per_block: u128]
+
+use crate::header::{target_from_bits, BlockHeader, HEADER_SIZE};
+use crate::{BlockHash, HeightLookup};
+use std::io::{self, Read, Write};
+#[cfg(feature = "embedded-chainwork")]
+use std::sync::OnceLock;
+
+// Embedded chainwork table at compile time
+#[cfg(feature = "embedded-chainwork")]
+const CHAINWORK_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/chainwork.runs.dat"
+));
+
+/// Expected number of hashes needed for a block at compact target `bits`
+///
+/// Same value as Bitcoin Core's `GetBlockProof`: 2^256 / (target + 1). Invalid
+/// targets have no work. Work above `u128::MAX` (targets below 2^128, far past
+/// any real difficulty) saturates.
+pub fn work_from_bits(bits: u32) -> u128 {
+    let target = target_from_bits(bits);
+    let lo = u128::from_le_bytes(target[..16].try_into().unwrap());
+    let hi = u128::from_le_bytes(target[16..].try_into().unwrap());
+    if (hi, lo) == (0, 0) {
+        return 0;
+    }
+
+    // 2^256 does not fit in 256 bits, so compute ~target / (target + 1) + 1 instead.
+    // Compact targets are below 2^255, so target + 1 cannot overflow.
+    let (d_lo, carry) = lo.overflowing_add(1);
+    let d_hi = hi + u128::from(carry);
+    let (n_hi, n_lo) = (!hi, !lo);
+
+    // Shift-subtract long division, one numerator bit at a time
+    let (mut r_hi, mut r_lo) = (0u128, 0u128);
+    let mut quotient = 0u128;
+    for i in (0..256).rev() {
+        let bit = if i >= 128 {
+            (n_hi >> (i - 128)) & 1
+        } else {
+            (n_lo >> i) & 1
+        };
+        let overflow = r_hi >> 127 == 1;
+        r_hi = (r_hi << 1) | (r_lo >> 127);
+        r_lo = (r_lo << 1) | bit;
+        if overflow || (r_hi, r_lo) >= (d_hi, d_lo) {
+            let (lo, borrow) = r_lo.overflowing_sub(d_lo);
+            r_hi = r_hi.wrapping_sub(d_hi).wrapping_sub(u128::from(borrow));
+            r_lo = lo;
+            if i >= 128 {
+                return u128::MAX;
+            }
+            quotient |= 1 << i;
+        }
+    }
+    quotient.saturating_add(1)
+}
+
+/// Consecutive heights sharing the same per-block work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WorkRun {
+    start: u32,
+    work: u128,
+    /// Cumulative chainwork of the blocks before `start`
+    chainwork_before: u128,
+}
+
+/// Cumulative chainwork per height
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chainwork {
+    num_blocks: u32,
+    runs: Vec<WorkRun>,
+}
+
+impl Chainwork {
+    /// Build from raw headers in height order, starting at genesis
+    pub fn from_headers(headers: &[[u8; HEADER_SIZE]]) -> Self {
+        let works: Vec<u128> = headers
+            .iter()
+            .map(|raw| work_from_bits(BlockHeader::parse(raw).bits))
+            .collect();
+        Self::from_block_work(&works)
+    }
+
+    /// Build from each block's own work, indexed by height
+    fn from_block_work(works: &[u128]) -> Self {
+        let mut runs: Vec<(u32, u128)> = Vec::new();
+        for (height, &work) in (0u32..).zip(works) {
+            if runs.last().is_none_or(|&(_, last)| last != work) {
+                runs.push((height, work));
+            }
+        }
+        Self::from_runs(works.len() as u32, &runs)
+    }
+
+    /// Fill in the cumulative work at the start of each `(start, work)` run
+    fn from_runs(num_blocks: u32, runs: &[(u32, u128)]) -> Self {
+        let mut chainwork = 0u128;
+        let runs = runs
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, work))| {
+                let end = runs.get(i + 1).map_or(num_blocks, |&(next, _)| next);
+                let run = WorkRun {
+                    start,
+                    work,
+                    chainwork_before: chainwork,
+                };
+                chainwork = chainwork.saturating_add(work.saturating_mul(u128::from(end - start)));
+                run
+            })
+            .collect();
+        Self { num_blocks, runs }
+    }
+
+    /// Total work of the chain up to and including `height` (Bitcoin Core's `chainwork`)
+    ///
+    /// `None` past the end of the table.
+    pub fn chainwork_at(&self, height: u32) -> Option<u128> {
+        if height >= self.num_blocks {
+            return None;
+        }
+        let run = &self.runs[self.runs.partition_point(|run| run.start <= height) - 1];
+        let blocks = u128::from(height - run.start + 1);
+        Some(
+            run.chainwork_before
+                .saturating_add(run.work.saturating_mul(blocks)),
+        )
+    }
+
+    /// Chainwork at the height `oracle` returns for `block_hash` (unchecked)
+    ///
+    /// Like the lookup itself, hashes outside the oracle's dataset get the
+    /// chainwork of some other height.
+    pub fn chainwork_for<L: HeightLookup + ?Sized>(
+        &self,
+        oracle: &L,
+        block_hash: &BlockHash,
+    ) -> Option<u128> {
+        self.chainwork_at(oracle.get_height_unchecked(block_hash))
+    }
+
+    /// Number of heights covered
+    pub fn len(&self) -> usize {
+        self.num_blocks as usize
+    }
+
+    /// Check if no heights are covered
+    pub fn is_empty(&self) -> bool {
+        self.num_blocks == 0
+    }
+
+    /// Serialize the table to writer
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.num_blocks.to_le_bytes())?;
+        writer.write_all(&(self.runs.len() as u32).to_le_bytes())?;
+        for run in &self.runs {
+            writer.write_all(&run.start.to_le_bytes())?;
+            writer.write_all(&run.work.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a table from reader
+    pub fn deserialize<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes)?;
+        let num_blocks = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let num_runs = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+
+        let mut runs = Vec::with_capacity(num_runs.min(num_blocks) as usize);
+        for _ in 0..num_runs {
+            let mut run = [0u8; 20];
+            reader.read_exact(&mut run)?;
+            let start = u32::from_le_bytes(run[0..4].try_into().unwrap());
+            let work = u128::from_le_bytes(run[4..20].try_into().unwrap());
+            runs.push((start, work));
+        }
+
+        // Runs must start at height 0 and strictly increase within the table
+        let valid = runs
+            .first()
+            .map_or(num_blocks == 0, |&(start, _)| start == 0)
+            && runs.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && runs.last().is_none_or(|&(start, _)| start < num_blocks);
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Chainwork runs do not cover the table in order",
+            ));
+        }
+        Ok(Self::from_runs(num_blocks, &runs))
+    }
+}
+
+/// Global singleton for the embedded chainwork table
+#[cfg(feature = "embedded-chainwork")]
+static EMBEDDED_CHAINWORK: OnceLock<Chainwork> = OnceLock::new();
+
+#[cfg(feature = "embedded-chainwork")]
+fn embedded_chainwork() -> &'static Chainwork {
+    EMBEDDED_CHAINWORK.get_or_init(|| {
+        Chainwork::deserialize(CHAINWORK_DATA).expect("Failed to load embedded chainwork")
+    })
+}
+
+/// Chainwork up to and including `height`, from the embedded table (Feature: embedded-chainwork)
+#[cfg(feature = "embedded-chainwork")]
+pub fn chainwork_at(height: u32) -> Option<u128> {
+    embedded_chainwork().chainwork_at(height)
+}
+
+/// Chainwork of a pre-BIP34 block, using the embedded oracle and table (unchecked)
+/// (Feature: embedded-chainwork)
+#[cfg(feature = "embedded-chainwork")]
+pub fn chainwork_for(block_hash: &BlockHash) -> Option<u128> {
+    chainwork_at(crate::guess_height_prebip34block_unchecked(block_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_from_bits() {
+        // Difficulty 1: genesis has chainwork 0x100010001
+        assert_eq!(work_from_bits(0x1d00ffff), 0x1_0001_0001);
+        // Block 32,256, the first retarget above difficulty 1
+        assert_eq!(work_from_bits(0x1d00d86a), 0x1_2ed3_afd2);
+        assert_eq!(work_from_bits(0x1d800000), 0);
+        assert_eq!(work_from_bits(0x1001_0000), u128::MAX);
+    }
+
+    #[test]
+    fn test_chainwork_runs() {
+        let works = [5, 5, 5, 7, 7, 5];
+        let chainwork = Chainwork::from_block_work(&works);
+        assert_eq!(chainwork.runs.len(), 3);
+        assert_eq!(chainwork.len(), 6);
+
+        let expected: Vec<u128> = works
+            .iter()
+            .scan(0, |total, &work| {
+                *total += work;
+                Some(*total)
+            })
+            .collect();
+        for (height, &total) in (0u32..).zip(&expected) {
+            assert_eq!(chainwork.chainwork_at(height), Some(total));
+        }
+        assert_eq!(chainwork.chainwork_at(6), None);
+
+        let mut data = Vec::new();
+        chainwork.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + 3 * 20);
+        assert_eq!(Chainwork::deserialize(data.as_slice()).unwrap(), chainwork);
+
+        // A run starting past the end is rejected
+        data[8 + 2 * 20..8 + 2 * 20 + 4].copy_from_slice(&9u32.to_le_bytes());
+        assert!(Chainwork::deserialize(data.as_slice()).is_err());
+    }
+}
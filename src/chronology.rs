@@ -0,0 +1,167 @@
+//! Date → height ranges
+//!
+//! A [`Chronology`] holds, for every UTC day from genesis to the end of the
+//! dataset, the range of heights whose header timestamps fall on that day. It is
+//! built from the raw headers during generation and answers questions like "what
+//! heights existed around March 2011" without a node.
+//!
+//! Header timestamps are not monotonic, so neighbouring days' ranges can overlap
+//! by a few blocks. Days without blocks (e.g. the days after genesis) get an
+//! empty range at the next height.
+//!
+//! Asset format: [first_day: u32][num_days: u32][per day: start: u32, end: u32]
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+#[cfg(feature = "embedded-chronology")]
+use std::sync::OnceLock;
+
+// Embedded chronology table at compile time
+#[cfg(feature = "embedded-chronology")]
+const CHRONOLOGY_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/chronology.days.dat"
+));
+
+/// Seconds in a UTC day
+pub const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Height ranges per UTC day
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chronology {
+    /// Days since the Unix epoch of `days[0]`
+    first_day: u32,
+    days: Vec<Range<u32>>,
+}
+
+impl Chronology {
+    /// Build from header timestamps indexed by height (starting at 0)
+    pub fn from_timestamps(timestamps: &[u32]) -> Self {
+        let day_of = |timestamp: u32| (u64::from(timestamp) / SECONDS_PER_DAY) as u32;
+        let (Some(first_day), Some(last_day)) = (
+            timestamps.iter().map(|&t| day_of(t)).min(),
+            timestamps.iter().map(|&t| day_of(t)).max(),
+        ) else {
+            return Self::default();
+        };
+
+        let mut days: Vec<Option<Range<u32>>> = vec![None; (last_day - first_day + 1) as usize];
+        for (height, &timestamp) in (0u32..).zip(timestamps) {
+            let day = &mut days[(day_of(timestamp) - first_day) as usize];
+            *day = Some(match day.take() {
+                Some(range) => range.start.min(height)..range.end.max(height + 1),
+                None => height..height + 1,
+            });
+        }
+
+        // Empty days sit at the start of the next day with blocks
+        let mut next_start = timestamps.len() as u32;
+        let mut filled: Vec<Range<u32>> = days
+            .into_iter()
+            .rev()
+            .map(|day| {
+                let range = day.unwrap_or(next_start..next_start);
+                next_start = range.start;
+                range
+            })
+            .collect();
+        filled.reverse();
+
+        Self {
+            first_day,
+            days: filled,
+        }
+    }
+
+    /// Heights of the blocks timestamped on the UTC day containing `unix_ts`
+    ///
+    /// Empty before genesis and after the last covered day.
+    pub fn height_range_for_date(&self, unix_ts: u64) -> Range<u32> {
+        let day = unix_ts / SECONDS_PER_DAY;
+        day.checked_sub(u64::from(self.first_day))
+            .and_then(|offset| self.days.get(offset as usize))
+            .cloned()
+            .unwrap_or(0..0)
+    }
+
+    /// Number of days covered
+    pub fn len(&self) -> usize {
+        self.days.len()
+    }
+
+    /// Check if no days are covered
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    /// Serialize the table to writer
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.first_day.to_le_bytes())?;
+        writer.write_all(&(self.days.len() as u32).to_le_bytes())?;
+        for range in &self.days {
+            writer.write_all(&range.start.to_le_bytes())?;
+            writer.write_all(&range.end.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a table from reader
+    pub fn deserialize<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut bytes = [0u8; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let first_day = read_u32()?;
+        let num_days = read_u32()?;
+        let days = (0..num_days)
+            .map(|_| Ok(read_u32()?..read_u32()?))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { first_day, days })
+    }
+}
+
+/// Global singleton for the embedded chronology
+#[cfg(feature = "embedded-chronology")]
+static EMBEDDED_CHRONOLOGY: OnceLock<Chronology> = OnceLock::new();
+
+/// Heights timestamped on the UTC day containing `unix_ts`, from the embedded table
+/// (Feature: embedded-chronology)
+#[cfg(feature = "embedded-chronology")]
+pub fn height_range_for_date(unix_ts: u64) -> Range<u32> {
+    EMBEDDED_CHRONOLOGY
+        .get_or_init(|| {
+            Chronology::deserialize(CHRONOLOGY_DATA).expect("Failed to load embedded chronology")
+        })
+        .height_range_for_date(unix_ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u32 = SECONDS_PER_DAY as u32;
+
+    #[test]
+    fn test_height_range_for_date() {
+        // Day 10: heights 0-1, day 11: none, day 12: heights 2-4 with height 3 on day 13
+        let timestamps = [10 * DAY, 10 * DAY + 5, 12 * DAY, 13 * DAY, 12 * DAY + 9];
+        let chronology = Chronology::from_timestamps(&timestamps);
+        assert_eq!(chronology.len(), 4);
+
+        let at = |day: u32| chronology.height_range_for_date(u64::from(day * DAY + 100));
+        assert_eq!(at(9), 0..0);
+        assert_eq!(at(10), 0..2);
+        assert_eq!(at(11), 2..2);
+        assert_eq!(at(12), 2..5);
+        assert_eq!(at(13), 3..4);
+        assert_eq!(at(14), 0..0);
+
+        let mut data = Vec::new();
+        chronology.serialize(&mut data).unwrap();
+        assert_eq!(
+            Chronology::deserialize(data.as_slice()).unwrap(),
+            chronology
+        );
+    }
+}
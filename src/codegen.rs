@@ -0,0 +1,114 @@
+//! Rust source generation for compiled-in assets (Feature: generate)
+//!
+//! [`write_static_assets`] turns a PtrHash asset and a heights asset into a
+//! Rust file holding both as `static` byte arrays, plus a `load()` function
+//! that wraps them in a [`HeightOracleEmbedded`](crate::HeightOracleEmbedded)
+//! through `from_static`. Include it in a crate that depends on
+//! `height-oracle` with `runtime-assets`:
+//!
+//! ```text
+//! height-oracle codegen assets --output src/oracle_assets.rs
+//! ```
+//!
+//! This is the same load as `include_bytes!` plus `from_static`, as a source
+//! file that needs neither the asset files nor `ORACLE_ASSET_DIR` at build
+//! time. The packed heights are read in place, but the PtrHash asset is still
+//! fully deserialized onto the heap at load, so the result is not an
+//! allocation-free or `no_std` oracle.
+
+use crate::format::{self, AssetKind};
+use crate::packing::PackedHeights;
+use crate::phf::Phf;
+use anyhow::{Context, Result};
+use std::io::Write;
+
+/// Bytes per line of the emitted arrays
+const BYTES_PER_LINE: usize = 16;
+
+/// Write Rust source defining `PTRHASH_DATA`, `HEIGHTS_DATA` and `load()`
+///
+/// Both assets are checked first, so a mismatched pair fails here instead of
+/// in the crate that compiles the output.
+pub fn write_static_assets<W: Write>(
+    ptrhash_data: &[u8],
+    heights_data: &[u8],
+    mut writer: W,
+) -> Result<()> {
+    let phash = Phf::read(format::strip_header(ptrhash_data, AssetKind::PtrHash)?)
+        .context("Invalid PtrHash asset")?;
+    let heights =
+        PackedHeights::from_serialized(format::strip_header(heights_data, AssetKind::Heights)?)
+            .context("Invalid heights asset")?;
+    phash.check_table_len(heights.len(), "heights")?;
+
+    writeln!(
+        writer,
+        "// @generated by `height-oracle codegen` from {} keys; do not edit",
+        heights.len()
+    )?;
+    writeln!(writer)?;
+    write_array(&mut writer, "PTRHASH_DATA", "PtrHash asset", ptrhash_data)?;
+    writeln!(writer)?;
+    write_array(
+        &mut writer,
+        "HEIGHTS_DATA",
+        "Packed heights asset",
+        heights_data,
+    )?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "/// Load the oracle over the statics above, reading the heights in place"
+    )?;
+    writeln!(
+        writer,
+        "pub fn load() -> ::std::io::Result<::height_oracle::HeightOracleEmbedded> {{"
+    )?;
+    writeln!(
+        writer,
+        "    ::height_oracle::HeightOracleEmbedded::from_static(&PTRHASH_DATA, &HEIGHTS_DATA)"
+    )?;
+    writeln!(writer, "}}")?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_array<W: Write>(writer: &mut W, name: &str, doc: &str, data: &[u8]) -> Result<()> {
+    writeln!(writer, "/// {doc} ({} bytes)", data.len())?;
+    writeln!(writer, "pub static {name}: [u8; {}] = [", data.len())?;
+    for line in data.chunks(BYTES_PER_LINE) {
+        write!(writer, "   ")?;
+        for byte in line {
+            write!(writer, " 0x{byte:02x},")?;
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer, "];")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::test_support::{asset_bytes, test_hashes, test_oracle};
+
+    #[test]
+    fn test_write_static_assets() {
+        let (ptrhash_data, heights_data) = asset_bytes(&test_oracle(&test_hashes(20), &[7; 20]));
+
+        let mut source = Vec::new();
+        write_static_assets(&ptrhash_data, &heights_data, &mut source).unwrap();
+        let source = String::from_utf8(source).unwrap();
+        assert!(source.contains(&format!(
+            "pub static HEIGHTS_DATA: [u8; {}] = [",
+            heights_data.len()
+        )));
+        assert!(source.contains("from_static(&PTRHASH_DATA, &HEIGHTS_DATA)"));
+        let bytes = source.matches(" 0x").count();
+        assert_eq!(bytes, ptrhash_data.len() + heights_data.len());
+
+        // Assets from different builds are refused
+        let (_, short_heights) = asset_bytes(&test_oracle(&test_hashes(19), &[7; 19]));
+        assert!(write_static_assets(&ptrhash_data, &short_heights, Vec::new()).is_err());
+    }
+}
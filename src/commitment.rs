@@ -0,0 +1,122 @@
+//! Dataset commitments
+//!
+//! A [`DatasetCommitment`] is a SHA-256 digest over the canonical hash list an
+//! oracle was built from: the tag `height-oracle/dataset/v1`, then one 37-byte
+//! record per block in height order:
+//! [height: u32][version_2: u8][block_hash: 32 bytes, network order].
+//! Bare `x` placeholders carry no hash and are left out.
+//!
+//! Builds from a dataset record the commitment in the heights asset header
+//! (format version 3, see [`crate::format`]), so whoever regenerates assets
+//! elsewhere can show they started from exactly the same dataset. It covers
+//! the whole dataset, before any input policy or height range is applied.
+
+use std::fmt;
+
+/// Domain tag hashed before the records
+pub const DOMAIN_TAG: &[u8] = b"height-oracle/dataset/v1";
+
+/// SHA-256 commitment to a dataset's (height, version-2 flag, block hash) list
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DatasetCommitment([u8; 32]);
+
+impl DatasetCommitment {
+    /// Wrap raw digest bytes
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Raw digest bytes
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Commit to `entries`, which must be in height order (Feature: sha256d)
+    #[cfg(feature = "sha256d")]
+    pub fn compute<'a, I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, bool, &'a crate::BlockHash)>,
+    {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN_TAG);
+        for (height, is_v2, block_hash) in entries {
+            hasher.update(height.to_le_bytes());
+            hasher.update([u8::from(is_v2)]);
+            hasher.update(block_hash.as_bytes());
+        }
+        Self(hasher.finalize().into())
+    }
+}
+
+impl fmt::Display for DatasetCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for DatasetCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DatasetCommitment({self})")
+    }
+}
+
+impl std::str::FromStr for DatasetCommitment {
+    type Err = String;
+
+    /// Parse the 64 hex characters [`fmt::Display`] writes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: &[u8; 64] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| "Dataset commitment must be exactly 64 hex characters")?;
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(hex.chunks_exact(2)) {
+            let digit = |c: u8| (c as char).to_digit(16).ok_or("Invalid hex");
+            *byte = ((digit(pair[0])? << 4) | digit(pair[1])?) as u8;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let commitment = DatasetCommitment::from_bytes(std::array::from_fn(|i| i as u8 * 7));
+        let hex = commitment.to_string();
+        assert_eq!(hex.len(), 64);
+        assert_eq!(hex.parse::<DatasetCommitment>().unwrap(), commitment);
+        assert!("00".parse::<DatasetCommitment>().is_err());
+        assert!("zz".repeat(32).parse::<DatasetCommitment>().is_err());
+    }
+
+    #[cfg(feature = "sha256d")]
+    #[test]
+    fn test_compute() {
+        let a = crate::BlockHash::from([1; 32]);
+        let b = crate::BlockHash::from([2; 32]);
+        let commitment = DatasetCommitment::compute([(0, false, &a), (1, true, &b)]);
+
+        // Every field of every record is committed to
+        assert_ne!(
+            commitment,
+            DatasetCommitment::compute([(0, false, &a), (1, false, &b)])
+        );
+        assert_ne!(
+            commitment,
+            DatasetCommitment::compute([(0, false, &a), (2, true, &b)])
+        );
+        assert_ne!(
+            commitment,
+            DatasetCommitment::compute([(0, false, &b), (1, true, &a)])
+        );
+        assert_ne!(commitment, DatasetCommitment::compute([(0, false, &a)]));
+    }
+}
@@ -0,0 +1,214 @@
+//! Chained lookups across oracles over disjoint height ranges
+//!
+//! One oracle covers one dataset. People extending the technique past the
+//! pre-BIP34 window, say with a fork's v1-style blocks at heights
+//! 227,931..300,000, or splitting assets into 50k-block pieces, end up with
+//! several. [`CompositeOracle`] puts them behind one [`BlockHeightProvider`]:
+//! a hash goes to each member in turn, with the checked lookup, until one
+//! answers with a height inside the range it was added for.
+//!
+//! A bare oracle maps nearly any hash to some height in its range, so the
+//! first member would answer for every other member's blocks. Members are
+//! therefore [`FilteredProvider`]s, whose XOR filter over the member's own
+//! dataset passes about 1 in 256 foreign hashes.
+
+use crate::provider::{BlockHeightProvider, FilteredProvider};
+use crate::BlockHash;
+use std::ops::Range;
+
+/// One member of a [`CompositeOracle`]
+struct Member {
+    heights: Range<u32>,
+    provider: Box<dyn BlockHeightProvider>,
+}
+
+/// Several providers over disjoint height ranges, queried in order until one answers
+#[derive(Default)]
+pub struct CompositeOracle {
+    members: Vec<Member>,
+}
+
+impl CompositeOracle {
+    /// A composite with no members, which rejects every hash
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `provider`, answering for the heights in `heights`
+    ///
+    /// Members are queried in the order they are added, so add the one most
+    /// lookups hit first. The filter must be built over the provider's own
+    /// dataset, so it rejects the other members' blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `heights` is empty or overlaps the range of an earlier member.
+    pub fn with_member<P: BlockHeightProvider + 'static>(
+        mut self,
+        heights: Range<u32>,
+        provider: FilteredProvider<P>,
+    ) -> Self {
+        assert!(!heights.is_empty(), "empty height range {heights:?}");
+        let overlaps = |other: &Range<u32>| other.start < heights.end && heights.start < other.end;
+        if let Some(other) = self.members.iter().find(|member| overlaps(&member.heights)) {
+            panic!(
+                "height range {heights:?} overlaps {:?} of an earlier member",
+                other.heights
+            );
+        }
+        self.members.push(Member {
+            heights,
+            provider: Box::new(provider),
+        });
+        self
+    }
+
+    /// Height ranges of the members, in query order
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u32>> + '_ {
+        self.members.iter().map(|member| member.heights.clone())
+    }
+
+    /// Number of members
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Check if there are no members
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+impl BlockHeightProvider for CompositeOracle {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.members.iter().find_map(|member| {
+            member
+                .provider
+                .block_height(block_hash)
+                .filter(|height| member.heights.contains(height))
+        })
+    }
+
+    /// Each member gets one batched lookup of the hashes the earlier members missed
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        assert_eq!(
+            block_hashes.len(),
+            heights.len(),
+            "one output slot per block hash"
+        );
+        heights.fill(None);
+        let mut missing: Vec<usize> = (0..block_hashes.len()).collect();
+        for member in &self.members {
+            if missing.is_empty() {
+                break;
+            }
+            let queried: Vec<BlockHash> = missing.iter().map(|&i| block_hashes[i]).collect();
+            let mut found = vec![None; queried.len()];
+            member.provider.block_heights(&queried, &mut found);
+            // `retain` visits `missing` in order, the order `found` is in
+            let mut found = found.into_iter();
+            missing.retain(|&i| {
+                let height = found.next().flatten();
+                match height.filter(|height| member.heights.contains(height)) {
+                    Some(height) => {
+                        heights[i] = Some(height);
+                        false
+                    }
+                    None => true,
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::XorFilter;
+
+    /// Answers `.1` for every hash, like an unfiltered oracle
+    struct Any(u32);
+
+    impl BlockHeightProvider for Any {
+        fn block_height(&self, _block_hash: &BlockHash) -> Option<u32> {
+            Some(self.0)
+        }
+    }
+
+    /// `Any(height)`, filtered to the single hash `[tag; 32]`
+    fn member(tag: u8, height: u32) -> FilteredProvider<Any> {
+        FilteredProvider::new(Any(height), XorFilter::build(&[BlockHash::from([tag; 32])]))
+    }
+
+    #[test]
+    fn test_composite_oracle() {
+        let composite = CompositeOracle::new()
+            .with_member(0..227_931, member(1, 100))
+            .with_member(227_931..300_000, member(2, 250_000))
+            // Claims a height outside its range, so it never answers
+            .with_member(300_000..350_000, member(3, 10));
+        assert_eq!(composite.len(), 3);
+        assert_eq!(
+            composite.ranges().collect::<Vec<_>>(),
+            [0..227_931, 227_931..300_000, 300_000..350_000]
+        );
+
+        let block_hashes: Vec<BlockHash> = (1..=4).map(|i| BlockHash::from([i; 32])).collect();
+        let expected = [Some(100), Some(250_000), None, None];
+        for (block_hash, height) in block_hashes.iter().zip(expected) {
+            assert_eq!(composite.block_height(block_hash), height);
+            assert_eq!(composite.contains_block(block_hash), height.is_some());
+        }
+        let mut heights = vec![Some(0); 4];
+        composite.block_heights(&block_hashes, &mut heights);
+        assert_eq!(heights, expected);
+
+        assert_eq!(CompositeOracle::new().block_height(&block_hashes[0]), None);
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_chained_oracles() {
+        use crate::generate::test_support::{test_hashes, test_oracle};
+
+        // Two oracles over consecutive height ranges
+        let all_hashes = test_hashes(12);
+        let (low, high) = all_hashes.split_at(6);
+        let filtered = |block_hashes: &[BlockHash], heights: Range<u32>| {
+            let heights: Vec<u32> = heights.collect();
+            FilteredProvider::new(
+                test_oracle(block_hashes, &heights),
+                XorFilter::build(block_hashes),
+            )
+        };
+        let composite = CompositeOracle::new()
+            .with_member(0..6, filtered(low, 0..6))
+            .with_member(6..12, filtered(high, 6..12));
+
+        // The first member does not answer for the second one's blocks
+        let expected: Vec<Option<u32>> = (0..12).map(Some).collect();
+        let found: Vec<Option<u32>> = all_hashes
+            .iter()
+            .map(|block_hash| composite.block_height(block_hash))
+            .collect();
+        assert_eq!(found, expected);
+        let mut heights = vec![None; 12];
+        composite.block_heights(&all_hashes, &mut heights);
+        assert_eq!(heights, expected);
+
+        let foreign: Vec<BlockHash> = (100..=255).map(|i| BlockHash::from([i; 32])).collect();
+        let answered = foreign
+            .iter()
+            .filter(|block_hash| composite.contains_block(block_hash))
+            .count();
+        assert!(answered < foreign.len() / 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn test_overlapping_ranges() {
+        let _ = CompositeOracle::new()
+            .with_member(0..100, member(1, 1))
+            .with_member(99..200, member(2, 150));
+    }
+}
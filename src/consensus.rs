@@ -0,0 +1,183 @@
+//! Mainnet consensus facts by height
+//!
+//! Pure helpers for the block subsidy, halving epochs and soft-fork activation
+//! heights. Feed them the height from an oracle lookup to map a block hash
+//! straight to its consensus era, e.g. its subsidy or whether P2SH applied.
+
+/// Blocks between subsidy halvings
+pub const HALVING_INTERVAL: u32 = 210_000;
+
+/// Subsidy of the first halving epoch, in satoshis (50 BTC)
+pub const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+
+/// First height enforcing BIP16 (P2SH)
+///
+/// P2SH activated by timestamp (2012-04-01); this is the first block at or past it.
+pub const BIP16_HEIGHT: u32 = 173_805;
+
+/// Heights exempt from BIP30 (no duplicate unspent txids)
+///
+/// Their coinbases duplicate those of blocks 91,812 and 91,722. BIP30 is checked
+/// at every other height until BIP34 makes duplicate coinbases impossible.
+pub const BIP30_EXCEPTION_HEIGHTS: [u32; 2] = [91_842, 91_880];
+
+/// First height where BIP30 is checked again after BIP34
+///
+/// Coinbases of blocks before BIP34 can encode heights at or past this one,
+/// so BIP34 alone no longer rules out duplicate txids.
+pub const BIP30_REENABLE_HEIGHT: u32 = 1_983_702;
+
+/// First height enforcing BIP34 (height in coinbase)
+pub const BIP34_HEIGHT: u32 = crate::BIP34_ACTIVATION_HEIGHT;
+
+/// First height enforcing BIP66 (strict DER signatures)
+pub const BIP66_HEIGHT: u32 = 363_725;
+
+/// First height enforcing BIP65 (`OP_CHECKLOCKTIMEVERIFY`)
+pub const BIP65_HEIGHT: u32 = 388_381;
+
+/// Blocks between difficulty retargets
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u32 = 2_016;
+
+/// Halving epoch of `height`, starting at 0
+pub const fn halving_epoch(height: u32) -> u32 {
+    height / HALVING_INTERVAL
+}
+
+/// Block subsidy at `height` in satoshis, excluding fees
+///
+/// Halves every [`HALVING_INTERVAL`] blocks and reaches 0 after 64 halvings.
+pub const fn subsidy_at_height(height: u32) -> u64 {
+    let epoch = halving_epoch(height);
+    if epoch >= 64 {
+        0
+    } else {
+        INITIAL_SUBSIDY >> epoch
+    }
+}
+
+/// Whether `height` is before BIP34 activation, i.e. in the oracle's domain
+pub const fn is_prebip34(height: u32) -> bool {
+    height < BIP34_HEIGHT
+}
+
+/// Difficulty period of `height`, starting at 0
+///
+/// Every block of a period shares the same `nBits`.
+pub const fn difficulty_period(height: u32) -> u32 {
+    height / DIFFICULTY_ADJUSTMENT_INTERVAL
+}
+
+/// Soft-fork rules in force at a height
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BipFlags {
+    /// P2SH scripts are evaluated ([`BIP16_HEIGHT`])
+    pub bip16: bool,
+    /// Duplicate unspent txids are checked: before BIP34 except at
+    /// [`BIP30_EXCEPTION_HEIGHTS`], and again from [`BIP30_REENABLE_HEIGHT`]
+    pub bip30: bool,
+    /// The coinbase commits to the height ([`BIP34_HEIGHT`])
+    pub bip34: bool,
+    /// Signatures must be strict DER ([`BIP66_HEIGHT`])
+    pub bip66: bool,
+    /// `OP_CHECKLOCKTIMEVERIFY` is enforced ([`BIP65_HEIGHT`])
+    pub bip65: bool,
+}
+
+impl BipFlags {
+    /// Rules in force at `height`
+    pub const fn at(height: u32) -> Self {
+        Self {
+            bip16: height >= BIP16_HEIGHT,
+            bip30: (is_prebip34(height)
+                && height != BIP30_EXCEPTION_HEIGHTS[0]
+                && height != BIP30_EXCEPTION_HEIGHTS[1])
+                || height >= BIP30_REENABLE_HEIGHT,
+            bip34: height >= BIP34_HEIGHT,
+            bip66: height >= BIP66_HEIGHT,
+            bip65: height >= BIP65_HEIGHT,
+        }
+    }
+}
+
+/// Consensus era of a height, from [`era_info`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EraInfo {
+    /// The height described
+    pub height: u32,
+    /// See [`halving_epoch`]
+    pub halving_epoch: u32,
+    /// See [`difficulty_period`]
+    pub difficulty_period: u32,
+    /// Soft-fork rules in force
+    pub bip_flags: BipFlags,
+}
+
+impl EraInfo {
+    /// Block subsidy of the era in satoshis, see [`subsidy_at_height`]
+    pub const fn subsidy(&self) -> u64 {
+        subsidy_at_height(self.height)
+    }
+}
+
+/// Halving epoch, difficulty period and soft-fork rules of `height`
+///
+/// [`crate::HeightLookup::era_info_for`] looks the height of a block hash up first.
+pub const fn era_info(height: u32) -> EraInfo {
+    EraInfo {
+        height,
+        halving_epoch: halving_epoch(height),
+        difficulty_period: difficulty_period(height),
+        bip_flags: BipFlags::at(height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsidy_schedule() {
+        assert_eq!(subsidy_at_height(0), 5_000_000_000);
+        assert_eq!(subsidy_at_height(209_999), 5_000_000_000);
+        assert_eq!(subsidy_at_height(210_000), 2_500_000_000);
+        assert_eq!(subsidy_at_height(840_000), 312_500_000);
+        assert_eq!(subsidy_at_height(HALVING_INTERVAL * 33), 0);
+        assert_eq!(subsidy_at_height(u32::MAX), 0);
+
+        assert_eq!(halving_epoch(BIP34_HEIGHT), 1);
+        assert!(is_prebip34(BIP34_HEIGHT - 1));
+        assert!(!is_prebip34(BIP34_HEIGHT));
+        assert!(BIP30_EXCEPTION_HEIGHTS.iter().all(|&h| is_prebip34(h)));
+    }
+
+    #[test]
+    fn test_era_info() {
+        let genesis = era_info(0);
+        assert_eq!(genesis.halving_epoch, 0);
+        assert_eq!(genesis.difficulty_period, 0);
+        assert_eq!(
+            genesis.bip_flags,
+            BipFlags {
+                bip30: true,
+                ..BipFlags::default()
+            }
+        );
+        assert_eq!(genesis.subsidy(), INITIAL_SUBSIDY);
+
+        assert!(!era_info(BIP30_EXCEPTION_HEIGHTS[1]).bip_flags.bip30);
+        assert_eq!(era_info(32_255).difficulty_period, 15);
+        assert_eq!(era_info(32_256).difficulty_period, 16);
+
+        let p2sh = era_info(BIP16_HEIGHT);
+        assert!(p2sh.bip_flags.bip16 && !p2sh.bip_flags.bip34);
+        assert!(!era_info(BIP16_HEIGHT - 1).bip_flags.bip16);
+
+        let cltv = era_info(BIP65_HEIGHT).bip_flags;
+        assert!(cltv.bip16 && cltv.bip34 && cltv.bip66 && cltv.bip65);
+        assert!(!cltv.bip30 && era_info(BIP30_REENABLE_HEIGHT).bip_flags.bip30);
+        assert_eq!(era_info(BIP34_HEIGHT).halving_epoch, 1);
+    }
+}
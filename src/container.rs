@@ -0,0 +1,108 @@
+//! Single-file asset container, optionally zstd-compressed
+//!
+//! Layout: [magic: "HORC"][ptrhash_len: u64][PtrHash asset][packed heights asset].
+//! Each asset keeps its format header (see [`crate::format`]), so the input
+//! policy, height range and dataset commitment survive the container; older
+//! containers of headerless assets still load. A zstd frame wrapping the whole container is detected by its magic bytes on load
+//! (decompression needs the "zstd" feature).
+
+use std::borrow::Cow;
+use std::io;
+
+/// Magic bytes at the start of an uncompressed container
+pub const CONTAINER_MAGIC: [u8; 4] = *b"HORC";
+
+/// Magic bytes at the start of a zstd frame
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Default zstd level for compressed containers (assets are built once, loaded often)
+pub const DEFAULT_ZSTD_LEVEL: i32 = 19;
+
+const HEADER_LEN: usize = CONTAINER_MAGIC.len() + 8;
+
+/// Pack the two asset blobs into one container
+pub fn encode(ptrhash_data: &[u8], heights_data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + ptrhash_data.len() + heights_data.len());
+    out.extend_from_slice(&CONTAINER_MAGIC);
+    out.extend_from_slice(&(ptrhash_data.len() as u64).to_le_bytes());
+    out.extend_from_slice(ptrhash_data);
+    out.extend_from_slice(heights_data);
+    out
+}
+
+/// Split an uncompressed container into (PtrHash asset, packed heights asset)
+pub fn decode(data: &[u8]) -> io::Result<(&[u8], &[u8])> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    if data.len() < HEADER_LEN || data[..CONTAINER_MAGIC.len()] != CONTAINER_MAGIC {
+        return Err(invalid("Not a height oracle container"));
+    }
+    let len_bytes: [u8; 8] = data[CONTAINER_MAGIC.len()..HEADER_LEN]
+        .try_into()
+        .expect("slice is 8 bytes");
+    let ptrhash_len = usize::try_from(u64::from_le_bytes(len_bytes))
+        .map_err(|_| invalid("PtrHash length overflows usize"))?;
+
+    let body = &data[HEADER_LEN..];
+    if ptrhash_len > body.len() {
+        return Err(invalid("Container truncated inside the PtrHash asset"));
+    }
+    Ok(body.split_at(ptrhash_len))
+}
+
+/// Check whether `data` starts with a zstd frame
+pub fn is_zstd(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Compress a container with zstd (Feature: zstd)
+#[cfg(feature = "zstd")]
+pub fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    zstd::bulk::compress(data, level)
+}
+
+/// Return the uncompressed container, decompressing if it is a zstd frame
+pub fn decompress_if_needed(data: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    if !is_zstd(data) {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    #[cfg(feature = "zstd")]
+    {
+        zstd::stream::decode_all(data).map(Cow::Owned)
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Container is zstd-compressed; enable the \"zstd\" feature",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_roundtrip() {
+        let container = encode(b"phash", b"heights");
+        assert_eq!(
+            decode(&container).unwrap(),
+            (&b"phash"[..], &b"heights"[..])
+        );
+
+        assert!(decode(b"nope").is_err());
+        assert!(decode(&container[..HEADER_LEN + 2]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_compressed_roundtrip() {
+        let container = encode(&[7u8; 1000], &[9u8; 1000]);
+        let compressed = compress(&container, 3).unwrap();
+        assert!(is_zstd(&compressed));
+        assert!(compressed.len() < container.len());
+        assert_eq!(decompress_if_needed(&compressed).unwrap(), &container[..]);
+    }
+}
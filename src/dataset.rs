@@ -0,0 +1,799 @@
+//! Input datasets for building and validating oracles (Feature: generate)
+//!
+//! The canonical dataset is a text file with one reverse-hex block hash per line,
+//! where the line number is the height and `x` marks a skipped (version-2) block.
+//! A placeholder may carry the block's hash as `x <hash>`; such blocks are kept
+//! and flagged as version >= 2 (see [`crate::packing::VersionFlags`]).
+//! The same data can also be stored in the binary format of [`crate::hashdump`],
+//! or given as CSV rows with an explicit height column (see [`CsvOptions`]) or
+//! as JSON Lines records (feature `jsonl`). Any of them may be gzip- or
+//! zstd-compressed (features `gzip` and `zstd`), detected by magic bytes.
+
+use crate::commitment::DatasetCommitment;
+use crate::generate::{BuildOptions, BuildProgress};
+use crate::hashdump::{HashDump, HashRecord};
+use crate::BlockHash;
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Magic bytes at the start of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Parsed (block hash, height) pairs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dataset {
+    /// Block hashes in network byte order
+    pub block_hashes: Vec<BlockHash>,
+    /// Height of each block hash (same index)
+    pub heights: Vec<u32>,
+    /// Whether each block hash (same index) came from an `x <hash>` line
+    pub v2_flags: Vec<bool>,
+    /// Number of `x` placeholder lines that were skipped
+    pub placeholders: usize,
+}
+
+/// Column layout of a CSV dataset (see [`Dataset::from_csv`])
+///
+/// The default matches `height,block_hash,...` with a header row, as written
+/// by `export --format csv`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Zero-based index of the height column
+    pub height_col: usize,
+    /// Zero-based index of the block hash column
+    pub hash_col: usize,
+    /// Whether the first record is a header row
+    pub has_header: bool,
+    /// Whether hashes are hex in network byte order instead of the reverse hex bitcoind shows
+    pub network_order: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            height_col: 0,
+            hash_col: 1,
+            has_header: true,
+            network_order: false,
+        }
+    }
+}
+
+impl Dataset {
+    /// Parse a text file with one hash per line (height = line number)
+    pub fn from_txt(txt_path: &str) -> Result<Self> {
+        Self::from_txt_with_options(txt_path, &BuildOptions::default())
+    }
+
+    /// Parse a text file, reporting progress and honoring cancellation from `options`
+    ///
+    /// With the `parallel-build` feature the whole file is read at once and its
+    /// lines are parsed on the rayon thread pool; progress is then reported once.
+    pub fn from_txt_with_options(txt_path: &str, options: &BuildOptions) -> Result<Self> {
+        if cfg!(feature = "parallel-build") {
+            return Self::from_txt_parallel(txt_path, options);
+        }
+
+        let reader = open_dataset(Path::new(txt_path))?;
+
+        let mut dataset = Dataset::default();
+
+        for (line_number, line_result) in reader.lines().enumerate() {
+            let line = line_result.context("Failed to read line")?;
+
+            options.checkpoint(line_number + 1, |lines| BuildProgress::Parsing { lines })?;
+
+            dataset.push_line(line_number, parse_txt_line(line_number, &line)?);
+        }
+
+        Ok(dataset)
+    }
+
+    fn from_txt_parallel(txt_path: &str, options: &BuildOptions) -> Result<Self> {
+        use rayon::prelude::*;
+
+        let content = read_dataset_to_string(Path::new(txt_path))?;
+        let lines: Vec<&str> = content.lines().collect();
+        options.check_cancelled()?;
+
+        let parsed = lines
+            .par_iter()
+            .enumerate()
+            .map(|(line_number, line)| parse_txt_line(line_number, line))
+            .collect::<Result<Vec<_>>>()?;
+        options.report(BuildProgress::Parsing { lines: lines.len() });
+        options.check_cancelled()?;
+
+        let mut dataset = Dataset::default();
+        for (line_number, line) in parsed.into_iter().enumerate() {
+            dataset.push_line(line_number, line);
+        }
+        Ok(dataset)
+    }
+
+    /// Add one parsed text line; its height is the line number (0-indexed)
+    fn push_line(&mut self, line_number: usize, line: TxtLine) {
+        match line {
+            TxtLine::Empty => {}
+            TxtLine::Placeholder => self.placeholders += 1,
+            TxtLine::Entry { block_hash, is_v2 } => {
+                self.heights.push(line_number as u32);
+                self.block_hashes.push(block_hash);
+                self.v2_flags.push(is_v2);
+            }
+        }
+    }
+
+    /// Copy of the dataset without its version-2 entries, which count as placeholders
+    pub fn without_v2(&self) -> Self {
+        let mut dataset = Dataset {
+            placeholders: self.placeholders,
+            ..Dataset::default()
+        };
+        for ((&block_hash, &height), &is_v2) in self
+            .block_hashes
+            .iter()
+            .zip(&self.heights)
+            .zip(&self.v2_flags)
+        {
+            if is_v2 {
+                dataset.placeholders += 1;
+            } else {
+                dataset.block_hashes.push(block_hash);
+                dataset.heights.push(height);
+                dataset.v2_flags.push(false);
+            }
+        }
+        dataset
+    }
+
+    /// Copy of the dataset with only the entries whose height is in `range`
+    ///
+    /// Placeholders carry no height, so their count is kept as is.
+    pub fn in_height_range(&self, range: &std::ops::Range<u32>) -> Self {
+        let mut dataset = Dataset {
+            placeholders: self.placeholders,
+            ..Dataset::default()
+        };
+        for ((&block_hash, &height), &is_v2) in self
+            .block_hashes
+            .iter()
+            .zip(&self.heights)
+            .zip(&self.v2_flags)
+        {
+            if range.contains(&height) {
+                dataset.block_hashes.push(block_hash);
+                dataset.heights.push(height);
+                dataset.v2_flags.push(is_v2);
+            }
+        }
+        dataset
+    }
+
+    /// Load a binary dataset (see [`crate::hashdump`])
+    pub fn from_bin<P: AsRef<Path>>(bin_path: P) -> Result<Self> {
+        Ok(Self::from_hash_dump(&read_hash_dump(bin_path.as_ref())?))
+    }
+
+    /// Collect the (hash, height) entries of in-memory dataset records
+    pub fn from_hash_dump(dump: &HashDump) -> Self {
+        let mut dataset = Dataset::default();
+        for (height, record) in dump.iter() {
+            match *record {
+                HashRecord::Placeholder => dataset.placeholders += 1,
+                HashRecord::Hash { block_hash, is_v2 } => {
+                    dataset.block_hashes.push(block_hash);
+                    dataset.heights.push(height);
+                    dataset.v2_flags.push(is_v2);
+                }
+            }
+        }
+        dataset
+    }
+
+    /// Parse a CSV file of (height, block hash) rows
+    ///
+    /// Rows may come in any order and fields may be quoted. There are no
+    /// placeholders: heights missing from the file are simply not covered.
+    pub fn from_csv<P: AsRef<Path>>(csv_path: P, options: &CsvOptions) -> Result<Self> {
+        let path = csv_path.as_ref();
+        let content = read_dataset_to_string(path)?;
+
+        let mut dataset = Dataset::default();
+        let mut seen_heights = std::collections::HashSet::new();
+        let records = csv_records(&content)?;
+        for (line_number, fields) in records.iter().skip(usize::from(options.has_header)) {
+            let field = |col: usize, name: &str| {
+                fields.get(col).map(|field| field.trim()).with_context(|| {
+                    format!(
+                        "Line {line_number} has {} fields, no {name} column {col}",
+                        fields.len()
+                    )
+                })
+            };
+
+            let height = field(options.height_col, "height")?;
+            let height: u32 = height
+                .parse()
+                .with_context(|| format!("Invalid height {height:?} on line {line_number}"))?;
+            let parse_options = crate::HashParseOptions::new().network_order(options.network_order);
+            let block_hash =
+                crate::parse_block_hash_with(field(options.hash_col, "hash")?, &parse_options)
+                    .map_err(|e| anyhow::anyhow!(e))
+                    .with_context(|| format!("Failed to parse block hash on line {line_number}"))?;
+            anyhow::ensure!(
+                seen_heights.insert(height),
+                "Height {height} appears twice (again on line {line_number})"
+            );
+
+            dataset.block_hashes.push(block_hash);
+            dataset.heights.push(height);
+            dataset.v2_flags.push(false);
+        }
+        Ok(dataset)
+    }
+
+    /// Parse a JSON Lines file of `{"height": .., "hash": ".."}` records (Feature: jsonl)
+    ///
+    /// The hash is reverse hex and may also be called `block_hash`. An optional
+    /// `is_v2` boolean flags version-2 blocks, so `export --format jsonl` output
+    /// reads back. Other fields are ignored, and records are parsed one line at a time.
+    #[cfg(feature = "jsonl")]
+    pub fn from_jsonl<P: AsRef<Path>>(jsonl_path: P) -> Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct Record {
+            height: u32,
+            #[serde(alias = "block_hash")]
+            hash: BlockHash,
+            #[serde(default)]
+            is_v2: Option<bool>,
+        }
+
+        let reader = open_dataset(jsonl_path.as_ref())?;
+
+        let mut dataset = Dataset::default();
+        let mut seen_heights = std::collections::HashSet::new();
+        for (index, line_result) in reader.lines().enumerate() {
+            let line = line_result.context("Failed to read line")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_number = index + 1;
+            let record: Record = serde_json::from_str(&line)
+                .with_context(|| format!("Failed to parse record on line {line_number}"))?;
+            anyhow::ensure!(
+                seen_heights.insert(record.height),
+                "Height {} appears twice (again on line {line_number})",
+                record.height
+            );
+
+            dataset.block_hashes.push(record.hash);
+            dataset.heights.push(record.height);
+            dataset.v2_flags.push(record.is_v2.unwrap_or(false));
+        }
+        Ok(dataset)
+    }
+
+    /// Load a dataset in the format given by its extension
+    ///
+    /// `.bin` is binary, `.csv` is CSV read with [`CsvOptions::default`],
+    /// `.jsonl` is JSON Lines (feature `jsonl`), and anything else is text.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match format_extension(path) {
+            Some("bin") => Self::from_bin(path),
+            Some("csv") => Self::from_csv(path, &CsvOptions::default()),
+            #[cfg(feature = "jsonl")]
+            Some("jsonl") => Self::from_jsonl(path),
+            #[cfg(not(feature = "jsonl"))]
+            Some("jsonl") => anyhow::bail!(
+                "{} is a JSON Lines dataset; enable the \"jsonl\" feature",
+                path.display()
+            ),
+            _ => Self::from_txt(path_str(path)?),
+        }
+    }
+
+    /// Number of (hash, height) entries
+    pub fn len(&self) -> usize {
+        self.block_hashes.len()
+    }
+
+    /// Check if the dataset is empty
+    pub fn is_empty(&self) -> bool {
+        self.block_hashes.is_empty()
+    }
+
+    /// Iterate over (block hash, height) pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&BlockHash, u32)> + '_ {
+        self.block_hashes.iter().zip(self.heights.iter().copied())
+    }
+
+    /// Commitment to the entries and their version-2 flags (see [`crate::commitment`])
+    ///
+    /// Entries are taken in height order, so the same blocks give the same
+    /// commitment whichever format they were read from.
+    pub fn commitment(&self) -> DatasetCommitment {
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        order.sort_unstable_by_key(|&i| (self.heights[i], self.block_hashes[i]));
+        DatasetCommitment::compute(
+            order
+                .into_iter()
+                .map(|i| (self.heights[i], self.v2_flags[i], &self.block_hashes[i])),
+        )
+    }
+}
+
+/// Boxed iterator over (block hash, height) entries, as yielded by a [`KeySource`]
+pub type EntryIter<'a> = Box<dyn Iterator<Item = Result<(BlockHash, u32)>> + 'a>;
+
+/// A re-readable source of (block hash, height) entries
+///
+/// Used by [`HeightOracle::build_streaming`](crate::HeightOracle::build_streaming),
+/// which makes several passes and never holds all entries in memory.
+pub trait KeySource: Sync {
+    /// Start a new pass over all entries
+    fn entries(&self) -> Result<EntryIter<'_>>;
+}
+
+impl KeySource for Dataset {
+    fn entries(&self) -> Result<EntryIter<'_>> {
+        Ok(Box::new(
+            self.iter()
+                .map(|(block_hash, height)| Ok((*block_hash, height))),
+        ))
+    }
+}
+
+/// [`KeySource`] that re-reads a text dataset on every pass
+#[derive(Debug, Clone)]
+pub struct TxtKeySource {
+    path: std::path::PathBuf,
+}
+
+impl TxtKeySource {
+    /// Stream entries from the text dataset at `path` (same format as [`Dataset::from_txt`])
+    pub fn new<P: Into<std::path::PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl KeySource for TxtKeySource {
+    fn entries(&self) -> Result<EntryIter<'_>> {
+        let entries = open_dataset(&self.path)?.lines().enumerate().filter_map(
+            |(line_number, line_result)| {
+                let line = match line_result.context("Failed to read line") {
+                    Ok(line) => line,
+                    Err(e) => return Some(Err(e)),
+                };
+                let line = line.trim();
+                if line.is_empty() || line == "x" {
+                    return None;
+                }
+                let (line, _) = split_v2_annotation(line);
+                Some(
+                    crate::parse_block_hash(line)
+                        .map(|block_hash| (block_hash, line_number as u32))
+                        .map_err(|e| anyhow::anyhow!(e))
+                        .with_context(|| {
+                            format!("Failed to parse block hash on line {}", line_number + 1)
+                        }),
+                )
+            },
+        );
+        Ok(Box::new(entries))
+    }
+}
+
+/// Read a text or binary dataset as one entry per height, `None` for placeholders
+///
+/// Binary datasets are chosen by the `.bin` extension and must start at height 0.
+pub fn read_entries<P: AsRef<Path>>(path: P) -> Result<Vec<Option<BlockHash>>> {
+    let path = path.as_ref();
+    if !is_bin_path(path) {
+        return read_txt_entries(path_str(path)?);
+    }
+
+    let dump = read_hash_dump(path)?;
+    anyhow::ensure!(
+        dump.start_height == 0,
+        "Binary dataset {} starts at height {}, expected 0",
+        path.display(),
+        dump.start_height
+    );
+    Ok(dump
+        .records
+        .iter()
+        .map(|record| match *record {
+            HashRecord::Placeholder => None,
+            HashRecord::Hash { block_hash, .. } => Some(block_hash),
+        })
+        .collect())
+}
+
+fn is_bin_path(path: &Path) -> bool {
+    format_extension(path) == Some("bin")
+}
+
+/// Extension naming a dataset's format, looking past a `.gz` or `.zst` suffix
+fn format_extension(path: &Path) -> Option<&str> {
+    match path.extension()?.to_str()? {
+        "gz" | "zst" => Path::new(path.file_stem()?).extension()?.to_str(),
+        ext => Some(ext),
+    }
+}
+
+/// Open a dataset file, decompressing it if it starts with gzip or zstd magic bytes
+fn open_dataset(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let start = reader
+        .fill_buf()
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let (is_gzip, is_zstd) = (
+        start.starts_with(&GZIP_MAGIC),
+        crate::container::is_zstd(start),
+    );
+
+    if is_gzip {
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )));
+        #[cfg(not(feature = "gzip"))]
+        anyhow::bail!(
+            "{} is gzip-compressed; enable the \"gzip\" feature",
+            path.display()
+        );
+    }
+    if is_zstd {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .with_context(|| format!("Failed to start zstd decoding: {}", path.display()))?,
+        )));
+        #[cfg(not(feature = "zstd"))]
+        anyhow::bail!(
+            "{} is zstd-compressed; enable the \"zstd\" feature",
+            path.display()
+        );
+    }
+    Ok(Box::new(reader))
+}
+
+/// Read a whole (possibly compressed) dataset file as text
+fn read_dataset_to_string(path: &Path) -> Result<String> {
+    let mut content = String::new();
+    open_dataset(path)?
+        .read_to_string(&mut content)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    Ok(content)
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("Dataset path is not valid UTF-8: {}", path.display()))
+}
+
+fn read_hash_dump(path: &Path) -> Result<HashDump> {
+    crate::hashdump::read(open_dataset(path)?)
+        .with_context(|| format!("Failed to read binary dataset: {}", path.display()))
+}
+
+/// Read a text dataset as one entry per line (height = line number), `None` for placeholders
+pub fn read_txt_entries(txt_path: &str) -> Result<Vec<Option<BlockHash>>> {
+    let mut entries = Vec::new();
+    for (line_number, line_result) in open_dataset(Path::new(txt_path))?.lines().enumerate() {
+        let line = line_result.context("Failed to read line")?;
+        let line = line.trim();
+        if line.is_empty() || line == "x" {
+            entries.push(None);
+            continue;
+        }
+        let (line, _) = split_v2_annotation(line);
+        let block_hash = crate::parse_block_hash(line)
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("Failed to parse block hash on line {}", line_number + 1))?;
+        entries.push(Some(block_hash));
+    }
+    Ok(entries)
+}
+
+/// Records of CSV text, each with the (1-based) line it starts on
+///
+/// Quoted fields may contain commas, newlines and `""` escapes. Blank lines are skipped.
+fn csv_records(content: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let (mut line_number, mut record_line, mut in_quotes) = (1, 1, false);
+
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => {
+                    line_number += usize::from(c == '\n');
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                push_csv_record(&mut records, record_line, std::mem::take(&mut fields));
+                line_number += 1;
+                record_line = line_number;
+            }
+            _ => field.push(c),
+        }
+    }
+    anyhow::ensure!(
+        !in_quotes,
+        "Unterminated quoted field in the record starting on line {record_line}"
+    );
+    fields.push(field);
+    push_csv_record(&mut records, record_line, fields);
+    Ok(records)
+}
+
+fn push_csv_record(records: &mut Vec<(usize, Vec<String>)>, line: usize, fields: Vec<String>) {
+    let blank = fields.len() == 1 && fields[0].trim().is_empty();
+    if !blank {
+        records.push((line, fields));
+    }
+}
+
+/// One line of a text dataset
+enum TxtLine {
+    Empty,
+    Placeholder,
+    Entry { block_hash: BlockHash, is_v2: bool },
+}
+
+/// Parse one line of a text dataset; `line_number` (0-indexed) is only used in errors
+fn parse_txt_line(line_number: usize, line: &str) -> Result<TxtLine> {
+    let line = line.trim();
+
+    // Skip empty lines
+    if line.is_empty() {
+        return Ok(TxtLine::Empty);
+    }
+
+    // If the line is a placeholder 'x' (version-2 blocks are marked with 'x'), skip it
+    if line == "x" {
+        return Ok(TxtLine::Placeholder);
+    }
+
+    // Annotated placeholders 'x <hash>' keep the hash and flag the block as version 2
+    let (line, is_v2) = split_v2_annotation(line);
+
+    // Convert from reverse hex to network byte order
+    let block_hash = crate::parse_block_hash(line)
+        .map_err(|e| anyhow::anyhow!(e))
+        .with_context(|| format!("Failed to parse block hash on line {}", line_number + 1))?;
+
+    Ok(TxtLine::Entry { block_hash, is_v2 })
+}
+
+/// Strip an `x ` version-2 annotation, returning the hash part and whether it was present
+fn split_v2_annotation(line: &str) -> (&str, bool) {
+    match line.strip_prefix("x ") {
+        Some(hash) => (hash.trim_start(), true),
+        None => (line, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const GENESIS: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+    const BLOCK_1: &str = "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048";
+
+    fn write_txt(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_txt_key_source_matches_dataset() {
+        let file = write_txt(&format!("{GENESIS}\nx\n\nx {BLOCK_1}\n"));
+        let path = file.path().to_str().unwrap();
+
+        let dataset = Dataset::from_txt(path).unwrap();
+        assert_eq!(dataset.heights, vec![0, 3]);
+        assert_eq!(dataset.v2_flags, vec![false, true]);
+        assert_eq!(dataset.placeholders, 1);
+
+        // Every pass yields the same entries as the in-memory dataset
+        let source = TxtKeySource::new(path);
+        for _ in 0..2 {
+            let streamed: Vec<_> = source.entries().unwrap().map(Result::unwrap).collect();
+            let loaded: Vec<_> = dataset.entries().unwrap().map(Result::unwrap).collect();
+            assert_eq!(streamed, loaded);
+        }
+    }
+
+    #[test]
+    fn test_without_v2() {
+        let file = write_txt(&format!("{GENESIS}\nx\nx {BLOCK_1}\n"));
+        let dataset = Dataset::from_txt(file.path().to_str().unwrap()).unwrap();
+
+        let v1_only = dataset.without_v2();
+        assert_eq!(v1_only.heights, vec![0]);
+        assert_eq!(v1_only.v2_flags, vec![false]);
+        assert_eq!(v1_only.placeholders, 2);
+    }
+
+    #[test]
+    fn test_in_height_range() {
+        let file = write_txt(&format!("{GENESIS}\nx\nx {BLOCK_1}\n"));
+        let dataset = Dataset::from_txt(file.path().to_str().unwrap()).unwrap();
+
+        let partial = dataset.in_height_range(&(1..10));
+        assert_eq!(partial.heights, vec![2]);
+        assert_eq!(partial.v2_flags, vec![true]);
+        assert_eq!(partial.placeholders, 1);
+        assert!(dataset.in_height_range(&(3..10)).is_empty());
+    }
+
+    #[test]
+    fn test_bin_matches_txt() {
+        let txt = write_txt(&format!("{GENESIS}\nx\nx {BLOCK_1}\n"));
+        let records = [
+            HashRecord::Hash {
+                block_hash: GENESIS.parse().unwrap(),
+                is_v2: false,
+            },
+            HashRecord::Placeholder,
+            HashRecord::Hash {
+                block_hash: BLOCK_1.parse().unwrap(),
+                is_v2: true,
+            },
+        ];
+        let bin = tempfile::Builder::new().suffix(".bin").tempfile().unwrap();
+        crate::hashdump::write(bin.as_file(), 0, &records).unwrap();
+
+        let from_txt = Dataset::from_path(txt.path()).unwrap();
+        assert_eq!(Dataset::from_path(bin.path()).unwrap(), from_txt);
+        assert_eq!(
+            read_entries(bin.path()).unwrap(),
+            read_entries(txt.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_csv_dataset() {
+        let csv = write_txt(&format!(
+            "\"hash\",\"height\"\r\n\"{BLOCK_1}\",1\r\n\r\n  {GENESIS} , \"0\"\r\n"
+        ));
+        let options = CsvOptions {
+            height_col: 1,
+            hash_col: 0,
+            ..CsvOptions::default()
+        };
+        let dataset = Dataset::from_csv(csv.path(), &options).unwrap();
+        assert_eq!(dataset.heights, vec![1, 0]);
+        assert_eq!(dataset.block_hashes[1], GENESIS.parse().unwrap());
+        assert_eq!(dataset.placeholders, 0);
+
+        // Network byte order, no header
+        let genesis: BlockHash = GENESIS.parse().unwrap();
+        let csv = write_txt(&format!("0,{}\n", hex::encode(genesis.as_bytes())));
+        let options = CsvOptions {
+            has_header: false,
+            network_order: true,
+            ..CsvOptions::default()
+        };
+        let dataset = Dataset::from_csv(csv.path(), &options).unwrap();
+        assert_eq!(dataset.block_hashes, vec![genesis]);
+
+        // Our own CSV export reads back with the default options
+        let csv = tempfile::Builder::new().suffix(".csv").tempfile().unwrap();
+        std::fs::write(
+            csv.path(),
+            format!("height,block_hash,is_v2\n0,{GENESIS},\n1,\"{BLOCK_1}\",false\n"),
+        )
+        .unwrap();
+        assert_eq!(Dataset::from_path(csv.path()).unwrap().heights, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_csv_dataset_errors() {
+        let options = CsvOptions::default();
+        let error = |contents: &str| {
+            let csv = write_txt(contents);
+            format!("{:#}", Dataset::from_csv(csv.path(), &options).unwrap_err())
+        };
+        assert!(error(&format!("h,b\n0,{GENESIS}\n0,{BLOCK_1}\n")).contains("appears twice"));
+        assert!(error("h,b\n0\n").contains("no hash column 1"));
+        assert!(error(&format!("h,b\n-1,{GENESIS}\n")).contains("line 2"));
+        assert!(error(&format!("h,b\n0,\"{GENESIS}\n")).contains("Unterminated"));
+    }
+
+    #[cfg(feature = "jsonl")]
+    #[test]
+    fn test_jsonl_dataset() {
+        let jsonl = tempfile::Builder::new()
+            .suffix(".jsonl")
+            .tempfile()
+            .unwrap();
+        std::fs::write(
+            jsonl.path(),
+            format!(
+                "{{\"height\": 1, \"hash\": \"{BLOCK_1}\", \"size\": 215}}\n\n\
+                 {{\"height\":0,\"block_hash\":\"{GENESIS}\",\"is_v2\":true}}\n"
+            ),
+        )
+        .unwrap();
+        let dataset = Dataset::from_path(jsonl.path()).unwrap();
+        assert_eq!(dataset.heights, vec![1, 0]);
+        assert_eq!(dataset.block_hashes[1], GENESIS.parse().unwrap());
+        assert_eq!(dataset.v2_flags, vec![false, true]);
+
+        let bad = write_txt(&format!(
+            "{{\"height\":0,\"hash\":\"{GENESIS}\"}}\n{{\"height\":1}}\n"
+        ));
+        let err = Dataset::from_jsonl(bad.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+    }
+
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn test_compressed_datasets() {
+        let text = format!("{GENESIS}\nx\nx {BLOCK_1}\n");
+        let expected = Dataset::from_txt(write_txt(&text).path().to_str().unwrap()).unwrap();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let zstd = zstd::encode_all(text.as_bytes(), 0).unwrap();
+
+        for (suffix, compressed) in [(".txt.gz", gzip.finish().unwrap()), (".txt.zst", zstd)] {
+            let file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+            std::fs::write(file.path(), compressed).unwrap();
+            assert_eq!(Dataset::from_path(file.path()).unwrap(), expected);
+            assert_eq!(
+                read_entries(file.path()).unwrap(),
+                vec![
+                    Some(GENESIS.parse().unwrap()),
+                    None,
+                    Some(BLOCK_1.parse().unwrap())
+                ]
+            );
+        }
+
+        // The format still comes from the extension under the compression suffix
+        let csv = format!("height,block_hash\n0,{GENESIS}\n");
+        let file = tempfile::Builder::new()
+            .suffix(".csv.zst")
+            .tempfile()
+            .unwrap();
+        std::fs::write(file.path(), zstd::encode_all(csv.as_bytes(), 0).unwrap()).unwrap();
+        assert_eq!(Dataset::from_path(file.path()).unwrap().heights, vec![0]);
+    }
+
+    #[test]
+    fn test_txt_key_source_reports_bad_lines() {
+        let file = write_txt(&format!("{GENESIS}\nnot-a-hash\n"));
+        let source = TxtKeySource::new(file.path());
+        let errors = source.entries().unwrap().filter(Result::is_err).count();
+        assert_eq!(errors, 1);
+    }
+}
@@ -0,0 +1,239 @@
+//! Height → difficulty target
+//!
+//! Verification tooling checking proof of work needs each block's target, and
+//! storing whole headers for that is wasteful. On mainnet `nBits` only changes
+//! at a retarget, every [`DIFFICULTY_ADJUSTMENT_INTERVAL`] blocks, so a
+//! [`DifficultyTable`] stores one value per retarget period: 114 for the
+//! pre-BIP34 chain, under 500 bytes. Testnet3's minimum-difficulty blocks
+//! differ from their period and are stored as exceptions.
+//!
+//! The builder writes the table from raw headers (`--timestamps-from`).
+//!
+//! Asset format: [num_blocks: u32][num_periods: u32][num_exceptions: u32]
+//! [per period: bits: u32][per exception: height: u32, bits: u32]
+
+use crate::consensus::{difficulty_period, DIFFICULTY_ADJUSTMENT_INTERVAL};
+use crate::header::{target_from_bits, BlockHeader, HEADER_SIZE};
+use crate::{BlockHash, HeightLookup};
+use std::io::{self, Read, Write};
+#[cfg(feature = "embedded-difficulty")]
+use std::sync::OnceLock;
+
+// Embedded difficulty table at compile time
+#[cfg(feature = "embedded-difficulty")]
+const DIFFICULTY_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/difficulty.bits.dat"
+));
+
+/// Compact target (`nBits`) per height
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DifficultyTable {
+    num_blocks: u32,
+    /// `nBits` of the first block of each retarget period
+    periods: Vec<u32>,
+    /// `(height, nBits)` of blocks that differ from their period, by height
+    exceptions: Vec<(u32, u32)>,
+}
+
+impl DifficultyTable {
+    /// Build from raw headers in height order, starting at genesis
+    pub fn from_headers(headers: &[[u8; HEADER_SIZE]]) -> Self {
+        let bits: Vec<u32> = headers
+            .iter()
+            .map(|raw| BlockHeader::parse(raw).bits)
+            .collect();
+        Self::from_bits(&bits)
+    }
+
+    /// Build from each block's `nBits`, indexed by height
+    fn from_bits(bits: &[u32]) -> Self {
+        let periods: Vec<u32> = bits
+            .chunks(DIFFICULTY_ADJUSTMENT_INTERVAL as usize)
+            .map(|period| period[0])
+            .collect();
+        let exceptions = (0u32..)
+            .zip(bits)
+            .filter(|&(height, &bits)| bits != periods[difficulty_period(height) as usize])
+            .map(|(height, &bits)| (height, bits))
+            .collect();
+        Self {
+            num_blocks: bits.len() as u32,
+            periods,
+            exceptions,
+        }
+    }
+
+    /// Compact target (`nBits`) of the block at `height`
+    ///
+    /// `None` past the end of the table.
+    pub fn nbits_at(&self, height: u32) -> Option<u32> {
+        if height >= self.num_blocks {
+            return None;
+        }
+        match self
+            .exceptions
+            .binary_search_by_key(&height, |&(height, _)| height)
+        {
+            Ok(i) => Some(self.exceptions[i].1),
+            Err(_) => Some(self.periods[difficulty_period(height) as usize]),
+        }
+    }
+
+    /// Proof-of-work target of the block at `height`, as a 256-bit little-endian number
+    pub fn target_for(&self, height: u32) -> Option<[u8; 32]> {
+        self.nbits_at(height).map(target_from_bits)
+    }
+
+    /// `nBits` at the height `oracle` returns for `block_hash` (unchecked)
+    ///
+    /// Like the lookup itself, hashes outside the oracle's dataset get the
+    /// `nBits` of some other height.
+    pub fn nbits_for<L: HeightLookup + ?Sized>(
+        &self,
+        oracle: &L,
+        block_hash: &BlockHash,
+    ) -> Option<u32> {
+        self.nbits_at(oracle.get_height_unchecked(block_hash))
+    }
+
+    /// Number of retarget periods stored
+    pub fn num_periods(&self) -> usize {
+        self.periods.len()
+    }
+
+    /// Number of heights covered
+    pub fn len(&self) -> usize {
+        self.num_blocks as usize
+    }
+
+    /// Check if no heights are covered
+    pub fn is_empty(&self) -> bool {
+        self.num_blocks == 0
+    }
+
+    /// Serialize the table to writer
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.num_blocks.to_le_bytes())?;
+        writer.write_all(&(self.periods.len() as u32).to_le_bytes())?;
+        writer.write_all(&(self.exceptions.len() as u32).to_le_bytes())?;
+        for bits in &self.periods {
+            writer.write_all(&bits.to_le_bytes())?;
+        }
+        for (height, bits) in &self.exceptions {
+            writer.write_all(&height.to_le_bytes())?;
+            writer.write_all(&bits.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a table from reader
+    pub fn deserialize<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut bytes = [0u8; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let num_blocks = read_u32()?;
+        let num_periods = read_u32()?;
+        let num_exceptions = read_u32()?;
+        if num_periods != num_blocks.div_ceil(DIFFICULTY_ADJUSTMENT_INTERVAL)
+            || num_exceptions > num_blocks
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Difficulty table has {num_periods} periods and {num_exceptions} exceptions for {num_blocks} blocks"
+                ),
+            ));
+        }
+
+        let periods = (0..num_periods)
+            .map(|_| read_u32())
+            .collect::<io::Result<Vec<_>>>()?;
+        let exceptions = (0..num_exceptions)
+            .map(|_| Ok((read_u32()?, read_u32()?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let valid = exceptions.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && exceptions
+                .last()
+                .is_none_or(|&(height, _)| height < num_blocks);
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Difficulty exceptions are not in height order within the table",
+            ));
+        }
+        Ok(Self {
+            num_blocks,
+            periods,
+            exceptions,
+        })
+    }
+}
+
+/// Global singleton for the embedded difficulty table
+#[cfg(feature = "embedded-difficulty")]
+static EMBEDDED_DIFFICULTY: OnceLock<DifficultyTable> = OnceLock::new();
+
+#[cfg(feature = "embedded-difficulty")]
+fn embedded_difficulty() -> &'static DifficultyTable {
+    EMBEDDED_DIFFICULTY.get_or_init(|| {
+        DifficultyTable::deserialize(DIFFICULTY_DATA)
+            .expect("Failed to load embedded difficulty table")
+    })
+}
+
+/// Proof-of-work target at `height`, from the embedded table (Feature: embedded-difficulty)
+#[cfg(feature = "embedded-difficulty")]
+pub fn target_for(height: u32) -> Option<[u8; 32]> {
+    embedded_difficulty().target_for(height)
+}
+
+/// `nBits` of a pre-BIP34 block, using the embedded oracle and table (unchecked)
+/// (Feature: embedded-difficulty)
+#[cfg(feature = "embedded-difficulty")]
+pub fn nbits_for(block_hash: &BlockHash) -> Option<u32> {
+    embedded_difficulty().nbits_at(crate::guess_height_prebip34block_unchecked(block_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difficulty_table() {
+        // Two full periods and part of a third, with one min-difficulty block
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let mut bits = vec![0x1d00ffff; interval];
+        bits.extend(vec![0x1d00d86a; interval]);
+        bits.extend(vec![0x1c2a1115; 10]);
+        bits[interval + 5] = 0x1d00ffff;
+        let table = DifficultyTable::from_bits(&bits);
+        assert_eq!(table.num_periods(), 3);
+        assert_eq!(table.exceptions, [(interval as u32 + 5, 0x1d00ffff)]);
+        assert_eq!(table.len(), bits.len());
+
+        for (height, &expected) in (0u32..).zip(&bits) {
+            assert_eq!(table.nbits_at(height), Some(expected));
+        }
+        assert_eq!(table.nbits_at(bits.len() as u32), None);
+        assert_eq!(table.target_for(0), Some(target_from_bits(0x1d00ffff)));
+
+        let mut data = Vec::new();
+        table.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 12 + 3 * 4 + 8);
+        assert_eq!(
+            DifficultyTable::deserialize(data.as_slice()).unwrap(),
+            table
+        );
+
+        // A period count that does not match the block count is rejected
+        data[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(DifficultyTable::deserialize(data.as_slice()).is_err());
+        assert_eq!(
+            DifficultyTable::deserialize(&[0u8; 12][..]).unwrap(),
+            DifficultyTable::default()
+        );
+    }
+}
@@ -1,68 +1,922 @@
-//! Zero-Copy Runtime (Feature: embedded)
+//! Zero-Copy Runtime (Features: embedded, embedded-testnet3, runtime-assets)
 //!
-//! This module provides zero-copy runtime lookups using embedded asset data.
+//! This module provides zero-copy runtime lookups using embedded asset data
+//! (`embedded`), or the same lookup API over asset files loaded at runtime
+//! (`runtime-assets`) for packagers who ship the assets separately.
+//!
+//! Each network's assets are separate statics: mainnet behind `embedded` (alias
+//! `embedded-mainnet`), testnet3 behind `embedded-testnet3`. Enable both to serve
+//! both networks from one binary through [`HeightOracleEmbedded::for_network`].
+//!
+//! Loading normally decodes every height up front. [`HeightOracleEmbedded::from_bytes_lazy`]
+//! keeps them packed instead and decodes only the 9-byte group holding an entry
+//! on each lookup, which suits short-lived processes that do a few lookups. The
+//! `embedded-lazy` feature loads the embedded oracle that way, borrowing the
+//! embedded bytes without copying them.
 
-use crate::{BlockHash, PtrHashType};
-use epserde::prelude::*;
+use crate::cache::LookupCache;
+use crate::commitment::DatasetCommitment;
+use crate::format::{self, AssetKind, InputPolicy};
+use crate::memory::MemoryStats;
+use crate::packing::{PackedHeights, VersionFlags};
+use crate::phf::Phf;
+use crate::stats::QueryCounters;
+#[cfg(feature = "mmap")]
+use crate::store::MmapHeights;
+use crate::store::{HeightStore, Lookup};
+use crate::{BlockHash, HeightLookup, Network};
+use std::io;
+use std::sync::Arc;
+#[cfg(feature = "embedded")]
 use std::sync::OnceLock;
 
 // Embedded oracle data at compile time
-const PTRHASH_DATA: &[u8] = include_bytes!(concat!(
-    env!("CARGO_MANIFEST_DIR"),
-    "/assets/phash.ptrh.dat"
-));
+#[cfg(all(feature = "embedded", not(feature = "embedded-zstd")))]
+const PTRHASH_DATA: &[u8] =
+    include_bytes!(concat!(env!("HEIGHT_ORACLE_ASSET_DIR"), "/phash.ptrh.dat"));
+#[cfg(all(feature = "embedded", not(feature = "embedded-zstd")))]
 const HEIGHTS_DATA: &[u8] = include_bytes!(concat!(
-    env!("CARGO_MANIFEST_DIR"),
-    "/assets/heights.u18packed.dat"
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/heights.u18packed.dat"
+));
+
+// Testnet3 assets (Feature: embedded-testnet3); small enough to embed uncompressed
+#[cfg(feature = "embedded-testnet3")]
+const TESTNET3_PTRHASH_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/testnet3/phash.ptrh.dat"
+));
+#[cfg(feature = "embedded-testnet3")]
+const TESTNET3_HEIGHTS_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/testnet3/heights.u18packed.dat"
 ));
 
+// zstd-compressed container of both assets, produced by build.rs (Feature: embedded-zstd)
+#[cfg(feature = "embedded-zstd")]
+const CONTAINER_DATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/oracle.hoc.zst"));
+
+/// Environment variable naming a PtrHash asset file to use instead of the embedded one
+///
+/// Read by [`HeightOracleEmbedded::load_embedded_with_override`] together with
+/// [`HEIGHTS_OVERRIDE_VAR`].
+pub const PTRHASH_OVERRIDE_VAR: &str = "HEIGHT_ORACLE_PTRHASH";
+
+/// Environment variable naming a heights asset file to use instead of the embedded one
+pub const HEIGHTS_OVERRIDE_VAR: &str = "HEIGHT_ORACLE_HEIGHTS";
+
+/// Known mainnet (block hash, height) pairs checked by [`HeightOracleEmbedded::verify_checkpoints`]
+///
+/// All are version-1 blocks, so they are in every mainnet oracle. Height 227,930,
+/// the last pre-BIP34 height, is not listed: that block is version 2 and is not
+/// part of the oracle.
+pub const MAINNET_CHECKPOINTS: &[(&str, u32)] = &[
+    (
+        "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        0,
+    ),
+    (
+        "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+        1,
+    ),
+    (
+        "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d",
+        11_111,
+    ),
+    (
+        "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6",
+        33_333,
+    ),
+    (
+        "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20",
+        74_000,
+    ),
+    (
+        "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506",
+        100_000,
+    ),
+    (
+        "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97",
+        105_000,
+    ),
+    (
+        "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe",
+        134_444,
+    ),
+];
+
 /// Zero-copy embedded oracle using real epserde deserialization
+///
+/// A handle to reference-counted tables: cloning is cheap and clones share the
+/// loaded data, which is freed when the last clone is dropped. Each loader call
+/// returns a new, independent oracle.
+#[derive(Clone)]
 pub struct HeightOracleEmbedded {
-    phash: PtrHashType,
-    heights: Vec<u32>,
+    tables: Arc<Tables>,
+    version_flags: Option<Arc<VersionFlags>>,
+    input_policy: Option<InputPolicy>,
+    height_range: Option<std::ops::Range<u32>>,
+    dataset_commitment: Option<DatasetCommitment>,
+    cache: Option<Arc<LookupCache>>,
+}
+
+/// The lookup tables shared by clones of a [`HeightOracleEmbedded`]
+struct Tables {
+    phash: Phf,
+    heights: Heights,
+    query_counters: QueryCounters,
+}
+
+/// The heights store picked at load time
+///
+/// The built-in stores are variants rather than trait objects, so lookups
+/// through them dispatch statically; only [`HeightOracleEmbedded::from_store`]
+/// backends pay for a virtual call.
+enum Heights {
+    Decoded(Vec<u32>),
+    Packed(PackedHeights),
+    #[cfg(feature = "mmap")]
+    Mmap(MmapHeights),
+    Custom(Box<dyn HeightStore>),
+}
+
+impl HeightStore for Heights {
+    fn len(&self) -> usize {
+        match self {
+            Self::Decoded(heights) => heights.len(),
+            Self::Packed(heights) => heights.len(),
+            #[cfg(feature = "mmap")]
+            Self::Mmap(heights) => heights.len(),
+            Self::Custom(heights) => heights.len(),
+        }
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        match self {
+            Self::Decoded(heights) => heights[index],
+            Self::Packed(heights) => heights.at(index),
+            #[cfg(feature = "mmap")]
+            Self::Mmap(heights) => heights.at(index),
+            Self::Custom(heights) => heights.at(index),
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Self::Decoded(heights) => heights.heap_bytes(),
+            Self::Packed(heights) => heights.heap_bytes(),
+            #[cfg(feature = "mmap")]
+            Self::Mmap(heights) => heights.heap_bytes(),
+            Self::Custom(heights) => heights.heap_bytes(),
+        }
+    }
+}
+
+/// Build metadata recorded in a heights asset header
+#[derive(Default)]
+struct HeightsHeader {
+    input_policy: Option<InputPolicy>,
+    height_range: Option<std::ops::Range<u32>>,
+    dataset_commitment: Option<DatasetCommitment>,
+}
+
+impl HeightsHeader {
+    fn detect(heights_data: &[u8]) -> Self {
+        Self {
+            input_policy: format::detect_input_policy(heights_data),
+            height_range: format::detect_height_range(heights_data),
+            dataset_commitment: format::detect_dataset_commitment(heights_data),
+        }
+    }
 }
 
 impl HeightOracleEmbedded {
     /// Load from the embedded static data using epserde
+    #[cfg(feature = "embedded")]
     pub fn load_embedded() -> Self {
-        // Load PtrHash from embedded data using epserde
-        let mut ptrhash_cursor = std::io::Cursor::new(PTRHASH_DATA);
-        let phash = PtrHashType::deserialize_full(&mut ptrhash_cursor)
-            .expect("Failed to deserialize embedded PtrHash");
+        Self::try_load_embedded().expect("Failed to deserialize embedded oracle")
+    }
+
+    /// Load the embedded data and check it against [`MAINNET_CHECKPOINTS`]
+    ///
+    /// Returns an error instead of panicking, so corrupted or mismatched
+    /// embedded assets are caught at startup rather than serving wrong heights.
+    #[cfg(feature = "embedded")]
+    pub fn load_embedded_verified() -> io::Result<Self> {
+        let oracle = Self::try_load_embedded()?;
+        oracle.verify_checkpoints()?;
+        Ok(oracle)
+    }
+
+    /// Load an independent instance from the embedded static data
+    ///
+    /// Unlike the global oracle behind [`guess_height_prebip34block_unchecked`],
+    /// each call returns a new instance that is freed when its last clone is dropped.
+    ///
+    /// With `embedded-lazy`, the heights stay packed in the embedded bytes
+    /// (see [`Self::from_bytes_lazy`]).
+    #[cfg(all(feature = "embedded", not(feature = "embedded-zstd")))]
+    pub fn try_load_embedded() -> io::Result<Self> {
+        if cfg!(feature = "embedded-lazy") {
+            Self::from_static(PTRHASH_DATA, HEIGHTS_DATA)
+        } else {
+            Self::from_bytes(PTRHASH_DATA, HEIGHTS_DATA)
+        }
+    }
 
-        // Load heights from embedded data using our packing format
-        let mut heights_cursor = std::io::Cursor::new(HEIGHTS_DATA);
-        let heights = crate::packing::deserialize_heights(&mut heights_cursor)
-            .expect("Failed to deserialize embedded heights");
+    /// Decompress and load an independent instance from the embedded container (Feature: embedded-zstd)
+    #[cfg(feature = "embedded-zstd")]
+    pub fn try_load_embedded() -> io::Result<Self> {
+        let data = crate::container::decompress_if_needed(CONTAINER_DATA)?;
+        let (ptrhash_data, heights_data) = crate::container::decode(&data)?;
+        Self::from_embedded_bytes(ptrhash_data, heights_data)
+    }
+
+    /// [`Self::from_bytes`], or [`Self::from_bytes_lazy`] with `embedded-lazy`
+    #[cfg(feature = "embedded")]
+    fn from_embedded_bytes(ptrhash_data: &[u8], heights_data: &[u8]) -> io::Result<Self> {
+        if cfg!(feature = "embedded-lazy") {
+            Self::from_bytes_lazy(ptrhash_data, heights_data)
+        } else {
+            Self::from_bytes(ptrhash_data, heights_data)
+        }
+    }
+
+    /// Load an independent instance of the embedded oracle for `network`
+    ///
+    /// Fails with `Unsupported` if this build did not embed that network's
+    /// assets (see the module docs for the features).
+    pub fn for_network(network: Network) -> io::Result<Self> {
+        match network {
+            #[cfg(feature = "embedded")]
+            Network::Mainnet => Self::try_load_embedded(),
+            #[cfg(feature = "embedded-testnet3")]
+            Network::Testnet3 => Self::from_bytes(TESTNET3_PTRHASH_DATA, TESTNET3_HEIGHTS_DATA),
+            #[allow(unreachable_patterns)]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("No embedded {network} oracle in this build (enable embedded-{network})"),
+            )),
+        }
+    }
 
-        Self { phash, heights }
+    /// Load the asset files named by [`PTRHASH_OVERRIDE_VAR`] and [`HEIGHTS_OVERRIDE_VAR`]
+    /// if set, otherwise the embedded data
+    ///
+    /// Lets packagers ship newer or alternative assets without recompiling
+    /// downstream binaries. The global oracle behind
+    /// [`guess_height_prebip34block_unchecked`] is loaded this way. Set both
+    /// variables or neither; setting only one is an error.
+    #[cfg(feature = "embedded")]
+    pub fn load_embedded_with_override() -> io::Result<Self> {
+        Self::load_override(
+            std::env::var_os(PTRHASH_OVERRIDE_VAR),
+            std::env::var_os(HEIGHTS_OVERRIDE_VAR),
+        )
+    }
+
+    #[cfg(feature = "embedded")]
+    fn load_override(
+        ptrhash_path: Option<std::ffi::OsString>,
+        heights_path: Option<std::ffi::OsString>,
+    ) -> io::Result<Self> {
+        let read = |path: &std::ffi::OsStr| {
+            std::fs::read(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to read {}: {e}",
+                        std::path::Path::new(path).display()
+                    ),
+                )
+            })
+        };
+        match (ptrhash_path, heights_path) {
+            (Some(ptrhash_path), Some(heights_path)) => {
+                Self::from_embedded_bytes(&read(&ptrhash_path)?, &read(&heights_path)?)
+            }
+            (None, None) => Self::try_load_embedded(),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Set both {PTRHASH_OVERRIDE_VAR} and {HEIGHTS_OVERRIDE_VAR}, or neither"),
+            )),
+        }
+    }
+
+    /// Load from a single container (see [`crate::container`]), decompressing if needed
+    pub fn from_container_bytes(data: &[u8]) -> io::Result<Self> {
+        let data = crate::container::decompress_if_needed(data)?;
+        let (ptrhash_data, heights_data) = crate::container::decode(&data)?;
+        Self::from_bytes(ptrhash_data, heights_data)
+    }
+
+    /// Load from in-memory asset bytes (PtrHash asset + packed heights asset)
+    ///
+    /// Accepts both versioned and legacy (headerless) assets; see [`crate::format`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ptrhash_bytes = ptrhash_data.len(), heights_bytes = heights_data.len())
+        )
+    )]
+    pub fn from_bytes(ptrhash_data: &[u8], heights_data: &[u8]) -> io::Result<Self> {
+        Self::load(ptrhash_data, heights_data, |heights_data| {
+            // Load heights using our packing format
+            crate::packing::deserialize_heights(io::Cursor::new(heights_data)).map(Heights::Decoded)
+        })
+    }
+
+    /// Load from in-memory asset bytes, keeping the heights packed
+    ///
+    /// Copies the packed heights instead of decoding them, so loading costs
+    /// little more than the PtrHash deserialization. Each lookup then decodes
+    /// the one 9-byte group holding its entry, which is slightly slower than
+    /// [`Self::from_bytes`] for lookup-heavy workloads.
+    pub fn from_bytes_lazy(ptrhash_data: &[u8], heights_data: &[u8]) -> io::Result<Self> {
+        Self::load(ptrhash_data, heights_data, |heights_data| {
+            PackedHeights::from_serialized(heights_data).map(Heights::Packed)
+        })
+    }
+
+    /// Load from asset bytes compiled into the binary, keeping the heights packed in place
+    ///
+    /// Like [`Self::from_bytes_lazy`], but the heights are borrowed rather than
+    /// copied, so the only allocation is the deserialized PtrHash. Pairs with the
+    /// statics `height-oracle codegen` emits (see [`crate::codegen`]).
+    pub fn from_static(ptrhash_data: &[u8], heights_data: &'static [u8]) -> io::Result<Self> {
+        Self::load(ptrhash_data, heights_data, |heights_data| {
+            PackedHeights::from_static(heights_data).map(Heights::Packed)
+        })
+    }
+
+    /// Load over any heights backend (see [`crate::store`])
+    ///
+    /// `heights` must hold one height per perfect-hash index of the PtrHash
+    /// asset. There is no heights asset header to read, so
+    /// [`Self::input_policy`], [`Self::height_range`] and
+    /// [`Self::dataset_commitment`] are `None`.
+    pub fn from_store<S: HeightStore + 'static>(
+        ptrhash_data: &[u8],
+        heights: S,
+    ) -> io::Result<Self> {
+        let heights = Heights::Custom(Box::new(heights));
+        Self::assemble(ptrhash_data, HeightsHeader::default(), heights)
+    }
+
+    /// Load a PtrHash asset file and memory-map a heights asset file (Feature: mmap)
+    ///
+    /// The heights stay packed in the mapped file (see [`MmapHeights`]), so
+    /// loading reads only the PtrHash asset.
+    #[cfg(feature = "mmap")]
+    pub fn from_files_mmap<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        heights_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let heights = MmapHeights::open(heights_path)?;
+        let header = HeightsHeader::detect(heights.asset_bytes());
+        Self::assemble(&ptrhash_data, header, Heights::Mmap(heights))
+    }
+
+    /// Like [`Self::from_store`], taking the build metadata from a heights asset header
+    #[cfg(feature = "shm")]
+    pub(crate) fn from_store_with_header<S: HeightStore + 'static>(
+        ptrhash_data: &[u8],
+        heights_header: &[u8],
+        heights: S,
+    ) -> io::Result<Self> {
+        let header = HeightsHeader::detect(heights_header);
+        Self::assemble(ptrhash_data, header, Heights::Custom(Box::new(heights)))
+    }
+
+    /// Parse the heights asset header, and the heights after it with `load_heights`
+    fn load<'a>(
+        ptrhash_data: &[u8],
+        heights_data: &'a [u8],
+        load_heights: impl FnOnce(&'a [u8]) -> io::Result<Heights>,
+    ) -> io::Result<Self> {
+        let header = HeightsHeader::detect(heights_data);
+        let heights = load_heights(format::strip_header(heights_data, AssetKind::Heights)?)?;
+        Self::assemble(ptrhash_data, header, heights)
+    }
+
+    /// Parse the PtrHash asset and check that it matches `heights`
+    fn assemble(ptrhash_data: &[u8], header: HeightsHeader, heights: Heights) -> io::Result<Self> {
+        let ptrhash_data = format::strip_header(ptrhash_data, AssetKind::PtrHash)?;
+
+        // Load PtrHash using epserde, honoring its config header if present
+        let phash = Phf::read(ptrhash_data)?;
+        phash.check_table_len(heights.len(), "heights")?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(entries = heights.len(), "loaded oracle assets");
+
+        Ok(Self {
+            tables: Arc::new(Tables {
+                phash,
+                heights,
+                query_counters: QueryCounters::default(),
+            }),
+            version_flags: None,
+            input_policy: header.input_policy,
+            height_range: header.height_range,
+            dataset_commitment: header.dataset_commitment,
+            cache: None,
+        })
+    }
+
+    /// Attach the optional version flags asset (one "version >= 2" bit per entry)
+    pub fn with_version_flags(mut self, versions_data: &[u8]) -> io::Result<Self> {
+        let flags = VersionFlags::deserialize(io::Cursor::new(versions_data))?;
+        if flags.len() != self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Versions asset has {} entries but the oracle has {}",
+                    flags.len(),
+                    self.len()
+                ),
+            ));
+        }
+        self.version_flags = Some(Arc::new(flags));
+        Ok(self)
+    }
+
+    /// Load from asset files on disk
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_files<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        heights_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let heights_data = std::fs::read(heights_path)?;
+        Self::from_bytes(&ptrhash_data, &heights_data)
+    }
+
+    /// Load from the asset directory named by the environment variable `var`
+    ///
+    /// The directory must contain [`crate::PTRHASH_FILE_NAME`] and [`crate::HEIGHTS_FILE_NAME`],
+    /// e.g. `HeightOracleEmbedded::from_env("HEIGHT_ORACLE_ASSETS")`. If it also contains
+    /// [`crate::VERSIONS_FILE_NAME`], the version flags are loaded too.
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_env(var: &str) -> io::Result<Self> {
+        let dir = std::env::var_os(var).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Environment variable {var} is not set"),
+            )
+        })?;
+        let dir = std::path::Path::new(&dir);
+        let oracle = Self::from_files(
+            dir.join(crate::PTRHASH_FILE_NAME),
+            dir.join(crate::HEIGHTS_FILE_NAME),
+        )?;
+
+        match std::fs::read(dir.join(crate::VERSIONS_FILE_NAME)) {
+            Ok(versions_data) => oracle.with_version_flags(&versions_data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(oracle),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Perfect-hash index of a block hash, in `0..len()` (unchecked)
+    ///
+    /// Stable for a given asset, so per-block side tables can be stored by index.
+    pub fn index_of(&self, block_hash: &BlockHash) -> usize {
+        self.tables.phash.index(block_hash.as_bytes())
     }
 
     /// Core lookup function
     pub fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
-        let index = self.phash.index(block_hash);
-        self.heights[index]
+        self.lookup().height(block_hash)
+    }
+
+    /// Like [`Self::get_height_unchecked`], but `None` instead of a panic if the
+    /// perfect hash yields an index past the heights table
+    ///
+    /// Still unchecked with respect to domain membership.
+    pub fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.lookup().height_opt(block_hash)
+    }
+
+    /// The shared lookup path over this oracle's tables
+    fn lookup(&self) -> Lookup<'_, Heights> {
+        Lookup {
+            phash: &self.tables.phash,
+            heights: &self.tables.heights,
+            cache: self.cache.as_deref(),
+            query_counters: &self.tables.query_counters,
+        }
+    }
+
+    /// Whether the block has header version 1 (unchecked)
+    ///
+    /// Returns `None` if no version flags were attached.
+    pub fn is_v1_block(&self, block_hash: &BlockHash) -> Option<bool> {
+        let flags = self.version_flags.as_ref()?;
+        Some(!flags.get(self.index_of(block_hash)))
+    }
+
+    /// Input policy recorded in the heights asset, `None` for legacy assets
+    /// and assets built without one
+    pub fn input_policy(&self) -> Option<InputPolicy> {
+        self.input_policy
+    }
+
+    /// Height range recorded in the heights asset, `None` for full builds
+    pub fn height_range(&self) -> Option<std::ops::Range<u32>> {
+        self.height_range.clone()
+    }
+
+    /// Dataset commitment recorded in the heights asset (see [`crate::commitment`]),
+    /// `None` for assets written before format version 3
+    pub fn dataset_commitment(&self) -> Option<DatasetCommitment> {
+        self.dataset_commitment
+    }
+
+    /// Check that every entry of [`MAINNET_CHECKPOINTS`] resolves to its height
+    ///
+    /// Fails with `InvalidData` on the first mismatch. Only meaningful for
+    /// mainnet assets covering the checkpoint heights.
+    pub fn verify_checkpoints(&self) -> io::Result<()> {
+        for &(hash, expected) in MAINNET_CHECKPOINTS {
+            let block_hash = crate::parse_block_hash(hash)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // Read the table directly, so the self-check does not count as queries
+            let height = self.tables.heights.at(self.index_of(&block_hash));
+            if height != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Checkpoint mismatch: block {hash} resolved to height {height}, expected {expected}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.tables.heights.len()
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.tables.heights.len() == 0
+    }
+
+    /// Lookups made since the tables were loaded or last reset, by this handle
+    /// and its clones (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn query_stats(&self) -> crate::QueryStats {
+        self.tables.query_counters.snapshot()
+    }
+
+    /// Zero the counters behind [`Self::query_stats`] (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn reset_query_stats(&self) {
+        self.tables.query_counters.reset();
+    }
+
+    /// Put a direct-mapped cache of `entries` slots (rounded up to a power of
+    /// two) in front of the perfect hash; 0 removes it
+    ///
+    /// Worth it for lookups concentrated on a few hot hashes, especially with
+    /// the lazily decoded stores, where a hit also skips unpacking a group. On
+    /// spread-out lookups it only adds work. Batched lookups bypass it; clones
+    /// made afterwards share it.
+    pub fn with_cache(mut self, entries: usize) -> Self {
+        self.cache = (entries > 0).then(|| Arc::new(LookupCache::new(entries)));
+        self
+    }
+
+    /// Memory used by this oracle, split into heap allocations and the asset
+    /// data compiled into the binary
+    ///
+    /// Heap figures count the tables shared by clones once per call, so sum
+    /// them over distinct [`Self::ptr_eq`] groups only.
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats::new(
+            &self.tables.phash,
+            &self.tables.heights,
+            self.version_flags.as_deref(),
+            self.cache.as_deref(),
+            format::header_version(
+                self.height_range.is_some(),
+                self.dataset_commitment.is_some(),
+            ),
+        )
+    }
+
+    /// Whether `self` and `other` share the same loaded tables, i.e. one is a
+    /// clone of the other
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.tables, &other.tables)
     }
 }
 
+impl HeightLookup for HeightOracleEmbedded {
+    fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+        HeightOracleEmbedded::get_height_unchecked(self, block_hash)
+    }
+
+    fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        HeightOracleEmbedded::get_height_opt(self, block_hash)
+    }
+
+    fn get_heights_unchecked(&self, block_hashes: &[BlockHash], heights: &mut [u32]) {
+        self.lookup().heights(block_hashes, heights)
+    }
+
+    fn len(&self) -> usize {
+        HeightOracleEmbedded::len(self)
+    }
+}
+
+/// Size of the oracle assets compiled into the binary
+pub(crate) fn static_asset_bytes() -> usize {
+    #[allow(unused_mut)]
+    let mut bytes = 0;
+    #[cfg(all(feature = "embedded", not(feature = "embedded-zstd")))]
+    {
+        bytes += PTRHASH_DATA.len() + HEIGHTS_DATA.len();
+    }
+    #[cfg(feature = "embedded-zstd")]
+    {
+        bytes += CONTAINER_DATA.len();
+    }
+    #[cfg(feature = "embedded-testnet3")]
+    {
+        bytes += TESTNET3_PTRHASH_DATA.len() + TESTNET3_HEIGHTS_DATA.len();
+    }
+    bytes
+}
+
+/// The embedded PtrHash asset on its own, for side tables indexed by it
+#[cfg(all(
+    any(feature = "embedded-timestamps", feature = "embedded-headers"),
+    not(feature = "embedded-zstd")
+))]
+pub(crate) fn embedded_phf() -> io::Result<Phf> {
+    Phf::read(format::strip_header(PTRHASH_DATA, AssetKind::PtrHash)?)
+}
+
+/// The embedded PtrHash asset on its own, decompressed from the container
+#[cfg(all(
+    any(feature = "embedded-timestamps", feature = "embedded-headers"),
+    feature = "embedded-zstd"
+))]
+pub(crate) fn embedded_phf() -> io::Result<Phf> {
+    let data = crate::container::decompress_if_needed(CONTAINER_DATA)?;
+    let (ptrhash_data, _) = crate::container::decode(&data)?;
+    Phf::read(format::strip_header(ptrhash_data, AssetKind::PtrHash)?)
+}
+
 /// Global singleton for embedded oracle
+#[cfg(feature = "embedded")]
 static EMBEDDED_ORACLE: OnceLock<HeightOracleEmbedded> = OnceLock::new();
 
+/// The global embedded oracle, loaded on first use
+#[cfg(feature = "embedded")]
+pub(crate) fn embedded_oracle() -> &'static HeightOracleEmbedded {
+    EMBEDDED_ORACLE.get_or_init(|| {
+        HeightOracleEmbedded::load_embedded_with_override()
+            .expect("Failed to deserialize embedded oracle")
+    })
+}
+
+/// Load the global embedded oracle now instead of on the first lookup
+///
+/// Call at program start to keep deserialization out of the first lookup's
+/// latency and to get an error instead of a panic on bad assets. Calling it
+/// again, or after a lookup, is a no-op. The global oracle lives until the
+/// process exits; use [`HeightOracleEmbedded::try_load_embedded`] for an
+/// instance that can be dropped.
+#[cfg(feature = "embedded")]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug"))]
+pub fn init() -> io::Result<()> {
+    if EMBEDDED_ORACLE.get().is_none() {
+        let oracle = HeightOracleEmbedded::load_embedded_with_override()?;
+        // Another thread may have won the race; its instance is identical
+        let _ = EMBEDDED_ORACLE.set(oracle);
+    }
+    Ok(())
+}
+
+/// A handle to the global embedded oracle, loading it if needed
+///
+/// The handle shares the global oracle's tables, so it is cheap to get and
+/// can be stored or sent to other threads. See [`init`] for how the oracle
+/// is loaded.
+#[cfg(feature = "embedded")]
+pub fn global() -> io::Result<HeightOracleEmbedded> {
+    init()?;
+    Ok(embedded_oracle().clone())
+}
+
+/// Whether the global embedded oracle has been loaded
+#[cfg(feature = "embedded")]
+pub fn is_initialized() -> bool {
+    EMBEDDED_ORACLE.get().is_some()
+}
+
 /// Global lookup function for embedded oracle
+#[cfg(feature = "embedded")]
 pub fn guess_height_prebip34block_unchecked(block_hash: &BlockHash) -> u32 {
-    let oracle = EMBEDDED_ORACLE.get_or_init(HeightOracleEmbedded::load_embedded);
-    oracle.get_height_unchecked(block_hash)
+    embedded_oracle().get_height_unchecked(block_hash)
+}
+
+/// Global lookup function for embedded oracle, keyed by raw 80-byte header (Feature: sha256d)
+#[cfg(all(feature = "embedded", feature = "sha256d"))]
+pub fn guess_height_from_header_unchecked(header: &[u8; crate::header::HEADER_SIZE]) -> u32 {
+    guess_height_prebip34block_unchecked(&crate::header::block_hash(header))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "generate")]
+    use crate::generate::test_support::{asset_bytes, test_hashes, test_oracle};
 
     #[test]
+    #[cfg(feature = "embedded")]
     fn test_global_lookup() {
         // Test that the global function doesn't panic
-        let test_hash = [0u8; 32];
+        let test_hash = BlockHash::from([0u8; 32]);
         let _height = guess_height_prebip34block_unchecked(&test_hash);
         // Just ensure it doesn't panic (actual correctness tested in validate_oracle.rs)
     }
+
+    #[test]
+    #[cfg(feature = "embedded")]
+    fn test_init() {
+        init().unwrap();
+        assert!(is_initialized());
+        // Idempotent
+        init().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "embedded")]
+    fn test_handles_share_tables() {
+        let global_handle = global().unwrap();
+        assert!(global_handle.ptr_eq(&global().unwrap()));
+
+        let independent = HeightOracleEmbedded::try_load_embedded().unwrap();
+        assert!(!independent.ptr_eq(&global_handle));
+        let clone = independent.clone();
+        assert!(clone.ptr_eq(&independent));
+        drop(independent);
+        assert_eq!(clone.len(), global_handle.len());
+    }
+
+    #[test]
+    #[cfg(feature = "embedded")]
+    fn test_override_paths() {
+        let partial = HeightOracleEmbedded::load_override(Some("phash.ptrh.dat".into()), None);
+        assert_eq!(partial.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        let missing = HeightOracleEmbedded::load_override(
+            Some("/nonexistent/phash.ptrh.dat".into()),
+            Some("/nonexistent/heights.u18packed.dat".into()),
+        );
+        let err = missing.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("/nonexistent/phash.ptrh.dat"));
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_lazy_heights_match_decoded() {
+        let block_hashes = test_hashes(9);
+        let heights: Vec<u32> = (0..9).map(|i| i * 1_000).collect();
+        let (ptrhash_data, heights_data) = asset_bytes(&test_oracle(&block_hashes, &heights));
+
+        let decoded = HeightOracleEmbedded::from_bytes(&ptrhash_data, &heights_data).unwrap();
+        let lazy = HeightOracleEmbedded::from_bytes_lazy(&ptrhash_data, &heights_data).unwrap();
+        assert_eq!(lazy.len(), decoded.len());
+        for block_hash in &block_hashes {
+            assert_eq!(
+                lazy.get_height_opt(block_hash),
+                decoded.get_height_opt(block_hash)
+            );
+        }
+        let mut batch = vec![0; block_hashes.len()];
+        HeightLookup::get_heights_unchecked(&lazy, &block_hashes, &mut batch);
+        assert_eq!(batch, (0..9).map(|i| i * 1_000).collect::<Vec<_>>());
+
+        // Borrowing the heights in place behaves the same
+        let heights_data: &'static [u8] = heights_data.leak();
+        let borrowed = HeightOracleEmbedded::from_static(&ptrhash_data, heights_data).unwrap();
+        for block_hash in &block_hashes {
+            assert_eq!(
+                borrowed.get_height_opt(block_hash),
+                decoded.get_height_opt(block_hash)
+            );
+        }
+
+        // Truncated heights still fail at load time
+        let truncated = &heights_data[..heights_data.len() - 1];
+        assert!(HeightOracleEmbedded::from_bytes_lazy(&ptrhash_data, truncated).is_err());
+        assert!(HeightOracleEmbedded::from_static(&ptrhash_data, truncated).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_memory_stats() {
+        let (ptrhash_data, heights_data) = asset_bytes(&test_oracle(&test_hashes(9), &[0; 9]));
+
+        let decoded = HeightOracleEmbedded::from_bytes(&ptrhash_data, &heights_data).unwrap();
+        let stats = decoded.memory_stats();
+        assert_eq!(stats.num_elements, 9);
+        assert!(stats.ptrhash_heap_bytes > 0);
+        assert!(stats.heights_heap_bytes >= 9 * 4);
+        assert_eq!(stats.cache_heap_bytes, 0);
+        assert_eq!(stats.static_asset_bytes, static_asset_bytes());
+        assert_eq!(
+            stats.heap_bytes(),
+            stats.ptrhash_heap_bytes + stats.heights_heap_bytes
+        );
+        assert!(stats.to_string().contains("Elements: 9"));
+
+        // Packed heights cost their 27 bytes when copied, nothing when borrowed
+        let lazy = HeightOracleEmbedded::from_bytes_lazy(&ptrhash_data, &heights_data).unwrap();
+        assert_eq!(lazy.memory_stats().heights_heap_bytes, 27);
+        let borrowed =
+            HeightOracleEmbedded::from_static(&ptrhash_data, heights_data.leak()).unwrap();
+        assert_eq!(borrowed.memory_stats().heights_heap_bytes, 0);
+
+        let cached = borrowed.with_cache(4).memory_stats();
+        assert!(cached.cache_heap_bytes >= 4 * 36);
+        assert_eq!(
+            cached.heap_bytes(),
+            stats.ptrhash_heap_bytes + cached.cache_heap_bytes
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_from_store() {
+        let block_hashes = test_hashes(6);
+        let oracle = test_oracle(&block_hashes, &[10, 11, 12, 13, 14, 15]);
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        let ptrhash_data = std::fs::read(&ptrhash_path).unwrap();
+
+        let heights = crate::HeightOracle::load_from_paths(&ptrhash_path, &heights_path)
+            .unwrap()
+            .heights()
+            .to_vec();
+        let custom = HeightOracleEmbedded::from_store(&ptrhash_data, heights).unwrap();
+        assert_eq!(custom.get_height_opt(&block_hashes[3]), Some(13));
+        assert_eq!(
+            custom.era_info_for(&block_hashes[3]),
+            Some(crate::consensus::era_info(13))
+        );
+        assert_eq!(custom.height_range(), None);
+
+        // A store of the wrong length is refused
+        assert!(HeightOracleEmbedded::from_store(&ptrhash_data, vec![0u32; 5]).is_err());
+
+        #[cfg(feature = "mmap")]
+        {
+            let mapped =
+                HeightOracleEmbedded::from_files_mmap(&ptrhash_path, &heights_path).unwrap();
+            for (height, block_hash) in (10..).zip(&block_hashes) {
+                assert_eq!(mapped.get_height_unchecked(block_hash), height);
+            }
+            assert!(mapped.input_policy().is_none());
+            assert_eq!(mapped.dataset_commitment(), oracle.dataset_commitment());
+        }
+    }
+
+    #[test]
+    fn test_mainnet_checkpoints_are_valid() {
+        let mut previous = None;
+        for &(hash, height) in MAINNET_CHECKPOINTS {
+            assert!(crate::parse_block_hash(hash).is_ok(), "bad hash {hash}");
+            assert!(height < crate::BIP34_ACTIVATION_HEIGHT);
+            assert!(previous < Some(height), "checkpoints must be sorted");
+            previous = Some(height);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "embedded-testnet3"))]
+    fn test_for_network_without_assets() {
+        let err = HeightOracleEmbedded::for_network(Network::Testnet3)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("embedded-testnet3"));
+    }
+
+    #[test]
+    #[cfg(feature = "runtime-assets")]
+    fn test_from_env_missing_var() {
+        let result = HeightOracleEmbedded::from_env("HEIGHT_ORACLE_TEST_UNSET_VAR");
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::NotFound);
+    }
 }
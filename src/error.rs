@@ -0,0 +1,36 @@
+//! Error type of the fallible lookups
+
+use std::fmt;
+
+/// Why a lookup could not return a height
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeightOracleError {
+    /// The block hash is not 64 hex characters (with or without `0x`)
+    InvalidHash { reason: String },
+    /// The perfect hash maps the block hash past the heights table
+    OutOfRange,
+    /// Columnar input of `len` bytes is not a whole number of 32-byte hashes
+    InvalidLength { len: usize },
+}
+
+impl fmt::Display for HeightOracleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeightOracleError::InvalidHash { reason } => write!(f, "Invalid block hash: {reason}"),
+            HeightOracleError::OutOfRange => {
+                write!(f, "Block hash maps past the end of the heights table")
+            }
+            HeightOracleError::InvalidLength { len } => {
+                write!(f, "Columnar input is {len} bytes, not a multiple of 32")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeightOracleError {}
+
+/// [`crate::parse_block_hash`] with its error wrapped in [`HeightOracleError`]
+pub(crate) fn parse_hex(hex_str: &str) -> Result<crate::BlockHash, HeightOracleError> {
+    crate::parse_block_hash(hex_str).map_err(|reason| HeightOracleError::InvalidHash { reason })
+}
@@ -0,0 +1,145 @@
+//! Export oracle contents as text, CSV or JSON lines (Feature: generate)
+//!
+//! A perfect hash cannot enumerate its keys, so an export takes the block hashes
+//! to report (the dataset an oracle was built from, or the reverse dataset for
+//! distributed assets) and writes the height the oracle returns for each one.
+//! Exporting a loaded asset to text and diffing it against the source dataset
+//! audits the asset end to end.
+
+use crate::BlockHash;
+use anyhow::{Context, Result};
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+/// Output format of [`HeightOracle::export`](crate::HeightOracle::export)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Canonical dataset text: one hash per line, line number = height, `x` for gaps
+    Txt,
+    /// `height,block_hash,is_v2` with a header row
+    Csv,
+    /// One `{"height":..,"block_hash":"..","is_v2":..}` object per line
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "txt" => Ok(ExportFormat::Txt),
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            _ => Err(format!(
+                "Unknown export format {s:?}, expected txt, csv or jsonl"
+            )),
+        }
+    }
+}
+
+/// Write `block_hashes` with the heights and version flags an oracle returns, sorted by height
+///
+/// `is_v2` returns `None` when the oracle has no version flags; the flag is then
+/// left out (text), empty (CSV) or `null` (JSON lines).
+pub(crate) fn export<W: Write>(
+    block_hashes: &[BlockHash],
+    height_of: impl Fn(&BlockHash) -> u32,
+    is_v2: impl Fn(&BlockHash) -> Option<bool>,
+    format: ExportFormat,
+    writer: W,
+) -> Result<()> {
+    let mut rows: Vec<(u32, BlockHash, Option<bool>)> = block_hashes
+        .iter()
+        .map(|block_hash| (height_of(block_hash), *block_hash, is_v2(block_hash)))
+        .collect();
+    rows.sort_unstable();
+
+    let mut out = BufWriter::new(writer);
+    match format {
+        ExportFormat::Txt => {
+            let mut next_height = 0;
+            for window in rows.windows(2) {
+                let ((height, a, _), (next, b, _)) = (window[0], window[1]);
+                anyhow::ensure!(
+                    height != next,
+                    "Block hashes {a} and {b} both map to height {height}"
+                );
+            }
+            for (height, block_hash, is_v2) in &rows {
+                for _ in next_height..*height {
+                    writeln!(out, "x")?;
+                }
+                if *is_v2 == Some(true) {
+                    writeln!(out, "x {block_hash}")?;
+                } else {
+                    writeln!(out, "{block_hash}")?;
+                }
+                next_height = height + 1;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "height,block_hash,is_v2")?;
+            for (height, block_hash, is_v2) in &rows {
+                let is_v2 = is_v2.map_or(String::new(), |flag| flag.to_string());
+                writeln!(out, "{height},{block_hash},{is_v2}")?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            for (height, block_hash, is_v2) in &rows {
+                let is_v2 = is_v2.map_or("null".to_string(), |flag| flag.to_string());
+                writeln!(
+                    out,
+                    "{{\"height\":{height},\"block_hash\":\"{block_hash}\",\"is_v2\":{is_v2}}}"
+                )?;
+            }
+        }
+    }
+    out.flush().context("Failed to write export")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+    const BLOCK_2: &str = "000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd";
+
+    fn export_to_string(
+        heights: [u32; 2],
+        v2: Option<bool>,
+        format: ExportFormat,
+    ) -> Result<String> {
+        let hashes = [BLOCK_2.parse().unwrap(), GENESIS.parse().unwrap()];
+        let height_of = |hash: &BlockHash| heights[hashes.iter().position(|h| h == hash).unwrap()];
+        let is_v2 = |hash: &BlockHash| v2.map(|flag| flag && *hash == hashes[0]);
+
+        let mut out = Vec::new();
+        export(&hashes, height_of, is_v2, format, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_export_formats() {
+        assert_eq!(
+            export_to_string([2, 0], Some(true), ExportFormat::Txt).unwrap(),
+            format!("{GENESIS}\nx\nx {BLOCK_2}\n")
+        );
+        assert_eq!(
+            export_to_string([2, 0], None, ExportFormat::Csv).unwrap(),
+            format!("height,block_hash,is_v2\n0,{GENESIS},\n2,{BLOCK_2},\n")
+        );
+        assert_eq!(
+            export_to_string([2, 0], Some(false), ExportFormat::Jsonl).unwrap(),
+            format!(
+                "{{\"height\":0,\"block_hash\":\"{GENESIS}\",\"is_v2\":false}}\n\
+                 {{\"height\":2,\"block_hash\":\"{BLOCK_2}\",\"is_v2\":false}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_txt_rejects_height_collisions() {
+        assert!(export_to_string([1, 1], None, ExportFormat::Txt).is_err());
+        assert!(export_to_string([1, 1], None, ExportFormat::Csv).is_ok());
+    }
+}
@@ -0,0 +1,298 @@
+//! Export the hash → height mapping as a key-value store (Feature: generate)
+//!
+//! Consumers that cannot read the perfect hash assets (other languages, or
+//! tools that want exact misses instead of unchecked lookups) still get an
+//! offline lookup artifact: every dataset hash with the height the oracle
+//! returns for it, in a conventional key-value file.
+//!
+//! Every format uses the same encoding: the key is the 32-byte block hash in
+//! network byte order, the value the height as a 4-byte little-endian `u32`.
+//!
+//! - `cdb`: a [constant database](https://cr.yp.to/cdb/cdb.txt) file, readable
+//!   from C, Python, Go and most other languages. Always available.
+//! - `redb`: a [redb](https://www.redb.org) file with the table
+//!   [`REDB_TABLE`] (Feature: export-redb).
+//! - `sled`: a [sled](https://sled.rs) directory (Feature: export-sled).
+
+use crate::save::{write_atomic, SaveOptions};
+use crate::BlockHash;
+use anyhow::{Context, Result};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Name of the table holding the mapping in a redb export
+pub const REDB_TABLE: &str = "heights";
+
+/// Output format of [`HeightOracle::export_kv`](crate::HeightOracle::export_kv)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvFormat {
+    /// Constant database file
+    Cdb,
+    /// redb database file (Feature: export-redb)
+    Redb,
+    /// sled database directory (Feature: export-sled)
+    Sled,
+}
+
+impl FromStr for KvFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cdb" => Ok(KvFormat::Cdb),
+            "redb" => Ok(KvFormat::Redb),
+            "sled" => Ok(KvFormat::Sled),
+            _ => Err(format!(
+                "Unknown key-value format {s:?}, expected cdb, redb or sled"
+            )),
+        }
+    }
+}
+
+/// Write `block_hashes` with the heights an oracle returns to a new store at `path`
+///
+/// Refuses to touch an existing `path`, so an export never merges into an
+/// older database.
+pub(crate) fn export_kv(
+    block_hashes: &[BlockHash],
+    height_of: impl Fn(&BlockHash) -> u32,
+    format: KvFormat,
+    path: &Path,
+    options: &SaveOptions,
+) -> Result<()> {
+    anyhow::ensure!(!path.exists(), "{} already exists", path.display());
+    let entries: Vec<(BlockHash, u32)> = block_hashes
+        .iter()
+        .map(|block_hash| (*block_hash, height_of(block_hash)))
+        .collect();
+    match format {
+        KvFormat::Cdb => write_atomic(path, options, |writer| write_cdb(&entries, writer)),
+        KvFormat::Redb => write_redb(&entries, path),
+        KvFormat::Sled => write_sled(&entries, path),
+    }
+    .with_context(|| format!("Failed to export {}", path.display()))
+}
+
+/// The cdb hash function
+fn cdb_hash(key: &[u8]) -> u32 {
+    key.iter().fold(5381u32, |h, &byte| {
+        (h << 5).wrapping_add(h) ^ u32::from(byte)
+    })
+}
+
+/// Write `entries` as a constant database
+///
+/// Layout: 256 `(table position, slot count)` pairs, the records as
+/// `[key length][value length][key][value]`, then 256 open-addressing tables
+/// of `(hash, record position)` slots, each twice as long as its entry count.
+/// All numbers are little-endian `u32`.
+fn write_cdb<W: Write>(entries: &[(BlockHash, u32)], mut writer: W) -> io::Result<()> {
+    const HEADER_LEN: usize = 256 * 8;
+    const RECORD_LEN: usize = 8 + 32 + 4;
+
+    let records_end = HEADER_LEN + entries.len() * RECORD_LEN;
+    let total = records_end + entries.len() * 2 * 8;
+    if total > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} entries do not fit in a cdb file", entries.len()),
+        ));
+    }
+
+    // (hash, record position) per table
+    let mut buckets: Vec<Vec<(u32, u32)>> = vec![Vec::new(); 256];
+    for (i, (block_hash, _)) in entries.iter().enumerate() {
+        let hash = cdb_hash(block_hash.as_bytes());
+        buckets[(hash & 0xff) as usize].push((hash, (HEADER_LEN + i * RECORD_LEN) as u32));
+    }
+
+    let mut position = records_end;
+    for bucket in &buckets {
+        let slots = bucket.len() * 2;
+        writer.write_all(&(position as u32).to_le_bytes())?;
+        writer.write_all(&(slots as u32).to_le_bytes())?;
+        position += slots * 8;
+    }
+    for (block_hash, height) in entries {
+        writer.write_all(&32u32.to_le_bytes())?;
+        writer.write_all(&4u32.to_le_bytes())?;
+        writer.write_all(block_hash.as_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+    }
+    for bucket in &buckets {
+        // Record position 0 marks an empty slot; records start after the header
+        let mut table = vec![(0u32, 0u32); bucket.len() * 2];
+        for &(hash, record) in bucket {
+            let mut slot = (hash >> 8) as usize % table.len();
+            while table[slot].1 != 0 {
+                slot = (slot + 1) % table.len();
+            }
+            table[slot] = (hash, record);
+        }
+        for (hash, record) in table {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&record.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "export-redb")]
+fn write_redb(entries: &[(BlockHash, u32)], path: &Path) -> Result<()> {
+    let table = redb::TableDefinition::<&[u8], u32>::new(REDB_TABLE);
+    let db = redb::Database::create(path)?;
+    let txn = db.begin_write()?;
+    {
+        let mut table = txn.open_table(table)?;
+        for (block_hash, height) in entries {
+            table.insert(block_hash.as_bytes().as_slice(), height)?;
+        }
+    }
+    txn.commit()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "export-redb"))]
+fn write_redb(_entries: &[(BlockHash, u32)], _path: &Path) -> Result<()> {
+    anyhow::bail!("redb export requires the export-redb feature")
+}
+
+#[cfg(feature = "export-sled")]
+fn write_sled(entries: &[(BlockHash, u32)], path: &Path) -> Result<()> {
+    let db = sled::open(path)?;
+    let mut batch = sled::Batch::default();
+    for (block_hash, height) in entries {
+        batch.insert(block_hash.as_bytes().as_slice(), &height.to_le_bytes());
+    }
+    db.apply_batch(batch)?;
+    db.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "export-sled"))]
+fn write_sled(_entries: &[(BlockHash, u32)], _path: &Path) -> Result<()> {
+    anyhow::bail!("sled export requires the export-sled feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Look `key` up in a cdb file the way the reference reader does
+    fn cdb_get(data: &[u8], key: &[u8]) -> Option<u32> {
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let hash = cdb_hash(key);
+        let bucket = (hash & 0xff) as usize * 8;
+        let (table, slots) = (u32_at(bucket) as usize, u32_at(bucket + 4) as usize);
+        if slots == 0 {
+            return None;
+        }
+        let start = (hash >> 8) as usize % slots;
+        for probe in 0..slots {
+            let slot = table + (start + probe) % slots * 8;
+            let record = u32_at(slot + 4) as usize;
+            if record == 0 {
+                return None;
+            }
+            let key_len = u32_at(record) as usize;
+            if u32_at(slot) == hash && &data[record + 8..record + 8 + key_len] == key {
+                return Some(u32_at(record + 8 + key_len));
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_cdb() {
+        let entries: Vec<(BlockHash, u32)> = (0..1_000u32)
+            .map(|height| {
+                let mut bytes = [0; 32];
+                bytes[..4].copy_from_slice(&height.to_le_bytes());
+                (BlockHash::from(bytes), height)
+            })
+            .collect();
+        let mut data = Vec::new();
+        write_cdb(&entries, &mut data).unwrap();
+        assert_eq!(data.len(), 2048 + 1_000 * (44 + 16));
+
+        for (block_hash, height) in &entries {
+            assert_eq!(cdb_get(&data, block_hash.as_bytes()), Some(*height));
+        }
+        assert_eq!(cdb_get(&data, &[0xff; 32]), None);
+
+        let mut empty = Vec::new();
+        write_cdb(&[], &mut empty).unwrap();
+        assert_eq!(cdb_get(&empty, &[0; 32]), None);
+    }
+
+    #[test]
+    fn test_export_kv() {
+        let block_hashes = [BlockHash::from([1; 32]), BlockHash::from([2; 32])];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heights.cdb");
+        export_kv(
+            &block_hashes,
+            |_| 7,
+            KvFormat::Cdb,
+            &path,
+            &SaveOptions::new(),
+        )
+        .unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(cdb_get(&data, &[2; 32]), Some(7));
+
+        // An existing output is never overwritten
+        assert!(export_kv(
+            &block_hashes,
+            |_| 7,
+            KvFormat::Cdb,
+            &path,
+            &SaveOptions::new()
+        )
+        .is_err());
+        assert_eq!("sled".parse(), Ok(KvFormat::Sled));
+        assert!("lmdb".parse::<KvFormat>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "export-redb")]
+    fn test_export_redb() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heights.redb");
+        export_kv(
+            &[BlockHash::from([1; 32])],
+            |_| 7,
+            KvFormat::Redb,
+            &path,
+            &SaveOptions::new(),
+        )
+        .unwrap();
+        let db = redb::Database::open(&path).unwrap();
+        let txn = db.begin_read().unwrap();
+        let table = txn
+            .open_table(redb::TableDefinition::<&[u8], u32>::new(REDB_TABLE))
+            .unwrap();
+        assert_eq!(table.get([1u8; 32].as_slice()).unwrap().unwrap().value(), 7);
+    }
+
+    #[test]
+    #[cfg(feature = "export-sled")]
+    fn test_export_sled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heights.sled");
+        export_kv(
+            &[BlockHash::from([1; 32])],
+            |_| 7,
+            KvFormat::Sled,
+            &path,
+            &SaveOptions::new(),
+        )
+        .unwrap();
+        let db = sled::open(&path).unwrap();
+        assert_eq!(
+            db.get([1u8; 32]).unwrap().as_deref(),
+            Some(&7u32.to_le_bytes()[..])
+        );
+    }
+}
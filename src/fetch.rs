@@ -0,0 +1,680 @@
+//! Dataset fetching (Feature: fetch)
+//!
+//! Downloads the pre-BIP34 block hash list used as input for oracle generation.
+//! Four backends are available: a local bitcoind over JSON-RPC or its REST
+//! interface (`-rest=1`, no credentials), any Esplora REST API (e.g.
+//! blockstream.info), and a headers sync over the P2P protocol from any public
+//! node, for users without their own node.
+
+pub mod checkpoint;
+pub mod esplora;
+pub mod ordered;
+pub mod p2p;
+pub mod rest;
+pub mod rpc;
+pub mod throttle;
+
+pub use checkpoint::FetchCheckpoint;
+pub use ordered::OrderedWriter;
+pub use throttle::ThrottleOptions;
+
+use crate::hashdump::HashRecord;
+use crate::header::{BlockHeader, HEADER_SIZE};
+use crate::{Network, BIP34_ACTIVATION_HEIGHT};
+use anyhow::{anyhow, Context, Result};
+use futures::{stream, StreamExt};
+use std::io::Write;
+use std::path::Path;
+
+/// Placeholder line written instead of the hash for version-2 blocks
+pub const V2_PLACEHOLDER: &str = "x";
+
+/// A single block as returned by a fetch backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedBlock {
+    /// Block height
+    pub height: u32,
+    /// Block hash in reverse hex (RPC display order)
+    pub hash: String,
+    /// Header version
+    pub version: i32,
+    /// Raw 80-byte header
+    pub header: [u8; HEADER_SIZE],
+}
+
+impl FetchedBlock {
+    /// Build from a fetched hash and the header's hex serialization
+    pub fn from_header_hex(height: u32, hash: String, header_hex: &str) -> Result<Self> {
+        let bytes = hex::decode(header_hex.trim())
+            .with_context(|| format!("Invalid header hex for block {hash}"))?;
+        let header: [u8; HEADER_SIZE] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            anyhow!(
+                "Header for block {hash} is {} bytes, expected {HEADER_SIZE}",
+                bytes.len()
+            )
+        })?;
+        Ok(Self::from_header(height, hash, header))
+    }
+
+    /// Build from a fetched hash and the raw header
+    pub fn from_header(height: u32, hash: String, header: [u8; HEADER_SIZE]) -> Self {
+        Self {
+            height,
+            hash,
+            version: BlockHeader::parse(&header).version,
+            header,
+        }
+    }
+
+    /// The dataset line for this block: its hash, or the `x` placeholder for version-2 blocks
+    pub fn dataset_line(&self) -> &str {
+        if self.version == 2 {
+            V2_PLACEHOLDER
+        } else {
+            &self.hash
+        }
+    }
+
+    /// The binary dataset record for this block (see [`crate::hashdump`])
+    pub fn hash_record(&self, keep_v2_hashes: bool) -> Result<HashRecord> {
+        if self.version == 2 && !keep_v2_hashes {
+            return Ok(HashRecord::Placeholder);
+        }
+        let block_hash = crate::parse_block_hash(&self.hash)
+            .map_err(|e| anyhow!("Invalid hash for block {}: {e}", self.height))?;
+        Ok(HashRecord::Hash {
+            block_hash,
+            is_v2: self.version == 2,
+        })
+    }
+
+    /// The dataset line for this block, keeping version-2 hashes as `x <hash>`
+    pub fn annotated_dataset_line(&self) -> String {
+        if self.version == 2 {
+            format!("{V2_PLACEHOLDER} {}", self.hash)
+        } else {
+            self.hash.clone()
+        }
+    }
+}
+
+/// Default heights per batch request
+///
+/// 500 heights take the pre-BIP34 range to a few hundred batches, while each
+/// batch stays small enough to answer quickly.
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Height range and parallelism for a fetch run
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct FetchConfig {
+    /// Network the backend is expected to serve (checked against its genesis hash)
+    pub network: Network,
+    /// First height to fetch
+    pub start_height: u32,
+    /// Last height to fetch (inclusive)
+    pub end_height: u32,
+    /// Number of requests in flight at once (blocks, or batches when batching)
+    pub concurrency: usize,
+    /// Heights per batch request (RPC and REST backends; 1 disables batching)
+    pub batch_size: usize,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::Mainnet,
+            start_height: 0,
+            end_height: BIP34_ACTIVATION_HEIGHT - 1,
+            concurrency: 32,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+impl FetchConfig {
+    /// Number of blocks in the configured range
+    pub fn total(&self) -> u64 {
+        (self.end_height as u64) - (self.start_height as u64) + 1
+    }
+
+    /// Check that the range is non-empty and ends before BIP34 activates on the network
+    fn check_range(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.end_height >= self.start_height,
+            "end-height must be >= start-height"
+        );
+        let activation = self.network.bip34_activation_height();
+        anyhow::ensure!(
+            self.end_height < activation,
+            "end-height {} is past the pre-BIP34 range of {} (heights 0..={})",
+            self.end_height,
+            self.network,
+            activation - 1
+        );
+        Ok(())
+    }
+}
+
+/// Check that fetched blocks are well-formed and belong to `network`
+///
+/// Every hash must be 64 hex characters, every height must be pre-BIP34, and
+/// a block at height 0 must be the network's genesis block. A node on another
+/// network fails the genesis check instead of producing a wrong dataset.
+pub fn verify_blocks(blocks: &[FetchedBlock], network: Network) -> Result<()> {
+    let activation = network.bip34_activation_height();
+    for block in blocks {
+        crate::parse_block_hash(&block.hash).map_err(|e| {
+            anyhow!(
+                "Backend returned an invalid hash for height {}: {e}",
+                block.height
+            )
+        })?;
+        anyhow::ensure!(
+            block.height < activation,
+            "Backend returned height {}, past the pre-BIP34 range of {network} (heights 0..={})",
+            block.height,
+            activation - 1
+        );
+        anyhow::ensure!(
+            block.height != 0 || block.hash.eq_ignore_ascii_case(network.genesis_hash()),
+            "Block 0 is {}, not the {network} genesis block {}; is the backend on another network?",
+            block.hash,
+            network.genesis_hash()
+        );
+    }
+    Ok(())
+}
+
+/// Where block hashes and headers are fetched from
+pub enum Backend {
+    /// bitcoind JSON-RPC
+    Rpc(rpc::RpcClient),
+    /// bitcoind REST interface
+    Rest(rest::RestClient),
+    /// Esplora REST API
+    Esplora(esplora::EsploraClient),
+    /// Bitcoin P2P headers sync
+    P2p(p2p::P2pClient),
+}
+
+impl Backend {
+    /// Fetch hash and raw header for a single height
+    pub async fn fetch_block(&self, height: u32) -> Result<FetchedBlock> {
+        match self {
+            Backend::Rpc(client) => client.fetch_block(height).await,
+            Backend::Rest(client) => client.fetch_block(height).await,
+            Backend::Esplora(client) => client.fetch_block(height).await,
+            Backend::P2p(client) => client.fetch_block(height).await,
+        }
+    }
+
+    /// Fetch a group of heights, batching them into one round trip where the backend supports it
+    async fn fetch_group(&self, heights: Vec<u32>) -> Vec<Result<FetchedBlock, FetchFailure>> {
+        let failure = |height: u32, error: &anyhow::Error| FetchFailure {
+            height,
+            error: format!("{error:#}"),
+        };
+
+        match self {
+            Backend::Rpc(client) if heights.len() > 1 => {
+                match client.fetch_blocks_batch(&heights).await {
+                    Ok(results) => heights
+                        .iter()
+                        .zip(results)
+                        .map(|(&height, result)| result.map_err(|e| failure(height, &e)))
+                        .collect(),
+                    Err(e) => heights
+                        .iter()
+                        .map(|&height| Err(failure(height, &e)))
+                        .collect(),
+                }
+            }
+            Backend::Rest(client) if heights.len() > 1 && is_contiguous(&heights) => {
+                match client.fetch_range(heights[0], heights.len()).await {
+                    Ok(blocks) => blocks.into_iter().map(Ok).collect(),
+                    Err(e) => heights
+                        .iter()
+                        .map(|&height| Err(failure(height, &e)))
+                        .collect(),
+                }
+            }
+            _ => {
+                let mut results = Vec::with_capacity(heights.len());
+                for height in heights {
+                    results.push(
+                        self.fetch_block(height)
+                            .await
+                            .map_err(|e| failure(height, &e)),
+                    );
+                }
+                results
+            }
+        }
+    }
+}
+
+/// Check that `heights` are consecutive, so one header range covers them
+fn is_contiguous(heights: &[u32]) -> bool {
+    heights.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
+
+/// A height that could not be fetched
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FetchFailure {
+    pub height: u32,
+    pub error: String,
+}
+
+/// Result of a fetch run
+#[derive(Debug, Default)]
+pub struct FetchOutcome {
+    /// Successfully fetched blocks, sorted by height
+    pub blocks: Vec<FetchedBlock>,
+    /// Heights that failed
+    pub failures: Vec<FetchFailure>,
+}
+
+impl FetchOutcome {
+    fn sort(&mut self) {
+        self.blocks.sort_unstable_by_key(|block| block.height);
+        self.failures.sort_unstable_by_key(|failure| failure.height);
+    }
+}
+
+/// Fetch every block in the configured range using up to `config.concurrency` parallel requests
+///
+/// With the RPC backend and `config.batch_size > 1`, heights are fetched in
+/// JSON-RPC batches of that size (two HTTP requests per batch). The REST backend
+/// fetches consecutive heights as one header range, up to
+/// [`rest::MAX_BATCH_SIZE`] at a time (also two HTTP requests per batch).
+///
+/// Block 0 is fetched first and checked against the network's genesis hash,
+/// even when the range starts above it. Fetched blocks are checked with
+/// [`verify_blocks`] as they arrive; the first mismatch aborts the run.
+pub async fn fetch_blocks(backend: &Backend, config: &FetchConfig) -> Result<FetchOutcome> {
+    config.check_range()?;
+    check_genesis(backend, config.network).await?;
+
+    let heights: Vec<u32> = (config.start_height..=config.end_height).collect();
+    let mut blocks = Vec::new();
+    let failures = fetch_heights(backend, config, &heights, false, |group| {
+        blocks.extend(group);
+        Ok(())
+    })
+    .await?;
+    let mut outcome = FetchOutcome { blocks, failures };
+    outcome.sort();
+    Ok(outcome)
+}
+
+/// Like [`fetch_blocks`], but skips heights already in `checkpoint` and records new ones
+///
+/// Blocks are appended to the checkpoint as each request completes, so a run
+/// that fails or is interrupted can be resumed with [`FetchCheckpoint::resume`].
+/// The outcome includes the previously recorded blocks within the range.
+pub async fn fetch_blocks_resumable(
+    backend: &Backend,
+    config: &FetchConfig,
+    checkpoint: &mut FetchCheckpoint,
+) -> Result<FetchOutcome> {
+    config.check_range()?;
+    check_genesis(backend, config.network).await?;
+
+    let range = config.start_height..=config.end_height;
+    let done: std::collections::HashSet<u32> = checkpoint
+        .completed()
+        .iter()
+        .map(|block| block.height)
+        .collect();
+    let heights: Vec<u32> = range
+        .clone()
+        .filter(|height| !done.contains(height))
+        .collect();
+
+    let mut blocks = Vec::new();
+    let failures = fetch_heights(backend, config, &heights, false, |group| {
+        checkpoint.record(&group)?;
+        blocks.extend(group);
+        Ok(())
+    })
+    .await?;
+    let mut outcome = FetchOutcome { blocks, failures };
+    let mut seen = std::collections::HashSet::new();
+    outcome.blocks.extend(
+        checkpoint
+            .completed()
+            .iter()
+            .filter(|block| range.contains(&block.height) && seen.insert(block.height))
+            .cloned(),
+    );
+    outcome.sort();
+    Ok(outcome)
+}
+
+/// Fetch the configured range, passing each block to `on_block` in height order
+///
+/// Unlike [`fetch_blocks`], fetched blocks are not collected: a block is
+/// passed on as soon as every lower height has been, through an
+/// [`OrderedWriter`]. Groups are handed over in the order they were requested,
+/// so at most `config.concurrency` finished groups wait behind a slow one.
+/// Writing them straight to a file keeps memory flat and leaves a valid prefix
+/// behind if the run is interrupted.
+///
+/// The run stops at the first failed group. Every height below the first
+/// failure has then been passed to `on_block`, and the failures are returned.
+/// The genesis block is checked first, as in [`fetch_blocks`].
+pub async fn fetch_blocks_ordered(
+    backend: &Backend,
+    config: &FetchConfig,
+    on_block: impl FnMut(&FetchedBlock) -> Result<()>,
+) -> Result<Vec<FetchFailure>> {
+    config.check_range()?;
+    check_genesis(backend, config.network).await?;
+
+    let heights: Vec<u32> = (config.start_height..=config.end_height).collect();
+    let mut writer = OrderedWriter::new(config.start_height, on_block);
+    fetch_heights(backend, config, &heights, true, |group| {
+        group.into_iter().try_for_each(|block| writer.push(block))
+    })
+    .await
+}
+
+/// Check that the backend serves `network` by fetching its block 0
+///
+/// A range that starts above genesis never sees block 0 otherwise, and a node
+/// on another network would go unnoticed.
+async fn check_genesis(backend: &Backend, network: Network) -> Result<()> {
+    let genesis = backend
+        .fetch_block(0)
+        .await
+        .context("Failed to fetch the genesis block")?;
+    verify_blocks(std::slice::from_ref(&genesis), network)
+}
+
+/// Fetch `heights` in groups, calling `on_blocks` with each group's successes as it completes
+///
+/// With `ordered`, groups are passed on in the order of `heights`, and the run
+/// stops after the first group with a failure.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(heights = heights.len(), concurrency = config.concurrency)
+    )
+)]
+async fn fetch_heights(
+    backend: &Backend,
+    config: &FetchConfig,
+    heights: &[u32],
+    ordered: bool,
+    mut on_blocks: impl FnMut(Vec<FetchedBlock>) -> Result<()>,
+) -> Result<Vec<FetchFailure>> {
+    let batch_size = match backend {
+        Backend::Rpc(_) => config.batch_size.max(1),
+        Backend::Rest(_) => config.batch_size.clamp(1, rest::MAX_BATCH_SIZE),
+        Backend::Esplora(_) => 1,
+        // One sequential sync over a single connection covers every height
+        Backend::P2p(client) => {
+            return match client.fetch_heights(heights).await {
+                Ok(blocks) => {
+                    verify_blocks(&blocks, config.network)?;
+                    on_blocks(blocks)?;
+                    Ok(Vec::new())
+                }
+                Err(e) => Ok(heights
+                    .iter()
+                    .map(|&height| FetchFailure {
+                        height,
+                        error: format!("{e:#}"),
+                    })
+                    .collect()),
+            };
+        }
+    };
+
+    let requests = stream::iter(heights.chunks(batch_size).map(<[u32]>::to_vec))
+        .map(|group| backend.fetch_group(group));
+    let concurrency = config.concurrency.max(1);
+    // In order, a finished group waits for the slower ones before it instead of
+    // piling up in the caller
+    let mut groups = if ordered {
+        requests.buffered(concurrency).left_stream()
+    } else {
+        requests.buffer_unordered(concurrency).right_stream()
+    };
+
+    // Partition successes and failures
+    let mut failures = Vec::new();
+    #[cfg(feature = "tracing")]
+    let mut fetched = 0usize;
+    while let Some(results) = groups.next().await {
+        let mut blocks = Vec::with_capacity(results.len());
+        for result in results {
+            match result {
+                Ok(block) => blocks.push(block),
+                Err(failure) => failures.push(failure),
+            }
+        }
+        // Abort before recording anything from a misconfigured backend
+        verify_blocks(&blocks, config.network)?;
+        #[cfg(feature = "tracing")]
+        {
+            fetched += blocks.len();
+        }
+        on_blocks(blocks)?;
+        if ordered && !failures.is_empty() {
+            break;
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        blocks = fetched,
+        failures = failures.len(),
+        "fetched heights"
+    );
+    Ok(failures)
+}
+
+/// Write fetched blocks as a dataset file (one hash or placeholder per line, in height order)
+///
+/// With `keep_v2_hashes`, version-2 blocks are written as `x <hash>` so the
+/// built oracle can answer `HeightOracle::is_v1_block`.
+pub fn write_dataset<P: AsRef<Path>>(
+    blocks: &[FetchedBlock],
+    output_path: P,
+    keep_v2_hashes: bool,
+) -> Result<()> {
+    let output_path = output_path.as_ref();
+
+    // Ensure the parent directory exists
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Failed to create dataset file: {}", output_path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    for block in blocks {
+        if keep_v2_hashes {
+            writeln!(writer, "{}", block.annotated_dataset_line())?;
+        } else {
+            writeln!(writer, "{}", block.dataset_line())?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write fetched blocks as a binary dataset (see [`crate::hashdump`])
+///
+/// Same content as [`write_dataset`] at half the size. `blocks` must be
+/// contiguous and in height order; the first block's height is recorded.
+pub fn write_dataset_bin<P: AsRef<Path>>(
+    blocks: &[FetchedBlock],
+    output_path: P,
+    keep_v2_hashes: bool,
+) -> Result<()> {
+    let output_path = output_path.as_ref();
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let start_height = blocks.first().map_or(0, |block| block.height);
+    let records = blocks
+        .iter()
+        .map(|block| block.hash_record(keep_v2_hashes))
+        .collect::<Result<Vec<_>>>()?;
+
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Failed to create dataset file: {}", output_path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    crate::hashdump::write(&mut writer, start_height, &records)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write the raw headers of fetched blocks back to back, in height order
+pub fn write_headers<P: AsRef<Path>>(blocks: &[FetchedBlock], output_path: P) -> Result<()> {
+    let output_path = output_path.as_ref();
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Failed to create headers file: {}", output_path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    for block in blocks {
+        writer.write_all(&block.header)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Blocks per `getblockfilter` batch in [`fetch_filter_headers`]
+const FILTER_HEADER_BATCH: usize = 1000;
+
+/// Fetch the BIP158 basic filter header of every fetched block over RPC
+///
+/// The node must run with `-blockfilterindex`. `blocks` must start at genesis
+/// and be contiguous, in height order, so the result is indexed by height. Any
+/// failed lookup fails the whole fetch.
+pub async fn fetch_filter_headers(
+    client: &rpc::RpcClient,
+    blocks: &[FetchedBlock],
+) -> Result<Vec<[u8; 32]>> {
+    anyhow::ensure!(
+        (0u32..)
+            .zip(blocks)
+            .all(|(height, block)| block.height == height),
+        "Filter headers need contiguous blocks starting at genesis"
+    );
+    let mut headers = Vec::with_capacity(blocks.len());
+    for batch in blocks.chunks(FILTER_HEADER_BATCH) {
+        let hashes: Vec<String> = batch.iter().map(|block| block.hash.clone()).collect();
+        for (block, header) in batch
+            .iter()
+            .zip(client.fetch_filter_headers_batch(&hashes).await?)
+        {
+            headers.push(
+                header.with_context(|| format!("No filter header for height {}", block.height))?,
+            );
+        }
+    }
+    Ok(headers)
+}
+
+/// Write filter headers back to back, in height order (see [`crate::filterheaders`])
+pub fn write_filter_headers<P: AsRef<Path>>(headers: &[[u8; 32]], output_path: P) -> Result<()> {
+    let output_path = output_path.as_ref();
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let file = std::fs::File::create(output_path).with_context(|| {
+        format!(
+            "Failed to create filter headers file: {}",
+            output_path.display()
+        )
+    })?;
+    let mut writer = std::io::BufWriter::new(file);
+    for header in headers {
+        writer.write_all(header)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write the cumulative chainwork table for fetched blocks (see [`crate::chainwork`])
+///
+/// `blocks` must start at genesis and be contiguous, in height order.
+pub fn write_chainwork<P: AsRef<Path>>(blocks: &[FetchedBlock], output_path: P) -> Result<()> {
+    let output_path = output_path.as_ref();
+    anyhow::ensure!(
+        (0u32..)
+            .zip(blocks)
+            .all(|(height, block)| block.height == height),
+        "Chainwork needs contiguous blocks starting at genesis"
+    );
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let headers: Vec<[u8; HEADER_SIZE]> = blocks.iter().map(|block| block.header).collect();
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Failed to create chainwork file: {}", output_path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    crate::chainwork::Chainwork::from_headers(&headers).serialize(&mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u32, hash: &str) -> FetchedBlock {
+        FetchedBlock::from_header(height, hash.to_string(), [0; HEADER_SIZE])
+    }
+
+    #[test]
+    fn test_verify_blocks() {
+        let mainnet = [
+            block(0, Network::Mainnet.genesis_hash()),
+            block(1, &"ab".repeat(32)),
+        ];
+        let testnet = [block(0, Network::Testnet3.genesis_hash())];
+        assert!(verify_blocks(&mainnet, Network::Mainnet).is_ok());
+        assert!(verify_blocks(&testnet, Network::Testnet3).is_ok());
+
+        let err = verify_blocks(&testnet, Network::Mainnet).unwrap_err();
+        assert!(err.to_string().contains("another network"));
+        assert!(verify_blocks(&[block(5, "not hex")], Network::Mainnet).is_err());
+        assert!(verify_blocks(
+            &[block(21_111, Network::Mainnet.genesis_hash())],
+            Network::Testnet3
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Resumable fetch state
+//!
+//! A checkpoint file records every fetched block as `<height> <hash> <header hex>`,
+//! appended and flushed as batches complete, so an interrupted run can resume
+//! without refetching. Resuming rewrites the file atomically, so a crash then
+//! cannot lose the records already there.
+
+use super::FetchedBlock;
+use crate::save::{write_atomic, SaveOptions};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Append-only record of fetched blocks for [`super::fetch_blocks_resumable`]
+pub struct FetchCheckpoint {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    completed: Vec<FetchedBlock>,
+}
+
+impl FetchCheckpoint {
+    /// Start a fresh checkpoint at `path`, discarding any previous state
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open(path.as_ref(), Vec::new())
+    }
+
+    /// Load the blocks recorded at `path` (if any) and keep appending to it
+    ///
+    /// A truncated last record, e.g. from a crash mid-write, is dropped.
+    pub fn resume<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read checkpoint file: {}", path.display()))
+            }
+        };
+
+        // Only newline-terminated records are complete
+        let complete = &content[..content.rfind('\n').map_or(0, |end| end + 1)];
+        let completed = complete
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                parse_record(line).with_context(|| {
+                    format!("Invalid record on line {} of {}", i + 1, path.display())
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::open(path, completed)
+    }
+
+    /// Rewrite the file with `completed` and open it for appending
+    fn open(path: &Path, completed: Vec<FetchedBlock>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        write_atomic(path, &SaveOptions::default(), |writer| {
+            completed
+                .iter()
+                .try_for_each(|block| write_record(writer, block))
+        })?;
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open checkpoint file: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            completed,
+        })
+    }
+
+    /// Path of the checkpoint file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Blocks recorded before this run (when resuming)
+    pub fn completed(&self) -> &[FetchedBlock] {
+        &self.completed
+    }
+
+    /// Heights recorded before this run, merged into contiguous ranges
+    pub fn completed_ranges(&self) -> Vec<RangeInclusive<u32>> {
+        let mut heights: Vec<u32> = self.completed.iter().map(|block| block.height).collect();
+        heights.sort_unstable();
+        heights.dedup();
+
+        let mut ranges: Vec<RangeInclusive<u32>> = Vec::new();
+        for height in heights {
+            match ranges.last_mut() {
+                Some(range) if *range.end() + 1 == height => *range = *range.start()..=height,
+                _ => ranges.push(height..=height),
+            }
+        }
+        ranges
+    }
+
+    /// Append blocks and flush, so they survive a crash
+    pub fn record(&mut self, blocks: &[FetchedBlock]) -> Result<()> {
+        for block in blocks {
+            write_record(&mut self.writer, block)?;
+        }
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write checkpoint: {}", self.path.display()))
+    }
+
+    /// Delete the checkpoint file once the fetch has fully succeeded
+    pub fn remove(self) -> Result<()> {
+        let Self { path, writer, .. } = self;
+        drop(writer);
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove checkpoint file: {}", path.display()))
+    }
+}
+
+fn write_record(writer: &mut impl Write, block: &FetchedBlock) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "{} {} {}",
+        block.height,
+        block.hash,
+        hex::encode(block.header)
+    )
+}
+
+fn parse_record(line: &str) -> Result<FetchedBlock> {
+    let mut fields = line.split_whitespace();
+    let (Some(height), Some(hash), Some(header_hex), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        anyhow::bail!("expected `<height> <hash> <header hex>`");
+    };
+    let height = height.parse().context("Invalid height")?;
+    FetchedBlock::from_header_hex(height, hash.to_string(), header_hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u32) -> FetchedBlock {
+        let mut header = [0u8; crate::header::HEADER_SIZE];
+        header[0] = 1;
+        header[76] = height as u8;
+        FetchedBlock {
+            height,
+            hash: format!("{height:064x}"),
+            version: 1,
+            header,
+        }
+    }
+
+    #[test]
+    fn test_resume_skips_truncated_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+
+        let mut checkpoint = FetchCheckpoint::create(&path).unwrap();
+        checkpoint.record(&[block(0), block(1), block(5)]).unwrap();
+        drop(checkpoint);
+
+        // Simulate a crash halfway through writing the next record
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        write!(file, "6 00ab").unwrap();
+        drop(file);
+
+        let checkpoint = FetchCheckpoint::resume(&path).unwrap();
+        assert_eq!(checkpoint.completed(), &[block(0), block(1), block(5)]);
+        assert_eq!(checkpoint.completed_ranges(), vec![0..=1, 5..=5]);
+
+        // The partial record was dropped from the file too
+        let resumed_again = FetchCheckpoint::resume(&path).unwrap();
+        assert_eq!(resumed_again.completed().len(), 3);
+        resumed_again.remove().unwrap();
+        assert!(!path.exists());
+    }
+}
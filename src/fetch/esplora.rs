@@ -0,0 +1,87 @@
+//! Esplora REST backend (blockstream.info, mempool.space and self-hosted instances)
+
+use super::throttle::RateLimiter;
+use super::FetchedBlock;
+use anyhow::{anyhow, Context, Result};
+use std::time::Duration;
+
+/// Default public Esplora instance
+pub const DEFAULT_ESPLORA_URL: &str = "https://blockstream.info/api";
+
+/// Retries for rate-limited (HTTP 429) or unavailable (HTTP 503) responses
+const MAX_RETRIES: u32 = 5;
+
+/// Esplora REST client with a global request rate limit
+pub struct EsploraClient {
+    client: reqwest::Client,
+    base_url: String,
+    rate_limiter: RateLimiter,
+}
+
+impl EsploraClient {
+    /// Create a client for `base_url` (e.g. `https://blockstream.info/api`),
+    /// issuing at most `requests_per_second` requests across all tasks
+    pub fn new(base_url: impl Into<String>, requests_per_second: f64) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            rate_limiter: RateLimiter::new(requests_per_second),
+        }
+    }
+
+    /// GET `path` relative to the base URL, retrying on 429/503 with exponential backoff
+    async fn get(&self, path: &str) -> Result<String> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut backoff = Duration::from_millis(500);
+
+        for attempt in 0..=MAX_RETRIES {
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            self.rate_limiter.acquire().await;
+            let response = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .with_context(|| format!("GET {url} failed"))?;
+
+            let status = response.status();
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            {
+                continue;
+            }
+
+            let text = response.text().await?;
+            if !status.is_success() {
+                return Err(anyhow!("GET {url}: HTTP {status}: {text}"));
+            }
+            return Ok(text);
+        }
+
+        Err(anyhow!(
+            "GET {url}: still rate limited after {MAX_RETRIES} retries"
+        ))
+    }
+
+    /// `/block-height/:height` → block hash
+    pub async fn get_block_hash(&self, height: u32) -> Result<String> {
+        Ok(self
+            .get(&format!("/block-height/{height}"))
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    /// Fetch hash and raw header for a single height
+    pub async fn fetch_block(&self, height: u32) -> Result<FetchedBlock> {
+        let hash = self.get_block_hash(height).await?;
+
+        // `/block/:hash/header` returns the serialized header as hex
+        let header_hex = self.get(&format!("/block/{hash}/header")).await?;
+
+        FetchedBlock::from_header_hex(height, hash, &header_hex)
+    }
+}
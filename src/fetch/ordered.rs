@@ -0,0 +1,241 @@
+//! Writing fetched blocks in height order as they arrive
+//!
+//! Requests complete out of order, but a dataset is written in height order.
+//! [`OrderedWriter`] holds back only the blocks that arrive ahead of a gap
+//! and passes each block on once every lower height has been passed on. It
+//! does not bound how many it holds: [`super::fetch_blocks_ordered`] feeds it
+//! groups in request order, so that stays within one group.
+//! [`PartialDataset`] is a text dataset written that way to `<path>.partial`,
+//! which a later run can resume from and which is renamed into place once
+//! complete.
+
+use super::FetchedBlock;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Reorders blocks into height order, passing each on as soon as possible
+pub struct OrderedWriter<F> {
+    next_height: u32,
+    pending: BTreeMap<u32, FetchedBlock>,
+    emit: F,
+}
+
+impl<F: FnMut(&FetchedBlock) -> Result<()>> OrderedWriter<F> {
+    /// Pass blocks to `emit` in height order, starting at `start_height`
+    pub fn new(start_height: u32, emit: F) -> Self {
+        Self {
+            next_height: start_height,
+            pending: BTreeMap::new(),
+            emit,
+        }
+    }
+
+    /// Accept a block, then emit it and any held blocks that now follow on
+    ///
+    /// Blocks below the next height (duplicates) are ignored.
+    pub fn push(&mut self, block: FetchedBlock) -> Result<()> {
+        if block.height < self.next_height {
+            return Ok(());
+        }
+        self.pending.insert(block.height, block);
+        while let Some(block) = self.pending.remove(&self.next_height) {
+            (self.emit)(&block)?;
+            self.next_height += 1;
+        }
+        Ok(())
+    }
+
+    /// Height of the next block to emit
+    pub fn next_height(&self) -> u32 {
+        self.next_height
+    }
+
+    /// Number of blocks held back, waiting for a lower height
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// A text dataset written incrementally to `<path>.partial`
+///
+/// Lines are the same as [`super::write_dataset`] writes.
+pub struct PartialDataset {
+    path: PathBuf,
+    partial_path: PathBuf,
+    writer: BufWriter<File>,
+    lines: u64,
+    keep_v2_hashes: bool,
+}
+
+impl PartialDataset {
+    /// Start a new partial dataset for `path`, discarding any previous one
+    pub fn create<P: AsRef<Path>>(path: P, keep_v2_hashes: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let partial_path = partial_path(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        let file = File::create(&partial_path).with_context(|| {
+            format!("Failed to create dataset file: {}", partial_path.display())
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            partial_path,
+            writer: BufWriter::new(file),
+            lines: 0,
+            keep_v2_hashes,
+        })
+    }
+
+    /// Keep the complete lines of an existing partial dataset and append to it
+    ///
+    /// A truncated last line, e.g. from a crash mid-write, is dropped. Starts
+    /// empty if there is no partial dataset.
+    pub fn resume<P: AsRef<Path>>(path: P, keep_v2_hashes: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let partial_path = partial_path(path);
+        let file = match OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&partial_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Self::create(path, keep_v2_hashes)
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to open dataset file: {}", partial_path.display())
+                })
+            }
+        };
+
+        // Count complete lines without reading the file into memory
+        let mut reader = BufReader::new(&file);
+        let mut lines = 0u64;
+        let mut complete_len = 0u64;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            lines += 1;
+            complete_len += read as u64;
+        }
+        file.set_len(complete_len)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            partial_path,
+            writer: BufWriter::new(file),
+            lines,
+            keep_v2_hashes,
+        })
+    }
+
+    /// Number of lines written so far, including resumed ones
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    /// Append the line for `block`
+    pub fn write(&mut self, block: &FetchedBlock) -> Result<()> {
+        if self.keep_v2_hashes {
+            writeln!(self.writer, "{}", block.annotated_dataset_line())?;
+        } else {
+            writeln!(self.writer, "{}", block.dataset_line())?;
+        }
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Flush buffered lines to the partial file
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write {}", self.partial_path.display()))
+    }
+
+    /// Flush and move the partial file to the dataset path
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        std::fs::rename(&self.partial_path, &self.path).with_context(|| {
+            format!(
+                "Failed to move {} to {}",
+                self.partial_path.display(),
+                self.path.display()
+            )
+        })
+    }
+}
+
+/// `<path>.partial`
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u32) -> FetchedBlock {
+        let mut header = [0u8; crate::header::HEADER_SIZE];
+        header[0] = 1;
+        FetchedBlock::from_header(height, format!("{height:064x}"), header)
+    }
+
+    #[test]
+    fn test_ordered_writer() {
+        let mut emitted = Vec::new();
+        let mut writer = OrderedWriter::new(10, |block: &FetchedBlock| {
+            emitted.push(block.height);
+            Ok(())
+        });
+        writer.push(block(12)).unwrap();
+        writer.push(block(11)).unwrap();
+        assert_eq!(writer.pending(), 2);
+        writer.push(block(10)).unwrap();
+        writer.push(block(10)).unwrap();
+        assert_eq!((writer.next_height(), writer.pending()), (13, 0));
+        drop(writer);
+        assert_eq!(emitted, [10, 11, 12]);
+    }
+
+    #[test]
+    fn test_resume_partial_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prebip34.txt");
+
+        let mut dataset = PartialDataset::create(&path, false).unwrap();
+        dataset.write(&block(0)).unwrap();
+        dataset.write(&block(1)).unwrap();
+        dataset.flush().unwrap();
+        drop(dataset);
+
+        // Simulate a crash halfway through writing the next line
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(partial_path(&path))
+            .unwrap();
+        write!(file, "00ab").unwrap();
+        drop(file);
+
+        let mut dataset = PartialDataset::resume(&path, false).unwrap();
+        assert_eq!(dataset.lines(), 2);
+        dataset.write(&block(2)).unwrap();
+        dataset.finish().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let expected: Vec<String> = (0..3).map(|height| block(height).hash).collect();
+        assert_eq!(content.lines().collect::<Vec<_>>(), expected);
+        assert!(!partial_path(&path).exists());
+    }
+}
@@ -0,0 +1,451 @@
+//! Bitcoin P2P headers-sync backend
+//!
+//! Connects to any listening node, performs the version/verack handshake and
+//! walks the chain with `getheaders` (2,000 headers per message), so no RPC
+//! credentials or local node are needed. Every header is checked to link to the
+//! previous one, starting from the hard-coded genesis header, and to meet the
+//! proof-of-work target mainnet's rules require: no easier than the limit,
+//! unchanged within a retarget period and re-derived at each retarget, so a
+//! peer cannot make up a low-difficulty chain.
+//!
+//! Headers only arrive in chain order over one connection, so a run is a single
+//! sequential sync from genesis up to the highest requested height.
+
+use super::FetchedBlock;
+use crate::consensus::DIFFICULTY_ADJUSTMENT_INTERVAL;
+use crate::header::{self, BlockHeader, HEADER_SIZE};
+use crate::BlockHash;
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Default peer: a DNS seed resolving to listening mainnet nodes
+pub const DEFAULT_P2P_ADDR: &str = "seed.bitcoin.sipa.be:8333";
+
+/// Mainnet message start bytes
+const MAINNET_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+/// Protocol version announced in `version` and `getheaders`
+const PROTOCOL_VERSION: u32 = 70016;
+
+/// Most headers a peer sends per `headers` message
+const MAX_HEADERS_PER_MESSAGE: usize = 2_000;
+
+/// Largest payload accepted from a peer (Bitcoin Core's MAX_PROTOCOL_MESSAGE_LENGTH)
+const MAX_PAYLOAD_SIZE: usize = 4_000_000;
+
+/// Message header: magic[4] | command[12] | length[4] | checksum[4]
+const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// Mainnet genesis block header
+const GENESIS_HEADER_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+/// Headers-sync client for a single peer
+#[derive(Clone)]
+pub struct P2pClient {
+    addr: String,
+    timeout: Duration,
+}
+
+impl P2pClient {
+    /// Create a client for `addr` (`host:port`, e.g. `seed.bitcoin.sipa.be:8333`)
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Give up on a peer that sends nothing for `timeout` (default 30 s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Download and verify the headers of heights `0..count`
+    pub async fn fetch_headers(&self, count: usize) -> Result<Vec<[u8; HEADER_SIZE]>> {
+        let mut chain = HeaderChain::new();
+        if count <= 1 {
+            chain.headers.truncate(count);
+            return Ok(chain.headers);
+        }
+
+        let mut peer = Peer::connect(&self.addr, self.timeout).await?;
+        peer.handshake().await?;
+        while chain.headers.len() < count {
+            peer.send("getheaders", &getheaders_payload(&chain.tip))
+                .await?;
+            let headers = peer.recv_headers().await?;
+            anyhow::ensure!(
+                !headers.is_empty(),
+                "Peer {} has no headers past height {}",
+                self.addr,
+                chain.headers.len() - 1
+            );
+            chain.extend(&headers)?;
+        }
+
+        chain.headers.truncate(count);
+        Ok(chain.headers)
+    }
+
+    /// Fetch the blocks at `heights`, syncing headers up to the highest one
+    pub async fn fetch_heights(&self, heights: &[u32]) -> Result<Vec<FetchedBlock>> {
+        let Some(&max_height) = heights.iter().max() else {
+            return Ok(Vec::new());
+        };
+        let headers = self.fetch_headers(max_height as usize + 1).await?;
+        Ok(heights
+            .iter()
+            .map(|&height| {
+                let raw = headers[height as usize];
+                FetchedBlock::from_header(height, header::block_hash(&raw).to_string(), raw)
+            })
+            .collect())
+    }
+
+    /// Fetch hash and raw header for a single height (syncs every header below it)
+    pub async fn fetch_block(&self, height: u32) -> Result<FetchedBlock> {
+        self.fetch_heights(&[height])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("No header for height {height}"))
+    }
+}
+
+/// Verified headers from genesis, in height order
+struct HeaderChain {
+    headers: Vec<[u8; HEADER_SIZE]>,
+    tip: BlockHash,
+}
+
+impl HeaderChain {
+    /// Start from the hard-coded genesis header
+    fn new() -> Self {
+        let genesis: [u8; HEADER_SIZE] = hex::decode(GENESIS_HEADER_HEX)
+            .expect("genesis header is valid hex")
+            .try_into()
+            .expect("genesis header is HEADER_SIZE bytes");
+        Self {
+            tip: header::block_hash(&genesis),
+            headers: vec![genesis],
+        }
+    }
+
+    /// Append headers that link to the tip and meet the required proof-of-work target
+    fn extend(&mut self, headers: &[[u8; HEADER_SIZE]]) -> Result<()> {
+        for raw in headers {
+            let height = self.headers.len();
+            let parsed = BlockHeader::parse(raw);
+            anyhow::ensure!(
+                parsed.prev_blockhash == self.tip,
+                "Header at height {height} does not link to the previous block"
+            );
+            let expected = self.required_bits(height);
+            anyhow::ensure!(
+                parsed.bits == expected,
+                "Header at height {height} has nBits {:#010x}, expected {expected:#010x}",
+                parsed.bits
+            );
+            anyhow::ensure!(
+                header::within_pow_limit(&parsed.target()),
+                "Header at height {height} has a target easier than the proof-of-work limit"
+            );
+            let hash = header::block_hash(raw);
+            anyhow::ensure!(
+                header::hash_meets_target(&hash, &parsed.target()),
+                "Header at height {height} does not meet its proof-of-work target"
+            );
+            self.headers.push(*raw);
+            self.tip = hash;
+        }
+        Ok(())
+    }
+
+    /// `nBits` mainnet requires of the block at `height`, which follows the tip
+    fn required_bits(&self, height: usize) -> u32 {
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let last = BlockHeader::parse(&self.headers[height - 1]);
+        if !height.is_multiple_of(interval) {
+            return last.bits;
+        }
+        let first = BlockHeader::parse(&self.headers[height - interval]);
+        header::retarget_bits(last.bits, first.time, last.time)
+    }
+}
+
+/// One TCP connection to a peer
+struct Peer {
+    stream: TcpStream,
+    timeout: Duration,
+}
+
+impl Peer {
+    async fn connect(addr: &str, timeout: Duration) -> Result<Self> {
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow!("Timed out connecting to {addr}"))?
+            .with_context(|| format!("Failed to connect to {addr}"))?;
+        Ok(Self { stream, timeout })
+    }
+
+    /// Exchange `version`/`verack` with the peer
+    async fn handshake(&mut self) -> Result<()> {
+        self.send("version", &version_payload()).await?;
+        let (mut got_version, mut got_verack) = (false, false);
+        while !(got_version && got_verack) {
+            let (command, _) = self.recv().await?;
+            match command.as_str() {
+                "version" => {
+                    got_version = true;
+                    self.send("verack", &[]).await?;
+                }
+                "verack" => got_verack = true,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, command: &str, payload: &[u8]) -> Result<()> {
+        self.stream
+            .write_all(&encode_message(command, payload))
+            .await
+            .with_context(|| format!("Failed to send {command}"))
+    }
+
+    /// Read the next message, answering pings along the way
+    async fn recv(&mut self) -> Result<(String, Vec<u8>)> {
+        loop {
+            let mut header = [0u8; MESSAGE_HEADER_SIZE];
+            self.read_exact(&mut header).await?;
+            let (command, length, checksum) = decode_message_header(&header)?;
+            let mut payload = vec![0u8; length];
+            self.read_exact(&mut payload).await?;
+            anyhow::ensure!(
+                sha256d(&payload)[..4] == checksum,
+                "Bad checksum on {command} message"
+            );
+
+            if command == "ping" {
+                self.send("pong", &payload).await?;
+                continue;
+            }
+            return Ok((command, payload));
+        }
+    }
+
+    /// Wait for the next `headers` message, skipping unrelated ones
+    async fn recv_headers(&mut self) -> Result<Vec<[u8; HEADER_SIZE]>> {
+        loop {
+            let (command, payload) = self.recv().await?;
+            if command == "headers" {
+                return parse_headers_payload(&payload);
+            }
+        }
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        tokio::time::timeout(self.timeout, self.stream.read_exact(buf))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for peer"))?
+            .context("Peer closed the connection")?;
+        Ok(())
+    }
+}
+
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+/// Frame `payload` as a mainnet message
+fn encode_message(command: &str, payload: &[u8]) -> Vec<u8> {
+    let mut name = [0u8; 12];
+    name[..command.len()].copy_from_slice(command.as_bytes());
+
+    let mut message = Vec::with_capacity(MESSAGE_HEADER_SIZE + payload.len());
+    message.extend_from_slice(&MAINNET_MAGIC);
+    message.extend_from_slice(&name);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(&sha256d(payload)[..4]);
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Split a message header into command, payload length and checksum
+fn decode_message_header(header: &[u8; MESSAGE_HEADER_SIZE]) -> Result<(String, usize, [u8; 4])> {
+    anyhow::ensure!(
+        header[..4] == MAINNET_MAGIC,
+        "Peer is not on mainnet (message start {})",
+        hex::encode(&header[..4])
+    );
+    let name = &header[4..16];
+    let command = String::from_utf8_lossy(&name[..name.iter().position(|&b| b == 0).unwrap_or(12)])
+        .into_owned();
+    let length = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+    anyhow::ensure!(
+        length <= MAX_PAYLOAD_SIZE,
+        "Peer sent a {length}-byte {command} message"
+    );
+    Ok((command, length, header[20..24].try_into().unwrap()))
+}
+
+/// `version` payload for a headers-only client that does not relay transactions
+fn version_payload() -> Vec<u8> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    // services[8] | ip[16] | port[2], left unset
+    let empty_addr = [0u8; 26];
+
+    let mut payload = Vec::with_capacity(86);
+    payload.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    payload.extend_from_slice(&0u64.to_le_bytes()); // services
+    payload.extend_from_slice(&timestamp.to_le_bytes());
+    payload.extend_from_slice(&empty_addr); // addr_recv
+    payload.extend_from_slice(&empty_addr); // addr_from
+    payload.extend_from_slice(&rand_nonce().to_le_bytes());
+    let user_agent = concat!("/height-oracle:", env!("CARGO_PKG_VERSION"), "/");
+    payload.push(user_agent.len() as u8);
+    payload.extend_from_slice(user_agent.as_bytes());
+    payload.extend_from_slice(&0i32.to_le_bytes()); // start_height
+    payload.push(0); // relay
+    payload
+}
+
+/// Connection nonce, only used by peers to detect connecting to themselves
+fn rand_nonce() -> u64 {
+    use std::hash::{BuildHasher, RandomState};
+    RandomState::new().hash_one(std::process::id())
+}
+
+/// `getheaders` payload asking for the headers after `tip`
+fn getheaders_payload(tip: &BlockHash) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(4 + 1 + 32 + 32);
+    payload.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    payload.push(1); // locator length
+    payload.extend_from_slice(tip.as_bytes());
+    payload.extend_from_slice(&[0u8; 32]); // hash_stop: as many as allowed
+    payload
+}
+
+/// Parse a `headers` payload: count, then each header followed by a zero tx count
+fn parse_headers_payload(payload: &[u8]) -> Result<Vec<[u8; HEADER_SIZE]>> {
+    let (count, mut rest) = read_compact_size(payload)?;
+    anyhow::ensure!(
+        count <= MAX_HEADERS_PER_MESSAGE as u64,
+        "Peer sent {count} headers in one message"
+    );
+    let mut headers = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        anyhow::ensure!(rest.len() > HEADER_SIZE, "Truncated headers message");
+        let (raw, tail) = rest.split_at(HEADER_SIZE);
+        headers.push(raw.try_into().expect("split at HEADER_SIZE"));
+        let (_tx_count, tail) = read_compact_size(tail)?;
+        rest = tail;
+    }
+    Ok(headers)
+}
+
+/// Read a CompactSize integer, returning it and the remaining bytes
+fn read_compact_size(data: &[u8]) -> Result<(u64, &[u8])> {
+    let (&first, rest) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("Truncated message"))?;
+    let width = match first {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        n => return Ok((u64::from(n), rest)),
+    };
+    anyhow::ensure!(rest.len() >= width, "Truncated message");
+    let mut bytes = [0u8; 8];
+    bytes[..width].copy_from_slice(&rest[..width]);
+    Ok((u64::from_le_bytes(bytes), &rest[width..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_1_HEADER_HEX: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    fn block_1() -> [u8; HEADER_SIZE] {
+        hex::decode(BLOCK_1_HEADER_HEX).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_message_framing() {
+        // verack has an empty payload with the well-known checksum 5df6e0e2
+        let message = encode_message("verack", &[]);
+        assert_eq!(
+            hex::encode(&message),
+            "f9beb4d976657261636b000000000000000000005df6e0e2"
+        );
+        let (command, length, checksum) =
+            decode_message_header(message[..24].try_into().unwrap()).unwrap();
+        assert_eq!((command.as_str(), length), ("verack", 0));
+        assert_eq!(checksum, [0x5d, 0xf6, 0xe0, 0xe2]);
+
+        let mut testnet = message.clone();
+        testnet[..4].copy_from_slice(&[0x0b, 0x11, 0x09, 0x07]);
+        assert!(decode_message_header(testnet[..24].try_into().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_headers_payload() {
+        let mut payload = vec![2];
+        for _ in 0..2 {
+            payload.extend_from_slice(&block_1());
+            payload.push(0);
+        }
+        assert_eq!(parse_headers_payload(&payload).unwrap(), vec![block_1(); 2]);
+        assert!(parse_headers_payload(&payload[..payload.len() - 1]).is_err());
+        assert_eq!(read_compact_size(&[0xfd, 0xd0, 0x07]).unwrap().0, 2_000);
+    }
+
+    #[test]
+    fn test_header_chain() {
+        let mut chain = HeaderChain::new();
+        assert_eq!(
+            chain.tip.to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        chain.extend(&[block_1()]).unwrap();
+        assert_eq!(chain.headers.len(), 2);
+
+        // Block 1 again does not link to block 1
+        assert!(chain.extend(&[block_1()]).is_err());
+
+        // A tampered nonce breaks proof of work
+        let mut chain = HeaderChain::new();
+        let mut tampered = block_1();
+        tampered[79] ^= 1;
+        assert!(chain.extend(&[tampered]).is_err());
+
+        // A peer cannot pick an easier target, even one its hash meets
+        let mut easy = block_1();
+        easy[72..76].copy_from_slice(&0x207fffffu32.to_le_bytes());
+        let err = HeaderChain::new().extend(&[easy]).unwrap_err();
+        assert!(err.to_string().contains("nBits"));
+    }
+
+    #[test]
+    fn test_required_bits() {
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let mut chain = HeaderChain::new();
+        let at = |time: u32| {
+            let mut raw = block_1();
+            raw[68..72].copy_from_slice(&time.to_le_bytes());
+            raw
+        };
+        // Headers are only read for their time and bits here
+        chain.headers = (0..interval as u32).map(|i| at(i * 300)).collect();
+        assert_eq!(chain.required_bits(interval - 1), 0x1d00ffff);
+
+        // A period mined in just under half the target time just over halves the target
+        assert_eq!(chain.required_bits(interval), 0x1c7fef3f);
+    }
+}
@@ -0,0 +1,214 @@
+//! bitcoind REST backend (`-rest=1`)
+//!
+//! The REST interface needs no credentials and returns up to 2,000 headers per
+//! request, so a group of consecutive heights costs two requests: one for the
+//! first block hash and one for the headers. Every header is hashed, and each
+//! hash must equal the `prev_blockhash` of the following header, so the server
+//! cannot pair a header with some other block's hash.
+
+use super::throttle::{self, Throttle, ThrottleOptions, WorkQueueFull};
+use super::FetchedBlock;
+use crate::header::{self, BlockHeader, HEADER_SIZE};
+use anyhow::{anyhow, Context, Result};
+use std::sync::Arc;
+
+/// Default bitcoind REST endpoint (same port as RPC)
+pub const DEFAULT_REST_URL: &str = "http://127.0.0.1:8332";
+
+/// Most headers bitcoind returns for one `/rest/headers` request
+pub const MAX_HEADERS_PER_REQUEST: usize = 2_000;
+
+/// Largest group of heights fetched in one round trip (one extra header is needed for the last hash)
+pub const MAX_BATCH_SIZE: usize = MAX_HEADERS_PER_REQUEST - 1;
+
+/// Minimal bitcoind REST client
+///
+/// Shares bitcoind's work queue with RPC, so it retries the same way as
+/// [`super::rpc::RpcClient`].
+#[derive(Clone)]
+pub struct RestClient {
+    client: reqwest::Client,
+    base_url: String,
+    throttle: Arc<Throttle>,
+}
+
+impl RestClient {
+    /// Create a client for `base_url` (e.g. `http://127.0.0.1:8332`)
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            throttle: Arc::default(),
+        }
+    }
+
+    /// Replace the default throttle (no caps, adaptive retries) with one using `options`
+    pub fn with_throttle(mut self, options: ThrottleOptions) -> Self {
+        self.throttle = Arc::new(Throttle::new(options));
+        self
+    }
+
+    /// GET `path` relative to the base URL through the throttle
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let url = format!("{}{}", self.base_url, path);
+        self.throttle.run(|| self.get_once(&url)).await
+    }
+
+    /// GET an absolute URL
+    async fn get_once(&self, url: &str) -> Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("GET {url} failed (is bitcoind running with -rest=1?)"))?;
+
+        let status = response.status();
+        let body = response.bytes().await?.to_vec();
+        if throttle::is_work_queue_full(status, &String::from_utf8_lossy(&body)) {
+            return Err(WorkQueueFull.into());
+        }
+        if !status.is_success() {
+            return Err(anyhow!(
+                "GET {url}: HTTP {status}: {}",
+                String::from_utf8_lossy(&body).trim()
+            ));
+        }
+        Ok(body)
+    }
+
+    /// `/rest/blockhashbyheight/<height>.hex` → block hash
+    pub async fn get_block_hash(&self, height: u32) -> Result<String> {
+        let body = self
+            .get(&format!("/rest/blockhashbyheight/{height}.hex"))
+            .await?;
+        Ok(String::from_utf8(body)
+            .context("Block hash is not UTF-8")?
+            .trim()
+            .to_string())
+    }
+
+    /// `/rest/headers/<hash>.bin?count=<count>` → raw headers starting at `hash`
+    pub async fn get_headers(&self, hash: &str, count: usize) -> Result<Vec<u8>> {
+        self.get(&format!("/rest/headers/{hash}.bin?count={count}"))
+            .await
+    }
+
+    /// Fetch hash and raw header for a single height
+    pub async fn fetch_block(&self, height: u32) -> Result<FetchedBlock> {
+        let hash = self.get_block_hash(height).await?;
+        let data = self.get_headers(&hash, 1).await?;
+        let header: [u8; HEADER_SIZE] = data
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Expected one {HEADER_SIZE}-byte header for block {hash}"))?;
+        Ok(FetchedBlock::from_header(height, hash, header))
+    }
+
+    /// Fetch `count` consecutive blocks starting at `start_height` in two requests
+    ///
+    /// `count` must not exceed [`MAX_BATCH_SIZE`].
+    pub async fn fetch_range(&self, start_height: u32, count: usize) -> Result<Vec<FetchedBlock>> {
+        anyhow::ensure!(
+            count <= MAX_BATCH_SIZE,
+            "At most {MAX_BATCH_SIZE} blocks per REST request, got {count}"
+        );
+        let first_hash = self.get_block_hash(start_height).await?;
+        let data = self.get_headers(&first_hash, count + 1).await?;
+        blocks_from_headers(start_height, first_hash, &data, count)
+    }
+}
+
+/// Split `count` blocks out of at least `count + 1` consecutive raw headers
+///
+/// Each block's hash is its header's double SHA-256, which must be the next
+/// header's `prev_blockhash`; the first one must match `first_hash`.
+fn blocks_from_headers(
+    start_height: u32,
+    first_hash: String,
+    data: &[u8],
+    count: usize,
+) -> Result<Vec<FetchedBlock>> {
+    anyhow::ensure!(
+        data.len().is_multiple_of(HEADER_SIZE),
+        "Headers response is {} bytes, not a multiple of {HEADER_SIZE}",
+        data.len()
+    );
+    let headers: Vec<[u8; HEADER_SIZE]> = data
+        .chunks_exact(HEADER_SIZE)
+        .map(|chunk| chunk.try_into().expect("chunk is HEADER_SIZE bytes"))
+        .collect();
+    anyhow::ensure!(
+        headers.len() > count,
+        "Asked for {} headers from block {first_hash}, got {}",
+        count + 1,
+        headers.len()
+    );
+
+    let blocks = (start_height..)
+        .zip(headers.windows(2).take(count))
+        .map(|(height, pair)| {
+            let hash = header::block_hash(&pair[0]);
+            anyhow::ensure!(
+                BlockHeader::parse(&pair[1]).prev_blockhash == hash,
+                "Header at height {height} ({hash}) is not the parent of the next header"
+            );
+            Ok(FetchedBlock::from_header(height, hash.to_string(), pair[0]))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(first) = blocks.first() {
+        anyhow::ensure!(
+            first.hash == first_hash,
+            "Headers from {first_hash} do not start with that block (got {})",
+            first.hash
+        );
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` version-1 headers, each linking to the one before
+    fn chain(n: usize) -> Vec<[u8; HEADER_SIZE]> {
+        let mut headers: Vec<[u8; HEADER_SIZE]> = Vec::with_capacity(n);
+        for nonce in 0..n as u32 {
+            let mut raw = [0u8; HEADER_SIZE];
+            raw[0] = 1;
+            if let Some(prev) = headers.last() {
+                raw[4..36].copy_from_slice(header::block_hash(prev).as_bytes());
+            }
+            raw[76..80].copy_from_slice(&nonce.to_le_bytes());
+            headers.push(raw);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_blocks_from_headers() {
+        let headers = chain(4);
+        let data = headers.concat();
+        let hash = |i: usize| header::block_hash(&headers[i]).to_string();
+
+        let blocks = blocks_from_headers(10, hash(0), &data, 3).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].height, 10);
+        assert_eq!(blocks[2].hash, hash(2));
+        assert_eq!(blocks[1].header, headers[1]);
+        assert_eq!(blocks[1].version, 1);
+
+        // The last block needs one more header to check its hash
+        assert!(blocks_from_headers(10, hash(0), &data, 4).is_err());
+        assert!(blocks_from_headers(10, hash(1), &data, 3).is_err());
+        assert!(blocks_from_headers(10, hash(0), &data[1..], 2).is_err());
+
+        // A header that is not the parent of the next one, including the last block's
+        for tampered in [1, 2] {
+            let mut headers = headers.clone();
+            headers[tampered][76] ^= 1;
+            let err = blocks_from_headers(10, hash(0), &headers.concat(), 3).unwrap_err();
+            assert!(err.to_string().contains("not the parent"));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Request throttling for the bitcoind backends
+//!
+//! bitcoind answers HTTP 503 `Work queue depth exceeded` when more requests
+//! arrive than its `-rpcworkqueue` holds. [`Throttle`] retries those requests
+//! after a jittered exponential backoff and adapts to the node: each rejection
+//! halves the number of requests it lets through at once, and every run of
+//! successful requests as long as the current cap raises the cap by one again.
+//! It can also cap requests per second across all tasks.
+
+use crate::rng::splitmix64;
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+
+/// Global requests-per-second cap shared by all tasks
+pub struct RateLimiter {
+    min_interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow at most `requests_per_second` requests (0 or less: no cap)
+    pub fn new(requests_per_second: f64) -> Self {
+        let min_interval = if requests_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+        Self {
+            min_interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request slot
+    pub async fn acquire(&self) {
+        if self.min_interval.is_zero() {
+            return;
+        }
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.min_interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// The node rejected a request because its work queue was full (HTTP 503)
+#[derive(Debug)]
+pub struct WorkQueueFull;
+
+impl std::fmt::Display for WorkQueueFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bitcoind work queue depth exceeded (raise -rpcworkqueue or lower the concurrency)"
+        )
+    }
+}
+
+impl std::error::Error for WorkQueueFull {}
+
+/// Whether an HTTP response is bitcoind's work queue rejection
+pub fn is_work_queue_full(status: reqwest::StatusCode, body: &str) -> bool {
+    status == reqwest::StatusCode::SERVICE_UNAVAILABLE && body.contains("Work queue depth exceeded")
+}
+
+/// Limits for a [`Throttle`]
+#[derive(Debug, Clone)]
+pub struct ThrottleOptions {
+    /// Most requests in flight at once, and the ceiling the adaptive cap grows back to
+    pub max_in_flight: usize,
+    /// Requests per second across all tasks (0: no cap)
+    pub requests_per_second: f64,
+    /// Retries of a request rejected with [`WorkQueueFull`]
+    pub max_retries: u32,
+    /// Backoff before the first retry; doubles on each further retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for ThrottleOptions {
+    fn default() -> Self {
+        Self {
+            max_in_flight: usize::MAX,
+            requests_per_second: 0.0,
+            max_retries: 8,
+            initial_backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+struct State {
+    /// Current cap on requests in flight
+    limit: usize,
+    in_flight: usize,
+    /// Successes since the cap last changed
+    successes: usize,
+    /// Bumped on every cut, so one wave of rejections cuts the cap only once
+    cuts: u64,
+    /// Generator state for the retry jitter
+    rng: u64,
+}
+
+/// An in-flight slot, freed when dropped
+///
+/// A request future dropped mid-flight (a timeout, a cancelled task) still
+/// gives its slot back; only a finished request adapts the cap.
+struct Slot<'a> {
+    throttle: &'a Throttle,
+    /// Cut count when the slot was taken
+    cuts: u64,
+    /// Whether the node rejected the request, once it finished
+    overloaded: Option<bool>,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.throttle.release(self.cuts, self.overloaded);
+    }
+}
+
+/// Adaptive in-flight cap, rate limit and retry policy for one node
+pub struct Throttle {
+    max_in_flight: usize,
+    max_retries: u32,
+    initial_backoff: Duration,
+    rate_limiter: RateLimiter,
+    state: std::sync::Mutex<State>,
+    released: Notify,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(ThrottleOptions::default())
+    }
+}
+
+impl Throttle {
+    /// Create a throttle that starts at `options.max_in_flight`
+    pub fn new(options: ThrottleOptions) -> Self {
+        let max_in_flight = options.max_in_flight.max(1);
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self {
+            max_in_flight,
+            max_retries: options.max_retries,
+            initial_backoff: options.initial_backoff,
+            rate_limiter: RateLimiter::new(options.requests_per_second),
+            state: std::sync::Mutex::new(State {
+                limit: max_in_flight,
+                in_flight: 0,
+                successes: 0,
+                cuts: 0,
+                rng: seed,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current cap on requests in flight
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Run `request`, retrying with backoff while it fails with [`WorkQueueFull`]
+    ///
+    /// Each attempt waits for a free in-flight slot and a rate limit slot.
+    /// Other errors are returned as they are.
+    pub async fn run<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.initial_backoff;
+        for attempt in 0..=self.max_retries {
+            let mut slot = self.acquire().await;
+            let result = request().await;
+            let overloaded = matches!(&result, Err(e) if e.is::<WorkQueueFull>());
+            slot.overloaded = Some(overloaded);
+            drop(slot);
+
+            if !overloaded {
+                return result;
+            }
+            if attempt < self.max_retries {
+                tokio::time::sleep(self.jitter(backoff)).await;
+                backoff *= 2;
+            }
+        }
+        Err(anyhow!(WorkQueueFull)
+            .context(format!("Still rejected after {} retries", self.max_retries)))
+    }
+
+    /// Wait for a free in-flight slot, then for the rate limiter
+    async fn acquire(&self) -> Slot<'_> {
+        let cuts = loop {
+            let released = self.released.notified();
+            let mut released = std::pin::pin!(released);
+            // Register before checking, so a release in between is not missed
+            released.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    break state.cuts;
+                }
+            }
+            released.await;
+        };
+        // Hold the slot from here, so cancelling during the rate limit wait frees it
+        let slot = Slot {
+            throttle: self,
+            cuts,
+            overloaded: None,
+        };
+        self.rate_limiter.acquire().await;
+        slot
+    }
+
+    /// Free a slot and adapt the cap to how the request went
+    ///
+    /// A rejection only cuts the cap if no other rejection already did since
+    /// the request started (`cuts` is the count when its slot was taken).
+    /// `overloaded` is `None` for a request that never finished, which leaves
+    /// the cap as it is.
+    fn release(&self, cuts: u64, overloaded: Option<bool>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if overloaded == Some(true) {
+                if state.cuts == cuts {
+                    // The node took fewer requests than were in flight
+                    state.limit = (state.limit.min(state.in_flight) / 2).max(1);
+                    state.successes = 0;
+                    state.cuts += 1;
+                }
+            } else if overloaded == Some(false) && state.limit < self.max_in_flight {
+                state.successes += 1;
+                if state.successes >= state.limit {
+                    state.limit += 1;
+                    state.successes = 0;
+                }
+            }
+            state.in_flight -= 1;
+        }
+        self.released.notify_waiters();
+    }
+
+    /// A random duration between half of `backoff` and `backoff`, so retries spread out
+    fn jitter(&self, backoff: Duration) -> Duration {
+        let random = splitmix64(&mut self.state.lock().unwrap().rng);
+        let fraction = (random >> 11) as f64 / (1u64 << 53) as f64;
+        backoff.mul_f64(0.5 + fraction / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_adaptive_limit() {
+        let throttle = Throttle::new(ThrottleOptions {
+            max_in_flight: 8,
+            ..Default::default()
+        });
+        throttle.state.lock().unwrap().in_flight = 8;
+        throttle.release(0, Some(true));
+        assert_eq!(throttle.limit(), 4);
+
+        // Rejections of requests started before that cut do not cut again
+        throttle.release(0, Some(true));
+        assert_eq!(throttle.limit(), 4);
+        throttle.state.lock().unwrap().in_flight = 0;
+
+        // One slot back after a full window of successes, never past the maximum
+        for _ in 0..4 {
+            throttle.state.lock().unwrap().in_flight += 1;
+            throttle.release(1, Some(false));
+        }
+        assert_eq!(throttle.limit(), 5);
+        for _ in 0..100 {
+            throttle.state.lock().unwrap().in_flight += 1;
+            throttle.release(1, Some(false));
+        }
+        assert_eq!(throttle.limit(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_retries_work_queue_rejections() {
+        let throttle = Throttle::new(ThrottleOptions {
+            max_in_flight: 4,
+            max_retries: 3,
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        });
+        let attempts = AtomicU32::new(0);
+        let result = throttle
+            .run(|| async {
+                if attempts.fetch_add(1, Ordering::Relaxed) < 2 {
+                    Err(anyhow!(WorkQueueFull))
+                } else {
+                    Ok(7)
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+        assert_eq!(throttle.limit(), 2);
+
+        // Other errors are not retried, and retries run out
+        let result: Result<()> = throttle.run(|| async { Err(anyhow!("boom")) }).await;
+        assert_eq!(result.unwrap_err().to_string(), "boom");
+        let result: Result<()> = throttle.run(|| async { Err(anyhow!(WorkQueueFull)) }).await;
+        assert!(result.unwrap_err().is::<WorkQueueFull>());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_frees_its_slot() {
+        let throttle = Throttle::new(ThrottleOptions {
+            max_in_flight: 1,
+            ..Default::default()
+        });
+        let pending = throttle.run(std::future::pending::<Result<()>>);
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), pending).await;
+        assert!(timed_out.is_err());
+        assert_eq!(throttle.state.lock().unwrap().in_flight, 0);
+        assert_eq!(throttle.limit(), 1);
+
+        // The freed slot is available to the next request
+        let result = throttle.run(|| async { Ok(3) }).await;
+        assert_eq!(result.unwrap(), 3);
+    }
+}
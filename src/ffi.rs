@@ -0,0 +1,205 @@
+//! C ABI over the embedded oracle (Feature: ffi)
+//!
+//! Backs the .NET bindings in `bindings/dotnet` and any other language that
+//! can call C; `bindings/c/height_oracle.h` declares the functions. An oracle
+//! is an opaque handle from [`height_oracle_open`], freed with
+//! [`height_oracle_free`]. Handles are independent of the global oracle and
+//! of each other, and are safe to share between threads.
+//!
+//! Every function returns a `HEIGHT_ORACLE_*` status code and writes results
+//! through out pointers. Block hashes are 32 bytes in network byte order
+//! (NBitcoin's `uint256.ToBytes()`), or 64 reverse-hex characters.
+
+use crate::{BlockHash, HeightLookup, HeightOracleEmbedded, Network};
+use std::ffi::{c_char, CStr};
+
+/// Success
+pub const HEIGHT_ORACLE_OK: i32 = 0;
+/// A required pointer argument was null
+pub const HEIGHT_ORACLE_NULL_POINTER: i32 = 1;
+/// A hex block hash was not 64 hex characters
+pub const HEIGHT_ORACLE_INVALID_HASH: i32 = 2;
+/// The network id is unknown or its assets are not embedded in this build
+pub const HEIGHT_ORACLE_UNSUPPORTED_NETWORK: i32 = 3;
+/// The embedded assets failed to load
+pub const HEIGHT_ORACLE_LOAD_FAILED: i32 = 4;
+
+/// Network ids accepted by [`height_oracle_open`]
+pub const HEIGHT_ORACLE_MAINNET: u32 = 0;
+/// Testnet3 (needs the `embedded-testnet3` feature)
+pub const HEIGHT_ORACLE_TESTNET3: u32 = 1;
+
+/// Opaque oracle handle
+pub struct HeightOracleHandle(HeightOracleEmbedded);
+
+/// Load the embedded oracle for `network` into a new handle stored in `*out`
+///
+/// # Safety
+///
+/// `out` must be null or valid for writing a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_open(
+    network: u32,
+    out: *mut *mut HeightOracleHandle,
+) -> i32 {
+    if out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    let network = match network {
+        HEIGHT_ORACLE_MAINNET => Network::Mainnet,
+        HEIGHT_ORACLE_TESTNET3 => Network::Testnet3,
+        _ => return HEIGHT_ORACLE_UNSUPPORTED_NETWORK,
+    };
+    match HeightOracleEmbedded::for_network(network) {
+        Ok(oracle) => {
+            *out = Box::into_raw(Box::new(HeightOracleHandle(oracle)));
+            HEIGHT_ORACLE_OK
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => HEIGHT_ORACLE_UNSUPPORTED_NETWORK,
+        Err(_) => HEIGHT_ORACLE_LOAD_FAILED,
+    }
+}
+
+/// Free a handle from [`height_oracle_open`]; null is ignored
+///
+/// # Safety
+///
+/// `handle` must be null or a handle from [`height_oracle_open`] that has not
+/// been freed, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_free(handle: *mut HeightOracleHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Number of blocks in the oracle, or 0 for a null handle
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_len(handle: *const HeightOracleHandle) -> u64 {
+    handle.as_ref().map_or(0, |handle| handle.0.len() as u64)
+}
+
+/// Look up the height of a 32-byte block hash in network byte order (unchecked)
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `hash` null or valid for reading
+/// 32 bytes, and `out` null or valid for writing a `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_get_height(
+    handle: *const HeightOracleHandle,
+    hash: *const u8,
+    out: *mut u32,
+) -> i32 {
+    let Some(handle) = handle.as_ref() else {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    };
+    if hash.is_null() || out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    let block_hash = BlockHash::from_byte_array(*hash.cast::<[u8; 32]>());
+    *out = handle.0.get_height_unchecked(&block_hash);
+    HEIGHT_ORACLE_OK
+}
+
+/// Look up the height of a block hash given as a NUL-terminated reverse-hex string (unchecked)
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `hash_hex` null or a NUL-terminated
+/// string, and `out` null or valid for writing a `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_get_height_hex(
+    handle: *const HeightOracleHandle,
+    hash_hex: *const c_char,
+    out: *mut u32,
+) -> i32 {
+    let Some(handle) = handle.as_ref() else {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    };
+    if hash_hex.is_null() || out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    let Some(block_hash) = CStr::from_ptr(hash_hex)
+        .to_str()
+        .ok()
+        .and_then(|hex| crate::parse_block_hash(hex).ok())
+    else {
+        return HEIGHT_ORACLE_INVALID_HASH;
+    };
+    *out = handle.0.get_height_unchecked(&block_hash);
+    HEIGHT_ORACLE_OK
+}
+
+/// Look up `count` block hashes stored back to back (32 bytes each, network
+/// byte order), writing `count` heights to `out` (unchecked)
+///
+/// Uses the batched lookup (see [`HeightLookup::get_heights_unchecked`]).
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `hashes` null or valid for reading
+/// `32 * count` bytes, and `out` null or valid for writing `count` `u32`s.
+#[no_mangle]
+pub unsafe extern "C" fn height_oracle_get_heights(
+    handle: *const HeightOracleHandle,
+    hashes: *const u8,
+    count: usize,
+    out: *mut u32,
+) -> i32 {
+    let Some(handle) = handle.as_ref() else {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    };
+    if count == 0 {
+        return HEIGHT_ORACLE_OK;
+    }
+    if hashes.is_null() || out.is_null() {
+        return HEIGHT_ORACLE_NULL_POINTER;
+    }
+    // BlockHash is a transparent wrapper around [u8; 32], alignment 1
+    let block_hashes = std::slice::from_raw_parts(hashes.cast::<BlockHash>(), count);
+    let heights = std::slice::from_raw_parts_mut(out, count);
+    handle.0.get_heights_unchecked(block_hashes, heights);
+    HEIGHT_ORACLE_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_null_pointers() {
+        let mut height = 0u32;
+        unsafe {
+            assert_eq!(
+                height_oracle_open(HEIGHT_ORACLE_MAINNET, ptr::null_mut()),
+                HEIGHT_ORACLE_NULL_POINTER
+            );
+            assert_eq!(
+                height_oracle_get_height(ptr::null(), [0u8; 32].as_ptr(), &mut height),
+                HEIGHT_ORACLE_NULL_POINTER
+            );
+            assert_eq!(
+                height_oracle_get_heights(ptr::null(), ptr::null(), 0, ptr::null_mut()),
+                HEIGHT_ORACLE_NULL_POINTER
+            );
+            assert_eq!(height_oracle_len(ptr::null()), 0);
+            height_oracle_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_open_unknown_network() {
+        let mut handle = ptr::null_mut();
+        assert_eq!(
+            unsafe { height_oracle_open(7, &mut handle) },
+            HEIGHT_ORACLE_UNSUPPORTED_NETWORK
+        );
+        assert!(handle.is_null());
+    }
+}
@@ -0,0 +1,255 @@
+//! XOR filter over the pre-BIP34 key set for fast negative checks
+//!
+//! Oracle lookups are unchecked: a hash outside the dataset still returns some
+//! height. Pipelines fed mostly post-BIP34 hashes can ask [`XorFilter::contains`]
+//! first and skip the oracle for hashes that are certainly not pre-BIP34.
+//!
+//! This is an 8-bit XOR filter (Graf & Lemire, 2019): about 9.8 bits per key
+//! and a false positive rate of 1/256. There are no false negatives. Block hashes
+//! are already uniformly random, so their first 16 bytes are mixed with a seed
+//! rather than hashed again.
+//!
+//! Asset format: [seed: u64][block_length: u32][fingerprints: 3 * block_length bytes]
+
+use crate::BlockHash;
+use std::io::{self, Read, Write};
+#[cfg(feature = "embedded-filter")]
+use std::sync::OnceLock;
+
+// Embedded filter asset at compile time
+#[cfg(feature = "embedded-filter")]
+const FILTER_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/prebip34.xor8.dat"
+));
+
+/// Seeds tried before giving up; each attempt succeeds with high probability
+const MAX_ATTEMPTS: u64 = 100;
+
+/// Probabilistic set membership for block hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XorFilter {
+    seed: u64,
+    block_length: u32,
+    fingerprints: Vec<u8>,
+}
+
+/// 64-bit finalizer from MurmurHash3
+fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+fn key_hash(block_hash: &BlockHash, seed: u64) -> u64 {
+    let bytes = block_hash.as_bytes();
+    let a = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+    let b = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    mix(a ^ mix(b ^ seed))
+}
+
+fn fingerprint(hash: u64) -> u8 {
+    (hash ^ (hash >> 32)) as u8
+}
+
+impl XorFilter {
+    /// Build a filter containing `block_hashes`
+    ///
+    /// Duplicates are allowed. Deterministic: the same keys give the same filter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no seed works, which for distinct keys happens with negligible probability.
+    pub fn build(block_hashes: &[BlockHash]) -> Self {
+        let capacity = 32 + (1.23 * block_hashes.len() as f64).ceil() as usize;
+        let block_length = (capacity / 3) as u32;
+
+        for attempt in 0..MAX_ATTEMPTS {
+            let seed = mix(attempt.wrapping_add(0x9e37_79b9_7f4a_7c15));
+            let mut hashes: Vec<u64> = block_hashes.iter().map(|h| key_hash(h, seed)).collect();
+            hashes.sort_unstable();
+            hashes.dedup();
+
+            let mut filter = Self {
+                seed,
+                block_length,
+                fingerprints: vec![0; 3 * block_length as usize],
+            };
+            if filter.assign(&hashes) {
+                return filter;
+            }
+        }
+        panic!("Failed to build XOR filter after {MAX_ATTEMPTS} seeds");
+    }
+
+    /// The three fingerprint slots of a key hash, one per block
+    fn slots(&self, hash: u64) -> [usize; 3] {
+        let reduce = |h: u64| ((h as u32 as u64 * u64::from(self.block_length)) >> 32) as usize;
+        let block_length = self.block_length as usize;
+        [
+            reduce(hash),
+            reduce(hash.rotate_left(21)) + block_length,
+            reduce(hash.rotate_left(42)) + 2 * block_length,
+        ]
+    }
+
+    /// Peel the key hashes and fill the fingerprints; false if the hypergraph has a cycle
+    fn assign(&mut self, hashes: &[u64]) -> bool {
+        let capacity = self.fingerprints.len();
+        let mut xor_masks = vec![0u64; capacity];
+        let mut counts = vec![0u32; capacity];
+        for &hash in hashes {
+            for slot in self.slots(hash) {
+                xor_masks[slot] ^= hash;
+                counts[slot] += 1;
+            }
+        }
+
+        // Repeatedly remove keys that are alone in one of their slots
+        let mut queue: Vec<usize> = (0..capacity).filter(|&i| counts[i] == 1).collect();
+        let mut stack = Vec::with_capacity(hashes.len());
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let hash = xor_masks[slot];
+            stack.push((hash, slot));
+            for other in self.slots(hash) {
+                xor_masks[other] ^= hash;
+                counts[other] -= 1;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+        if stack.len() != hashes.len() {
+            return false;
+        }
+
+        // Assign in reverse peeling order so each key's own slot is set last
+        for &(hash, slot) in stack.iter().rev() {
+            let [a, b, c] = self.slots(hash);
+            self.fingerprints[slot] = fingerprint(hash)
+                ^ self.fingerprints[a]
+                ^ self.fingerprints[b]
+                ^ self.fingerprints[c];
+        }
+        true
+    }
+
+    /// Whether `block_hash` may be in the set; `false` means it is certainly not
+    pub fn contains(&self, block_hash: &BlockHash) -> bool {
+        let hash = key_hash(block_hash, self.seed);
+        let [a, b, c] = self.slots(hash);
+        fingerprint(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+
+    /// Size of the fingerprint table in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Serialize the filter to writer
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.block_length.to_le_bytes())?;
+        writer.write_all(&self.fingerprints)
+    }
+
+    /// Deserialize a filter from reader
+    ///
+    /// Fails with `InvalidData` on a block length of 0, which [`Self::build`]
+    /// never writes, even for an empty key set.
+    pub fn deserialize<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut seed = [0u8; 8];
+        reader.read_exact(&mut seed)?;
+        let mut block_length = [0u8; 4];
+        reader.read_exact(&mut block_length)?;
+        let block_length = u32::from_le_bytes(block_length);
+        // Even a filter over no keys has slots, and lookups index into them
+        if block_length == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "XOR filter has a block length of 0",
+            ));
+        }
+
+        let mut fingerprints = vec![0u8; 3 * block_length as usize];
+        reader.read_exact(&mut fingerprints)?;
+        Ok(Self {
+            seed: u64::from_le_bytes(seed),
+            block_length,
+            fingerprints,
+        })
+    }
+}
+
+/// Global singleton for the embedded filter
+#[cfg(feature = "embedded-filter")]
+static EMBEDDED_FILTER: OnceLock<XorFilter> = OnceLock::new();
+
+/// Whether `block_hash` may be pre-BIP34, using the embedded filter (Feature: embedded-filter)
+///
+/// `false` means the hash is certainly not in the oracle, so the lookup can be skipped.
+#[cfg(feature = "embedded-filter")]
+pub fn probably_prebip34(block_hash: &BlockHash) -> bool {
+    EMBEDDED_FILTER
+        .get_or_init(|| {
+            XorFilter::deserialize(FILTER_DATA).expect("Failed to load embedded XOR filter")
+        })
+        .contains(block_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random_hashes(count: u64, offset: u64) -> Vec<BlockHash> {
+        (offset..offset + count)
+            .map(|i| {
+                let mut bytes = [0u8; 32];
+                for (j, chunk) in bytes.chunks_mut(8).enumerate() {
+                    chunk.copy_from_slice(&mix(i * 4 + j as u64).to_le_bytes());
+                }
+                BlockHash::from_byte_array(bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let keys = pseudo_random_hashes(10_000, 0);
+        let filter = XorFilter::build(&keys);
+        assert!(keys.iter().all(|key| filter.contains(key)));
+        assert!(filter.size_bytes() < 13_000);
+
+        // 1/256 expected; allow some slack
+        let others = pseudo_random_hashes(10_000, 1_000_000);
+        let false_positives = others.iter().filter(|key| filter.contains(key)).count();
+        assert!(false_positives < 100, "{false_positives} false positives");
+
+        let mut data = Vec::new();
+        filter.serialize(&mut data).unwrap();
+        assert_eq!(XorFilter::deserialize(data.as_slice()).unwrap(), filter);
+    }
+
+    #[test]
+    fn test_empty_and_duplicate_keys() {
+        let filter = XorFilter::build(&[]);
+        assert_eq!(filter.size_bytes(), 30);
+        let mut data = Vec::new();
+        filter.serialize(&mut data).unwrap();
+        assert_eq!(XorFilter::deserialize(data.as_slice()).unwrap(), filter);
+
+        // A forged zero block length would make every lookup panic
+        data[8..12].fill(0);
+        let err = XorFilter::deserialize(&data[..12]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let keys = pseudo_random_hashes(3, 0);
+        let filter = XorFilter::build(&[keys.clone(), keys.clone()].concat());
+        assert!(keys.iter().all(|key| filter.contains(key)));
+    }
+}
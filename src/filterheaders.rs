@@ -0,0 +1,180 @@
+//! BIP158 filter header oracle
+//!
+//! Maps pre-BIP34 block hashes to their basic compact filter headers, so light
+//! clients rescanning old blocks can check a filter against its header without
+//! downloading the whole filter header chain. Like [`crate::timestamps`], the
+//! headers are a [`SideTable`] indexed by the height oracle's perfect hash and
+//! only add [`crate::FILTER_HEADERS_FILE_NAME`].
+//!
+//! The headers come from a node running with `-blockfilterindex` (the fetcher's
+//! `--filter-headers-output`). They are kept in internal byte order, the order
+//! they are hashed in; RPC shows them reversed, like block hashes.
+//!
+//! Asset format: [num_entries: u32][num_entries * 32-byte headers]
+
+use crate::side_table::SideTable;
+use crate::BlockHash;
+use std::io::{self, Read, Write};
+
+/// Size of a filter header in bytes
+pub const FILTER_HEADER_SIZE: usize = 32;
+
+/// Parse a filter header from RPC's reverse hex into internal byte order
+pub fn parse_filter_header(hex_str: &str) -> Result<[u8; FILTER_HEADER_SIZE], String> {
+    let hex: &[u8; 64] = hex_str
+        .as_bytes()
+        .try_into()
+        .map_err(|_| "Filter header must be exactly 64 hex characters")?;
+    crate::block_hash::decode_reverse_hex(hex).ok_or_else(|| "Invalid hex".to_string())
+}
+
+/// Header of the filter following `prev_header`: `sha256d(sha256d(filter) || prev_header)` (Feature: sha256d)
+///
+/// `filter` is the serialized filter as returned by `getblockfilter`. Checking
+/// the result against the oracle's header for that block verifies the filter.
+#[cfg(feature = "sha256d")]
+pub fn next_filter_header(
+    filter: &[u8],
+    prev_header: &[u8; FILTER_HEADER_SIZE],
+) -> [u8; FILTER_HEADER_SIZE] {
+    use sha2::{Digest, Sha256};
+    let filter_hash = Sha256::digest(Sha256::digest(filter));
+    let mut preimage = [0u8; 2 * FILTER_HEADER_SIZE];
+    preimage[..FILTER_HEADER_SIZE].copy_from_slice(&filter_hash);
+    preimage[FILTER_HEADER_SIZE..].copy_from_slice(prev_header);
+    Sha256::digest(Sha256::digest(preimage)).into()
+}
+
+/// Read filter headers stored back to back, as written by the fetcher
+pub fn read_filter_headers<R: Read>(mut reader: R) -> io::Result<Vec<[u8; FILTER_HEADER_SIZE]>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() % FILTER_HEADER_SIZE != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Filter headers file length {} is not a multiple of {FILTER_HEADER_SIZE}",
+                data.len()
+            ),
+        ));
+    }
+    Ok(data
+        .chunks_exact(FILTER_HEADER_SIZE)
+        .map(|chunk| chunk.try_into().expect("chunk is FILTER_HEADER_SIZE bytes"))
+        .collect())
+}
+
+/// Serialize filter headers as a count followed by the headers
+pub fn serialize_filter_headers<W: Write>(
+    headers: &[[u8; FILTER_HEADER_SIZE]],
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(&(headers.len() as u32).to_le_bytes())?;
+    for header in headers {
+        writer.write_all(header)?;
+    }
+    Ok(())
+}
+
+/// Deserialize filter headers written by [`serialize_filter_headers`]
+pub fn deserialize_filter_headers<R: Read>(
+    mut reader: R,
+) -> io::Result<Vec<[u8; FILTER_HEADER_SIZE]>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    let byte_len = u64::from(len) * FILTER_HEADER_SIZE as u64;
+
+    // Read what is there rather than trusting the count for the allocation
+    let mut data = Vec::new();
+    reader.take(byte_len).read_to_end(&mut data)?;
+    if data.len() as u64 != byte_len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Filter headers asset is truncated: expected {len} headers"),
+        ));
+    }
+    read_filter_headers(data.as_slice())
+}
+
+/// Block hash → BIP158 basic filter header lookups for pre-BIP34 blocks
+pub struct FilterHeaderOracle {
+    table: SideTable<[u8; FILTER_HEADER_SIZE]>,
+}
+
+impl FilterHeaderOracle {
+    /// Load from in-memory asset bytes (the height oracle's PtrHash asset + filter headers asset)
+    pub fn from_bytes(ptrhash_data: &[u8], filter_headers_data: &[u8]) -> io::Result<Self> {
+        let headers = deserialize_filter_headers(filter_headers_data)?;
+        let table = SideTable::from_bytes(ptrhash_data, headers, "filter headers")?;
+        Ok(Self { table })
+    }
+
+    /// Load from asset files on disk
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_files<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        filter_headers_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let filter_headers_data = std::fs::read(filter_headers_path)?;
+        Self::from_bytes(&ptrhash_data, &filter_headers_data)
+    }
+
+    /// Look up the basic filter header for a block hash (unchecked)
+    ///
+    /// Like the height lookups, hashes outside the dataset return the filter
+    /// header of some other block.
+    pub fn get_filter_header_unchecked(&self, block_hash: &BlockHash) -> [u8; FILTER_HEADER_SIZE] {
+        self.table.get_unchecked(block_hash)
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Basic filter header of the mainnet genesis block, as `getblockfilter` shows it
+    const GENESIS_FILTER_HEADER: &str =
+        "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750";
+
+    #[test]
+    fn test_filter_headers_roundtrip() {
+        let headers = vec![parse_filter_header(GENESIS_FILTER_HEADER).unwrap(), [7; 32]];
+        assert_eq!(headers[0][31], 0x21);
+
+        let mut data = Vec::new();
+        serialize_filter_headers(&headers, &mut data).unwrap();
+        assert_eq!(data.len(), 4 + 2 * FILTER_HEADER_SIZE);
+        assert_eq!(
+            deserialize_filter_headers(data.as_slice()).unwrap(),
+            headers
+        );
+
+        let err = deserialize_filter_headers(&data[..data.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(read_filter_headers(&data[4..35]).is_err());
+        assert!(parse_filter_header("zz").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "sha256d")]
+    fn test_next_filter_header_genesis() {
+        // The genesis block's basic filter holds just its coinbase output script
+        let filter = [0x01, 0x9d, 0xfc, 0xa8];
+        assert_eq!(
+            next_filter_header(&filter, &[0; FILTER_HEADER_SIZE]),
+            parse_filter_header(GENESIS_FILTER_HEADER).unwrap()
+        );
+    }
+}
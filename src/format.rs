@@ -0,0 +1,492 @@
+//! On-disk format versioning for the PtrHash and heights assets
+//!
+//! Assets saved by this version of the crate start with an 8-byte header:
+//! [magic: "HOFV"][format_version: u16][asset_kind: u8][input_policy: u8].
+//! Format version 1 is the original raw layout, which follows the header
+//! unchanged. The input policy byte records which blocks the builder took from
+//! its dataset (see [`InputPolicy`]); 0 means it was not recorded. Files
+//! written before versioning have no header at all; they are called legacy
+//! assets and are read with `HeightOracle::load_legacy`.
+//!
+//! Format version 2 appends [height_start: u32][height_end: u32] to the header,
+//! the half-open height range a partial oracle was restricted to. Only assets
+//! with a recorded range are written as version 2, so others stay readable by
+//! older builds.
+//!
+//! Format version 3 appends [dataset_commitment: 32 bytes] to the version 2
+//! header (see [`crate::commitment`]); a height range of 0..0 there means no
+//! range was recorded. Only assets with a recorded commitment are written as
+//! version 3.
+//!
+//! Loaders refuse versions newer than [`FORMAT_VERSION`], so an old build fails
+//! with a clear error rather than misreading assets from a newer one.
+
+use crate::commitment::DatasetCommitment;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+/// Magic bytes at the start of a versioned asset
+pub const FORMAT_MAGIC: [u8; 4] = *b"HOFV";
+
+/// Newest format version this build reads, written for assets with a dataset commitment
+pub const FORMAT_VERSION: u16 = 3;
+
+/// Size of the version 1 format header, the prefix shared by every version
+pub const HEADER_LEN: usize = 8;
+
+/// Size of the version 2 format header, which adds the height range
+pub const HEADER_V2_LEN: usize = HEADER_LEN + 8;
+
+/// Size of the version 3 format header, which adds the dataset commitment
+pub const HEADER_V3_LEN: usize = HEADER_V2_LEN + 32;
+
+/// Which asset a header belongs to, so swapped paths are caught
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    /// PtrHash asset (`phash.ptrh.dat`)
+    PtrHash,
+    /// Packed heights asset (`heights.u18packed.dat`)
+    Heights,
+}
+
+impl AssetKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            AssetKind::PtrHash => 1,
+            AssetKind::Heights => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(AssetKind::PtrHash),
+            2 => Some(AssetKind::Heights),
+            _ => None,
+        }
+    }
+}
+
+/// Which dataset entries the builder turns into oracle entries
+///
+/// Version-2 blocks are marked in datasets with an `x` placeholder, which may
+/// carry the block's hash as `x <hash>`. The policy decides what happens to
+/// them and so which blocks the oracle covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum InputPolicy {
+    /// Include every block with a hash, version-2 blocks from `x <hash>` lines
+    /// too; bare `x` placeholders are skipped
+    #[default]
+    IncludeAll,
+    /// Include version-1 blocks only, even when version-2 hashes are present
+    SkipV2,
+    /// Fail on bare `x` placeholders, so every height in the dataset is covered
+    RejectPlaceholders,
+}
+
+impl InputPolicy {
+    fn to_byte(self) -> u8 {
+        match self {
+            InputPolicy::IncludeAll => 1,
+            InputPolicy::SkipV2 => 2,
+            InputPolicy::RejectPlaceholders => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(InputPolicy::IncludeAll),
+            2 => Some(InputPolicy::SkipV2),
+            3 => Some(InputPolicy::RejectPlaceholders),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InputPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputPolicy::IncludeAll => write!(f, "include-all"),
+            InputPolicy::SkipV2 => write!(f, "skip-v2"),
+            InputPolicy::RejectPlaceholders => write!(f, "reject-placeholders"),
+        }
+    }
+}
+
+impl std::str::FromStr for InputPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "include-all" => Ok(InputPolicy::IncludeAll),
+            "skip-v2" => Ok(InputPolicy::SkipV2),
+            "reject-placeholders" => Ok(InputPolicy::RejectPlaceholders),
+            _ => Err(format!(
+                "Unknown input policy {s:?}, expected include-all, skip-v2 or reject-placeholders"
+            )),
+        }
+    }
+}
+
+/// Fields of a checked format header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetHeader {
+    /// Format version
+    pub version: u16,
+    /// Input policy the asset was built with, if recorded
+    pub input_policy: Option<InputPolicy>,
+    /// Heights the asset was restricted to, if recorded (version 2 and later)
+    pub height_range: Option<Range<u32>>,
+    /// Commitment to the dataset the asset was built from, if recorded (version 3)
+    pub dataset_commitment: Option<DatasetCommitment>,
+}
+
+/// Size of the header written for an asset with or without a height range
+pub const fn header_len(has_height_range: bool) -> usize {
+    version_header_len(header_version(has_height_range, false))
+}
+
+/// Format version written for an asset with or without a height range and dataset commitment
+pub const fn header_version(has_height_range: bool, has_dataset_commitment: bool) -> u16 {
+    if has_dataset_commitment {
+        3
+    } else if has_height_range {
+        2
+    } else {
+        1
+    }
+}
+
+/// Size of the header of a known format version
+pub const fn version_header_len(version: u16) -> usize {
+    match version {
+        0 | 1 => HEADER_LEN,
+        2 => HEADER_V2_LEN,
+        _ => HEADER_V3_LEN,
+    }
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetKind::PtrHash => write!(f, "PtrHash"),
+            AssetKind::Heights => write!(f, "heights"),
+        }
+    }
+}
+
+/// Write the format header for `kind`, recording `input_policy`, `height_range`
+/// and `dataset_commitment` if known
+///
+/// The header is the oldest version that holds what there is to record (see
+/// [`header_version`]).
+pub fn write_header<W: Write>(
+    mut writer: W,
+    kind: AssetKind,
+    input_policy: Option<InputPolicy>,
+    height_range: Option<&Range<u32>>,
+    dataset_commitment: Option<&DatasetCommitment>,
+) -> io::Result<()> {
+    writer.write_all(&encode_header(
+        kind,
+        input_policy,
+        height_range,
+        dataset_commitment,
+    ))
+}
+
+fn encode_header(
+    kind: AssetKind,
+    input_policy: Option<InputPolicy>,
+    height_range: Option<&Range<u32>>,
+    dataset_commitment: Option<&DatasetCommitment>,
+) -> Vec<u8> {
+    let version = header_version(height_range.is_some(), dataset_commitment.is_some());
+    let mut header = Vec::with_capacity(version_header_len(version));
+    header.extend_from_slice(&FORMAT_MAGIC);
+    header.extend_from_slice(&version.to_le_bytes());
+    header.push(kind.to_byte());
+    header.push(input_policy.map_or(0, InputPolicy::to_byte));
+    if version >= 2 {
+        let range = height_range.cloned().unwrap_or(0..0);
+        header.extend_from_slice(&range.start.to_le_bytes());
+        header.extend_from_slice(&range.end.to_le_bytes());
+    }
+    if let Some(commitment) = dataset_commitment {
+        header.extend_from_slice(commitment.as_bytes());
+    }
+    header
+}
+
+/// Read and check the format header of a `kind` asset
+///
+/// Fails on legacy (headerless) assets, on the wrong asset kind, and on
+/// versions this build does not know.
+pub fn read_header<R: Read>(mut reader: R, kind: AssetKind) -> io::Result<AssetHeader> {
+    let mut header = [0u8; HEADER_V3_LEN];
+    reader.read_exact(&mut header[..HEADER_LEN])?;
+    if header[..4] != FORMAT_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{kind} asset has no format header (written before format versioning); \
+                 load it with HeightOracle::load_legacy"
+            ),
+        ));
+    }
+    let len = version_header_len(check_version(&header, kind)?);
+    reader.read_exact(&mut header[HEADER_LEN..len])?;
+    check_header(&header[..len], kind)
+}
+
+/// Skip the format header of a `kind` asset if it has one
+///
+/// For loaders that accept both versioned and legacy assets, such as the
+/// embedded oracle. The header, when present, is checked like [`read_header`].
+pub fn strip_header(data: &[u8], kind: AssetKind) -> io::Result<&[u8]> {
+    match data.first_chunk::<HEADER_LEN>() {
+        Some(header) if header[..4] == FORMAT_MAGIC => {
+            let len = version_header_len(check_version(header, kind)?);
+            let header = data.get(..len).ok_or(io::ErrorKind::UnexpectedEof)?;
+            check_header(header, kind)?;
+            Ok(&data[len..])
+        }
+        _ => Ok(data),
+    }
+}
+
+/// Format version of an asset, or `None` for a legacy asset
+pub fn detect_version(data: &[u8]) -> Option<u16> {
+    let header = data.first_chunk::<HEADER_LEN>()?;
+    (header[..4] == FORMAT_MAGIC).then(|| u16::from_le_bytes([header[4], header[5]]))
+}
+
+/// Input policy recorded in an asset's header, or `None` if it has none
+pub fn detect_input_policy(data: &[u8]) -> Option<InputPolicy> {
+    let header = data.first_chunk::<HEADER_LEN>()?;
+    (header[..4] == FORMAT_MAGIC)
+        .then(|| InputPolicy::from_byte(header[7]))
+        .flatten()
+}
+
+/// Height range recorded in an asset's header, or `None` if it has none
+pub fn detect_height_range(data: &[u8]) -> Option<Range<u32>> {
+    let header = data.first_chunk::<HEADER_V2_LEN>()?;
+    (header[..4] == FORMAT_MAGIC && detect_version(data)? >= 2)
+        .then(|| decode_height_range(header))
+        .flatten()
+}
+
+/// Dataset commitment recorded in an asset's header, or `None` if it has none
+pub fn detect_dataset_commitment(data: &[u8]) -> Option<DatasetCommitment> {
+    let header = data.first_chunk::<HEADER_V3_LEN>()?;
+    (header[..4] == FORMAT_MAGIC && detect_version(data)? >= 3)
+        .then(|| decode_dataset_commitment(header))
+}
+
+fn decode_dataset_commitment(header: &[u8; HEADER_V3_LEN]) -> DatasetCommitment {
+    DatasetCommitment::from_bytes(header[HEADER_V2_LEN..].try_into().unwrap())
+}
+
+/// Height range of a version 2 or 3 header, `None` if it is empty or reversed
+fn decode_height_range(header: &[u8; HEADER_V2_LEN]) -> Option<Range<u32>> {
+    let start = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let end = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    (start < end).then_some(start..end)
+}
+
+/// Version of a header with the magic already checked, refusing unknown versions
+fn check_version(header: &[u8], kind: AssetKind) -> io::Result<u16> {
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{kind} asset has format version {version}, but this build only reads \
+                 versions 1 to {FORMAT_VERSION}; upgrade height-oracle to load it"
+            ),
+        ));
+    }
+    Ok(version)
+}
+
+/// Check a complete header (8 bytes for version 1, 16 for version 2, 48 for version 3)
+fn check_header(header: &[u8], kind: AssetKind) -> io::Result<AssetHeader> {
+    let version = check_version(header, kind)?;
+    let height_range = match header.first_chunk::<HEADER_V2_LEN>() {
+        // Version 3 headers record "no range" as 0..0
+        Some(header) if version >= 3 && header[8..] == [0; 8] => None,
+        Some(header) if version >= 2 => Some(decode_height_range(header).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{kind} asset header has an empty height range"),
+            )
+        })?),
+        _ => None,
+    };
+    let dataset_commitment = match header.first_chunk::<HEADER_V3_LEN>() {
+        Some(header) if version >= 3 => Some(decode_dataset_commitment(header)),
+        _ => None,
+    };
+    match AssetKind::from_byte(header[6]) {
+        Some(found) if found == kind => Ok(AssetHeader {
+            version,
+            input_policy: InputPolicy::from_byte(header[7]),
+            height_range,
+            dataset_commitment,
+        }),
+        Some(found) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected a {kind} asset, found a {found} asset (are the paths swapped?)"),
+        )),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown asset kind {} in format header", header[6]),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let mut data = Vec::new();
+        write_header(
+            &mut data,
+            AssetKind::Heights,
+            Some(InputPolicy::SkipV2),
+            None,
+            None,
+        )
+        .unwrap();
+        data.extend_from_slice(b"payload");
+
+        assert_eq!(detect_version(&data), Some(1));
+        assert_eq!(strip_header(&data, AssetKind::Heights).unwrap(), b"payload");
+        let mut reader = data.as_slice();
+        assert_eq!(
+            read_header(&mut reader, AssetKind::Heights).unwrap(),
+            AssetHeader {
+                version: 1,
+                input_policy: Some(InputPolicy::SkipV2),
+                height_range: None,
+                dataset_commitment: None,
+            }
+        );
+        assert_eq!(detect_input_policy(&data), Some(InputPolicy::SkipV2));
+        assert_eq!(reader, b"payload");
+
+        // Swapped paths are caught
+        let err = read_header(data.as_slice(), AssetKind::PtrHash).unwrap_err();
+        assert!(err.to_string().contains("are the paths swapped?"));
+    }
+
+    #[test]
+    fn test_height_range_header() {
+        let mut data = Vec::new();
+        write_header(
+            &mut data,
+            AssetKind::Heights,
+            None,
+            Some(&(100..5_000)),
+            None,
+        )
+        .unwrap();
+        assert_eq!(data.len(), HEADER_V2_LEN);
+        data.extend_from_slice(b"payload");
+
+        assert_eq!(detect_version(&data), Some(2));
+        assert_eq!(detect_height_range(&data), Some(100..5_000));
+        assert_eq!(strip_header(&data, AssetKind::Heights).unwrap(), b"payload");
+        let mut reader = data.as_slice();
+        let header = read_header(&mut reader, AssetKind::Heights).unwrap();
+        assert_eq!(header.height_range, Some(100..5_000));
+        assert_eq!(reader, b"payload");
+
+        // Version 1 headers have no range
+        let v1 = encode_header(AssetKind::Heights, None, None, None);
+        assert_eq!(detect_height_range(&v1), None);
+
+        // An empty range is corrupt
+        let empty = encode_header(AssetKind::Heights, None, Some(&(7..7)), None);
+        let err = read_header(empty.as_slice(), AssetKind::Heights).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(strip_header(&empty[..HEADER_LEN + 4], AssetKind::Heights).is_err());
+    }
+
+    #[test]
+    fn test_dataset_commitment_header() {
+        let commitment = DatasetCommitment::from_bytes([0xab; 32]);
+        let mut data = Vec::new();
+        write_header(
+            &mut data,
+            AssetKind::Heights,
+            Some(InputPolicy::IncludeAll),
+            None,
+            Some(&commitment),
+        )
+        .unwrap();
+        assert_eq!(data.len(), HEADER_V3_LEN);
+        data.extend_from_slice(b"payload");
+
+        assert_eq!(detect_version(&data), Some(3));
+        assert_eq!(detect_dataset_commitment(&data), Some(commitment));
+        assert_eq!(detect_height_range(&data), None);
+        assert_eq!(strip_header(&data, AssetKind::Heights).unwrap(), b"payload");
+        let mut reader = data.as_slice();
+        assert_eq!(
+            read_header(&mut reader, AssetKind::Heights).unwrap(),
+            AssetHeader {
+                version: 3,
+                input_policy: Some(InputPolicy::IncludeAll),
+                height_range: None,
+                dataset_commitment: Some(commitment),
+            }
+        );
+        assert_eq!(reader, b"payload");
+
+        // With a height range as well
+        let both = encode_header(
+            AssetKind::Heights,
+            None,
+            Some(&(100..5_000)),
+            Some(&commitment),
+        );
+        let header = read_header(both.as_slice(), AssetKind::Heights).unwrap();
+        assert_eq!(header.height_range, Some(100..5_000));
+        assert_eq!(header.dataset_commitment, Some(commitment));
+
+        // Older headers have no commitment
+        let v2 = encode_header(AssetKind::Heights, None, Some(&(100..5_000)), None);
+        assert_eq!(detect_dataset_commitment(&v2), None);
+        assert!(strip_header(&both[..HEADER_V2_LEN], AssetKind::Heights).is_err());
+    }
+
+    #[test]
+    fn test_legacy_and_unknown_versions() {
+        let legacy = [0u8; 16];
+        assert_eq!(detect_version(&legacy), None);
+        assert_eq!(detect_input_policy(&legacy), None);
+        assert_eq!(strip_header(&legacy, AssetKind::PtrHash).unwrap(), legacy);
+        let err = read_header(legacy.as_slice(), AssetKind::PtrHash).unwrap_err();
+        assert!(err.to_string().contains("load_legacy"));
+
+        // Headers without a recorded policy still load
+        let unrecorded = encode_header(AssetKind::PtrHash, None, None, None);
+        let header = read_header(unrecorded.as_slice(), AssetKind::PtrHash).unwrap();
+        assert_eq!(header.input_policy, None);
+
+        let mut future = encode_header(AssetKind::PtrHash, None, None, None);
+        future[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let err = read_header(future.as_slice(), AssetKind::PtrHash).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(strip_header(&future, AssetKind::PtrHash).is_err());
+    }
+}
@@ -3,177 +3,698 @@
 //! This module contains all the code for building oracles from CSV files,
 //! serialization/deserialization, and file I/O operations.
 
-use crate::{packing, BlockHash, PtrHashType};
+use crate::cache::LookupCache;
+use crate::commitment::DatasetCommitment;
+use crate::container;
+use crate::dataset::{CsvOptions, Dataset, EntryIter, KeySource};
+use crate::export::ExportFormat;
+use crate::export_kv::KvFormat;
+use crate::format::{self, AssetKind, InputPolicy};
+pub use crate::memory::MemoryStats;
+use crate::packing::{self, PackedHeights, VersionFlags};
+use crate::phf::{Phf, PhfConfig};
+pub use crate::save::{write_atomic, SaveOptions};
+use crate::stats::QueryCounters;
+use crate::store::Lookup;
+use crate::{BlockHash, HeightLookup};
 use anyhow::{Context, Result};
-use epserde::prelude::*;
+use std::fs::File;
 use std::io::{Read, Write};
+use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// Memory usage statistics for the height oracle
-#[derive(Debug, Clone)]
-pub struct MemoryStats {
-    /// Bits per element for the PtrHash structure
-    pub ptrhash_bits_per_element: f64,
-    /// Bits per element for the heights vector
-    pub heights_bits_per_element: f64,
-    /// Total bits per element
-    pub total_bits_per_element: f64,
-    /// Number of elements
-    pub num_elements: usize,
+pub use crate::validation::{validate, ValidationReport};
+
+/// Progress events reported while building an oracle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildProgress {
+    /// `lines` input lines parsed so far
+    Parsing { lines: usize },
+    /// Perfect hash function construction started over `keys` keys
+    BuildingPhf { keys: usize },
+    /// `done` of `total` heights placed at their perfect-hash index
+    FillingHeights { done: usize, total: usize },
+    /// The oracle is complete
+    Done { entries: usize },
 }
 
-impl MemoryStats {
-    /// Total memory usage in bytes
-    pub fn total_bytes(&self) -> usize {
-        ((self.total_bits_per_element * self.num_elements as f64) / 8.0).ceil() as usize
+/// Shared flag used to abort a running build from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Total memory usage in KB
-    pub fn total_kb(&self) -> f64 {
-        self.total_bytes() as f64 / 1024.0
+    /// Request cancellation; the build stops at its next checkpoint
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
     }
 
-    /// Total memory usage in MB
-    pub fn total_mb(&self) -> f64 {
-        self.total_kb() / 1024.0
+    /// Whether cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
     }
 }
 
-impl std::fmt::Display for MemoryStats {
+/// Error returned when a build is aborted through its [`CancellationToken`]
+///
+/// Builds return `anyhow::Error`; use `err.is::<BuildCancelled>()` to tell
+/// cancellation apart from real failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildCancelled;
+
+impl std::fmt::Display for BuildCancelled {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Memory Statistics:")?;
-        writeln!(f, "  Elements: {}", self.num_elements)?;
-        writeln!(
-            f,
-            "  PtrHash: {:.2} bits/element",
-            self.ptrhash_bits_per_element
-        )?;
-        writeln!(
-            f,
-            "  Heights: {:.2} bits/element",
-            self.heights_bits_per_element
-        )?;
-        writeln!(
-            f,
-            "  Total: {:.2} bits/element ({:.1} KB)",
-            self.total_bits_per_element,
-            self.total_kb()
-        )?;
+        write!(f, "Oracle build was cancelled")
+    }
+}
+
+impl std::error::Error for BuildCancelled {}
+
+type ProgressCallback = Arc<dyn Fn(BuildProgress) + Send + Sync>;
+
+/// Default number of lines/entries between progress callbacks
+const DEFAULT_PROGRESS_INTERVAL: usize = 10_000;
+
+/// Options for building an oracle
+///
+/// With the `serde` feature, options can be read from config files; the
+/// progress callback and cancellation token are skipped and missing fields
+/// take their defaults.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct BuildOptions {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    progress: Option<ProgressCallback>,
+    progress_interval: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cancellation: Option<CancellationToken>,
+    phf: PhfConfig,
+    input_policy: InputPolicy,
+    height_range: Option<Range<u32>>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            progress: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            cancellation: None,
+            phf: PhfConfig::default(),
+            input_policy: InputPolicy::default(),
+            height_range: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for BuildOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuildOptions")
+            .field("progress", &self.progress.is_some())
+            .field("progress_interval", &self.progress_interval)
+            .field("cancellation", &self.cancellation)
+            .field("phf", &self.phf)
+            .field("input_policy", &self.input_policy)
+            .field("height_range", &self.height_range)
+            .finish()
+    }
+}
+
+impl BuildOptions {
+    /// Default options: no progress reporting, not cancellable
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invoke `callback` every `progress_interval` parsed lines / placed heights
+    /// and at each build phase
+    pub fn progress<F: Fn(BuildProgress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Number of lines/entries between progress callbacks (default 10,000)
+    pub fn progress_interval(mut self, interval: usize) -> Self {
+        self.progress_interval = interval.max(1);
+        self
+    }
+
+    /// Abort the build with [`BuildCancelled`] once `token` is cancelled
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Perfect hash parameters (see [`PhfConfig`]); recorded in the saved PtrHash asset
+    pub fn phf_config(mut self, config: PhfConfig) -> Self {
+        self.phf = config;
+        self
+    }
+
+    /// Which dataset entries become oracle entries (default [`InputPolicy::IncludeAll`]);
+    /// recorded in the saved heights asset
+    pub fn input_policy(mut self, policy: InputPolicy) -> Self {
+        self.input_policy = policy;
+        self
+    }
+
+    /// Only keep entries with heights in `range`, for a partial oracle;
+    /// recorded in the saved heights asset
+    ///
+    /// The range must not be empty. Placeholders are still counted over the
+    /// whole dataset by [`InputPolicy::RejectPlaceholders`].
+    pub fn height_range(mut self, range: Range<u32>) -> Self {
+        self.height_range = Some(range);
+        self
+    }
+
+    /// The height range, checked to be non-empty
+    fn checked_height_range(&self) -> Result<Option<&Range<u32>>> {
+        if let Some(range) = &self.height_range {
+            anyhow::ensure!(!range.is_empty(), "Height range {range:?} is empty");
+        }
+        Ok(self.height_range.as_ref())
+    }
+
+    /// The perfect hash parameters, checked with [`PhfConfig::check_params`]
+    pub(crate) fn checked_phf(&self) -> Result<&PhfConfig> {
+        self.phf.check_params()?;
+        Ok(&self.phf)
+    }
+
+    pub(crate) fn report(&self, progress: BuildProgress) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+
+    /// On every `progress_interval`-th `count`: report `make(count)` and check for cancellation
+    pub(crate) fn checkpoint(
+        &self,
+        count: usize,
+        make: impl FnOnce(usize) -> BuildProgress,
+    ) -> Result<()> {
+        if count.is_multiple_of(self.progress_interval) {
+            self.report(make(count));
+            self.check_cancelled()?;
+        }
         Ok(())
     }
+
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(BuildCancelled.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Query counters as the oracles here hold them: shared by clones with the
+/// `stats` feature, a zero-sized value without it
+#[cfg(feature = "stats")]
+type SharedCounters = Arc<QueryCounters>;
+#[cfg(not(feature = "stats"))]
+type SharedCounters = QueryCounters;
+
+/// Whether the dataset at `path` (any format [`Dataset::from_path`] reads) has
+/// commitment `expected`
+pub fn verify_dataset<P: AsRef<Path>>(path: P, expected: &DatasetCommitment) -> Result<bool> {
+    Ok(Dataset::from_path(path)?.commitment() == *expected)
+}
+
+/// Perfect-hash index of every block hash, in order
+///
+/// Computed on the rayon thread pool with the `parallel-build` feature.
+fn phf_indices(phash: &Phf, block_hashes: &[BlockHash]) -> Vec<usize> {
+    if cfg!(feature = "parallel-build") {
+        use rayon::prelude::*;
+        block_hashes
+            .par_iter()
+            .map(|block_hash| phash.index(block_hash.as_bytes()))
+            .collect()
+    } else {
+        block_hashes
+            .iter()
+            .map(|block_hash| phash.index(block_hash.as_bytes()))
+            .collect()
+    }
 }
 
 /// Height lookup oracle using perfect hash function - in-memory version
 ///
-/// Only available with "generate" feature for building oracles.
+/// Only available with "generate" feature for building oracles. Clones share
+/// the perfect hash and heights; `==` compares contents, not identity.
+#[derive(Clone)]
 pub struct HeightOracle {
     /// Perfect hash function mapping BlockHash -> index
-    phash: PtrHashType,
+    phash: Arc<Phf>,
     /// Vector mapping index -> height
-    heights: Vec<u32>,
+    heights: Arc<[u32]>,
+    /// Optional index -> "version >= 2" flags
+    version_flags: Option<VersionFlags>,
+    /// Input policy applied to the dataset, `None` when built from raw entries
+    input_policy: Option<InputPolicy>,
+    /// Heights the build was restricted to, `None` for a full build
+    height_range: Option<Range<u32>>,
+    /// Commitment to the dataset built from, `None` when built from raw entries
+    dataset_commitment: Option<DatasetCommitment>,
+    /// Lookup counters, shared by clones
+    query_counters: SharedCounters,
+    /// Optional lookup cache, shared by clones
+    cache: Option<Arc<LookupCache>>,
 }
 
 /// Height lookup oracle using perfect hash function - loaded from disk
 ///
-/// Only available with "generate" feature for loading oracles from disk.
+/// Only available with "generate" feature for loading oracles from disk. Clones
+/// share the perfect hash and heights; `==` compares contents and ignores the
+/// load time.
+#[derive(Clone)]
 pub struct HeightOracleLoaded {
     /// Perfect hash function mapping BlockHash -> index (loaded from disk)
-    phash: PtrHashType,
+    phash: Arc<Phf>,
     /// Vector mapping index -> height
-    heights: Vec<u32>,
+    heights: Arc<[u32]>,
+    /// Optional index -> "version >= 2" flags
+    version_flags: Option<VersionFlags>,
+    /// Input policy recorded in the heights asset header, if any
+    input_policy: Option<InputPolicy>,
+    /// Height range recorded in the heights asset header, if any
+    height_range: Option<Range<u32>>,
+    /// Dataset commitment recorded in the heights asset header, if any
+    dataset_commitment: Option<DatasetCommitment>,
+    /// Time taken by the load call
+    load_duration: Duration,
+    /// Lookup counters, shared by clones
+    query_counters: SharedCounters,
+    /// Optional lookup cache, shared by clones
+    cache: Option<Arc<LookupCache>>,
 }
 
-/// Minimal wrapper for height data serialization
-#[derive(Clone)]
-struct HeightData {
-    heights: Vec<u32>,
+/// Bytes handed to the heights sink per write in [`HeightOracle::build_streaming`]
+const STREAMING_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Result of [`HeightOracle::build_streaming`]: the perfect hash, whose heights went to the sink
+pub struct StreamingBuild {
+    phash: Phf,
+    len: usize,
 }
 
-impl HeightData {
-    fn new(heights: Vec<u32>) -> Self {
-        Self { heights }
+impl StreamingBuild {
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.len
     }
 
-    fn serialize_to_writer<W: Write>(&self, writer: W) -> Result<()> {
-        packing::serialize_heights(&self.heights, writer).context("Failed to serialize heights")
+    /// Check if the build was empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
-    fn deserialize_from_reader<R: Read>(reader: R) -> Result<Self> {
-        let heights =
-            packing::deserialize_heights(reader).context("Failed to deserialize heights")?;
-        Ok(Self::new(heights))
+    /// Perfect-hash index of a block hash (matches the position in the written heights)
+    pub fn index_of(&self, block_hash: &BlockHash) -> usize {
+        self.phash.index(block_hash.as_bytes())
+    }
+
+    /// Save the PtrHash asset to `ptrhash_path`
+    pub fn save_ptrhash<P: AsRef<Path>>(
+        &self,
+        ptrhash_path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        write_atomic(ptrhash_path, options, |writer| {
+            format::write_header(&mut *writer, AssetKind::PtrHash, None, None, None)?;
+            self.phash.write(writer).map(drop)
+        })
     }
+}
+
+/// Key source yielding only the entries of `source` within `range`
+struct InHeightRange<'a, S: ?Sized> {
+    source: &'a S,
+    range: Range<u32>,
+}
 
-    fn into_heights(self) -> Vec<u32> {
-        self.heights
+impl<S: KeySource + ?Sized> KeySource for InHeightRange<'_, S> {
+    fn entries(&self) -> Result<EntryIter<'_>> {
+        Ok(Box::new(self.source.entries()?.filter(|entry| {
+            entry
+                .as_ref()
+                .map_or(true, |(_, height)| self.range.contains(height))
+        })))
     }
 }
 
 impl HeightOracle {
     /// Create a new height oracle from a text file with one hash per line
     pub fn from_txt(txt_path: &str) -> Result<Self> {
-        let (block_hashes, heights) = Self::parse_txt(txt_path)?;
+        Self::from_txt_with_options(txt_path, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from a text file, with progress reporting and cancellation
+    pub fn from_txt_with_options(txt_path: &str, options: &BuildOptions) -> Result<Self> {
+        let dataset = Dataset::from_txt_with_options(txt_path, options)?;
+        Self::from_dataset_with_options(&dataset, options)
+    }
+
+    /// Create a new height oracle from a binary dataset (see [`crate::hashdump`])
+    pub fn from_bin<P: AsRef<Path>>(bin_path: P) -> Result<Self> {
+        Self::from_bin_with_options(bin_path, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from a binary dataset, with progress reporting and cancellation
+    pub fn from_bin_with_options<P: AsRef<Path>>(
+        bin_path: P,
+        options: &BuildOptions,
+    ) -> Result<Self> {
+        Self::from_dataset_with_options(&Dataset::from_bin(bin_path)?, options)
+    }
+
+    /// Create a new height oracle from a CSV file of (height, block hash) rows
+    pub fn from_csv<P: AsRef<Path>>(csv_path: P, csv_options: &CsvOptions) -> Result<Self> {
+        Self::from_csv_with_options(csv_path, csv_options, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from a CSV file, with progress reporting and cancellation
+    pub fn from_csv_with_options<P: AsRef<Path>>(
+        csv_path: P,
+        csv_options: &CsvOptions,
+        options: &BuildOptions,
+    ) -> Result<Self> {
+        Self::from_dataset_with_options(&Dataset::from_csv(csv_path, csv_options)?, options)
+    }
+
+    /// Create a new height oracle from a JSON Lines file of height/hash records (Feature: jsonl)
+    #[cfg(feature = "jsonl")]
+    pub fn from_jsonl<P: AsRef<Path>>(jsonl_path: P) -> Result<Self> {
+        Self::from_jsonl_with_options(jsonl_path, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from a JSON Lines file, with progress reporting and cancellation
+    #[cfg(feature = "jsonl")]
+    pub fn from_jsonl_with_options<P: AsRef<Path>>(
+        jsonl_path: P,
+        options: &BuildOptions,
+    ) -> Result<Self> {
+        Self::from_dataset_with_options(&Dataset::from_jsonl(jsonl_path)?, options)
+    }
+
+    /// Create a new height oracle from a parsed dataset
+    pub fn from_dataset(dataset: &Dataset) -> Result<Self> {
+        Self::from_dataset_with_options(dataset, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from a parsed dataset, with progress reporting and cancellation
+    ///
+    /// The options' [`InputPolicy`] decides which entries are kept. If the
+    /// input marks version-2 blocks with `x` lines, the kept entries' version
+    /// flags are stored alongside the heights; otherwise there are none and
+    /// [`Self::is_v1_block`] returns `None`.
+    pub fn from_dataset_with_options(dataset: &Dataset, options: &BuildOptions) -> Result<Self> {
+        let dataset_commitment = dataset.commitment();
+        let has_versions = dataset.placeholders > 0 || dataset.v2_flags.contains(&true);
+        let policy = options.input_policy;
+        if policy == InputPolicy::RejectPlaceholders {
+            anyhow::ensure!(
+                dataset.placeholders == 0,
+                "Dataset has {} `x` placeholder lines, which the {policy} input policy rejects",
+                dataset.placeholders
+            );
+        }
+        let v1_only;
+        let dataset = if policy == InputPolicy::SkipV2 {
+            v1_only = dataset.without_v2();
+            &v1_only
+        } else {
+            dataset
+        };
+        let in_range;
+        let dataset = match options.checked_height_range()? {
+            Some(range) => {
+                in_range = dataset.in_height_range(range);
+                &in_range
+            }
+            None => dataset,
+        };
+
+        let mut oracle =
+            Self::build_from_entries(&dataset.block_hashes, &dataset.heights, options)?;
+
+        anyhow::ensure!(
+            dataset.v2_flags.len() == dataset.block_hashes.len(),
+            "Got {} block hashes but {} version flags",
+            dataset.block_hashes.len(),
+            dataset.v2_flags.len()
+        );
+        if has_versions {
+            let mut flags = VersionFlags::new(oracle.len());
+            let indices = phf_indices(&oracle.phash, &dataset.block_hashes);
+            for (index, &is_v2) in indices.into_iter().zip(&dataset.v2_flags) {
+                flags.set(index, is_v2);
+            }
+            oracle.version_flags = Some(flags);
+        }
+        oracle.input_policy = Some(policy);
+        oracle.dataset_commitment = Some(dataset_commitment);
+
+        Ok(oracle)
+    }
+
+    /// Create a new height oracle from parallel slices of block hashes and heights
+    pub fn from_entries(block_hashes: &[BlockHash], heights: &[u32]) -> Result<Self> {
+        Self::from_entries_with_options(block_hashes, heights, &BuildOptions::default())
+    }
+
+    /// Create a new height oracle from parallel slices, with progress reporting and cancellation
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(entries = block_hashes.len()))
+    )]
+    pub fn from_entries_with_options(
+        block_hashes: &[BlockHash],
+        heights: &[u32],
+        options: &BuildOptions,
+    ) -> Result<Self> {
+        anyhow::ensure!(
+            block_hashes.len() == heights.len(),
+            "Got {} block hashes but {} heights",
+            block_hashes.len(),
+            heights.len()
+        );
+        let Some(range) = options.checked_height_range()? else {
+            return Self::build_from_entries(block_hashes, heights, options);
+        };
+        let (block_hashes, heights): (Vec<BlockHash>, Vec<u32>) = block_hashes
+            .iter()
+            .zip(heights)
+            .filter(|(_, height)| range.contains(height))
+            .unzip();
+        Self::build_from_entries(&block_hashes, &heights, options)
+    }
+
+    /// Build from entries already restricted to the options' height range
+    fn build_from_entries(
+        block_hashes: &[BlockHash],
+        heights: &[u32],
+        options: &BuildOptions,
+    ) -> Result<Self> {
+        options.check_cancelled()?;
 
-        // Building perfect hash function
         // Build the perfect hash function
-        let hash_to_index =
-            ptr_hash::DefaultPtrHash::new(&block_hashes, ptr_hash::PtrHashParams::default());
+        options.report(BuildProgress::BuildingPhf {
+            keys: block_hashes.len(),
+        });
+        let hash_to_index = Phf::build(
+            BlockHash::as_byte_arrays(block_hashes),
+            options.checked_phf()?,
+        );
+        #[cfg(feature = "tracing")]
+        tracing::debug!(keys = block_hashes.len(), "built perfect hash function");
+        options.check_cancelled()?;
 
         // Create mapping from perfect hash index to height
-        let mut height_map = vec![0u32; block_hashes.len()];
+        let total = block_hashes.len();
+        let mut height_map = vec![0u32; total];
 
-        for (block_hash, height) in block_hashes.iter().zip(heights.iter()) {
-            let index = hash_to_index.index(block_hash);
-            height_map[index] = *height;
+        if cfg!(feature = "parallel-build") {
+            // Hash on the thread pool; the scatter itself is a cheap serial pass
+            let indices = phf_indices(&hash_to_index, block_hashes);
+            options.check_cancelled()?;
+            for (index, &height) in indices.into_iter().zip(heights) {
+                height_map[index] = height;
+            }
+            options.report(BuildProgress::FillingHeights { done: total, total });
+        } else {
+            for (i, (block_hash, height)) in block_hashes.iter().zip(heights.iter()).enumerate() {
+                let index = hash_to_index.index(block_hash.as_bytes());
+                height_map[index] = *height;
+
+                options.checkpoint(i + 1, |done| BuildProgress::FillingHeights { done, total })?;
+            }
         }
 
+        options.report(BuildProgress::Done { entries: total });
         Ok(HeightOracle {
-            phash: hash_to_index,
-            heights: height_map,
+            phash: Arc::new(hash_to_index),
+            heights: height_map.into(),
+            version_flags: None,
+            input_policy: None,
+            height_range: options.height_range.clone(),
+            dataset_commitment: None,
+            query_counters: SharedCounters::default(),
+            cache: None,
         })
     }
 
-    /// Parse text file with one hash per line (height = line number)
-    fn parse_txt(txt_path: &str) -> Result<(Vec<BlockHash>, Vec<u32>)> {
-        use std::io::{BufRead, BufReader};
+    /// Build from a re-readable key source, streaming packed heights to `heights_sink`
+    ///
+    /// Low-memory alternative to [`Self::from_txt`] for large datasets: entries are
+    /// read in passes (count, PHF construction, height placement) and never collected.
+    /// Heights are held 18-bit packed while being placed, then written to the sink
+    /// in chunks in the usual heights asset format. Returns the perfect hash, which
+    /// is saved separately.
+    pub fn build_streaming<S: KeySource + ?Sized, W: Write>(
+        key_source: &S,
+        heights_sink: W,
+    ) -> Result<StreamingBuild> {
+        Self::build_streaming_with_options(key_source, heights_sink, &BuildOptions::default())
+    }
 
-        let file = std::fs::File::open(txt_path)
-            .with_context(|| format!("Failed to open file: {txt_path}"))?;
-        let reader = BufReader::new(file);
+    /// [`Self::build_streaming`] with progress reporting and cancellation
+    ///
+    /// A height range in `options` filters the key source on every pass.
+    pub fn build_streaming_with_options<S: KeySource + ?Sized, W: Write>(
+        key_source: &S,
+        heights_sink: W,
+        options: &BuildOptions,
+    ) -> Result<StreamingBuild> {
+        match options.checked_height_range()? {
+            Some(range) => {
+                let in_range = InHeightRange {
+                    source: key_source,
+                    range: range.clone(),
+                };
+                Self::stream_build(&in_range, heights_sink, options)
+            }
+            None => Self::stream_build(key_source, heights_sink, options),
+        }
+    }
 
-        let mut block_hashes = Vec::new();
-        let mut heights = Vec::new();
+    fn stream_build<S: KeySource + ?Sized, W: Write>(
+        key_source: &S,
+        mut heights_sink: W,
+        options: &BuildOptions,
+    ) -> Result<StreamingBuild> {
+        // Pass 1: count entries and check heights fit the packed format
+        let mut total = 0usize;
+        for entry in key_source.entries()? {
+            let (_, height) = entry?;
+            anyhow::ensure!(
+                height <= packing::MAX_HEIGHT,
+                "Height {height} exceeds maximum {} (18 bits)",
+                packing::MAX_HEIGHT
+            );
+            total += 1;
+            options.checkpoint(total, |lines| BuildProgress::Parsing { lines })?;
+        }
 
-        for (line_number, line_result) in reader.lines().enumerate() {
-            let line = line_result.context("Failed to read line")?;
-            let line = line.trim();
+        // Pass 2 (the perfect hash may iterate several times): build the perfect hash
+        // function, re-opening the source on every iteration.
+        options.report(BuildProgress::BuildingPhf { keys: total });
+        let source_error = std::sync::Mutex::new(None);
+        let error_slot = &source_error;
+        let keys = move || {
+            let entries = key_source.entries().unwrap_or_else(|e| {
+                let failed: EntryIter<'_> = Box::new(std::iter::once(Err(e)));
+                failed
+            });
+            entries.map_while(move |entry| match entry {
+                Ok((block_hash, _)) => Some(block_hash.to_byte_array()),
+                Err(e) => {
+                    error_slot.lock().unwrap().get_or_insert(e);
+                    None
+                }
+            })
+        };
+        let hash_to_index = Phf::build_from_passes(total, keys, options.checked_phf()?);
+        if let Some(e) = source_error.into_inner().unwrap() {
+            return Err(e.context("Key source failed while building the perfect hash"));
+        }
+        options.check_cancelled()?;
 
-            // Skip empty lines
-            if line.is_empty() {
-                continue;
-            }
+        // Pass 3: place heights in perfect-hash order
+        let mut heights = PackedHeights::new(total);
+        let mut placed = 0usize;
+        for entry in key_source.entries()? {
+            let (block_hash, height) = entry?;
+            anyhow::ensure!(
+                placed < total,
+                "Key source yielded more entries than on the first pass"
+            );
+            heights.set(hash_to_index.index(block_hash.as_bytes()), height);
+            placed += 1;
+            options.checkpoint(placed, |done| BuildProgress::FillingHeights { done, total })?;
+        }
+        anyhow::ensure!(
+            placed == total,
+            "Key source yielded {placed} entries, expected {total}"
+        );
 
-            // New: if the line is a placeholder 'x' (we may mark version-2 blocks with 'x'), skip it
-            if line == "x" {
-                continue;
-            }
+        format::write_header(
+            &mut heights_sink,
+            AssetKind::Heights,
+            None,
+            options.height_range.as_ref(),
+            None,
+        )
+        .context("Failed to write heights")?;
+        heights
+            .write_chunked(&mut heights_sink, STREAMING_CHUNK_BYTES)
+            .context("Failed to write heights")?;
+        heights_sink.flush().context("Failed to write heights")?;
 
-            // Height is the line number (0-indexed)
-            let height = line_number as u32;
+        options.report(BuildProgress::Done { entries: total });
+        Ok(StreamingBuild {
+            phash: hash_to_index,
+            len: total,
+        })
+    }
 
-            // Convert from reverse hex to network byte order
-            let block_hash = crate::parse_block_hash(line)
-                .map_err(|e| anyhow::anyhow!(e))
-                .with_context(|| {
-                    format!("Failed to parse block hash on line {}", line_number + 1)
-                })?;
+    /// Verify a text or binary dataset against a file of raw 80-byte headers before building
+    ///
+    /// Opt-in build step: checks that every header hashes to the hash on the
+    /// matching line, links to the previous header, and meets its PoW target.
+    /// See [`crate::chain::verify_chain`].
+    pub fn verify_chain_linkage<P: AsRef<Path>>(txt_path: &str, headers_path: P) -> Result<()> {
+        let headers_path = headers_path.as_ref();
+        let dataset = crate::dataset::read_entries(txt_path)?;
 
-            heights.push(height);
-            block_hashes.push(block_hash);
-        }
+        let headers_file = std::fs::File::open(headers_path)
+            .with_context(|| format!("Failed to open headers file: {}", headers_path.display()))?;
+        let headers = crate::header::read_headers(std::io::BufReader::new(headers_file))
+            .context("Failed to read headers")?;
 
-        // Parsed block hashes from text file
-        Ok((block_hashes, heights))
+        crate::chain::verify_chain(&dataset, &headers, 0).context("Chain verification failed")
+    }
+
+    /// Perfect-hash index of a block hash, in `0..len()`
+    ///
+    /// Every block in the oracle gets a distinct index, so callers can keep their
+    /// own per-block side tables (timestamps, filter hashes, ...) in a `Vec`
+    /// indexed the same way. Like the height lookups this is unchecked: hashes
+    /// outside the dataset map to some other block's index.
+    pub fn index_of(&self, block_hash: &BlockHash) -> usize {
+        self.phash.index(block_hash.as_bytes())
     }
 
     /// Look up the height for a given block hash (unchecked)
@@ -189,20 +710,107 @@ impl HeightOracle {
     /// is not possible at runtime. Validation should be done during testing
     /// with the original CSV data.
     pub fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
-        let index = self.phash.index(block_hash);
-        self.heights[index]
+        self.lookup().height(block_hash)
+    }
+
+    /// Like [`Self::get_height_unchecked`], but `None` instead of a panic if the
+    /// perfect hash yields an index past the heights table
+    pub fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.lookup().height_opt(block_hash)
+    }
+
+    /// The shared lookup path over this oracle's tables
+    fn lookup(&self) -> Lookup<'_, [u32]> {
+        Lookup {
+            phash: &self.phash,
+            heights: &self.heights,
+            cache: self.cache.as_deref(),
+            query_counters: &self.query_counters,
+        }
+    }
+
+    /// Whether the block has header version 1 (unchecked)
+    ///
+    /// Version-1 blocks predate BIP34, so their coinbase does not encode the
+    /// height. Returns `None` if the oracle was built without version flags.
+    /// Like [`Self::get_height_unchecked`], unknown hashes return the flag of
+    /// some other block.
+    pub fn is_v1_block(&self, block_hash: &BlockHash) -> Option<bool> {
+        let flags = self.version_flags.as_ref()?;
+        Some(!flags.get(self.index_of(block_hash)))
+    }
+
+    /// Write the heights this oracle returns for `block_hashes` as text, CSV or JSON lines
+    ///
+    /// Pass the dataset the oracle was built from (see [`crate::export`]). Text
+    /// output reproduces the dataset format and fails if two hashes share a height.
+    pub fn export<W: Write>(
+        &self,
+        block_hashes: &[BlockHash],
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<()> {
+        crate::export::export(
+            block_hashes,
+            |block_hash| self.get_height_unchecked(block_hash),
+            |block_hash| self.is_v1_block(block_hash).map(|is_v1| !is_v1),
+            format,
+            writer,
+        )
     }
 
-    /// Look up the height for a given block hash in reverse hex format (unchecked)
+    /// Write the heights this oracle returns for `block_hashes` to a new key-value store at `path`
     ///
-    /// # Panics
+    /// For consumers that cannot read the perfect hash; see [`crate::export_kv`]
+    /// for the formats and encoding. Pass the dataset the oracle was built from.
+    /// `options` apply to cdb files, which are written atomically; redb and
+    /// sled manage their own writes.
+    pub fn export_kv<P: AsRef<Path>>(
+        &self,
+        block_hashes: &[BlockHash],
+        format: KvFormat,
+        path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        crate::export_kv::export_kv(
+            block_hashes,
+            |block_hash| self.get_height_unchecked(block_hash),
+            format,
+            path.as_ref(),
+            options,
+        )
+    }
+
+    /// Version flags stored alongside the heights, if any
+    pub fn version_flags(&self) -> Option<&VersionFlags> {
+        self.version_flags.as_ref()
+    }
+
+    /// Input policy applied when building from a dataset, `None` for raw entries
+    pub fn input_policy(&self) -> Option<InputPolicy> {
+        self.input_policy
+    }
+
+    /// Heights the build was restricted to (see [`BuildOptions::height_range`]),
+    /// `None` for a full build
+    pub fn height_range(&self) -> Option<Range<u32>> {
+        self.height_range.clone()
+    }
+
+    /// Commitment to the dataset the oracle was built from (see [`crate::commitment`]),
+    /// `None` for raw entries
+    pub fn dataset_commitment(&self) -> Option<DatasetCommitment> {
+        self.dataset_commitment
+    }
+
+    /// Whether the dataset at `path` is the one this oracle was built from
     ///
-    /// Panics if the hex string is invalid. The caller must ensure the input
-    /// is valid hex. Use a separate validation function if error handling is needed.
-    pub fn get_height_from_hex_unchecked(&self, hex_str: &str) -> u32 {
-        let block_hash: BlockHash = crate::parse_block_hash(hex_str)
-            .unwrap_or_else(|_| panic!("Invalid hex string in unchecked function: {hex_str}"));
-        self.get_height_unchecked(&block_hash)
+    /// Fails if the oracle was built from raw entries, which have no commitment.
+    pub fn verify_dataset<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let expected = self
+            .dataset_commitment
+            .context("Oracle was built without a dataset commitment")?;
+        verify_dataset(path, &expected)
     }
 
     /// Get the number of blocks in the oracle
@@ -215,140 +823,1430 @@ impl HeightOracle {
         self.heights.is_empty()
     }
 
-    /// Save the oracle to disk using explicit file paths
-    pub fn save_to_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
+    /// Lookups made since the oracle was created or last reset, by this oracle
+    /// and its clones (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn query_stats(&self) -> crate::QueryStats {
+        self.query_counters.snapshot()
+    }
+
+    /// Zero the counters behind [`Self::query_stats`] (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn reset_query_stats(&self) {
+        self.query_counters.reset();
+    }
+
+    /// Put a direct-mapped cache of `entries` slots in front of the perfect hash
+    ///
+    /// `entries` is rounded up to a power of two; 0 removes the cache. A hit
+    /// skips the PHF evaluation and the heights read, which pays off when
+    /// lookups keep returning to a small hot set (the tip, checkpoints). For
+    /// uniformly spread lookups nearly every call misses and pays an extra
+    /// compare and store, so measure before enabling (`cargo bench --bench
+    /// lookup -- cache`). Only found heights are cached, batched lookups bypass
+    /// the cache, and clones made afterwards share it.
+    pub fn with_cache(mut self, entries: usize) -> Self {
+        self.cache = (entries > 0).then(|| Arc::new(LookupCache::new(entries)));
+        self
+    }
+
+    /// Save the version flags to disk (errors if the oracle has none)
+    pub fn save_version_flags<P: AsRef<Path>>(
         &self,
-        ptrhash_path: P1,
-        meta_path: P2,
+        versions_path: P,
+        options: &SaveOptions,
     ) -> Result<()> {
-        let ptrhash_path = ptrhash_path.as_ref();
-        let meta_path = meta_path.as_ref();
+        let versions_path = versions_path.as_ref();
+        let flags = self
+            .version_flags
+            .as_ref()
+            .context("Oracle was built without version flags")?;
 
-        // Save PtrHash using epserde
-        let hash_file = std::fs::File::create(ptrhash_path).with_context(|| {
-            format!("Failed to create PtrHash file: {}", ptrhash_path.display())
-        })?;
-        self.phash
-            .serialize(&mut std::io::BufWriter::new(hash_file))
-            .context("Failed to serialize PtrHash")?;
+        write_atomic(versions_path, options, |writer| flags.serialize(writer))
+    }
 
-        // Save metadata using 18-bit packed heights (25% space savings!)
-        let height_data = HeightData::new(self.heights.clone());
+    /// Save the header timestamp of every dataset entry, indexed like the heights
+    ///
+    /// `headers` are raw headers in height order starting at 0 (as written by the
+    /// fetcher's `--headers-output`). Each entry's header must hash to its block
+    /// hash. Load the result with [`crate::TimestampOracle`] and this oracle's
+    /// PtrHash asset.
+    pub fn save_timestamps<P: AsRef<Path>>(
+        &self,
+        dataset: &Dataset,
+        headers: &[[u8; crate::header::HEADER_SIZE]],
+        timestamps_path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        let timestamps_path = timestamps_path.as_ref();
+        anyhow::ensure!(
+            dataset.len() == self.len(),
+            "Dataset has {} entries but the oracle has {}",
+            dataset.len(),
+            self.len()
+        );
 
-        let meta_file = std::fs::File::create(meta_path)
-            .with_context(|| format!("Failed to create metadata file: {}", meta_path.display()))?;
-        height_data
-            .serialize_to_writer(std::io::BufWriter::new(meta_file))
-            .context("Failed to serialize metadata")?;
+        let mut timestamps = vec![0u32; self.len()];
+        for (block_hash, height) in dataset.iter() {
+            let raw = headers
+                .get(height as usize)
+                .with_context(|| format!("No header for height {height}"))?;
+            anyhow::ensure!(
+                crate::header::block_hash(raw) == *block_hash,
+                "Header at height {height} does not hash to {block_hash}"
+            );
+            timestamps[self.index_of(block_hash)] = crate::header::BlockHeader::parse(raw).time;
+        }
 
-        Ok(())
+        write_atomic(timestamps_path, options, |writer| {
+            crate::timestamps::serialize_timestamps(&timestamps, writer)
+        })
     }
 
-    /// Load the oracle from disk using explicit file paths
-    pub fn load_from_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
-        ptrhash_path: P1,
-        meta_path: P2,
-    ) -> Result<HeightOracleLoaded> {
-        let ptrhash_path = ptrhash_path.as_ref();
-        let meta_path = meta_path.as_ref();
-
-        // Load PtrHash using epserde full deserialization
-        let hash_file = std::fs::File::open(ptrhash_path)
-            .with_context(|| format!("Failed to open PtrHash file: {}", ptrhash_path.display()))?;
-        let hash_to_index = PtrHashType::deserialize_full(&mut std::io::BufReader::new(hash_file))
-            .context("Failed to deserialize PtrHash")?;
+    /// Save the median time past of every dataset entry, indexed like the heights
+    ///
+    /// `headers` are raw headers in height order starting at 0, as for
+    /// [`Self::save_timestamps`]. Load the result with [`crate::MedianTimeOracle`]
+    /// and this oracle's PtrHash asset.
+    pub fn save_median_time_past<P: AsRef<Path>>(
+        &self,
+        dataset: &Dataset,
+        headers: &[[u8; crate::header::HEADER_SIZE]],
+        mtp_path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            dataset.len() == self.len(),
+            "Dataset has {} entries but the oracle has {}",
+            dataset.len(),
+            self.len()
+        );
 
-        // Load metadata using 18-bit packed heights
-        let meta_file = std::fs::File::open(meta_path)
-            .with_context(|| format!("Failed to open metadata file: {}", meta_path.display()))?;
-        let height_data = HeightData::deserialize_from_reader(std::io::BufReader::new(meta_file))
-            .context("Failed to deserialize metadata")?;
+        let timestamps: Vec<u32> = headers
+            .iter()
+            .map(|raw| crate::header::BlockHeader::parse(raw).time)
+            .collect();
+        let by_height = crate::mtp::median_time_past(&timestamps);
+        let mut mtps = vec![0u32; self.len()];
+        for (block_hash, height) in dataset.iter() {
+            mtps[self.index_of(block_hash)] = *by_height
+                .get(height as usize)
+                .with_context(|| format!("No header for height {height}"))?;
+        }
 
-        Ok(HeightOracleLoaded {
-            phash: hash_to_index,
-            heights: height_data.into_heights(),
+        write_atomic(mtp_path, options, |writer| {
+            crate::timestamps::serialize_timestamps(&mtps, writer)
         })
     }
 
-    /// Memory usage statistics
-    pub fn memory_stats(&self) -> MemoryStats {
-        let (pilots_bits, remap_bits) = self.phash.bits_per_element();
-        let ptrhash_bits = pilots_bits + remap_bits;
-        let heights_bits = (self.heights.len() * 4 * 8) as f64 / self.heights.len() as f64;
+    /// Save the BIP158 filter header of every dataset entry, indexed like the heights
+    ///
+    /// `filter_headers` are indexed by height starting at 0 (as written by the
+    /// fetcher's `--filter-headers-output`). Filter headers do not commit to the
+    /// block hash, so only coverage is checked. Load the result with
+    /// [`crate::FilterHeaderOracle`] and this oracle's PtrHash asset.
+    pub fn save_filter_headers<P: AsRef<Path>>(
+        &self,
+        dataset: &Dataset,
+        filter_headers: &[[u8; crate::filterheaders::FILTER_HEADER_SIZE]],
+        filter_headers_path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            dataset.len() == self.len(),
+            "Dataset has {} entries but the oracle has {}",
+            dataset.len(),
+            self.len()
+        );
 
-        MemoryStats {
-            ptrhash_bits_per_element: ptrhash_bits,
-            heights_bits_per_element: heights_bits,
-            total_bits_per_element: ptrhash_bits + heights_bits,
-            num_elements: self.heights.len(),
+        let mut indexed = vec![[0u8; crate::filterheaders::FILTER_HEADER_SIZE]; self.len()];
+        for (block_hash, height) in dataset.iter() {
+            indexed[self.index_of(block_hash)] = *filter_headers
+                .get(height as usize)
+                .with_context(|| format!("No filter header for height {height}"))?;
         }
+
+        write_atomic(filter_headers_path, options, |writer| {
+            crate::filterheaders::serialize_filter_headers(&indexed, writer)
+        })
     }
-}
 
-impl HeightOracleLoaded {
-    /// Look up the height for a given block hash (unchecked)
-    ///
-    /// IMPORTANT: This function always returns a height, but does NOT validate
-    /// that the input hash was in the original dataset. For unknown hashes,
-    /// it will return a height corresponding to some other block in the range
+    /// Save the raw header of every dataset entry, indexed like the heights
     ///
-    /// The caller must ensure the input hash is from the valid domain
-    /// (i.e., was in the original CSV file used to build the oracle).
-    pub fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
-        let index = self.phash.index(block_hash);
-        self.heights[index]
+    /// `headers` are raw headers in height order starting at 0, as for
+    /// [`Self::save_timestamps`]; each must hash to its dataset entry. Load the
+    /// result with [`crate::HeaderOracle`] and this oracle's PtrHash asset.
+    pub fn save_headers<P: AsRef<Path>>(
+        &self,
+        dataset: &Dataset,
+        headers: &[[u8; crate::header::HEADER_SIZE]],
+        headers_path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            dataset.len() == self.len(),
+            "Dataset has {} entries but the oracle has {}",
+            dataset.len(),
+            self.len()
+        );
+
+        let mut indexed = vec![[0u8; crate::header::HEADER_SIZE]; self.len()];
+        for (block_hash, height) in dataset.iter() {
+            let raw = headers
+                .get(height as usize)
+                .with_context(|| format!("No header for height {height}"))?;
+            anyhow::ensure!(
+                crate::header::block_hash(raw) == *block_hash,
+                "Header at height {height} does not hash to {block_hash}"
+            );
+            indexed[self.index_of(block_hash)] = *raw;
+        }
+
+        write_atomic(headers_path, options, |writer| {
+            crate::blockheaders::serialize_headers(&indexed, writer)
+        })
     }
 
-    /// Look up the height for a given block hash in reverse hex format (unchecked)
-    ///
-    /// # Panics
+    /// Save the oracle to disk using explicit file paths
     ///
-    /// Panics if the hex string is invalid. The caller must ensure the input
-    /// is valid hex. Use a separate validation function if error handling is needed.
-    pub fn get_height_from_hex_unchecked(&self, hex_str: &str) -> u32 {
-        let block_hash: BlockHash = crate::parse_block_hash(hex_str)
-            .unwrap_or_else(|_| panic!("Invalid hex string in unchecked function: {hex_str}"));
-        self.get_height_unchecked(&block_hash)
+    /// Each file is written atomically (see [`write_atomic`]) and starts with a
+    /// format header (see [`crate::format`]).
+    pub fn save_to_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        ptrhash_path: P1,
+        meta_path: P2,
+    ) -> Result<()> {
+        self.save_to_paths_with_options(ptrhash_path, meta_path, &SaveOptions::default())
     }
 
-    /// Get the number of blocks in the oracle
-    pub fn len(&self) -> usize {
-        self.heights.len()
+    /// Save the oracle to disk using explicit file paths and save options
+    pub fn save_to_paths_with_options<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        ptrhash_path: P1,
+        meta_path: P2,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        write_atomic(ptrhash_path, options, |writer| {
+            self.write_ptrhash_asset(writer)
+        })?;
+        write_atomic(meta_path, options, |writer| {
+            self.write_heights_asset(writer)
+        })
     }
 
-    /// Check if the oracle is empty
-    pub fn is_empty(&self) -> bool {
-        self.heights.is_empty()
+    /// Write both assets to writers, in the same format as [`Self::save_to_paths`]
+    ///
+    /// For streaming assets to sockets, archive builders or in-memory buffers.
+    /// Nothing is buffered or written atomically; wrap file writers in a
+    /// `BufWriter`, and prefer [`Self::save_to_paths`] for files.
+    pub fn save_to_writers<W1: Write, W2: Write>(
+        &self,
+        ptrhash_writer: W1,
+        meta_writer: W2,
+    ) -> Result<()> {
+        self.write_ptrhash_asset(ptrhash_writer)
+            .context("Failed to write PtrHash asset")?;
+        self.write_heights_asset(meta_writer)
+            .context("Failed to write heights asset")
     }
 
-    /// Memory usage statistics
-    pub fn memory_stats(&self) -> MemoryStats {
-        let (pilots_bits, remap_bits) = self.phash.bits_per_element();
-        let ptrhash_bits = pilots_bits + remap_bits;
-        let heights_bits = (self.heights.len() * 4 * 8) as f64 / self.heights.len() as f64;
-
-        MemoryStats {
-            ptrhash_bits_per_element: ptrhash_bits,
-            heights_bits_per_element: heights_bits,
-            total_bits_per_element: ptrhash_bits + heights_bits,
-            num_elements: self.heights.len(),
-        }
+    /// Write the PtrHash asset: format header, then the perfect hash using
+    /// epserde, behind a config header if non-default
+    fn write_ptrhash_asset<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        format::write_header(&mut writer, AssetKind::PtrHash, None, None, None)?;
+        self.phash.write(writer).map(drop)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Write the heights asset: format header, then 18-bit packed heights (25% space savings!)
+    fn write_heights_asset<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        format::write_header(
+            &mut writer,
+            AssetKind::Heights,
+            self.input_policy,
+            self.height_range.as_ref(),
+            self.dataset_commitment.as_ref(),
+        )?;
+        packing::serialize_heights(&self.heights, writer)
+    }
 
-    #[test]
-    fn test_hex_conversion() {
-        let hex = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+    /// Save the oracle like [`Self::save_to_paths`], plus a detached signature
+    /// over both assets (Feature: signing)
+    ///
+    /// The signature goes to [`crate::signing::signature_path`] of `meta_path`.
+    /// Load such assets with [`Self::load_verified_signed`].
+    #[cfg(feature = "signing")]
+    pub fn save_to_paths_signed<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        ptrhash_path: P1,
+        meta_path: P2,
+        key: &crate::signing::SigningKey,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        let mut ptrhash_data = Vec::new();
+        self.write_ptrhash_asset(&mut ptrhash_data)
+            .context("Failed to serialize PtrHash")?;
+        let mut heights_data = Vec::new();
+        self.write_heights_asset(&mut heights_data)
+            .context("Failed to serialize heights")?;
+        let signature = crate::signing::sign_assets(key, &ptrhash_data, &heights_data);
+
+        write_atomic(ptrhash_path, options, |writer| {
+            writer.write_all(&ptrhash_data)
+        })?;
+        write_atomic(&meta_path, options, |writer| {
+            writer.write_all(&heights_data)
+        })?;
+        write_atomic(
+            crate::signing::signature_path(&meta_path),
+            options,
+            |writer| writer.write_all(&signature),
+        )
+    }
+
+    /// Serialize both assets, format headers included, into one uncompressed
+    /// container (see [`crate::container`])
+    fn to_container(&self) -> Result<Vec<u8>> {
+        let mut ptrhash_data = Vec::new();
+        self.write_ptrhash_asset(&mut ptrhash_data)
+            .context("Failed to serialize PtrHash")?;
+        let mut heights_data = Vec::new();
+        self.write_heights_asset(&mut heights_data)
+            .context("Failed to serialize heights")?;
+        Ok(container::encode(&ptrhash_data, &heights_data))
+    }
+
+    /// Save the oracle as a single container file, written atomically
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.save_to_file_with_options(path, &SaveOptions::default())
+    }
+
+    /// Save the oracle as a single container file with save options
+    pub fn save_to_file_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        let container = self.to_container()?;
+        write_atomic(path, options, |writer| writer.write_all(&container))
+    }
+
+    /// Save the oracle as a single zstd-compressed container file (Feature: zstd)
+    ///
+    /// [`Self::load_from_file`] detects the compression automatically.
+    #[cfg(feature = "zstd")]
+    pub fn save_to_file_compressed<P: AsRef<Path>>(
+        &self,
+        path: P,
+        level: i32,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        let compressed =
+            container::compress(&self.to_container()?, level).context("Failed to compress")?;
+        write_atomic(path, options, |writer| writer.write_all(&compressed))
+    }
+
+    /// Load the oracle from a container file, compressed or not
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))
+    )]
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<HeightOracleLoaded> {
+        let path = path.as_ref();
+        let started = Instant::now();
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read container: {}", path.display()))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = data.len(), "read container");
+        let data = container::decompress_if_needed(&data).context("Failed to decompress")?;
+        let (ptrhash_data, heights_data) = container::decode(&data)?;
+        let input_policy = format::detect_input_policy(heights_data);
+        let height_range = format::detect_height_range(heights_data);
+        let dataset_commitment = format::detect_dataset_commitment(heights_data);
+        let ptrhash_data = format::strip_header(ptrhash_data, AssetKind::PtrHash)?;
+        let heights_data = format::strip_header(heights_data, AssetKind::Heights)?;
+
+        let (phash, heights) = Self::deserialize_assets(ptrhash_data, heights_data)?;
+
+        let oracle = HeightOracleLoaded {
+            phash: Arc::new(phash),
+            heights: heights.into(),
+            version_flags: None,
+            input_policy,
+            height_range,
+            dataset_commitment,
+            load_duration: started.elapsed(),
+            query_counters: SharedCounters::default(),
+            cache: None,
+        };
+        oracle.trace_loaded();
+        Ok(oracle)
+    }
+
+    /// Load the oracle from disk using explicit file paths
+    ///
+    /// Both files must start with a format header of a version this build
+    /// supports (see [`crate::format`]). Assets saved before format versioning
+    /// are refused with an error pointing at [`Self::load_legacy`].
+    pub fn load_from_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
+        ptrhash_path: P1,
+        meta_path: P2,
+    ) -> Result<HeightOracleLoaded> {
+        Self::load_paths(ptrhash_path.as_ref(), meta_path.as_ref(), true)
+    }
+
+    /// Load headerless assets written before format versioning
+    ///
+    /// Same layout as format version 1 without the header. Prefer re-saving
+    /// such assets (see [`HeightOracleLoaded::into_oracle`]), which adds the header.
+    pub fn load_legacy<P1: AsRef<Path>, P2: AsRef<Path>>(
+        ptrhash_path: P1,
+        meta_path: P2,
+    ) -> Result<HeightOracleLoaded> {
+        Self::load_paths(ptrhash_path.as_ref(), meta_path.as_ref(), false)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ptrhash_path = %ptrhash_path.display(), meta_path = %meta_path.display())
+        )
+    )]
+    fn load_paths(
+        ptrhash_path: &Path,
+        meta_path: &Path,
+        versioned: bool,
+    ) -> Result<HeightOracleLoaded> {
+        let started = Instant::now();
+
+        let hash_file = std::fs::File::open(ptrhash_path)
+            .with_context(|| format!("Failed to open PtrHash file: {}", ptrhash_path.display()))?;
+        let meta_file = std::fs::File::open(meta_path)
+            .with_context(|| format!("Failed to open metadata file: {}", meta_path.display()))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            ptrhash_bytes = hash_file.metadata()?.len(),
+            heights_bytes = meta_file.metadata()?.len(),
+            versioned,
+            "opened assets"
+        );
+        let mut hash_reader = std::io::BufReader::new(hash_file);
+        let mut meta_reader = std::io::BufReader::new(meta_file);
+        let mut header = None;
+        if versioned {
+            format::read_header(&mut hash_reader, AssetKind::PtrHash)
+                .with_context(|| format!("Cannot load {}", ptrhash_path.display()))?;
+            header = Some(
+                format::read_header(&mut meta_reader, AssetKind::Heights)
+                    .with_context(|| format!("Cannot load {}", meta_path.display()))?,
+            );
+        }
+        let (phash, heights) = Self::deserialize_assets(hash_reader, meta_reader)?;
+
+        let oracle = HeightOracleLoaded {
+            phash: Arc::new(phash),
+            heights: heights.into(),
+            version_flags: None,
+            input_policy: header.as_ref().and_then(|header| header.input_policy),
+            height_range: header
+                .as_ref()
+                .and_then(|header| header.height_range.clone()),
+            dataset_commitment: header.and_then(|header| header.dataset_commitment),
+            load_duration: started.elapsed(),
+            query_counters: SharedCounters::default(),
+            cache: None,
+        };
+        oracle.trace_loaded();
+        Ok(oracle)
+    }
+
+    /// Load assets saved with [`Self::save_to_paths_signed`], refusing them unless
+    /// their signature verifies under `public_key` (Feature: signing)
+    ///
+    /// Missing signatures are an error, so unsigned assets are never loaded.
+    /// The bytes that were verified are the bytes that get deserialized.
+    #[cfg(feature = "signing")]
+    pub fn load_verified_signed<P1: AsRef<Path>, P2: AsRef<Path>>(
+        ptrhash_path: P1,
+        meta_path: P2,
+        public_key: &crate::signing::VerifyingKey,
+    ) -> Result<HeightOracleLoaded> {
+        let ptrhash_path = ptrhash_path.as_ref();
+        let meta_path = meta_path.as_ref();
+        let signature_path = crate::signing::signature_path(meta_path);
+        let started = Instant::now();
+
+        let ptrhash_data = std::fs::read(ptrhash_path)
+            .with_context(|| format!("Failed to read PtrHash file: {}", ptrhash_path.display()))?;
+        let meta_data = std::fs::read(meta_path)
+            .with_context(|| format!("Failed to read metadata file: {}", meta_path.display()))?;
+        let signature = std::fs::read(&signature_path).with_context(|| {
+            format!(
+                "Failed to read signature file: {} (unsigned assets are refused)",
+                signature_path.display()
+            )
+        })?;
+        crate::signing::verify_assets(public_key, &ptrhash_data, &meta_data, &signature)
+            .with_context(|| format!("Refusing to load {}", meta_path.display()))?;
+
+        let mut ptrhash_reader = ptrhash_data.as_slice();
+        format::read_header(&mut ptrhash_reader, AssetKind::PtrHash)
+            .with_context(|| format!("Cannot load {}", ptrhash_path.display()))?;
+        let mut meta_reader = meta_data.as_slice();
+        let header = format::read_header(&mut meta_reader, AssetKind::Heights)
+            .with_context(|| format!("Cannot load {}", meta_path.display()))?;
+        let (phash, heights) = Self::deserialize_assets(ptrhash_reader, meta_reader)?;
+
+        let oracle = HeightOracleLoaded {
+            phash: Arc::new(phash),
+            heights: heights.into(),
+            version_flags: None,
+            input_policy: header.input_policy,
+            height_range: header.height_range,
+            dataset_commitment: header.dataset_commitment,
+            load_duration: started.elapsed(),
+            query_counters: SharedCounters::default(),
+            cache: None,
+        };
+        oracle.trace_loaded();
+        Ok(oracle)
+    }
+
+    /// Load the oracle from disk without blocking the async runtime (Feature: tokio)
+    ///
+    /// Both files are read concurrently with `tokio::fs`, then deserialized on
+    /// the blocking thread pool via `spawn_blocking`.
+    #[cfg(feature = "tokio")]
+    pub async fn load_from_paths_async<P1: AsRef<Path>, P2: AsRef<Path>>(
+        ptrhash_path: P1,
+        meta_path: P2,
+    ) -> Result<HeightOracleLoaded> {
+        let ptrhash_path = ptrhash_path.as_ref();
+        let meta_path = meta_path.as_ref();
+        let started = Instant::now();
+
+        let (ptrhash_data, meta_data) = tokio::try_join!(
+            async {
+                tokio::fs::read(ptrhash_path).await.with_context(|| {
+                    format!("Failed to read PtrHash file: {}", ptrhash_path.display())
+                })
+            },
+            async {
+                tokio::fs::read(meta_path).await.with_context(|| {
+                    format!("Failed to read metadata file: {}", meta_path.display())
+                })
+            },
+        )?;
+        let mut ptrhash_reader = ptrhash_data.as_slice();
+        format::read_header(&mut ptrhash_reader, AssetKind::PtrHash)
+            .with_context(|| format!("Cannot load {}", ptrhash_path.display()))?;
+        let ptrhash_offset = ptrhash_data.len() - ptrhash_reader.len();
+        let mut meta_reader = meta_data.as_slice();
+        let header = format::read_header(&mut meta_reader, AssetKind::Heights)
+            .with_context(|| format!("Cannot load {}", meta_path.display()))?;
+        let meta_offset = meta_data.len() - meta_reader.len();
+        let (phash, heights) = tokio::task::spawn_blocking(move || {
+            Self::deserialize_assets(&ptrhash_data[ptrhash_offset..], &meta_data[meta_offset..])
+        })
+        .await
+        .context("Deserialization task failed")??;
+
+        let oracle = HeightOracleLoaded {
+            phash: Arc::new(phash),
+            heights: heights.into(),
+            version_flags: None,
+            input_policy: header.input_policy,
+            height_range: header.height_range,
+            dataset_commitment: header.dataset_commitment,
+            load_duration: started.elapsed(),
+            query_counters: SharedCounters::default(),
+            cache: None,
+        };
+        oracle.trace_loaded();
+        Ok(oracle)
+    }
+
+    /// Deserialize the PtrHash asset and the packed heights asset
+    fn deserialize_assets<R1: Read, R2: Read>(
+        ptrhash_reader: R1,
+        meta_reader: R2,
+    ) -> Result<(Phf, Vec<u32>)> {
+        // Load PtrHash (and its config header, if any) using epserde full deserialization
+        let phash = Phf::read(ptrhash_reader).context("Failed to deserialize PtrHash")?;
+
+        // Load metadata using 18-bit packed heights
+        let heights =
+            packing::deserialize_heights(meta_reader).context("Failed to deserialize heights")?;
+        phash.check_table_len(heights.len(), "heights")?;
+
+        Ok((phash, heights))
+    }
+
+    /// Load the oracle from an asset directory with the default file names
+    ///
+    /// Version flags are loaded too when the directory contains them. Unlike
+    /// [`Self::load_from_paths`], a directory of legacy (headerless) assets is
+    /// detected and loaded as with [`Self::load_legacy`].
+    pub fn load_from_dir<P: AsRef<Path>>(dir: P) -> Result<HeightOracleLoaded> {
+        let dir = dir.as_ref();
+        let ptrhash_path = dir.join(crate::PTRHASH_FILE_NAME);
+        let heights_path = dir.join(crate::HEIGHTS_FILE_NAME);
+        let versions_path = dir.join(crate::VERSIONS_FILE_NAME);
+
+        let mut header = Vec::with_capacity(format::HEADER_LEN);
+        File::open(&ptrhash_path)
+            .with_context(|| format!("Failed to open PtrHash file: {}", ptrhash_path.display()))?
+            .take(format::HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        let versioned = format::detect_version(&header).is_some();
+
+        let oracle = Self::load_paths(&ptrhash_path, &heights_path, versioned)?;
+        if versions_path.exists() {
+            oracle.with_version_flags_file(&versions_path)
+        } else {
+            Ok(oracle)
+        }
+    }
+
+    /// Load the oracle and its version flags from disk using explicit file paths
+    pub fn load_from_paths_with_versions<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+        ptrhash_path: P1,
+        meta_path: P2,
+        versions_path: P3,
+    ) -> Result<HeightOracleLoaded> {
+        Self::load_from_paths(ptrhash_path, meta_path)?
+            .with_version_flags_file(versions_path.as_ref())
+    }
+
+    /// Memory usage statistics
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats::new(
+            &self.phash,
+            &*self.heights,
+            self.version_flags.as_ref(),
+            self.cache.as_deref(),
+            format::header_version(
+                self.height_range.is_some(),
+                self.dataset_commitment.is_some(),
+            ),
+        )
+    }
+
+    /// Perfect hash parameters this oracle was built with
+    pub fn phf_config(&self) -> &PhfConfig {
+        self.phash.config()
+    }
+}
+
+impl HeightOracleLoaded {
+    /// Attach version flags read from `versions_path`
+    fn with_version_flags_file(mut self, versions_path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(versions_path).with_context(|| {
+            format!("Failed to open versions file: {}", versions_path.display())
+        })?;
+        let flags = VersionFlags::deserialize(std::io::BufReader::new(file))
+            .context("Failed to deserialize version flags")?;
+        anyhow::ensure!(
+            flags.len() == self.len(),
+            "Versions file has {} entries but the oracle has {}",
+            flags.len(),
+            self.len()
+        );
+        self.version_flags = Some(flags);
+        Ok(self)
+    }
+
+    /// Emit a `tracing` event for a finished load (no-op without the tracing feature)
+    fn trace_loaded(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            entries = self.heights.len(),
+            elapsed_ms = self.load_duration.as_secs_f64() * 1e3,
+            "loaded oracle"
+        );
+    }
+
+    /// Perfect-hash index of a block hash, in `0..len()` (see [`HeightOracle::index_of`])
+    pub fn index_of(&self, block_hash: &BlockHash) -> usize {
+        self.phash.index(block_hash.as_bytes())
+    }
+
+    /// Look up the height for a given block hash (unchecked)
+    ///
+    /// IMPORTANT: This function always returns a height, but does NOT validate
+    /// that the input hash was in the original dataset. For unknown hashes,
+    /// it will return a height corresponding to some other block in the range
+    ///
+    /// The caller must ensure the input hash is from the valid domain
+    /// (i.e., was in the original CSV file used to build the oracle).
+    pub fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+        self.lookup().height(block_hash)
+    }
+
+    /// Like [`Self::get_height_unchecked`], but `None` instead of a panic if the
+    /// perfect hash yields an index past the heights table
+    pub fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.lookup().height_opt(block_hash)
+    }
+
+    /// The shared lookup path over this oracle's tables
+    fn lookup(&self) -> Lookup<'_, [u32]> {
+        Lookup {
+            phash: &self.phash,
+            heights: &self.heights,
+            cache: self.cache.as_deref(),
+            query_counters: &self.query_counters,
+        }
+    }
+
+    /// Whether the block has header version 1 (unchecked)
+    ///
+    /// Returns `None` if the oracle was loaded without version flags.
+    /// See [`HeightOracle::is_v1_block`].
+    pub fn is_v1_block(&self, block_hash: &BlockHash) -> Option<bool> {
+        let flags = self.version_flags.as_ref()?;
+        Some(!flags.get(self.index_of(block_hash)))
+    }
+
+    /// Write the heights this oracle returns for `block_hashes` as text, CSV or JSON lines
+    ///
+    /// See [`HeightOracle::export`]; with the reverse dataset this audits distributed assets.
+    pub fn export<W: Write>(
+        &self,
+        block_hashes: &[BlockHash],
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<()> {
+        crate::export::export(
+            block_hashes,
+            |block_hash| self.get_height_unchecked(block_hash),
+            |block_hash| self.is_v1_block(block_hash).map(|is_v1| !is_v1),
+            format,
+            writer,
+        )
+    }
+
+    /// Write the heights this oracle returns for `block_hashes` to a new key-value store at `path`
+    ///
+    /// See [`HeightOracle::export_kv`].
+    pub fn export_kv<P: AsRef<Path>>(
+        &self,
+        block_hashes: &[BlockHash],
+        format: KvFormat,
+        path: P,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        crate::export_kv::export_kv(
+            block_hashes,
+            |block_hash| self.get_height_unchecked(block_hash),
+            format,
+            path.as_ref(),
+            options,
+        )
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.heights.is_empty()
+    }
+
+    /// Lookups made since the oracle was created or last reset, by this oracle
+    /// and its clones (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn query_stats(&self) -> crate::QueryStats {
+        self.query_counters.snapshot()
+    }
+
+    /// Zero the counters behind [`Self::query_stats`] (Feature: stats)
+    #[cfg(feature = "stats")]
+    pub fn reset_query_stats(&self) {
+        self.query_counters.reset();
+    }
+
+    /// Put a direct-mapped cache of `entries` slots in front of the perfect
+    /// hash; 0 removes it (see [`HeightOracle::with_cache`])
+    pub fn with_cache(mut self, entries: usize) -> Self {
+        self.cache = (entries > 0).then(|| Arc::new(LookupCache::new(entries)));
+        self
+    }
+
+    /// Memory usage statistics
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            load_duration: Some(self.load_duration),
+            ..MemoryStats::new(
+                &self.phash,
+                &*self.heights,
+                self.version_flags.as_ref(),
+                self.cache.as_deref(),
+                format::header_version(
+                    self.height_range.is_some(),
+                    self.dataset_commitment.is_some(),
+                ),
+            )
+        }
+    }
+
+    /// Perfect hash parameters recorded in the loaded PtrHash asset
+    pub fn phf_config(&self) -> &PhfConfig {
+        self.phash.config()
+    }
+
+    /// Version flags loaded alongside the heights, if any
+    pub fn version_flags(&self) -> Option<&VersionFlags> {
+        self.version_flags.as_ref()
+    }
+
+    /// Input policy recorded in the heights asset, `None` for legacy assets and
+    /// assets built without one
+    pub fn input_policy(&self) -> Option<InputPolicy> {
+        self.input_policy
+    }
+
+    /// Height range recorded in the heights asset, `None` for full builds
+    pub fn height_range(&self) -> Option<Range<u32>> {
+        self.height_range.clone()
+    }
+
+    /// Dataset commitment recorded in the heights asset, `None` for assets
+    /// written before format version 3 or built from raw entries
+    pub fn dataset_commitment(&self) -> Option<DatasetCommitment> {
+        self.dataset_commitment
+    }
+
+    /// Whether the dataset at `path` is the one these assets were built from
+    ///
+    /// Fails if the heights asset records no dataset commitment.
+    pub fn verify_dataset<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let expected = self
+            .dataset_commitment
+            .context("Heights asset has no dataset commitment")?;
+        verify_dataset(path, &expected)
+    }
+
+    /// Convert into a [`HeightOracle`], which can be saved again
+    ///
+    /// Moves the tables without copying them. Saving writes the current format
+    /// header, so loading with [`HeightOracle::load_legacy`] and saving upgrades
+    /// headerless assets.
+    pub fn into_oracle(self) -> HeightOracle {
+        HeightOracle {
+            phash: self.phash,
+            heights: self.heights,
+            version_flags: self.version_flags,
+            input_policy: self.input_policy,
+            height_range: self.height_range,
+            dataset_commitment: self.dataset_commitment,
+            query_counters: self.query_counters,
+            cache: self.cache,
+        }
+    }
+
+    /// Heights in perfect-hash order
+    pub(crate) fn heights(&self) -> &[u32] {
+        &self.heights
+    }
+}
+
+impl From<HeightOracleLoaded> for HeightOracle {
+    fn from(loaded: HeightOracleLoaded) -> Self {
+        loaded.into_oracle()
+    }
+}
+
+impl HeightLookup for HeightOracle {
+    fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+        HeightOracle::get_height_unchecked(self, block_hash)
+    }
+
+    fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        HeightOracle::get_height_opt(self, block_hash)
+    }
+
+    fn get_heights_unchecked(&self, block_hashes: &[BlockHash], heights: &mut [u32]) {
+        self.lookup().heights(block_hashes, heights)
+    }
+
+    fn len(&self) -> usize {
+        HeightOracle::len(self)
+    }
+}
+
+impl HeightLookup for HeightOracleLoaded {
+    fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+        HeightOracleLoaded::get_height_unchecked(self, block_hash)
+    }
+
+    fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        HeightOracleLoaded::get_height_opt(self, block_hash)
+    }
+
+    fn get_heights_unchecked(&self, block_hashes: &[BlockHash], heights: &mut [u32]) {
+        self.lookup().heights(block_hashes, heights)
+    }
+
+    fn len(&self) -> usize {
+        HeightOracleLoaded::len(self)
+    }
+}
+
+/// What two oracles must agree on to compare equal
+struct Contents<'a> {
+    phash: &'a Arc<Phf>,
+    heights: &'a [u32],
+    version_flags: Option<&'a VersionFlags>,
+    input_policy: Option<InputPolicy>,
+    height_range: Option<&'a Range<u32>>,
+    dataset_commitment: Option<DatasetCommitment>,
+}
+
+impl PartialEq for Contents<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.heights == other.heights
+            && self.version_flags == other.version_flags
+            && self.input_policy == other.input_policy
+            && self.height_range == other.height_range
+            && self.dataset_commitment == other.dataset_commitment
+            && (Arc::ptr_eq(self.phash, other.phash)
+                || serialized_phf(self.phash)
+                    .is_some_and(|data| Some(data) == serialized_phf(other.phash)))
+    }
+}
+
+/// The PtrHash asset bytes; Phf has no structural equality of its own
+fn serialized_phf(phash: &Phf) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    phash.write(&mut data).ok()?;
+    Some(data)
+}
+
+impl HeightOracle {
+    fn contents(&self) -> Contents<'_> {
+        Contents {
+            phash: &self.phash,
+            heights: &self.heights,
+            version_flags: self.version_flags.as_ref(),
+            input_policy: self.input_policy,
+            height_range: self.height_range.as_ref(),
+            dataset_commitment: self.dataset_commitment,
+        }
+    }
+}
+
+impl HeightOracleLoaded {
+    fn contents(&self) -> Contents<'_> {
+        Contents {
+            phash: &self.phash,
+            heights: &self.heights,
+            version_flags: self.version_flags.as_ref(),
+            input_policy: self.input_policy,
+            height_range: self.height_range.as_ref(),
+            dataset_commitment: self.dataset_commitment,
+        }
+    }
+}
+
+impl PartialEq for HeightOracle {
+    fn eq(&self, other: &Self) -> bool {
+        self.contents() == other.contents()
+    }
+}
+
+impl PartialEq for HeightOracleLoaded {
+    fn eq(&self, other: &Self) -> bool {
+        self.contents() == other.contents()
+    }
+}
+
+impl PartialEq<HeightOracleLoaded> for HeightOracle {
+    fn eq(&self, other: &HeightOracleLoaded) -> bool {
+        self.contents() == other.contents()
+    }
+}
+
+impl PartialEq<HeightOracle> for HeightOracleLoaded {
+    fn eq(&self, other: &HeightOracle) -> bool {
+        self.contents() == other.contents()
+    }
+}
+
+impl std::fmt::Debug for HeightOracle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeightOracle")
+            .field("len", &self.len())
+            .field("max_height", &self.heights.iter().max())
+            .field("version_flags", &self.version_flags.is_some())
+            .field("input_policy", &self.input_policy)
+            .field("height_range", &self.height_range)
+            .field("dataset_commitment", &self.dataset_commitment)
+            .field("phf", self.phash.config())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for HeightOracleLoaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeightOracleLoaded")
+            .field("len", &self.len())
+            .field("max_height", &self.heights.iter().max())
+            .field("version_flags", &self.version_flags.is_some())
+            .field("input_policy", &self.input_policy)
+            .field("height_range", &self.height_range)
+            .field("dataset_commitment", &self.dataset_commitment)
+            .field("phf", self.phash.config())
+            .field("load_duration", &self.load_duration)
+            .finish()
+    }
+}
+
+/// Small oracles for the tests of this and other modules
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    /// PHF config for test oracles: FMPH with the `fmph` feature, else the default PtrHash
+    pub(crate) fn test_phf_config() -> PhfConfig {
+        let config = PhfConfig::new();
+        #[cfg(feature = "fmph")]
+        let config = config.backend(crate::phf::PhfBackend::Fmph);
+        config
+    }
+
+    /// Hashes `[1; 32]` through `[count; 32]`
+    pub(crate) fn test_hashes(count: u8) -> Vec<BlockHash> {
+        (1..=count).map(|i| BlockHash::from([i; 32])).collect()
+    }
+
+    /// Oracle mapping `block_hashes` to `heights`, built with `options` and [`test_phf_config`]
+    pub(crate) fn test_oracle_with(
+        block_hashes: &[BlockHash],
+        heights: &[u32],
+        options: BuildOptions,
+    ) -> HeightOracle {
+        let options = options.phf_config(test_phf_config());
+        HeightOracle::from_entries_with_options(block_hashes, heights, &options).unwrap()
+    }
+
+    /// Oracle mapping `block_hashes` to `heights`
+    pub(crate) fn test_oracle(block_hashes: &[BlockHash], heights: &[u32]) -> HeightOracle {
+        test_oracle_with(block_hashes, heights, BuildOptions::new())
+    }
+
+    /// Oracle over [`test_hashes`]`(3)` at heights 0..=2
+    pub(crate) fn small_oracle() -> HeightOracle {
+        test_oracle(&test_hashes(3), &[0, 1, 2])
+    }
+
+    /// The PtrHash and heights assets of `oracle`, as they are saved to disk
+    pub(crate) fn asset_bytes(oracle: &HeightOracle) -> (Vec<u8>, Vec<u8>) {
+        let (mut ptrhash_data, mut heights_data) = (Vec::new(), Vec::new());
+        oracle
+            .save_to_writers(&mut ptrhash_data, &mut heights_data)
+            .unwrap();
+        (ptrhash_data, heights_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+
+    #[test]
+    fn test_hex_conversion() {
+        let hex = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
         let result: BlockHash = crate::parse_block_hash(hex).unwrap();
 
         // First few bytes of genesis block hash in network order
-        assert_eq!(result[0], 0x6f);
-        assert_eq!(result[1], 0xe2);
-        assert_eq!(result[2], 0x8c);
-        assert_eq!(result[3], 0x0a);
+        assert_eq!(result.as_bytes()[0], 0x6f);
+        assert_eq!(result.as_bytes()[1], 0xe2);
+        assert_eq!(result.as_bytes()[2], 0x8c);
+        assert_eq!(result.as_bytes()[3], 0x0a);
+    }
+
+    #[test]
+    fn test_build_options_cancellation() {
+        let token = CancellationToken::new();
+        let options = BuildOptions::new().cancellation_token(token.clone());
+        assert!(options.check_cancelled().is_ok());
+
+        token.cancel();
+        let err = options.check_cancelled().unwrap_err();
+        assert!(err.is::<BuildCancelled>());
+    }
+
+    #[test]
+    fn test_reject_placeholders_policy() {
+        let dataset = Dataset {
+            block_hashes: vec![BlockHash::from([1; 32])],
+            heights: vec![0],
+            v2_flags: vec![false],
+            placeholders: 2,
+        };
+        let options = BuildOptions::new().input_policy(InputPolicy::RejectPlaceholders);
+        let err = HeightOracle::from_dataset_with_options(&dataset, &options)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("2 `x` placeholder lines"));
+    }
+
+    #[test]
+    fn test_version_flags_need_annotations() {
+        let block_hashes = test_hashes(3);
+        let options = BuildOptions::new().phf_config(test_phf_config());
+        let mut dataset = Dataset {
+            block_hashes: block_hashes.clone(),
+            heights: vec![0, 1, 2],
+            v2_flags: vec![false; 3],
+            placeholders: 0,
+        };
+        let oracle = HeightOracle::from_dataset_with_options(&dataset, &options).unwrap();
+        assert!(oracle.version_flags().is_none());
+        assert_eq!(oracle.is_v1_block(&block_hashes[0]), None);
+
+        dataset.v2_flags[2] = true;
+        let oracle = HeightOracle::from_dataset_with_options(&dataset, &options).unwrap();
+        assert_eq!(oracle.is_v1_block(&block_hashes[0]), Some(true));
+        assert_eq!(oracle.is_v1_block(&block_hashes[2]), Some(false));
+
+        // Skipped version-2 blocks still mark the input as annotated
+        let options = options.input_policy(InputPolicy::SkipV2);
+        let oracle = HeightOracle::from_dataset_with_options(&dataset, &options).unwrap();
+        assert_eq!(oracle.len(), 2);
+        assert_eq!(oracle.is_v1_block(&block_hashes[1]), Some(true));
+    }
+
+    #[test]
+    fn test_empty_height_range() {
+        let options = BuildOptions::new().height_range(5..5);
+        let err =
+            HeightOracle::from_entries_with_options(&[BlockHash::from([1; 32])], &[5], &options)
+                .err()
+                .unwrap();
+        assert!(err.to_string().contains("is empty"));
+    }
+
+    #[test]
+    fn test_invalid_phf_params() {
+        let options = BuildOptions::new().phf_config(test_phf_config().alpha(1.5));
+        let err =
+            HeightOracle::from_entries_with_options(&[BlockHash::from([1; 32])], &[5], &options)
+                .err()
+                .unwrap();
+        assert!(err.to_string().contains("alpha must be in (0, 1]"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_build_options_from_config() {
+        let options: BuildOptions = serde_json::from_str(
+            r#"{"phf": {"bucket_fn": "cubic-eps", "hash_fn": "xx64", "lambda": 3.5},
+                "input_policy": "skip-v2", "height_range": {"start": 0, "end": 1000}}"#,
+        )
+        .unwrap();
+        assert_eq!(options.phf.hash_fn, crate::phf::HashFunction::Xx64);
+        assert_eq!(options.phf.lambda, Some(3.5));
+        assert_eq!(options.input_policy, InputPolicy::SkipV2);
+        assert_eq!(options.height_range, Some(0..1000));
+        assert_eq!(options.progress_interval, DEFAULT_PROGRESS_INTERVAL);
+
+        // The callback and token are not part of the config
+        let options = BuildOptions::new()
+            .progress(|_| {})
+            .cancellation_token(CancellationToken::new());
+        let json = serde_json::to_string(&options).unwrap();
+        assert!(!json.contains("progress\""));
+        assert!(!json.contains("cancellation"));
+        let parsed: BuildOptions = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.phf, options.phf);
+    }
+
+    #[test]
+    fn test_clone_and_content_equality() {
+        let oracle = small_oracle();
+        let clone = oracle.clone();
+        assert!(Arc::ptr_eq(&oracle.heights, &clone.heights));
+        assert_eq!(oracle, clone);
+
+        // A reloaded copy shares nothing but compares equal
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        let loaded = HeightOracle::load_from_paths(&ptrhash_path, &heights_path).unwrap();
+        assert_eq!(oracle, loaded);
+        assert_eq!(loaded, loaded.clone());
+
+        let mut changed = oracle.clone();
+        changed.heights = vec![0, 1, 3].into();
+        assert_ne!(oracle, changed);
+
+        let debug = format!("{oracle:?}");
+        assert!(debug.starts_with("HeightOracle { len: 3, max_height: Some(2)"));
+        assert!(format!("{loaded:?}").contains("load_duration"));
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_query_stats() {
+        let oracle = small_oracle();
+        let clone = oracle.clone();
+        let block_hashes = test_hashes(3);
+        oracle.get_height_unchecked(&block_hashes[0]);
+        clone.get_height_opt(&block_hashes[1]);
+        let mut heights = [0; 3];
+        HeightLookup::get_heights_unchecked(&clone, &block_hashes, &mut heights);
+
+        let stats = oracle.query_stats();
+        assert_eq!(
+            (stats.lookups, stats.batches, stats.max_batch_size),
+            (5, 1, 3)
+        );
+        assert_eq!(stats.rejections, 0);
+        assert_eq!(stats, clone.query_stats());
+
+        clone.reset_query_stats();
+        assert_eq!(oracle.query_stats(), crate::QueryStats::default());
+    }
+
+    #[test]
+    fn test_get_height_from_hex() {
+        let oracle = small_oracle();
+        let hex = BlockHash::from([2; 32]).to_string();
+        assert_eq!(oracle.get_height_from_hex(&hex), Ok(1));
+        assert_eq!(
+            oracle.get_height_from_hex(&format!("0x{hex}")),
+            Ok(oracle.get_height_from_hex_unchecked(&hex))
+        );
+
+        for bad in ["", "00", &hex[1..], &format!("{}zz", &hex[2..])] {
+            let err = oracle.get_height_from_hex(bad).unwrap_err();
+            assert!(
+                matches!(err, crate::HeightOracleError::InvalidHash { .. }),
+                "{bad}"
+            );
+            assert!(err.to_string().starts_with("Invalid block hash"));
+        }
+    }
+
+    #[test]
+    fn test_with_cache() {
+        let oracle = small_oracle().with_cache(2);
+        let clone = oracle.clone();
+        for _ in 0..3 {
+            for (i, height) in (1..=3).zip(0..) {
+                let block_hash = BlockHash::from([i; 32]);
+                assert_eq!(oracle.get_height_unchecked(&block_hash), height);
+                assert_eq!(clone.get_height_opt(&block_hash), Some(height));
+            }
+        }
+        assert!(clone.cache.is_some());
+        assert!(clone.memory_stats().cache_heap_bytes > 0);
+        assert!(oracle.with_cache(0).cache.is_none());
+    }
+
+    #[test]
+    fn test_lookup_iter() {
+        let oracle = small_oracle();
+        let block_hashes = (1..=3).map(|i| BlockHash::from([i; 32]));
+        let heights: Vec<u32> = oracle
+            .lookup_iter(block_hashes)
+            .map(|(_, height)| height)
+            .collect();
+        assert_eq!(heights, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_resave_loaded_oracle() {
+        let dir = tempfile::tempdir().unwrap();
+        let container = dir.path().join("oracle.hoc");
+        let original = small_oracle();
+        original.save_to_file(&container).unwrap();
+
+        let resaved = HeightOracle::load_from_file(&container)
+            .unwrap()
+            .into_oracle();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        resaved.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        assert_eq!(
+            HeightOracle::load_from_paths(&ptrhash_path, &heights_path).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn test_container_keeps_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let txt_path = dir.path().join("blocks.txt");
+        let lines: Vec<String> = test_hashes(3)
+            .iter()
+            .map(|hash| format!("{hash}\n"))
+            .collect();
+        std::fs::write(&txt_path, lines.concat()).unwrap();
+        let dataset = Dataset::from_path(&txt_path).unwrap();
+        let options = BuildOptions::new()
+            .phf_config(test_phf_config())
+            .input_policy(InputPolicy::SkipV2)
+            .height_range(1..3);
+        let oracle = HeightOracle::from_dataset_with_options(&dataset, &options).unwrap();
+
+        let container = dir.path().join("oracle.hoc");
+        oracle.save_to_file(&container).unwrap();
+        let loaded = HeightOracle::load_from_file(&container).unwrap();
+        assert_eq!(loaded.input_policy(), Some(InputPolicy::SkipV2));
+        assert_eq!(loaded.height_range(), Some(1..3));
+        assert_eq!(loaded.dataset_commitment(), Some(dataset.commitment()));
+        assert_eq!(loaded.len(), 2);
+    }
+
+    #[test]
+    fn test_save_to_writers() {
+        let oracle = small_oracle();
+        let (ptrhash_data, heights_data) = asset_bytes(&oracle);
+
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        assert_eq!(std::fs::read(&ptrhash_path).unwrap(), ptrhash_data);
+        assert_eq!(std::fs::read(&heights_path).unwrap(), heights_data);
+    }
+
+    #[test]
+    fn test_dataset_commitment() {
+        let dir = tempfile::tempdir().unwrap();
+        let txt_path = dir.path().join("prebip34.txt");
+        let block_hashes: Vec<BlockHash> = (1..=3).map(|i| BlockHash::from([i; 32])).collect();
+        let lines: Vec<String> = block_hashes
+            .iter()
+            .map(|hash| format!("{hash}\n"))
+            .collect();
+        std::fs::write(&txt_path, lines.concat()).unwrap();
+
+        // The commitment covers the whole dataset, not just the kept range
+        let dataset = Dataset::from_path(&txt_path).unwrap();
+        let options = BuildOptions::new()
+            .phf_config(test_phf_config())
+            .height_range(1..3);
+        let oracle = HeightOracle::from_dataset_with_options(&dataset, &options).unwrap();
+        assert_eq!(oracle.dataset_commitment(), Some(dataset.commitment()));
+
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        let loaded = HeightOracle::load_from_paths(&ptrhash_path, &heights_path).unwrap();
+        assert_eq!(loaded.dataset_commitment(), Some(dataset.commitment()));
+        assert_eq!(loaded.height_range(), Some(1..3));
+        assert!(loaded.verify_dataset(&txt_path).unwrap());
+
+        // Dropping a block changes the commitment
+        std::fs::write(&txt_path, lines[..2].concat()).unwrap();
+        assert!(!loaded.verify_dataset(&txt_path).unwrap());
+
+        // Raw entries have nothing to verify against
+        assert!(small_oracle().verify_dataset(&txt_path).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "signing")]
+    fn test_signed_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        let key = crate::signing::SigningKey::from_bytes(&[7; 32]);
+        let oracle = small_oracle();
+        oracle
+            .save_to_paths_signed(&ptrhash_path, &heights_path, &key, &SaveOptions::new())
+            .unwrap();
+        let loaded =
+            HeightOracle::load_verified_signed(&ptrhash_path, &heights_path, &key.verifying_key())
+                .unwrap();
+        assert_eq!(oracle, loaded);
+
+        // Another key is refused
+        let other_key = crate::signing::SigningKey::from_bytes(&[8; 32]).verifying_key();
+        let err = HeightOracle::load_verified_signed(&ptrhash_path, &heights_path, &other_key)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("does not verify"));
+
+        // So are tampered and unsigned assets
+        let mut heights = std::fs::read(&heights_path).unwrap();
+        *heights.last_mut().unwrap() ^= 1;
+        std::fs::write(&heights_path, heights).unwrap();
+        assert!(HeightOracle::load_verified_signed(
+            &ptrhash_path,
+            &heights_path,
+            &key.verifying_key()
+        )
+        .is_err());
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        std::fs::remove_file(crate::signing::signature_path(&heights_path)).unwrap();
+        let err =
+            HeightOracle::load_verified_signed(&ptrhash_path, &heights_path, &key.verifying_key())
+                .unwrap_err();
+        assert!(err.to_string().contains("unsigned assets are refused"));
+    }
+
+    #[test]
+    fn test_build_options_progress_interval() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let options = BuildOptions::new()
+            .progress_interval(2)
+            .progress(move |event| sink.lock().unwrap().push(event));
+
+        for lines in 1..=5 {
+            options
+                .checkpoint(lines, |lines| BuildProgress::Parsing { lines })
+                .unwrap();
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                BuildProgress::Parsing { lines: 2 },
+                BuildProgress::Parsing { lines: 4 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(crate::HEIGHTS_FILE_NAME);
+        let options = SaveOptions::new().fsync(true);
+        write_atomic(&path, &options, |writer| writer.write_all(b"old")).unwrap();
+
+        // A failed write keeps the previous file and cleans up after itself
+        let err = write_atomic(&path, &options, |writer| {
+            writer.write_all(b"partial")?;
+            Err(std::io::Error::other("interrupted"))
+        })
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Failed to write"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        write_atomic(&path, &SaveOptions::new(), |writer| {
+            writer.write_all(b"new")
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_load_from_paths_async_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let Err(err) = HeightOracle::load_from_paths_async(
+            dir.path().join(crate::PTRHASH_FILE_NAME),
+            dir.path().join(crate::HEIGHTS_FILE_NAME),
+        )
+        .await
+        else {
+            panic!("loading from an empty directory should fail");
+        };
+        assert!(err.to_string().starts_with("Failed to read"));
     }
 }
@@ -0,0 +1,291 @@
+//! gRPC service over any oracle (Feature: grpc)
+//!
+//! Implements the `height_oracle.v1.Oracle` service from `proto/height_oracle.proto`.
+//! Serve it with [`OracleServer`], e.g. from the `height-oracle-grpc` binary.
+//! Attach [`Metrics`] with [`OracleService::with_metrics`] to record lookups.
+//! With the `verify` feature, `GetVerifiedHeight` confirms heights with a
+//! bitcoind node set by [`OracleService::with_cross_checker`].
+
+use crate::metrics::{Method, Metrics};
+#[cfg(feature = "verify")]
+use crate::verify::{Confirmation, RpcCrossChecker};
+use crate::HeightLookup;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+use tonic::{Request, Response, Status, Streaming};
+
+#[allow(clippy::all)]
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/height_oracle.v1.Oracle.rs"));
+}
+
+pub use proto::oracle_server::{Oracle, OracleServer};
+
+/// Request message for `GetHeight` / `GetHeights`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetHeightRequest {
+    /// Block hash in reverse hex, optional 0x prefix
+    #[prost(string, tag = "1")]
+    pub block_hash: String,
+}
+
+/// Response message for `GetHeight` / `GetHeights`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetHeightResponse {
+    /// The block hash from the request
+    #[prost(string, tag = "1")]
+    pub block_hash: String,
+    /// Block height (unchecked)
+    #[prost(uint32, tag = "2")]
+    pub height: u32,
+}
+
+/// Response message for `GetVerifiedHeight` (Feature: verify)
+#[cfg(feature = "verify")]
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetVerifiedHeightResponse {
+    /// The block hash from the request
+    #[prost(string, tag = "1")]
+    pub block_hash: String,
+    /// Block height, as returned by `GetHeight`
+    #[prost(uint32, tag = "2")]
+    pub height: u32,
+    /// Whether the node has this block at that height
+    #[prost(bool, tag = "3")]
+    pub confirmed: bool,
+    /// The node's block at that height when it is another one, empty otherwise
+    #[prost(string, tag = "4")]
+    pub node_block_hash: String,
+}
+
+/// `Oracle` service implementation backed by a [`HeightLookup`]
+pub struct OracleService<O> {
+    oracle: Arc<O>,
+    metrics: Option<Arc<Metrics>>,
+    #[cfg(feature = "verify")]
+    cross_checker: Option<Arc<RpcCrossChecker>>,
+}
+
+impl<O: HeightLookup + Send + Sync + 'static> OracleService<O> {
+    /// Serve lookups from `oracle`
+    pub fn new(oracle: O) -> Self {
+        Self::from_arc(Arc::new(oracle))
+    }
+
+    /// Serve lookups from a shared oracle
+    pub fn from_arc(oracle: Arc<O>) -> Self {
+        Self {
+            oracle,
+            metrics: None,
+            #[cfg(feature = "verify")]
+            cross_checker: None,
+        }
+    }
+
+    /// Record lookup counts, latency and stream batch sizes into `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Answer `GetVerifiedHeight` by confirming heights with `cross_checker` (Feature: verify)
+    ///
+    /// Without one, `GetVerifiedHeight` fails with `FAILED_PRECONDITION`.
+    #[cfg(feature = "verify")]
+    pub fn with_cross_checker(mut self, cross_checker: Arc<RpcCrossChecker>) -> Self {
+        self.cross_checker = Some(cross_checker);
+        self
+    }
+
+    /// Wrap this service in the tonic server type
+    pub fn into_server(self) -> OracleServer<Self> {
+        OracleServer::new(self)
+    }
+}
+
+/// Answer one request, rejecting malformed hashes with `INVALID_ARGUMENT`
+fn lookup<O: HeightLookup + ?Sized>(
+    oracle: &O,
+    request: GetHeightRequest,
+) -> Result<GetHeightResponse, Status> {
+    let block_hash = crate::parse_block_hash(&request.block_hash).map_err(|e| {
+        Status::invalid_argument(format!("Invalid block hash {:?}: {e}", request.block_hash))
+    })?;
+    Ok(GetHeightResponse {
+        height: oracle.get_height_unchecked(&block_hash),
+        block_hash: request.block_hash,
+    })
+}
+
+#[tonic::async_trait]
+impl<O: HeightLookup + Send + Sync + 'static> Oracle for OracleService<O> {
+    async fn get_height(
+        &self,
+        request: Request<GetHeightRequest>,
+    ) -> Result<Response<GetHeightResponse>, Status> {
+        let started = Instant::now();
+        let response = lookup(self.oracle.as_ref(), request.into_inner());
+        if let Some(metrics) = &self.metrics {
+            metrics.record_lookup(Method::GetHeight, started.elapsed(), response.is_ok());
+        }
+        response.map(Response::new)
+    }
+
+    type GetHeightsStream =
+        Pin<Box<dyn Stream<Item = Result<GetHeightResponse, Status>> + Send + 'static>>;
+
+    /// Answer each request as it arrives; a malformed hash ends the stream with `INVALID_ARGUMENT`
+    async fn get_heights(
+        &self,
+        request: Request<Streaming<GetHeightRequest>>,
+    ) -> Result<Response<Self::GetHeightsStream>, Status> {
+        let oracle = Arc::clone(&self.oracle);
+        let mut batch = self.metrics.clone().map(BatchRecorder::new);
+        let responses = request.into_inner().map(move |request| {
+            let started = Instant::now();
+            let response = lookup(oracle.as_ref(), request?);
+            if let Some(batch) = &mut batch {
+                batch.record(started, response.is_ok());
+            }
+            response
+        });
+        Ok(Response::new(Box::pin(responses)))
+    }
+
+    #[cfg(feature = "verify")]
+    async fn get_verified_height(
+        &self,
+        request: Request<GetHeightRequest>,
+    ) -> Result<Response<GetVerifiedHeightResponse>, Status> {
+        let cross_checker = self.cross_checker.as_ref().ok_or_else(|| {
+            Status::failed_precondition("No bitcoind node configured for verification")
+        })?;
+        let response = lookup(self.oracle.as_ref(), request.into_inner())?;
+        let block_hash = crate::parse_block_hash(&response.block_hash)
+            .expect("lookup only answers parsed hashes");
+        let confirmation = cross_checker
+            .confirm(&block_hash, response.height)
+            .await
+            .map_err(|e| Status::unavailable(format!("Verification failed: {e:#}")))?;
+        Ok(Response::new(verified_response(response, confirmation)))
+    }
+}
+
+/// Combine a lookup and the node's verdict on it
+#[cfg(feature = "verify")]
+fn verified_response(
+    response: GetHeightResponse,
+    confirmation: Confirmation,
+) -> GetVerifiedHeightResponse {
+    GetVerifiedHeightResponse {
+        block_hash: response.block_hash,
+        height: response.height,
+        confirmed: confirmation.is_confirmed(),
+        node_block_hash: match confirmation {
+            Confirmation::Mismatch { node_hash } => node_hash.to_string(),
+            Confirmation::Confirmed | Confirmation::BeyondTip => String::new(),
+        },
+    }
+}
+
+/// Counts the lookups of one `GetHeights` stream and records the batch size when dropped
+struct BatchRecorder {
+    metrics: Arc<Metrics>,
+    size: usize,
+}
+
+impl BatchRecorder {
+    fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics, size: 0 }
+    }
+
+    fn record(&mut self, started: Instant, ok: bool) {
+        self.size += 1;
+        self.metrics
+            .record_lookup(Method::GetHeights, started.elapsed(), ok);
+    }
+}
+
+impl Drop for BatchRecorder {
+    fn drop(&mut self) {
+        self.metrics.record_batch(self.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockHash;
+
+    struct Fixed(u32);
+    impl HeightLookup for Fixed {
+        fn get_height_unchecked(&self, _block_hash: &BlockHash) -> u32 {
+            self.0
+        }
+
+        fn len(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let hash = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let response = lookup(
+            &Fixed(7),
+            GetHeightRequest {
+                block_hash: hash.to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(response.height, 7);
+        assert_eq!(response.block_hash, hash);
+
+        let status = lookup(
+            &Fixed(7),
+            GetHeightRequest {
+                block_hash: "zz".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    #[cfg(feature = "verify")]
+    fn test_verified_response() {
+        let response = || GetHeightResponse {
+            block_hash: "00".repeat(32),
+            height: 7,
+        };
+        let confirmed = verified_response(response(), Confirmation::Confirmed);
+        assert!(confirmed.confirmed);
+        assert_eq!(confirmed.height, 7);
+        assert!(confirmed.node_block_hash.is_empty());
+
+        let node_hash = BlockHash::from([1; 32]);
+        let mismatch = verified_response(response(), Confirmation::Mismatch { node_hash });
+        assert!(!mismatch.confirmed);
+        assert_eq!(mismatch.node_block_hash, node_hash.to_string());
+        assert!(verified_response(response(), Confirmation::BeyondTip)
+            .node_block_hash
+            .is_empty());
+    }
+
+    #[test]
+    fn test_batch_recorder() {
+        let metrics = Arc::new(Metrics::new());
+        let mut batch = BatchRecorder::new(Arc::clone(&metrics));
+        batch.record(Instant::now(), true);
+        batch.record(Instant::now(), false);
+        drop(batch);
+
+        assert_eq!(metrics.lookups(Method::GetHeights), 2);
+        assert!(metrics
+            .render()
+            .lines()
+            .any(|line| line == "height_oracle_batch_size_count 1"));
+    }
+}
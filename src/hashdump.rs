@@ -0,0 +1,180 @@
+//! Binary block hash dataset
+//!
+//! A compact alternative to the text dataset: fixed 32-byte records in height
+//! order instead of 64-char hex lines, so it is half the size and needs no hex
+//! parsing at build time.
+//!
+//! Layout (little-endian):
+//! `[magic: "HOBD"][version: u8][flags: u8][reserved: 2][start_height: u32][count: u32]`
+//! followed by `count` 32-byte block hashes in network byte order. An all-zero
+//! record is a skipped placeholder (the text format's `x`). If flag bit 0 is set,
+//! a bitmap of `ceil(count / 8)` bytes follows, marking version-2 blocks whose
+//! hash was kept (the text format's `x <hash>`).
+
+use crate::BlockHash;
+use std::io::{self, Read, Write};
+
+/// Magic bytes at the start of a binary dataset
+pub const MAGIC: [u8; 4] = *b"HOBD";
+
+/// Current format version
+pub const VERSION: u8 = 1;
+
+/// Flag bit: a version-2 bitmap follows the records
+const FLAG_V2_BITMAP: u8 = 1;
+
+const HEADER_LEN: usize = 16;
+
+/// Most records [`read`] reserves up front before reading them
+const MAX_PREALLOCATED_RECORDS: usize = 1 << 20;
+
+/// One record of a binary dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashRecord {
+    /// Skipped block (no hash stored)
+    Placeholder,
+    /// Block hash, and whether the block is version 2
+    Hash { block_hash: BlockHash, is_v2: bool },
+}
+
+/// A decoded binary dataset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashDump {
+    /// Height of the first record
+    pub start_height: u32,
+    /// One record per height, starting at `start_height`
+    pub records: Vec<HashRecord>,
+}
+
+impl HashDump {
+    /// Iterate over (height, record) pairs
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &HashRecord)> + '_ {
+        (self.start_height..).zip(self.records.iter())
+    }
+}
+
+/// Write a binary dataset
+///
+/// The version-2 bitmap is only written when some record is flagged.
+pub fn write<W: Write>(mut writer: W, start_height: u32, records: &[HashRecord]) -> io::Result<()> {
+    let v2_bits: Vec<bool> = records
+        .iter()
+        .map(|record| matches!(record, HashRecord::Hash { is_v2: true, .. }))
+        .collect();
+    let has_v2 = v2_bits.iter().any(|&bit| bit);
+    let count = u32::try_from(records.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many records"))?;
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[VERSION, if has_v2 { FLAG_V2_BITMAP } else { 0 }, 0, 0])?;
+    writer.write_all(&start_height.to_le_bytes())?;
+    writer.write_all(&count.to_le_bytes())?;
+
+    for record in records {
+        match record {
+            HashRecord::Placeholder => writer.write_all(&[0u8; 32])?,
+            HashRecord::Hash { block_hash, .. } => writer.write_all(block_hash.as_bytes())?,
+        }
+    }
+
+    if has_v2 {
+        let mut bitmap = vec![0u8; records.len().div_ceil(8)];
+        for (i, _) in v2_bits.iter().enumerate().filter(|(_, &bit)| bit) {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+        writer.write_all(&bitmap)?;
+    }
+
+    Ok(())
+}
+
+/// Read a binary dataset
+pub fn read<R: Read>(mut reader: R) -> io::Result<HashDump> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if header[..4] != MAGIC {
+        return Err(invalid("Not a binary block hash dataset".to_string()));
+    }
+    if header[4] != VERSION {
+        return Err(invalid(format!(
+            "Unsupported binary dataset version {}",
+            header[4]
+        )));
+    }
+    let flags = header[5];
+    let start_height = u32::from_le_bytes(header[8..12].try_into().expect("4 bytes"));
+    let count = u32::from_le_bytes(header[12..16].try_into().expect("4 bytes")) as usize;
+
+    // The count is untrusted, so grow past the cap only as records arrive
+    let mut records = Vec::with_capacity(count.min(MAX_PREALLOCATED_RECORDS));
+    let mut hash = [0u8; 32];
+    for _ in 0..count {
+        reader.read_exact(&mut hash)?;
+        records.push(if hash == [0u8; 32] {
+            HashRecord::Placeholder
+        } else {
+            HashRecord::Hash {
+                block_hash: BlockHash::from_byte_array(hash),
+                is_v2: false,
+            }
+        });
+    }
+
+    if flags & FLAG_V2_BITMAP != 0 {
+        let mut bitmap = vec![0u8; count.div_ceil(8)];
+        reader.read_exact(&mut bitmap)?;
+        for (i, record) in records.iter_mut().enumerate() {
+            if let HashRecord::Hash { is_v2, .. } = record {
+                *is_v2 = bitmap[i / 8] & (1 << (i % 8)) != 0;
+            }
+        }
+    }
+
+    Ok(HashDump {
+        start_height,
+        records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let hash = |byte| BlockHash::from([byte; 32]);
+        let records = vec![
+            HashRecord::Hash {
+                block_hash: hash(1),
+                is_v2: false,
+            },
+            HashRecord::Placeholder,
+            HashRecord::Hash {
+                block_hash: hash(3),
+                is_v2: true,
+            },
+        ];
+
+        let mut buffer = Vec::new();
+        write(&mut buffer, 10, &records).unwrap();
+        assert_eq!(buffer.len(), HEADER_LEN + 3 * 32 + 1);
+
+        let dump = read(&buffer[..]).unwrap();
+        assert_eq!(dump.start_height, 10);
+        assert_eq!(dump.records, records);
+        assert_eq!(dump.iter().nth(2).unwrap().0, 12);
+
+        // Without v2 blocks there is no bitmap
+        let mut buffer = Vec::new();
+        write(&mut buffer, 0, &records[..2]).unwrap();
+        assert_eq!(buffer.len(), HEADER_LEN + 2 * 32);
+        assert!(read(&b"nope-not-a-dataset"[..]).is_err());
+
+        // A forged count fails on the missing records instead of reserving them
+        buffer[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = read(&buffer[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
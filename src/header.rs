@@ -0,0 +1,319 @@
+//! Raw 80-byte block header parsing
+//!
+//! Header layout (all integers little-endian):
+//! version[4] | prev_blockhash[32] | merkle_root[32] | time[4] | bits[4] | nonce[4]
+
+use crate::BlockHash;
+
+/// Size of a serialized block header
+pub const HEADER_SIZE: usize = 80;
+
+/// Parsed block header fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_blockhash: BlockHash,
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// Parse a raw 80-byte header
+    pub fn parse(raw: &[u8; HEADER_SIZE]) -> Self {
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                raw[offset],
+                raw[offset + 1],
+                raw[offset + 2],
+                raw[offset + 3],
+            ])
+        };
+        let mut prev_blockhash = [0u8; 32];
+        prev_blockhash.copy_from_slice(&raw[4..36]);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&raw[36..68]);
+
+        Self {
+            version: u32_at(0) as i32,
+            prev_blockhash: BlockHash::from_byte_array(prev_blockhash),
+            merkle_root,
+            time: u32_at(68),
+            bits: u32_at(72),
+            nonce: u32_at(76),
+        }
+    }
+
+    /// Proof-of-work target encoded by `bits`, as a 256-bit little-endian number
+    pub fn target(&self) -> [u8; 32] {
+        target_from_bits(self.bits)
+    }
+}
+
+/// Compact target of the mainnet proof-of-work limit (difficulty 1)
+pub const POW_LIMIT_BITS: u32 = 0x1d00ffff;
+
+/// Expand compact `nBits` into a 256-bit little-endian target
+///
+/// Returns an all-zero target for negative or overflowing encodings, which no hash can meet.
+pub fn target_from_bits(bits: u32) -> [u8; 32] {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    let mut target = [0u8; 32];
+
+    // Sign bit set → negative target, invalid for PoW
+    if bits & 0x0080_0000 != 0 || mantissa == 0 {
+        return target;
+    }
+
+    let mantissa_bytes = mantissa.to_le_bytes();
+    for (i, &byte) in mantissa_bytes[..3].iter().enumerate() {
+        // Byte i of the mantissa lands at position exponent - 3 + i
+        let Some(pos) = (exponent + i).checked_sub(3) else {
+            continue;
+        };
+        if pos >= 32 {
+            if byte != 0 {
+                return [0u8; 32];
+            }
+            continue;
+        }
+        target[pos] = byte;
+    }
+    target
+}
+
+/// Compact encoding (`nBits`) of a 256-bit little-endian target, as Bitcoin Core's `GetCompact`
+///
+/// Keeps the top three significant bytes, so it rounds the target down.
+pub fn bits_from_target(target: &[u8; 32]) -> u32 {
+    let Some(top) = target.iter().rposition(|&byte| byte != 0) else {
+        return 0;
+    };
+    let mut size = top + 1;
+    let mut mantissa = if size <= 3 {
+        let value = target[..size]
+            .iter()
+            .rev()
+            .fold(0u32, |value, &byte| (value << 8) | u32::from(byte));
+        value << (8 * (3 - size))
+    } else {
+        u32::from_be_bytes([0, target[size - 1], target[size - 2], target[size - 3]])
+    };
+    // The mantissa's top bit is the sign; move a set one into the exponent
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    ((size as u32) << 24) | mantissa
+}
+
+/// Whether `target` is no easier than the proof-of-work limit
+pub fn within_pow_limit(target: &[u8; 32]) -> bool {
+    // Targets are little-endian 256-bit integers, like hashes
+    hash_meets_target(&BlockHash::from(*target), &target_from_bits(POW_LIMIT_BITS))
+}
+
+/// Seconds a retarget period is meant to take: two weeks
+pub const TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60;
+
+/// `nBits` of the first block of a retarget period, as Bitcoin Core's `CalculateNextWorkRequired`
+///
+/// `bits` is the last block's `nBits`; `first_time` and `last_time` are the
+/// timestamps of the first and last block of the period ending there. The
+/// timespan is clamped to between a quarter and four times
+/// [`TARGET_TIMESPAN`], and the new target to the proof-of-work limit.
+pub fn retarget_bits(bits: u32, first_time: u32, last_time: u32) -> u32 {
+    let timespan = (i64::from(last_time) - i64::from(first_time)).clamp(
+        i64::from(TARGET_TIMESPAN / 4),
+        i64::from(TARGET_TIMESPAN) * 4,
+    ) as u128;
+
+    let target = target_from_bits(bits);
+    let mut limbs: [u64; 4] =
+        std::array::from_fn(|i| u64::from_le_bytes(target[i * 8..i * 8 + 8].try_into().unwrap()));
+    let mut carry = 0u128;
+    for limb in &mut limbs {
+        let product = u128::from(*limb) * timespan + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+    if carry != 0 {
+        // Past 2^256, so certainly past the limit
+        return POW_LIMIT_BITS;
+    }
+    let mut remainder = 0u128;
+    for limb in limbs.iter_mut().rev() {
+        let dividend = (remainder << 64) | u128::from(*limb);
+        *limb = (dividend / u128::from(TARGET_TIMESPAN)) as u64;
+        remainder = dividend % u128::from(TARGET_TIMESPAN);
+    }
+
+    let mut new_target = [0u8; 32];
+    for (bytes, limb) in new_target.chunks_exact_mut(8).zip(limbs) {
+        bytes.copy_from_slice(&limb.to_le_bytes());
+    }
+    if !within_pow_limit(&new_target) {
+        return POW_LIMIT_BITS;
+    }
+    bits_from_target(&new_target)
+}
+
+/// Whether a block hash (network byte order = little-endian number) meets a little-endian target
+pub fn hash_meets_target(hash: &BlockHash, target: &[u8; 32]) -> bool {
+    let hash = hash.as_bytes();
+    // Compare from the most significant byte down
+    for i in (0..32).rev() {
+        if hash[i] != target[i] {
+            return hash[i] < target[i];
+        }
+    }
+    true
+}
+
+/// Read a file of concatenated raw headers (as written by the fetcher's `--headers-output`)
+pub fn read_headers<R: std::io::Read>(mut reader: R) -> std::io::Result<Vec<[u8; HEADER_SIZE]>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() % HEADER_SIZE != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Headers file length {} is not a multiple of {HEADER_SIZE}",
+                data.len()
+            ),
+        ));
+    }
+    Ok(data
+        .chunks_exact(HEADER_SIZE)
+        .map(|chunk| chunk.try_into().expect("chunk is HEADER_SIZE bytes"))
+        .collect())
+}
+
+/// Double-SHA256 of a raw header, i.e. its block hash (Feature: sha256d)
+#[cfg(feature = "sha256d")]
+pub fn block_hash(raw: &[u8; HEADER_SIZE]) -> BlockHash {
+    use sha2::{Digest, Sha256};
+    let first = Sha256::digest(raw);
+    let second = Sha256::digest(first);
+    BlockHash::from_byte_array(second.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Genesis block header
+    const GENESIS_HEADER: [u8; HEADER_SIZE] = [
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3b, 0xa3, 0xed, 0xfd, 0x7a, 0x7b, 0x12, 0xb2, 0x7a,
+        0xc7, 0x2c, 0x3e, 0x67, 0x76, 0x8f, 0x61, 0x7f, 0xc8, 0x1b, 0xc3, 0x88, 0x8a, 0x51, 0x32,
+        0x3a, 0x9f, 0xb8, 0xaa, 0x4b, 0x1e, 0x5e, 0x4a, 0x29, 0xab, 0x5f, 0x49, 0xff, 0xff, 0x00,
+        0x1d, 0x1d, 0xac, 0x2b, 0x7c,
+    ];
+
+    #[test]
+    fn test_parse_genesis_header() {
+        let header = BlockHeader::parse(&GENESIS_HEADER);
+        assert_eq!(header.version, 1);
+        assert_eq!(header.prev_blockhash, BlockHash::default());
+        assert_eq!(header.time, 1231006505);
+        assert_eq!(header.bits, 0x1d00ffff);
+        assert_eq!(header.nonce, 2083236893);
+    }
+
+    #[test]
+    fn test_target_from_bits() {
+        // 0x1d00ffff → 0x00000000ffff0000...0000 (big-endian)
+        let target = target_from_bits(0x1d00ffff);
+        assert_eq!(target[26], 0xff);
+        assert_eq!(target[27], 0xff);
+        assert!(target[..26].iter().all(|&b| b == 0));
+        assert!(target[28..].iter().all(|&b| b == 0));
+
+        // Negative encodings are rejected
+        assert_eq!(target_from_bits(0x1d800000), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_bits_from_target() {
+        for bits in [0x1d00ffff, 0x1d00d86a, 0x1b0404cb, 0x1c3fffc0, 0x0300ffff] {
+            assert_eq!(bits_from_target(&target_from_bits(bits)), bits);
+        }
+        assert_eq!(bits_from_target(&[0; 32]), 0);
+        assert!(within_pow_limit(&target_from_bits(0x1d00d86a)));
+        assert!(!within_pow_limit(&target_from_bits(0x207fffff)));
+    }
+
+    #[test]
+    fn test_retarget_bits() {
+        // On schedule keeps the target; twice as fast halves it
+        assert_eq!(retarget_bits(0x1d00ffff, 0, TARGET_TIMESPAN), 0x1d00ffff);
+        assert_eq!(
+            retarget_bits(0x1d00ffff, 0, TARGET_TIMESPAN / 2),
+            0x1c7fff80
+        );
+        assert_eq!(
+            retarget_bits(0x1b0404cb, 1_000, 1_000 + TARGET_TIMESPAN - 12_345),
+            0x1b03fa4b
+        );
+
+        // Timespans clamp to a factor of four, targets to the limit
+        assert_eq!(retarget_bits(0x1d00ffff, 0, 1), 0x1c3fffc0);
+        assert_eq!(retarget_bits(0x1d00ffff, 10, 0), 0x1c3fffc0);
+        assert_eq!(
+            retarget_bits(0x1d00d86a, 0, TARGET_TIMESPAN * 100),
+            0x1d00ffff
+        );
+        assert_eq!(retarget_bits(0x207fffff, 0, TARGET_TIMESPAN), 0x1d00ffff);
+    }
+
+    #[test]
+    fn test_genesis_meets_target() {
+        let genesis = crate::parse_block_hash(
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        )
+        .unwrap();
+        assert!(hash_meets_target(&genesis, &target_from_bits(0x1d00ffff)));
+        assert!(!hash_meets_target(&genesis, &target_from_bits(0x1b00ffff)));
+    }
+
+    #[test]
+    #[cfg(feature = "sha256d")]
+    fn test_genesis_block_hash() {
+        assert_eq!(
+            block_hash(&GENESIS_HEADER).to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sha256d")]
+    fn test_height_from_header() {
+        use crate::HeightLookup;
+
+        struct GenesisOnly;
+        impl HeightLookup for GenesisOnly {
+            fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+                if block_hash.to_string()
+                    == "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+                {
+                    0
+                } else {
+                    u32::MAX
+                }
+            }
+
+            fn len(&self) -> usize {
+                1
+            }
+        }
+
+        assert_eq!(
+            GenesisOnly.get_height_from_header_unchecked(&GENESIS_HEADER),
+            0
+        );
+    }
+}
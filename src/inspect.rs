@@ -0,0 +1,332 @@
+//! Asset inspection and comparison (Feature: generate)
+//!
+//! [`AssetInfo`] summarizes an asset set (a directory with the default file
+//! names, or a container file): sizes, checksums, element count, bits per
+//! element and the perfect hash configuration recorded in its header.
+//! [`diff_assets`] checks whether two asset sets encode the same mapping by
+//! looking up every block of a dataset in both.
+
+use crate::anomalies::{DuplicateCoinbase, DUPLICATE_COINBASES};
+use crate::dataset::Dataset;
+use crate::generate::{HeightOracle, HeightOracleLoaded, MemoryStats};
+use crate::phf::PhfConfig;
+use crate::BlockHash;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How an asset set is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetLayout {
+    /// Directory with separate PtrHash / heights / version flag files
+    Directory,
+    /// Single container file (see [`crate::container`])
+    Container { compressed: bool },
+}
+
+/// Size and checksum of one asset file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    /// File name
+    pub name: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// SHA-256 of the file contents, hex encoded
+    pub sha256: String,
+}
+
+impl FileInfo {
+    fn new(path: &Path, data: &[u8]) -> Self {
+        Self {
+            name: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into(),
+            ),
+            bytes: data.len() as u64,
+            sha256: hex::encode(Sha256::digest(data)),
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Summary of an asset set
+#[derive(Debug, Clone)]
+pub struct AssetInfo {
+    /// Inspected path
+    pub path: PathBuf,
+    /// Directory or container
+    pub layout: AssetLayout,
+    /// Asset files with sizes and checksums
+    pub files: Vec<FileInfo>,
+    /// Perfect hash configuration from the PtrHash asset header
+    pub phf_config: PhfConfig,
+    /// Whether version flags were found
+    pub has_version_flags: bool,
+    /// Input policy recorded in the heights asset header
+    pub input_policy: Option<crate::InputPolicy>,
+    /// Lowest and highest stored height, `None` if empty
+    pub height_range: Option<(u32, u32)>,
+    /// Height range the build was restricted to, `None` for a full build
+    pub build_range: Option<std::ops::Range<u32>>,
+    /// Commitment to the dataset the assets were built from, if recorded
+    pub dataset_commitment: Option<crate::DatasetCommitment>,
+    /// Duplicate coinbase blocks (see [`crate::anomalies`]) the assets map to their heights
+    pub duplicate_coinbases: Vec<DuplicateCoinbase>,
+    /// Element count, bits per element and load time
+    pub stats: MemoryStats,
+}
+
+impl AssetInfo {
+    /// Inspect an asset directory or container file
+    pub fn inspect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let (layout, files, oracle) = if path.is_dir() {
+            let files = [
+                crate::PTRHASH_FILE_NAME,
+                crate::HEIGHTS_FILE_NAME,
+                crate::VERSIONS_FILE_NAME,
+            ]
+            .iter()
+            .map(|name| path.join(name))
+            .filter(|file| file.exists())
+            .map(|file| Ok(FileInfo::new(&file, &read_file(&file)?)))
+            .collect::<Result<Vec<_>>>()?;
+            (
+                AssetLayout::Directory,
+                files,
+                HeightOracle::load_from_dir(path)?,
+            )
+        } else {
+            let data = read_file(path)?;
+            let layout = AssetLayout::Container {
+                compressed: crate::container::is_zstd(&data),
+            };
+            let file = FileInfo::new(path, &data);
+            (layout, vec![file], HeightOracle::load_from_file(path)?)
+        };
+
+        let heights = oracle.heights();
+        let height_range = heights
+            .iter()
+            .min()
+            .zip(heights.iter().max())
+            .map(|(&min, &max)| (min, max));
+        let duplicate_coinbases = DUPLICATE_COINBASES
+            .iter()
+            .filter(|duplicate| {
+                duplicate.blocks().iter().all(|&(height, hash)| {
+                    hash.parse::<BlockHash>()
+                        .is_ok_and(|block_hash| oracle.get_height_opt(&block_hash) == Some(height))
+                })
+            })
+            .copied()
+            .collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            layout,
+            files,
+            phf_config: *oracle.phf_config(),
+            has_version_flags: oracle.version_flags().is_some(),
+            input_policy: oracle.input_policy(),
+            height_range,
+            build_range: oracle.height_range(),
+            dataset_commitment: oracle.dataset_commitment(),
+            duplicate_coinbases,
+            stats: oracle.memory_stats(),
+        })
+    }
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Asset: {}", self.path.display())?;
+        match self.layout {
+            AssetLayout::Directory => writeln!(f, "  Layout: directory")?,
+            AssetLayout::Container { compressed } => writeln!(
+                f,
+                "  Layout: container ({})",
+                if compressed { "zstd" } else { "uncompressed" }
+            )?,
+        }
+        for file in &self.files {
+            writeln!(
+                f,
+                "  {}: {} bytes, sha256 {}",
+                file.name, file.bytes, file.sha256
+            )?;
+        }
+        let config = &self.phf_config;
+        writeln!(
+            f,
+            "  PHF: {:?} / {:?} / {:?}, lambda {}, alpha {}, remap {:?}{}",
+            config.backend,
+            config.bucket_fn,
+            config.hash_fn,
+            config
+                .lambda
+                .map_or_else(|| "default".into(), |v| v.to_string()),
+            config
+                .alpha
+                .map_or_else(|| "default".into(), |v| v.to_string()),
+            config.remap,
+            if *config == PhfConfig::default() {
+                " (no header)"
+            } else {
+                ""
+            }
+        )?;
+        match self.height_range {
+            Some((min, max)) => writeln!(f, "  Heights: {min}..={max}")?,
+            None => writeln!(f, "  Heights: none")?,
+        }
+        match &self.build_range {
+            Some(range) => writeln!(f, "  Build range: {}..{} (partial)", range.start, range.end)?,
+            None => writeln!(f, "  Build range: all heights")?,
+        }
+        writeln!(
+            f,
+            "  Version flags: {}",
+            if self.has_version_flags { "yes" } else { "no" }
+        )?;
+        match self.input_policy {
+            Some(policy) => writeln!(f, "  Input policy: {policy}")?,
+            None => writeln!(f, "  Input policy: not recorded")?,
+        }
+        match &self.dataset_commitment {
+            Some(commitment) => writeln!(f, "  Dataset commitment: {commitment}")?,
+            None => writeln!(f, "  Dataset commitment: not recorded")?,
+        }
+        if self.duplicate_coinbases.is_empty() {
+            writeln!(f, "  Duplicate coinbases: none covered")?;
+        } else {
+            let pairs: Vec<String> = self
+                .duplicate_coinbases
+                .iter()
+                .map(|duplicate| {
+                    format!(
+                        "{}/{}",
+                        duplicate.original_height, duplicate.duplicate_height
+                    )
+                })
+                .collect();
+            writeln!(f, "  Duplicate coinbases: {}", pairs.join(", "))?;
+        }
+        write!(f, "{}", self.stats)
+    }
+}
+
+/// Result of comparing two asset sets over a dataset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetDiff {
+    /// Number of entries in each asset set
+    pub lens: (usize, usize),
+    /// Dataset entries looked up in both
+    pub probed: usize,
+    /// Entries where the two asset sets return different heights
+    pub disagreements: usize,
+    /// Entries where each asset set differs from the dataset
+    pub wrong: (usize, usize),
+    /// First few disagreeing block hashes with both heights
+    pub examples: Vec<(BlockHash, u32, u32)>,
+}
+
+/// Maximum number of disagreements recorded in [`AssetDiff::examples`]
+const MAX_DIFF_EXAMPLES: usize = 10;
+
+impl AssetDiff {
+    /// Whether both asset sets map every dataset entry to the same height
+    pub fn same_mapping(&self) -> bool {
+        self.lens.0 == self.lens.1 && self.disagreements == 0
+    }
+}
+
+impl fmt::Display for AssetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Entries: {} vs {}", self.lens.0, self.lens.1)?;
+        writeln!(f, "Probed: {}", self.probed)?;
+        writeln!(f, "Disagreements: {}", self.disagreements)?;
+        writeln!(f, "Wrong vs dataset: {} vs {}", self.wrong.0, self.wrong.1)?;
+        for (block_hash, a, b) in &self.examples {
+            writeln!(f, "  {block_hash}: {a} vs {b}")?;
+        }
+        write!(
+            f,
+            "{}",
+            if self.same_mapping() {
+                "Same mapping"
+            } else {
+                "Different mappings"
+            }
+        )
+    }
+}
+
+/// Load an asset directory or container file
+pub fn load_assets<P: AsRef<Path>>(path: P) -> Result<HeightOracleLoaded> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        HeightOracle::load_from_dir(path)
+    } else {
+        HeightOracle::load_from_file(path)
+    }
+}
+
+/// Look up every dataset entry in `a` and `b` and compare the results
+pub fn diff_assets<P1: AsRef<Path>, P2: AsRef<Path>>(
+    a: P1,
+    b: P2,
+    dataset: &Dataset,
+) -> Result<AssetDiff> {
+    let a = load_assets(a)?;
+    let b = load_assets(b)?;
+
+    let mut diff = AssetDiff {
+        lens: (a.len(), b.len()),
+        ..AssetDiff::default()
+    };
+    for (block_hash, expected) in dataset.iter() {
+        let height_a = a.get_height_unchecked(block_hash);
+        let height_b = b.get_height_unchecked(block_hash);
+        diff.probed += 1;
+        diff.wrong.0 += usize::from(height_a != expected);
+        diff.wrong.1 += usize::from(height_b != expected);
+        if height_a != height_b {
+            diff.disagreements += 1;
+            if diff.examples.len() < MAX_DIFF_EXAMPLES {
+                diff.examples.push((*block_hash, height_a, height_b));
+            }
+        }
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_info_checksum() {
+        let info = FileInfo::new(Path::new("assets/asset.dat"), b"abc");
+        assert_eq!(info.name, "asset.dat");
+        assert_eq!(info.bytes, 3);
+        assert_eq!(
+            info.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_same_mapping_requires_equal_lens() {
+        let diff = AssetDiff {
+            lens: (2, 3),
+            ..AssetDiff::default()
+        };
+        assert!(!diff.same_mapping());
+        assert!(AssetDiff::default().same_mapping());
+    }
+}
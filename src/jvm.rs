@@ -0,0 +1,168 @@
+//! JNI exports over the global embedded oracle (Feature: jni)
+//!
+//! Backs `heightoracle.Oracle` in `bindings/jvm`, for bitcoinj- and
+//! Scala-based indexers. Hashes cross the boundary as `byte[]` in the order
+//! bitcoinj's `Sha256Hash.getBytes()` returns, which is display order; they
+//! are reversed into network byte order here, so callers pass hashes as they
+//! already hold them.
+//!
+//! Bad arguments throw `IllegalArgumentException` (or `NullPointerException`),
+//! and assets that fail to load throw `IllegalStateException`. Lookups are
+//! unchecked, as everywhere else.
+
+use crate::{BlockHash, DisplayOrderedHash, HeightLookup, HeightOracleEmbedded};
+use jni::objects::{JByteArray, JClass};
+use jni::sys::{jint, jintArray};
+use jni::JNIEnv;
+
+const HASH_SIZE: usize = 32;
+
+const ILLEGAL_ARGUMENT: &str = "java/lang/IllegalArgumentException";
+const ILLEGAL_STATE: &str = "java/lang/IllegalStateException";
+const NULL_POINTER: &str = "java/lang/NullPointerException";
+
+/// Convert hashes stored back to back in display order to network byte order
+///
+/// `None` unless the length is a multiple of 32.
+fn network_ordered_hashes(bytes: &[u8]) -> Option<Vec<BlockHash>> {
+    if !bytes.len().is_multiple_of(HASH_SIZE) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(HASH_SIZE)
+            .map(|chunk| {
+                let display: [u8; HASH_SIZE] = chunk.try_into().unwrap();
+                DisplayOrderedHash::from_byte_array(display).into()
+            })
+            .collect(),
+    )
+}
+
+/// Throw `class` unless a JNI call already left an exception pending
+fn throw(env: &mut JNIEnv, class: &str, message: &str) {
+    if !env.exception_check().unwrap_or(false) {
+        let _ = env.throw_new(class, message);
+    }
+}
+
+/// Copy a Java `byte[]` of hashes and reorder them, throwing on bad input
+fn read_hashes(env: &mut JNIEnv, hashes: &JByteArray) -> Option<Vec<BlockHash>> {
+    if hashes.is_null() {
+        throw(env, NULL_POINTER, "block hashes must not be null");
+        return None;
+    }
+    let bytes = match env.convert_byte_array(hashes) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            throw(
+                env,
+                ILLEGAL_STATE,
+                &format!("failed to read block hashes: {e}"),
+            );
+            return None;
+        }
+    };
+    let block_hashes = network_ordered_hashes(&bytes);
+    if block_hashes.is_none() {
+        throw(env, ILLEGAL_ARGUMENT, "block hashes must be 32 bytes each");
+    }
+    block_hashes
+}
+
+/// The global embedded oracle, throwing if its assets fail to load
+fn oracle(env: &mut JNIEnv) -> Option<&'static HeightOracleEmbedded> {
+    match crate::embedded::init() {
+        Ok(()) => Some(crate::embedded::embedded_oracle()),
+        Err(e) => {
+            throw(
+                env,
+                ILLEGAL_STATE,
+                &format!("failed to load the embedded oracle: {e}"),
+            );
+            None
+        }
+    }
+}
+
+/// `static native int guessHeight(byte[] hash)`: height of one 32-byte hash in display order (unchecked)
+///
+/// Returns 0 with an exception pending on bad input.
+#[no_mangle]
+pub extern "system" fn Java_heightoracle_Oracle_guessHeight(
+    mut env: JNIEnv,
+    _class: JClass,
+    hash: JByteArray,
+) -> jint {
+    let Some(block_hashes) = read_hashes(&mut env, &hash) else {
+        return 0;
+    };
+    let [block_hash] = block_hashes[..] else {
+        throw(&mut env, ILLEGAL_ARGUMENT, "block hash must be 32 bytes");
+        return 0;
+    };
+    let Some(oracle) = oracle(&mut env) else {
+        return 0;
+    };
+    // Pre-BIP34 heights are far below i32::MAX
+    oracle.get_height_unchecked(&block_hash) as jint
+}
+
+/// `static native int[] guessHeights(byte[] hashes)`: heights of hashes stored back to back in display order (unchecked)
+///
+/// Uses the batched lookup (see [`HeightLookup::get_heights_unchecked`]).
+/// Returns null with an exception pending on bad input.
+#[no_mangle]
+pub extern "system" fn Java_heightoracle_Oracle_guessHeights(
+    mut env: JNIEnv,
+    _class: JClass,
+    hashes: JByteArray,
+) -> jintArray {
+    let Some(block_hashes) = read_hashes(&mut env, &hashes) else {
+        return std::ptr::null_mut();
+    };
+    let Some(oracle) = oracle(&mut env) else {
+        return std::ptr::null_mut();
+    };
+    let mut heights = vec![0u32; block_hashes.len()];
+    oracle.get_heights_unchecked(&block_hashes, &mut heights);
+    let heights: Vec<jint> = heights.into_iter().map(|height| height as jint).collect();
+
+    let result = env.new_int_array(heights.len() as jint).and_then(|array| {
+        env.set_int_array_region(&array, 0, &heights)?;
+        Ok(array)
+    });
+    match result {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            throw(
+                &mut env,
+                ILLEGAL_STATE,
+                &format!("failed to return heights: {e}"),
+            );
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_ordered_hashes() {
+        let genesis = crate::parse_block_hash(
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        )
+        .unwrap();
+        let display = DisplayOrderedHash::from(genesis).to_byte_array();
+        assert_eq!(display[0], 0x00);
+        assert_eq!(display[31], 0x6f);
+
+        let mut bytes = display.to_vec();
+        bytes.extend_from_slice(&display);
+        assert_eq!(network_ordered_hashes(&bytes), Some(vec![genesis, genesis]));
+        assert_eq!(network_ordered_hashes(&[]), Some(vec![]));
+        assert_eq!(network_ordered_hashes(&display[..31]), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Lookup latency measurement (Feature: generate)
+//!
+//! Backs `height-oracle bench`. [`LatencyReport::measure`] times lookups one at
+//! a time for the latency distribution, then runs the same queries back to back
+//! for throughput. Reading the clock around each lookup costs a few tens of
+//! nanoseconds, so the percentiles overstate the lookup itself slightly.
+//!
+//! [`compare_phf_configs`] backs `height-oracle bench --compare-remap`: it
+//! builds the oracle once per [`PhfConfig`] and reports asset size next to
+//! latency, to pick the smallest encoding a deployment can afford.
+
+use crate::rng::splitmix64;
+use crate::{BlockHash, BuildOptions, HeightLookup, HeightOracle, MemoryStats, PhfConfig};
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Latency distribution and throughput of one pass over a query set
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatencyReport {
+    /// Number of lookups timed
+    pub lookups: usize,
+    /// Median lookup latency
+    pub p50: Duration,
+    /// 95th percentile lookup latency
+    pub p95: Duration,
+    /// 99th percentile lookup latency
+    pub p99: Duration,
+    /// Slowest lookup
+    pub max: Duration,
+    /// Lookups per second with no per-lookup timing
+    pub throughput: f64,
+}
+
+impl LatencyReport {
+    /// Look up every query once timed and once untimed
+    pub fn measure<O: HeightLookup + ?Sized>(oracle: &O, queries: &[BlockHash]) -> Self {
+        let mut latencies: Vec<Duration> = queries
+            .iter()
+            .map(|query| {
+                let start = Instant::now();
+                black_box(oracle.get_height_unchecked(black_box(query)));
+                start.elapsed()
+            })
+            .collect();
+        latencies.sort_unstable();
+
+        let start = Instant::now();
+        for query in queries {
+            black_box(oracle.get_height_unchecked(black_box(query)));
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        Self {
+            lookups: queries.len(),
+            p50: percentile(&latencies, 50),
+            p95: percentile(&latencies, 95),
+            p99: percentile(&latencies, 99),
+            max: latencies.last().copied().unwrap_or_default(),
+            throughput: if elapsed > 0.0 {
+                queries.len() as f64 / elapsed
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 {:?}, p95 {:?}, p99 {:?}, max {:?}, {:.2}M lookups/s",
+            self.p50,
+            self.p95,
+            self.p99,
+            self.max,
+            self.throughput / 1e6
+        )
+    }
+}
+
+/// Size, build time and lookups of one [`PhfConfig`], from [`compare_phf_configs`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhfComparison {
+    /// Configuration the oracle was built with
+    pub config: PhfConfig,
+    /// Time to build the oracle
+    pub build_time: Duration,
+    /// Asset sizes and in-memory bits per key
+    pub memory: MemoryStats,
+    /// Lookups over the queries
+    pub latency: LatencyReport,
+}
+
+impl fmt::Display for PhfComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} / {:?} / remap {:?}: PtrHash asset {} bytes ({:.3} bits/key in memory), built in {:?}, {}",
+            self.config.bucket_fn,
+            self.config.hash_fn,
+            self.config.remap,
+            self.memory.ptrhash_asset_bytes,
+            self.memory.ptrhash_bits_per_element,
+            self.build_time,
+            self.latency
+        )
+    }
+}
+
+/// Build an oracle over `block_hashes` and `heights` with each of `configs`,
+/// and time `queries` against it
+///
+/// Each oracle is dropped before the next is built, so the peak memory is that
+/// of a single build.
+pub fn compare_phf_configs(
+    block_hashes: &[BlockHash],
+    heights: &[u32],
+    configs: &[PhfConfig],
+    queries: &[BlockHash],
+) -> anyhow::Result<Vec<PhfComparison>> {
+    configs
+        .iter()
+        .map(|config| {
+            let options = BuildOptions::new().phf_config(*config);
+            let start = Instant::now();
+            let oracle = HeightOracle::from_entries_with_options(block_hashes, heights, &options)?;
+            let build_time = start.elapsed();
+            Ok(PhfComparison {
+                config: *config,
+                build_time,
+                memory: oracle.memory_stats(),
+                latency: LatencyReport::measure(&oracle, queries),
+            })
+        })
+        .collect()
+}
+
+/// `count` queries drawn from `hits`, with about `miss_ratio` of them random hashes
+///
+/// Hits are picked with replacement in random order, so repeated runs with the
+/// same seed query the same hashes in the same order.
+pub fn bench_queries(
+    hits: &[BlockHash],
+    count: usize,
+    miss_ratio: f64,
+    seed: u64,
+) -> Vec<BlockHash> {
+    let mut state = seed;
+    let mut next = || splitmix64(&mut state);
+    // Compare against a 53-bit threshold to avoid float rounding per query
+    let miss_threshold = (miss_ratio.clamp(0.0, 1.0) * (1u64 << 53) as f64) as u64;
+
+    (0..count)
+        .map(|_| {
+            if hits.is_empty() || next() >> 11 < miss_threshold {
+                let mut bytes = [0u8; 32];
+                for word in bytes.chunks_exact_mut(8) {
+                    word.copy_from_slice(&next().to_le_bytes());
+                }
+                BlockHash::from_byte_array(bytes)
+            } else {
+                hits[(next() % hits.len() as u64) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * pct).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct GenesisOnly;
+
+    impl HeightLookup for GenesisOnly {
+        fn get_height_unchecked(&self, _block_hash: &BlockHash) -> u32 {
+            0
+        }
+
+        fn len(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_nanos).collect();
+        assert_eq!(percentile(&sorted, 50), Duration::from_nanos(50));
+        assert_eq!(percentile(&sorted, 99), Duration::from_nanos(99));
+        assert_eq!(percentile(&sorted[..1], 95), Duration::from_nanos(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_bench_queries() {
+        let hits = [BlockHash::from_byte_array([7; 32])];
+        let queries = bench_queries(&hits, 1000, 0.25, 1);
+        assert_eq!(queries, bench_queries(&hits, 1000, 0.25, 1));
+        let misses = queries.iter().filter(|query| **query != hits[0]).count();
+        assert!((150..350).contains(&misses), "{misses} misses");
+
+        assert!(bench_queries(&hits, 100, 0.0, 1)
+            .iter()
+            .all(|q| *q == hits[0]));
+        assert!(bench_queries(&[], 100, 0.0, 1)
+            .iter()
+            .all(|q| *q != hits[0]));
+
+        let report = LatencyReport::measure(&GenesisOnly, &queries);
+        assert_eq!(report.lookups, 1000);
+        assert!(report.p50 <= report.p95 && report.p95 <= report.p99 && report.p99 <= report.max);
+    }
+
+    #[test]
+    fn test_compare_phf_configs() {
+        use crate::generate::test_support::test_hashes;
+        use crate::phf::RemapEncoding;
+
+        let block_hashes = test_hashes(50);
+        let heights: Vec<u32> = (0..50).collect();
+        let configs = RemapEncoding::ALL.map(|remap| PhfConfig::new().remap(remap));
+        let queries = bench_queries(&block_hashes, 200, 0.1, 3);
+
+        let comparisons = compare_phf_configs(&block_hashes, &heights, &configs, &queries).unwrap();
+        assert_eq!(comparisons.len(), 2);
+        for (comparison, config) in comparisons.iter().zip(&configs) {
+            assert_eq!(&comparison.config, config);
+            assert_eq!(comparison.memory.num_elements, 50);
+            assert_eq!(comparison.latency.lookups, 200);
+            let remap = format!("remap {:?}", config.remap);
+            assert!(comparison.to_string().contains(&remap));
+        }
+        assert!(compare_phf_configs(&block_hashes, &heights[..49], &configs, &queries).is_err());
+
+        // The plain remap table survives a save and load
+        let options = BuildOptions::new().phf_config(PhfConfig::new().remap(RemapEncoding::Vec));
+        let oracle =
+            HeightOracle::from_entries_with_options(&block_hashes, &heights, &options).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_path, heights_path) = (dir.path().join("p"), dir.path().join("h"));
+        oracle.save_to_paths(&ptrhash_path, &heights_path).unwrap();
+        let loaded = HeightOracle::load_from_paths(&ptrhash_path, &heights_path).unwrap();
+        assert_eq!(loaded.phf_config().remap, RemapEncoding::Vec);
+        for (block_hash, &height) in block_hashes.iter().zip(&heights) {
+            assert_eq!(loaded.get_height_unchecked(block_hash), height);
+        }
+    }
+}
@@ -4,29 +4,359 @@
 //! Maps `BlockHash` → `height` for all pre-BIP34 blocks (0 to 227,930) with ~3.35 bits/element storage efficiency.
 
 // Core types and constants
-pub type BlockHash = [u8; 32]; // Network byte order
+mod block_hash;
+pub use block_hash::{BlockHash, DisplayOrderedHash, NetworkOrderedHash}; // BlockHash: network byte order
+mod error;
+pub use error::HeightOracleError;
 pub const BIP34_ACTIVATION_HEIGHT: u32 = 227_931;
 
-// PtrHash type configuration
+/// Raw block hash bytes in network byte order (the pre-newtype representation)
+#[deprecated(note = "use the `BlockHash` newtype; convert with `BlockHash::from`")]
+pub type RawBlockHash = [u8; 32];
+
+// Default asset file names
+pub const PTRHASH_FILE_NAME: &str = "phash.ptrh.dat";
+pub const HEIGHTS_FILE_NAME: &str = "heights.u18packed.dat";
+/// Optional asset: one "version >= 2" bit per entry (see [`packing::VersionFlags`])
+pub const VERSIONS_FILE_NAME: &str = "versions.v2bits.dat";
+/// Optional asset: header timestamps, bit-packed (see [`timestamps`])
+pub const TIMESTAMPS_FILE_NAME: &str = "timestamps.bitpacked.dat";
+/// Optional asset: median time past, bit-packed (see [`mtp`])
+pub const MTP_FILE_NAME: &str = "mtp.bitpacked.dat";
+/// Optional asset: height range per UTC day (see [`chronology`])
+pub const CHRONOLOGY_FILE_NAME: &str = "chronology.days.dat";
+/// Optional asset: cumulative chainwork per height (see [`chainwork`])
+pub const CHAINWORK_FILE_NAME: &str = "chainwork.runs.dat";
+/// Optional asset: `nBits` per retarget period (see [`difficulty`])
+pub const DIFFICULTY_FILE_NAME: &str = "difficulty.bits.dat";
+/// Optional asset: XOR filter over the block hashes (see [`filter`])
+pub const FILTER_FILE_NAME: &str = "prebip34.xor8.dat";
+/// Optional asset: BIP158 basic filter header per entry (see [`filterheaders`])
+pub const FILTER_HEADERS_FILE_NAME: &str = "filterheaders.bip158.dat";
+/// Optional asset: raw 80-byte header per entry (see [`blockheaders`])
+pub const HEADERS_FILE_NAME: &str = "headers.raw80.dat";
+/// Single-file container holding both assets (see [`container`]); `.zst` when compressed
+pub const CONTAINER_FILE_NAME: &str = "oracle.hoc";
+
+// A `compile_error!` per asset an enabled embedded feature needs but the asset
+// directory lacks, naming the command that generates it (written by build.rs)
+include!(concat!(env!("OUT_DIR"), "/missing_assets.rs"));
+
+// Default PtrHash type configuration (keyed by the raw network-order bytes of a `BlockHash`);
+// see [`phf::PhfConfig`] for the alternatives
 pub type PtrHashType =
-    ptr_hash::DefaultPtrHash<ptr_hash::hash::FxHash, BlockHash, ptr_hash::bucket_fn::CubicEps>;
+    ptr_hash::DefaultPtrHash<ptr_hash::hash::FxHash, [u8; 32], ptr_hash::bucket_fn::CubicEps>;
+
+/// Common lookup interface implemented by every oracle type
+pub trait HeightLookup {
+    /// Look up the height for a block hash without checking domain membership
+    fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32;
+
+    /// Like [`Self::get_height_unchecked`], but `None` where no height is found
+    ///
+    /// The oracle types return `None` if the perfect hash yields an index past
+    /// the heights table. Still unchecked with respect to domain membership.
+    fn get_height_opt(&self, block_hash: &BlockHash) -> Option<u32> {
+        Some(self.get_height_unchecked(block_hash))
+    }
+
+    /// Look up the height for a block hash in reverse hex format (unchecked)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hex string is invalid. Use [`Self::get_height_from_hex`]
+    /// to handle errors instead.
+    fn get_height_from_hex_unchecked(&self, hex_str: &str) -> u32 {
+        let block_hash = parse_block_hash(hex_str)
+            .unwrap_or_else(|_| panic!("Invalid hex string in unchecked function: {hex_str}"));
+        self.get_height_unchecked(&block_hash)
+    }
+
+    /// Look up the height for a block hash in reverse hex format (unchecked)
+    ///
+    /// Bad hex is an error rather than a panic, so user input can be passed
+    /// straight in. Still unchecked with respect to domain membership.
+    fn get_height_from_hex(&self, hex_str: &str) -> Result<u32, HeightOracleError> {
+        self.get_height_opt(&error::parse_hex(hex_str)?)
+            .ok_or(HeightOracleError::OutOfRange)
+    }
+
+    /// Halving epoch, difficulty period and soft-fork rules of the block (unchecked)
+    ///
+    /// `None` exactly when [`Self::get_height_opt`] is; see [`consensus::era_info`].
+    fn era_info_for(&self, block_hash: &BlockHash) -> Option<consensus::EraInfo> {
+        self.get_height_opt(block_hash).map(consensus::era_info)
+    }
+
+    /// Look up the heights for `block_hashes` into `heights`, without checking
+    /// domain membership
+    ///
+    /// The oracle types hash the whole batch before reading any height, which
+    /// is faster than one lookup at a time. Panics if the lengths differ.
+    fn get_heights_unchecked(&self, block_hashes: &[BlockHash], heights: &mut [u32]) {
+        assert_eq!(
+            block_hashes.len(),
+            heights.len(),
+            "one output slot per block hash"
+        );
+        for (height, block_hash) in heights.iter_mut().zip(block_hashes) {
+            *height = self.get_height_unchecked(block_hash);
+        }
+    }
+
+    /// Look up the heights of concatenated 32-byte hashes (network byte order)
+    ///
+    /// Suits columnar engines (Arrow, Polars) holding a hash column as one
+    /// contiguous buffer: the buffer is read in place through
+    /// [`Self::get_heights_unchecked`], without copying the hashes. Unchecked
+    /// like the other lookups; fails only if the length is not a multiple of 32.
+    fn get_heights_columnar(&self, hashes: &[u8]) -> Result<Vec<u32>, HeightOracleError> {
+        let block_hashes = BlockHash::slice_from_bytes(hashes)
+            .ok_or(HeightOracleError::InvalidLength { len: hashes.len() })?;
+        let mut heights = vec![0; block_hashes.len()];
+        self.get_heights_unchecked(block_hashes, &mut heights);
+        Ok(heights)
+    }
+
+    /// Lazily look up every hash of `hashes`, yielding `(hash, height)` pairs
+    ///
+    /// Hashes are resolved in batches (see [`lookup_iter`]), so this suits
+    /// pipelines that should not collect all hashes up front.
+    fn lookup_iter<I>(&self, hashes: I) -> lookup_iter::LookupIter<'_, Self, I::IntoIter>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = BlockHash>,
+    {
+        lookup_iter::LookupIter::new(self, hashes.into_iter())
+    }
+
+    /// Number of blocks in the oracle
+    fn len(&self) -> usize;
+
+    /// Check if the oracle is empty
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Double-SHA256 a raw 80-byte header and look up its height (unchecked, Feature: sha256d)
+    ///
+    /// Every oracle type gets this from the trait, so bring [`HeightLookup`]
+    /// into scope to call it.
+    #[cfg(feature = "sha256d")]
+    fn get_height_from_header_unchecked(&self, header: &[u8; header::HEADER_SIZE]) -> u32 {
+        self.get_height_unchecked(&header::block_hash(header))
+    }
+}
 
 // Import always-available modules
+pub mod anomalies;
+pub mod blockheaders;
+pub mod chainwork;
+pub mod chronology;
+pub mod commitment;
+pub mod composite;
+pub mod consensus;
+pub mod container;
+pub mod difficulty;
+pub mod filter;
+pub mod filterheaders;
+pub mod format;
+pub mod hashdump;
+pub mod header;
+pub mod lookup_iter;
+pub mod mtp;
+pub mod network;
 pub mod packing;
+pub mod phf;
+pub mod provider;
+pub mod side_table;
+pub mod store;
+pub mod timestamps;
+
+// Lookup cache of the oracle types
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+mod cache;
+// Lookup counters of the oracle types
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+mod stats;
+// Seeded generator for sampling, benchmarks and retry jitter
+#[cfg(any(feature = "generate", feature = "fetch"))]
+mod rng;
+// Memory accounting of the oracle types
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+pub mod memory;
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+pub use memory::MemoryStats;
+#[cfg(all(
+    feature = "stats",
+    any(
+        feature = "generate",
+        feature = "embedded",
+        feature = "embedded-testnet3",
+        feature = "runtime-assets"
+    )
+))]
+pub use stats::QueryStats;
 
 // Feature-gated modules
+#[cfg(feature = "sha256d")]
+pub mod chain;
+
+#[cfg(feature = "generate")]
+pub mod blocks;
+
+#[cfg(feature = "generate")]
+pub mod codegen;
+
+#[cfg(feature = "generate")]
+pub mod dataset;
+
+#[cfg(feature = "generate")]
+pub mod export;
+
+#[cfg(feature = "generate")]
+pub mod export_kv;
+
 #[cfg(feature = "generate")]
 pub mod generate;
 
-#[cfg(feature = "embedded")]
+#[cfg(any(feature = "generate", feature = "fetch"))]
+pub mod save;
+
+#[cfg(feature = "generate")]
+pub mod validation;
+
+#[cfg(feature = "generate")]
+pub mod inspect;
+
+#[cfg(feature = "generate")]
+pub mod latency;
+
+#[cfg(feature = "signing")]
+pub mod signing;
+
+#[cfg(feature = "shm")]
+pub mod shm;
+
+#[cfg(any(
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
 pub mod embedded;
 
+#[cfg(feature = "fetch")]
+pub mod fetch;
+
+#[cfg(feature = "verify")]
+pub mod verify;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "jni")]
+pub mod jvm;
+
+#[cfg(feature = "sqlite-ext")]
+pub mod sqlite;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+pub use blockheaders::HeaderOracle;
+pub use commitment::DatasetCommitment;
+pub use composite::CompositeOracle;
+pub use filterheaders::FilterHeaderOracle;
+pub use format::InputPolicy;
+pub use lookup_iter::LookupIter;
+pub use mtp::MedianTimeOracle;
+pub use network::Network;
+pub use phf::PhfConfig;
+pub use provider::{BlockHeightProvider, FilteredProvider};
+pub use side_table::SideTable;
+pub use store::HeightStore;
+pub use timestamps::TimestampOracle;
+
 // Re-exports based on features
 #[cfg(feature = "generate")]
-pub use generate::{HeightOracle, HeightOracleLoaded, MemoryStats};
+pub use dataset::{CsvOptions, Dataset, KeySource, TxtKeySource};
+
+#[cfg(feature = "generate")]
+pub use export::ExportFormat;
+
+#[cfg(feature = "generate")]
+pub use export_kv::KvFormat;
+
+#[cfg(feature = "generate")]
+pub use generate::{
+    BuildCancelled, BuildOptions, BuildProgress, CancellationToken, HeightOracle,
+    HeightOracleLoaded, SaveOptions, StreamingBuild,
+};
+
+#[cfg(feature = "generate")]
+pub use validation::{OracleValidator, ValidationReport};
+
+#[cfg(any(
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+pub use embedded::HeightOracleEmbedded;
 
 #[cfg(feature = "embedded")]
-pub use embedded::{guess_height_prebip34block_unchecked, HeightOracleEmbedded};
+pub use embedded::guess_height_prebip34block_unchecked;
+
+#[cfg(all(feature = "embedded", feature = "sha256d"))]
+pub use embedded::guess_height_from_header_unchecked;
+
+#[cfg(feature = "embedded-timestamps")]
+pub use timestamps::guess_timestamp_prebip34block_unchecked;
+
+#[cfg(feature = "embedded-headers")]
+pub use blockheaders::guess_header_prebip34block_unchecked;
+
+#[cfg(feature = "embedded-chronology")]
+pub use chronology::height_range_for_date;
+
+#[cfg(feature = "embedded-chainwork")]
+pub use chainwork::{chainwork_at, chainwork_for};
+
+#[cfg(feature = "embedded-difficulty")]
+pub use difficulty::{nbits_for, target_for};
+
+#[cfg(feature = "embedded-filter")]
+pub use filter::probably_prebip34;
 
 /// Parse a Bitcoin block hash from hex string to network byte order
 ///
@@ -35,24 +365,115 @@ pub use embedded::{guess_height_prebip34block_unchecked, HeightOracleEmbedded};
 /// 2. Parses hex to bytes
 /// 3. Reverses bytes to get network byte order
 pub fn parse_block_hash(hex_str: &str) -> Result<BlockHash, String> {
+    parse_block_hash_with(hex_str, &HashParseOptions::default())
+}
+
+/// How [`parse_block_hash_with`] reads its input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HashParseOptions {
+    /// Ignore leading and trailing whitespace, newlines included
+    pub trim_whitespace: bool,
+    /// The hex is in network byte order, as dumped from headers, instead of
+    /// the reversed order shown by RPC and explorers
+    pub network_order: bool,
+}
+
+impl HashParseOptions {
+    /// The strict defaults of [`parse_block_hash`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ignore surrounding whitespace
+    pub fn trim_whitespace(mut self, trim_whitespace: bool) -> Self {
+        self.trim_whitespace = trim_whitespace;
+        self
+    }
+
+    /// Read the hex in network byte order
+    pub fn network_order(mut self, network_order: bool) -> Self {
+        self.network_order = network_order;
+        self
+    }
+}
+
+/// [`parse_block_hash`] with optional whitespace trimming and byte order
+///
+/// An optional `0x` prefix is accepted either way, after any trimmed whitespace.
+pub fn parse_block_hash_with(
+    hex_str: &str,
+    options: &HashParseOptions,
+) -> Result<BlockHash, String> {
+    let hex_str = if options.trim_whitespace {
+        hex_str.trim()
+    } else {
+        hex_str
+    };
     // Remove 0x prefix if present
     let hex_str = hex_str.strip_prefix("0x").unwrap_or(hex_str);
 
     // Validate 64 hex characters exactly
-    if hex_str.len() != 64 {
-        return Err("Block hash must be exactly 64 hex characters".to_string());
+    let hex: &[u8; 64] = hex_str
+        .as_bytes()
+        .try_into()
+        .map_err(|_| "Block hash must be exactly 64 hex characters")?;
+
+    // Lookup-table decode, reversed straight into network byte order
+    let bytes = block_hash::decode_reverse_hex(hex).ok_or("Invalid hex")?;
+    if options.network_order {
+        // Unreversed hex decodes to display order
+        return Ok(BlockHash::from_display_bytes(bytes));
+    }
+    Ok(BlockHash::from_byte_array(bytes))
+}
+
+/// Parse 64 reverse-hex characters into a block hash in const context
+///
+/// The const counterpart of [`parse_block_hash`], for known hashes defined as
+/// constants. There is no `0x` prefix to strip: the length is in the type.
+///
+/// # Panics
+///
+/// Panics if any character is not hex. Evaluated in a `const`, that is a
+/// compile error.
+pub const fn parse_block_hash_const(hex: &[u8; 64]) -> BlockHash {
+    match block_hash::decode_reverse_hex(hex) {
+        Some(bytes) => BlockHash::from_byte_array(bytes),
+        None => panic!("Block hash contains a non-hex character"),
     }
+}
 
-    // Parse hex to bytes
-    let mut bytes = [0u8; 32];
-    for (i, chunk) in hex_str.as_bytes().chunks(2).enumerate() {
-        let hex_byte = std::str::from_utf8(chunk).map_err(|_| "Invalid UTF-8")?;
-        bytes[i] = u8::from_str_radix(hex_byte, 16).map_err(|_| "Invalid hex")?;
+/// [`parse_block_hash_const`] over a string, for [`block_hash!`]
+#[doc(hidden)]
+pub const fn parse_block_hash_str_const(hex: &str) -> BlockHash {
+    match hex.as_bytes().first_chunk::<64>() {
+        Some(chunk) if hex.len() == 64 => parse_block_hash_const(chunk),
+        _ => panic!("Block hash must be exactly 64 hex characters"),
     }
+}
 
-    // CRITICAL: Bitcoin uses reverse hex, so reverse to get network byte order
-    bytes.reverse();
-    Ok(bytes)
+/// A [`BlockHash`] constant from a reverse-hex string literal, checked at compile time
+///
+/// `block_hash!("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")`
+/// is evaluated in a `const`, so a typo in the literal fails the build instead
+/// of panicking at run time.
+#[macro_export]
+macro_rules! block_hash {
+    ($hex:literal) => {{
+        const BLOCK_HASH: $crate::BlockHash = $crate::parse_block_hash_str_const($hex);
+        BLOCK_HASH
+    }};
+}
+
+/// Parse many block hashes at once (see [`parse_block_hash`])
+///
+/// Fails on the first invalid entry, naming its index.
+pub fn parse_block_hashes_bulk(hex_strs: &[&str]) -> Result<Vec<BlockHash>, String> {
+    hex_strs
+        .iter()
+        .enumerate()
+        .map(|(i, hex_str)| parse_block_hash(hex_str).map_err(|e| format!("Block hash {i}: {e}")))
+        .collect()
 }
 
 #[cfg(test)]
@@ -66,7 +487,7 @@ mod tests {
         let result = parse_block_hash(hex).unwrap();
 
         // Should be 32 bytes
-        assert_eq!(result.len(), 32);
+        assert_eq!(result.as_bytes().len(), 32);
 
         // Test with 0x prefix
         let hex_with_prefix = "0x000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
@@ -83,14 +504,64 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn test_parse_block_hash_with() {
+        let hex = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let genesis = parse_block_hash(hex).unwrap();
+
+        let padded = format!("  0x{hex}\r\n");
+        assert!(parse_block_hash(&padded).is_err());
+        let trimming = HashParseOptions::new().trim_whitespace(true);
+        assert_eq!(parse_block_hash_with(&padded, &trimming), Ok(genesis));
+        assert!(parse_block_hash_with(" \n", &trimming).is_err());
+
+        let network_hex: String = genesis
+            .as_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let network = HashParseOptions::new().network_order(true);
+        assert_eq!(parse_block_hash_with(&network_hex, &network), Ok(genesis));
+        assert_ne!(parse_block_hash_with(hex, &network), Ok(genesis));
+    }
+
+    #[test]
+    fn test_parse_block_hash_const() {
+        const GENESIS: BlockHash = parse_block_hash_const(
+            b"000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        );
+        let hex = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        assert_eq!(GENESIS, parse_block_hash(hex).unwrap());
+        assert_eq!(
+            block_hash!("000000000019D6689C085AE165831E934FF763AE46A2A6C172B3F1B60A8CE26F"),
+            GENESIS
+        );
+        assert!(std::panic::catch_unwind(|| parse_block_hash_str_const("00")).is_err());
+        assert!(std::panic::catch_unwind(|| parse_block_hash_const(&[b'g'; 64])).is_err());
+    }
+
     #[test]
     fn test_block_hash_reverse() {
         // Simple test to verify reverse behavior
         let hex = "0000000000000000000000000000000000000000000000000000000000000001";
         let result = parse_block_hash(hex).unwrap();
 
-        // After parsing and reversing, the last byte should be 1
-        assert_eq!(result[31], 1);
-        assert_eq!(result[0], 0);
+        // After parsing and reversing, the trailing hex byte becomes the first byte
+        assert_eq!(result.as_bytes()[0], 1);
+        assert_eq!(result.as_bytes()[31], 0);
+    }
+
+    #[test]
+    fn test_parse_block_hashes_bulk() {
+        let genesis = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let upper = genesis.to_uppercase();
+        let hashes = parse_block_hashes_bulk(&[genesis, &upper]).unwrap();
+        assert_eq!(hashes[0], hashes[1]);
+        assert_eq!(hashes[0].to_string(), genesis);
+
+        let bad = genesis.replace('f', "g");
+        let err = parse_block_hashes_bulk(&[genesis, &bad]).unwrap_err();
+        assert_eq!(err, "Block hash 1: Invalid hex");
+        assert!(parse_block_hashes_bulk(&[]).unwrap().is_empty());
     }
 }
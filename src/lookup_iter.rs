@@ -0,0 +1,173 @@
+//! Lazy lookups over a stream of block hashes
+//!
+//! [`LookupIter`] pulls hashes from any iterator in chunks of [`LOOKUP_CHUNK`]
+//! and resolves each chunk with [`HeightLookup::get_heights_unchecked`]. The
+//! oracle types compute every perfect-hash index of a chunk before reading any
+//! height, so the chunk's memory accesses are independent and overlap instead
+//! of running one cache miss at a time. Only one chunk is held at a time, so
+//! block file parsers can feed hashes through without collecting them first.
+
+use crate::{BlockHash, HeightLookup};
+
+/// Hashes resolved per batch
+pub const LOOKUP_CHUNK: usize = 64;
+
+/// Iterator of `(hash, height)` pairs returned by [`HeightLookup::lookup_iter`]
+///
+/// Unchecked like [`HeightLookup::get_height_unchecked`]: hashes outside the
+/// oracle's domain yield the height of some other block.
+pub struct LookupIter<'a, O: ?Sized, I> {
+    oracle: &'a O,
+    hashes: I,
+    chunk: Vec<BlockHash>,
+    heights: Vec<u32>,
+    next: usize,
+}
+
+impl<'a, O: HeightLookup + ?Sized, I: Iterator<Item = BlockHash>> LookupIter<'a, O, I> {
+    /// Look up `hashes` in `oracle` lazily, a chunk at a time
+    ///
+    /// [`HeightLookup::lookup_iter`] is the usual entry point; this also works
+    /// for `dyn HeightLookup`.
+    pub fn new(oracle: &'a O, hashes: I) -> Self {
+        Self {
+            oracle,
+            hashes,
+            chunk: Vec::with_capacity(LOOKUP_CHUNK),
+            heights: Vec::with_capacity(LOOKUP_CHUNK),
+            next: 0,
+        }
+    }
+}
+
+impl<O: HeightLookup + ?Sized, I: Iterator<Item = BlockHash>> Iterator for LookupIter<'_, O, I> {
+    type Item = (BlockHash, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.chunk.len() {
+            self.chunk.clear();
+            self.chunk.extend(self.hashes.by_ref().take(LOOKUP_CHUNK));
+            if self.chunk.is_empty() {
+                return None;
+            }
+            self.heights.resize(self.chunk.len(), 0);
+            self.oracle
+                .get_heights_unchecked(&self.chunk, &mut self.heights);
+            self.next = 0;
+        }
+        let i = self.next;
+        self.next += 1;
+        Some((self.chunk[i], self.heights[i]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = self.chunk.len() - self.next;
+        let (lower, upper) = self.hashes.size_hint();
+        (
+            lower.saturating_add(buffered),
+            upper.and_then(|upper| upper.checked_add(buffered)),
+        )
+    }
+}
+
+/// Batched lookup over a perfect hash and its heights table, for the
+/// [`HeightLookup::get_heights_unchecked`] implementations of the oracle types
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+pub(crate) fn get_heights_batched<S: crate::store::HeightStore + ?Sized>(
+    phf: &crate::phf::Phf,
+    store: &S,
+    block_hashes: &[BlockHash],
+    heights: &mut [u32],
+) {
+    assert_eq!(
+        block_hashes.len(),
+        heights.len(),
+        "one output slot per block hash"
+    );
+    let mut indices = [0usize; LOOKUP_CHUNK];
+    for (hashes, out) in block_hashes
+        .chunks(LOOKUP_CHUNK)
+        .zip(heights.chunks_mut(LOOKUP_CHUNK))
+    {
+        let indices = &mut indices[..hashes.len()];
+        for (index, block_hash) in indices.iter_mut().zip(hashes) {
+            *index = phf.index(block_hash.as_bytes());
+        }
+        for (height, &index) in out.iter_mut().zip(indices.iter()) {
+            *height = store.at(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Height is the first byte of the hash; counts batched calls
+    struct FirstByte {
+        batches: std::cell::Cell<usize>,
+    }
+
+    impl HeightLookup for FirstByte {
+        fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+            block_hash.as_bytes()[0] as u32
+        }
+
+        fn get_heights_unchecked(&self, block_hashes: &[BlockHash], heights: &mut [u32]) {
+            self.batches.set(self.batches.get() + 1);
+            for (height, block_hash) in heights.iter_mut().zip(block_hashes) {
+                *height = self.get_height_unchecked(block_hash);
+            }
+        }
+
+        fn len(&self) -> usize {
+            256
+        }
+    }
+
+    #[test]
+    fn test_lookup_iter_chunks() {
+        let oracle = FirstByte {
+            batches: std::cell::Cell::new(0),
+        };
+        let hashes = (0..150u8).map(|i| BlockHash::from([i; 32]));
+
+        let mut iter = oracle.lookup_iter(hashes);
+        assert_eq!(iter.size_hint(), (150, Some(150)));
+        assert_eq!(iter.next(), Some((BlockHash::from([0; 32]), 0)));
+        assert_eq!(iter.size_hint(), (149, Some(149)));
+        assert_eq!(oracle.batches.get(), 1);
+
+        let rest: Vec<(BlockHash, u32)> = iter.collect();
+        assert_eq!(rest.len(), 149);
+        assert!(rest
+            .iter()
+            .all(|(block_hash, height)| block_hash.as_bytes()[0] as u32 == *height));
+        assert_eq!(oracle.batches.get(), 150usize.div_ceil(LOOKUP_CHUNK));
+
+        let dyn_oracle: &dyn HeightLookup = &oracle;
+        assert_eq!(LookupIter::new(dyn_oracle, std::iter::empty()).next(), None);
+    }
+
+    #[test]
+    fn test_get_heights_columnar() {
+        let oracle = FirstByte {
+            batches: std::cell::Cell::new(0),
+        };
+        let column: Vec<u8> = (0..100u8).flat_map(|i| [i; 32]).collect();
+        let heights = oracle.get_heights_columnar(&column).unwrap();
+        assert_eq!(heights, (0..100).collect::<Vec<u32>>());
+        assert_eq!(oracle.batches.get(), 1);
+
+        assert_eq!(
+            oracle.get_heights_columnar(&column[..33]),
+            Err(crate::HeightOracleError::InvalidLength { len: 33 })
+        );
+        assert_eq!(oracle.get_heights_columnar(&[]), Ok(Vec::new()));
+    }
+}
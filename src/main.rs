@@ -1,30 +1,192 @@
 #[cfg(feature = "generate")]
 use anyhow::{Context, Result};
 #[cfg(feature = "generate")]
-use height_oracle::HeightOracle;
+use height_oracle::chronology::Chronology;
+#[cfg(feature = "generate")]
+use height_oracle::difficulty::DifficultyTable;
+#[cfg(feature = "generate")]
+use height_oracle::filter::XorFilter;
+#[cfg(feature = "generate")]
+use height_oracle::latency::{self, LatencyReport};
+#[cfg(feature = "generate")]
+use height_oracle::phf::{BucketFunction, HashFunction, PhfBackend, RemapEncoding};
+#[cfg(feature = "generate")]
+use height_oracle::{
+    BuildOptions, BuildProgress, Dataset, ExportFormat, HeightLookup, HeightOracle, InputPolicy,
+    KvFormat, PhfConfig, SaveOptions,
+};
 #[cfg(feature = "generate")]
 use std::path::Path;
 
 #[cfg(feature = "generate")]
 fn main() -> Result<()> {
+    // Subcommands on existing assets; anything else builds new assets
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("inspect") => return inspect(args.skip(1)),
+        Some("diff") => return diff(args.skip(1)),
+        Some("export") => return export(args.skip(1)),
+        Some("export-kv") => return export_kv(args.skip(1)),
+        Some("bench") => return bench(args.skip(1)),
+        Some("verify-dataset") => return verify_dataset(args.skip(1)),
+        Some("codegen") => return codegen(args.skip(1)),
+        _ => {}
+    }
+
     println!("=== Height Oracle Asset Builder ===\n");
 
-    // Check for input file in order of preference
-    let input_file = if Path::new("assets/test_sample.txt").exists() {
-        "assets/test_sample.txt"
-    } else if Path::new("assets/prebip34.txt").exists() {
-        "assets/prebip34.txt"
+    // Optional: verify chain linkage against raw headers before building,
+    // read blocks straight from a Bitcoin Core blocks directory,
+    // save block timestamps, median time past, the date → height table and the
+    // per-period difficulty table from raw headers,
+    // save BIP158 filter headers fetched from a node,
+    // save the raw headers themselves,
+    // save an XOR filter over the block hashes,
+    // fsync the saved assets,
+    // which dataset entries and heights to keep (recorded in the heights asset),
+    // and perfect hash parameters (recorded in the PtrHash asset)
+    let mut blocks_dir = None;
+    let mut keep_v2_hashes = false;
+    let mut timestamps_from = None;
+    let mut filter_headers_from = None;
+    let mut headers_from = None;
+    let mut build_filter = false;
+    let mut save_options = SaveOptions::new();
+    let mut input_policy = InputPolicy::default();
+    let mut height_range = None;
+    let mut phf_config = PhfConfig::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verify-chain" => {
+                let headers_path = args
+                    .next()
+                    .context("--verify-chain requires a headers file path")?;
+                let input_file = find_input_file()?;
+                println!("🔗 Verifying chain linkage against {}...", headers_path);
+                HeightOracle::verify_chain_linkage(input_file, &headers_path)?;
+                println!("✅ Chain linkage and proof-of-work verified");
+            }
+            "--from-blocks-dir" => {
+                blocks_dir = Some(
+                    args.next()
+                        .context("--from-blocks-dir requires a directory")?,
+                );
+            }
+            "--keep-v2-hashes" => keep_v2_hashes = true,
+            "--filter" => build_filter = true,
+            "--fsync" => save_options = save_options.fsync(true),
+            "--input-policy" => {
+                let value = args.next().context("--input-policy requires a value")?;
+                input_policy = value.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            }
+            "--height-range" => {
+                let value = args.next().context("--height-range requires START..END")?;
+                let (start, end) = value
+                    .split_once("..")
+                    .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+                    .with_context(|| format!("--height-range expects START..END, got {}", value))?;
+                height_range = Some(start..end);
+            }
+            "--timestamps-from" => {
+                timestamps_from = Some(
+                    args.next()
+                        .context("--timestamps-from requires a headers file path")?,
+                );
+            }
+            "--filter-headers-from" => {
+                filter_headers_from = Some(
+                    args.next()
+                        .context("--filter-headers-from requires a filter headers file path")?,
+                );
+            }
+            "--headers-from" => {
+                headers_from = Some(
+                    args.next()
+                        .context("--headers-from requires a headers file path")?,
+                );
+            }
+            "--phf-backend" => {
+                phf_config.backend = match args.next().as_deref() {
+                    Some("ptrhash") => PhfBackend::PtrHash,
+                    #[cfg(feature = "fmph")]
+                    Some("fmph") => PhfBackend::Fmph,
+                    _ => anyhow::bail!("--phf-backend expects ptrhash or fmph (feature fmph)"),
+                };
+            }
+            "--bucket-fn" => {
+                phf_config.bucket_fn = match args.next().as_deref() {
+                    Some("cubic-eps") => BucketFunction::CubicEps,
+                    Some("linear") => BucketFunction::Linear,
+                    _ => anyhow::bail!("--bucket-fn expects cubic-eps or linear"),
+                };
+            }
+            "--hash-fn" => {
+                phf_config.hash_fn = match args.next().as_deref() {
+                    Some("fxhash") => HashFunction::FxHash,
+                    Some("xx64") => HashFunction::Xx64,
+                    _ => anyhow::bail!("--hash-fn expects fxhash or xx64"),
+                };
+            }
+            "--lambda" => {
+                let lambda = args.next().context("--lambda requires a value")?;
+                phf_config.lambda = Some(lambda.parse().context("Invalid --lambda")?);
+            }
+            "--alpha" => {
+                let alpha = args.next().context("--alpha requires a value")?;
+                phf_config.alpha = Some(alpha.parse().context("Invalid --alpha")?);
+            }
+            "--remap" => {
+                phf_config.remap = match args.next().as_deref() {
+                    Some("cacheline-ef") => RemapEncoding::CachelineEf,
+                    Some("vec") => RemapEncoding::Vec,
+                    _ => anyhow::bail!("--remap expects cacheline-ef or vec"),
+                };
+            }
+            _ => anyhow::bail!("Unknown build option {}", arg),
+        }
+    }
+    phf_config.check_params()?;
+
+    let mut options = BuildOptions::new()
+        .phf_config(phf_config)
+        .input_policy(input_policy)
+        .progress_interval(50_000)
+        .progress(|progress| match progress {
+            BuildProgress::Parsing { lines } => println!("  Parsed {} lines...", lines),
+            BuildProgress::BuildingPhf { keys } => {
+                println!("  Building perfect hash function over {} keys...", keys)
+            }
+            BuildProgress::FillingHeights { done, total } => {
+                println!("  Placed {}/{} heights...", done, total)
+            }
+            BuildProgress::Done { .. } => {}
+        });
+    if let Some(range) = height_range {
+        options = options.height_range(range);
+    }
+    let mut blocks_dataset = None;
+    let oracle = if let Some(blocks_dir) = blocks_dir {
+        println!("📁 Reading block files from {}...", blocks_dir);
+        let dump = height_oracle::blocks::read_blocks_dir(&blocks_dir, keep_v2_hashes)?;
+        let dataset = blocks_dataset.insert(Dataset::from_hash_dump(&dump));
+        println!("📁 Building oracle from {} blocks...", dataset.len());
+        HeightOracle::from_dataset_with_options(dataset, &options)
+            .context("Failed to build oracle from block files")?
     } else {
-        return Err(anyhow::anyhow!(
-            "No input file found. Please provide either assets/test_sample.txt or assets/prebip34.txt"
-        ));
+        let input_file = find_input_file()?;
+        println!("📁 Building oracle from {}...", input_file);
+        if input_file.ends_with(".bin") {
+            HeightOracle::from_bin_with_options(input_file, &options)
+        } else {
+            HeightOracle::from_txt_with_options(input_file, &options)
+        }
+        .with_context(|| format!("Failed to build oracle from {}", input_file))?
     };
 
-    println!("📁 Building oracle from {}...", input_file);
-    let oracle = HeightOracle::from_txt(input_file)
-        .with_context(|| format!("Failed to build oracle from {}", input_file))?;
-
     println!("✅ Oracle built with {} entries", oracle.len());
+    if let Some(commitment) = oracle.dataset_commitment() {
+        println!("🔏 Dataset commitment: {}", commitment);
+    }
     println!("📊 Memory stats:");
     println!("{}", oracle.memory_stats());
 
@@ -33,9 +195,161 @@ fn main() -> Result<()> {
 
     println!("\n💾 Saving oracle to assets/phash.ptrh.dat + assets/heights.u18packed.dat...");
     oracle
-        .save_to_paths("assets/phash.ptrh.dat", "assets/heights.u18packed.dat")
+        .save_to_paths_with_options(
+            "assets/phash.ptrh.dat",
+            "assets/heights.u18packed.dat",
+            &save_options,
+        )
         .context("Failed to save oracle files")?;
 
+    if let Some(flags) = oracle.version_flags() {
+        if flags.count_v2() > 0 {
+            println!(
+                "💾 Saving {} version-2 flags to assets/{}...",
+                flags.count_v2(),
+                height_oracle::VERSIONS_FILE_NAME
+            );
+            oracle
+                .save_version_flags(
+                    Path::new("assets").join(height_oracle::VERSIONS_FILE_NAME),
+                    &save_options,
+                )
+                .context("Failed to save version flags")?;
+        }
+    }
+
+    // Side assets need the block hashes, which the oracle does not keep
+    let dataset = match blocks_dataset {
+        Some(dataset) => Some(dataset),
+        None if timestamps_from.is_some()
+            || filter_headers_from.is_some()
+            || headers_from.is_some()
+            || build_filter =>
+        {
+            Some(Dataset::from_path(find_input_file()?)?)
+        }
+        None => None,
+    };
+    let dataset = match (dataset, oracle.height_range()) {
+        (Some(dataset), Some(range)) => Some(dataset.in_height_range(&range)),
+        (dataset, _) => dataset,
+    };
+
+    if let (Some(headers_path), Some(dataset)) = (timestamps_from, &dataset) {
+        let headers_file = std::fs::File::open(&headers_path)
+            .with_context(|| format!("Failed to open headers file: {}", headers_path))?;
+        let headers = height_oracle::header::read_headers(std::io::BufReader::new(headers_file))
+            .context("Failed to read headers")?;
+        println!(
+            "💾 Saving block timestamps to assets/{}...",
+            height_oracle::TIMESTAMPS_FILE_NAME
+        );
+        oracle
+            .save_timestamps(
+                dataset,
+                &headers,
+                Path::new("assets").join(height_oracle::TIMESTAMPS_FILE_NAME),
+                &save_options,
+            )
+            .context("Failed to save timestamps")?;
+        println!(
+            "💾 Saving median time past to assets/{}...",
+            height_oracle::MTP_FILE_NAME
+        );
+        oracle
+            .save_median_time_past(
+                dataset,
+                &headers,
+                Path::new("assets").join(height_oracle::MTP_FILE_NAME),
+                &save_options,
+            )
+            .context("Failed to save median time past")?;
+
+        let times: Vec<u32> = headers
+            .iter()
+            .map(|raw| height_oracle::header::BlockHeader::parse(raw).time)
+            .collect();
+        let chronology = Chronology::from_timestamps(&times);
+        println!(
+            "💾 Saving {}-day chronology to assets/{}...",
+            chronology.len(),
+            height_oracle::CHRONOLOGY_FILE_NAME
+        );
+        save_asset(
+            height_oracle::CHRONOLOGY_FILE_NAME,
+            &save_options,
+            |writer| chronology.serialize(writer),
+        )?;
+
+        let difficulty = DifficultyTable::from_headers(&headers);
+        println!(
+            "💾 Saving {}-period difficulty table to assets/{}...",
+            difficulty.num_periods(),
+            height_oracle::DIFFICULTY_FILE_NAME
+        );
+        save_asset(
+            height_oracle::DIFFICULTY_FILE_NAME,
+            &save_options,
+            |writer| difficulty.serialize(writer),
+        )?;
+    }
+
+    if let (Some(filter_headers_path), Some(dataset)) = (filter_headers_from, &dataset) {
+        let filter_headers_file = std::fs::File::open(&filter_headers_path).with_context(|| {
+            format!(
+                "Failed to open filter headers file: {}",
+                filter_headers_path
+            )
+        })?;
+        let filter_headers = height_oracle::filterheaders::read_filter_headers(
+            std::io::BufReader::new(filter_headers_file),
+        )
+        .context("Failed to read filter headers")?;
+        println!(
+            "💾 Saving filter headers to assets/{}...",
+            height_oracle::FILTER_HEADERS_FILE_NAME
+        );
+        oracle
+            .save_filter_headers(
+                dataset,
+                &filter_headers,
+                Path::new("assets").join(height_oracle::FILTER_HEADERS_FILE_NAME),
+                &save_options,
+            )
+            .context("Failed to save filter headers")?;
+    }
+
+    if let (Some(headers_path), Some(dataset)) = (headers_from, &dataset) {
+        let headers_file = std::fs::File::open(&headers_path)
+            .with_context(|| format!("Failed to open headers file: {}", headers_path))?;
+        let headers = height_oracle::header::read_headers(std::io::BufReader::new(headers_file))
+            .context("Failed to read headers")?;
+        println!(
+            "💾 Saving block headers to assets/{}...",
+            height_oracle::HEADERS_FILE_NAME
+        );
+        oracle
+            .save_headers(
+                dataset,
+                &headers,
+                Path::new("assets").join(height_oracle::HEADERS_FILE_NAME),
+                &save_options,
+            )
+            .context("Failed to save block headers")?;
+    }
+
+    if let (true, Some(dataset)) = (build_filter, &dataset) {
+        let filter = XorFilter::build(&dataset.block_hashes);
+        println!(
+            "💾 Saving {} KB XOR filter to assets/{}...",
+            filter.size_bytes() / 1024,
+            height_oracle::FILTER_FILE_NAME
+        );
+        save_asset(height_oracle::FILTER_FILE_NAME, &save_options, |writer| {
+            filter.serialize(writer)
+        })?;
+    }
+
     println!("✅ Assets saved successfully!");
     println!("\nYou can now run validation with:");
     println!(
@@ -45,6 +359,349 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Write a side asset to `assets/<name>`
+#[cfg(feature = "generate")]
+fn save_asset(
+    name: &str,
+    options: &SaveOptions,
+    write: impl FnOnce(&mut std::io::BufWriter<std::fs::File>) -> std::io::Result<()>,
+) -> Result<()> {
+    height_oracle::generate::write_atomic(Path::new("assets").join(name), options, write)
+}
+
+/// Dataset to build from, in order of preference
+#[cfg(feature = "generate")]
+fn find_input_file() -> Result<&'static str> {
+    [
+        "assets/test_sample.txt",
+        "assets/prebip34.bin",
+        "assets/prebip34.txt",
+        "assets/prebip34.txt.zst",
+        "assets/prebip34.txt.gz",
+    ]
+    .into_iter()
+    .find(|path| Path::new(path).exists())
+    .context(
+        "No input file found. Please provide assets/test_sample.txt, assets/prebip34.bin or assets/prebip34.txt (optionally .zst or .gz), or use --from-blocks-dir",
+    )
+}
+
+/// `inspect <asset>`: print sizes, checksums and build parameters of an asset set
+#[cfg(feature = "generate")]
+fn inspect(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let path = args
+        .next()
+        .context("Usage: height-oracle inspect <asset dir or container>")?;
+    let info = height_oracle::inspect::AssetInfo::inspect(&path)
+        .with_context(|| format!("Failed to inspect {}", path))?;
+    println!("{}", info);
+    Ok(())
+}
+
+/// `diff <a> <b> [--dataset <path>]`: check whether two asset sets encode the same mapping
+#[cfg(feature = "generate")]
+fn diff(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "Usage: height-oracle diff <a> <b> [--dataset <path>]";
+    let a = args.next().context(USAGE)?;
+    let b = args.next().context(USAGE)?;
+    let dataset_path = match args.next().as_deref() {
+        Some("--dataset") => Some(args.next().context("--dataset requires a path")?),
+        Some(_) => anyhow::bail!(USAGE),
+        None => None,
+    };
+
+    let dataset = load_reverse_dataset(dataset_path)?;
+    let diff = height_oracle::inspect::diff_assets(&a, &b, &dataset)?;
+    println!("{}", diff);
+
+    if !diff.same_mapping() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `export <asset> [--format txt|csv|jsonl] [--dataset <path>] [--output <path>]`
+///
+/// Writes the height the asset returns for every dataset hash, to stdout by default.
+#[cfg(feature = "generate")]
+fn export(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let asset = args.next().context(
+        "Usage: height-oracle export <asset> [--format txt|csv|jsonl] [--dataset <path>] [--output <path>]",
+    )?;
+    let mut format = ExportFormat::Txt;
+    let mut dataset_path = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().context("--format requires a value")?;
+                format = value.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            }
+            "--dataset" => dataset_path = Some(args.next().context("--dataset requires a path")?),
+            "--output" => output = Some(args.next().context("--output requires a path")?),
+            _ => anyhow::bail!("Unknown export option {}", arg),
+        }
+    }
+
+    let dataset = load_reverse_dataset(dataset_path)?;
+    let oracle = height_oracle::inspect::load_assets(&asset)
+        .with_context(|| format!("Failed to load {}", asset))?;
+    match output {
+        Some(path) => {
+            let file = std::fs::File::create(&path)
+                .with_context(|| format!("Failed to create {}", path))?;
+            oracle.export(&dataset.block_hashes, format, file)?;
+            eprintln!("✅ Exported {} entries to {}", dataset.len(), path);
+        }
+        None => oracle.export(&dataset.block_hashes, format, std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// `export-kv --format cdb|redb|sled [--assets <path>] [--dataset <path>] [--fsync] <out>`
+///
+/// Writes every dataset hash with the height the assets return for it to a new
+/// key-value store, for consumers that cannot read the perfect hash. The
+/// dataset must be the one the assets were built from, checked against the
+/// commitment in the heights asset.
+#[cfg(feature = "generate")]
+fn export_kv(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "Usage: height-oracle export-kv --format cdb|redb|sled [--assets <path>] [--dataset <path>] [--fsync] <out>";
+    let mut format = None;
+    let mut assets = "assets".to_string();
+    let mut dataset_path = None;
+    let mut save_options = SaveOptions::new();
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().context("--format requires a value")?;
+                format = Some(value.parse::<KvFormat>().map_err(|e| anyhow::anyhow!(e))?);
+            }
+            "--assets" => assets = args.next().context("--assets requires a path")?,
+            "--dataset" => dataset_path = Some(args.next().context("--dataset requires a path")?),
+            "--fsync" => save_options = save_options.fsync(true),
+            _ if arg.starts_with("--") => anyhow::bail!("Unknown export-kv option {}", arg),
+            _ if output.is_none() => output = Some(arg),
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    let (Some(format), Some(output)) = (format, output) else {
+        anyhow::bail!(USAGE);
+    };
+
+    let dataset_path = reverse_dataset_path(dataset_path);
+    let oracle = height_oracle::inspect::load_assets(&assets)
+        .with_context(|| format!("Failed to load {}", assets))?;
+    if !oracle.verify_dataset(&dataset_path)? {
+        anyhow::bail!(
+            "{} is not the dataset {} was built from",
+            dataset_path,
+            assets
+        );
+    }
+    let dataset = load_reverse_dataset(Some(dataset_path))?;
+    oracle.export_kv(&dataset.block_hashes, format, &output, &save_options)?;
+    eprintln!("✅ Exported {} entries to {}", dataset.len(), output);
+    Ok(())
+}
+
+/// `verify-dataset <asset> [--dataset <path>]`: check that a dataset is the one an asset set was built from
+#[cfg(feature = "generate")]
+fn verify_dataset(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "Usage: height-oracle verify-dataset <asset> [--dataset <path>]";
+    let path = args.next().context(USAGE)?;
+    let dataset_path = match args.next().as_deref() {
+        Some("--dataset") => args.next().context("--dataset requires a path")?,
+        Some(_) => anyhow::bail!(USAGE),
+        None => find_input_file()?.to_string(),
+    };
+
+    let oracle = height_oracle::inspect::load_assets(&path)
+        .with_context(|| format!("Failed to load {}", path))?;
+    if oracle.verify_dataset(&dataset_path)? {
+        println!("✅ {} matches the dataset of {}", dataset_path, path);
+        Ok(())
+    } else {
+        println!(
+            "❌ {} is not the dataset {} was built from",
+            dataset_path, path
+        );
+        std::process::exit(1);
+    }
+}
+
+/// `codegen [<asset dir>] [--output <path>]`: emit the assets as Rust statics
+///
+/// Writes to stdout by default; see `height_oracle::codegen`.
+#[cfg(feature = "generate")]
+fn codegen(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut dir = "assets".to_string();
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = Some(args.next().context("--output requires a path")?),
+            _ if !arg.starts_with("--") => dir = arg,
+            _ => anyhow::bail!("Unknown codegen option {}", arg),
+        }
+    }
+
+    let dir = Path::new(&dir);
+    let read = |name: &str| {
+        let path = dir.join(name);
+        std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let ptrhash_data = read(height_oracle::PTRHASH_FILE_NAME)?;
+    let heights_data = read(height_oracle::HEIGHTS_FILE_NAME)?;
+    match output {
+        Some(path) => {
+            let file = std::fs::File::create(&path)
+                .with_context(|| format!("Failed to create {}", path))?;
+            height_oracle::codegen::write_static_assets(
+                &ptrhash_data,
+                &heights_data,
+                std::io::BufWriter::new(file),
+            )?;
+            eprintln!("✅ Wrote {}", path);
+        }
+        None => height_oracle::codegen::write_static_assets(
+            &ptrhash_data,
+            &heights_data,
+            std::io::stdout().lock(),
+        )?,
+    }
+    Ok(())
+}
+
+/// `bench [--embedded | --assets <path> | --compare-remap] [--lookups <n>] [--miss-ratio <r>] [--dataset <path>]`
+///
+/// Times random lookups of dataset hashes mixed with random misses. The cold
+/// pass runs right after loading, the warm pass repeats the same queries.
+/// `--compare-remap` instead builds the dataset once per remap encoding and
+/// reports the PtrHash size next to the latency of each.
+#[cfg(feature = "generate")]
+fn bench(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut embedded = false;
+    let mut compare_remap = false;
+    let mut assets = None;
+    let mut lookups = 1_000_000;
+    let mut miss_ratio = 0.1;
+    let mut dataset_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--embedded" => embedded = true,
+            "--compare-remap" => compare_remap = true,
+            "--assets" => assets = Some(args.next().context("--assets requires a path")?),
+            "--lookups" => {
+                let value = args.next().context("--lookups requires a value")?;
+                lookups = value
+                    .parse()
+                    .with_context(|| format!("Invalid --lookups value {}", value))?;
+            }
+            "--miss-ratio" => {
+                let value = args.next().context("--miss-ratio requires a value")?;
+                miss_ratio = value
+                    .parse()
+                    .ok()
+                    .filter(|ratio| (0.0..=1.0).contains(ratio))
+                    .with_context(|| format!("--miss-ratio expects 0 to 1, got {}", value))?;
+            }
+            "--dataset" => dataset_path = Some(args.next().context("--dataset requires a path")?),
+            _ => anyhow::bail!(
+                "Unknown bench option {}\nUsage: height-oracle bench [--embedded | --assets <path> | --compare-remap] [--lookups <n>] [--miss-ratio <r>] [--dataset <path>]",
+                arg
+            ),
+        }
+    }
+    if [embedded, assets.is_some(), compare_remap]
+        .iter()
+        .filter(|&&set| set)
+        .count()
+        > 1
+    {
+        anyhow::bail!("--embedded, --assets and --compare-remap cannot be combined");
+    }
+
+    let dataset = load_reverse_dataset(dataset_path)?;
+    let queries = latency::bench_queries(&dataset.block_hashes, lookups, miss_ratio, 0);
+
+    if compare_remap {
+        println!("=== Remap encodings ===\n");
+        let configs = RemapEncoding::ALL.map(|remap| PhfConfig::new().remap(remap));
+        for comparison in latency::compare_phf_configs(
+            &dataset.block_hashes,
+            &dataset.heights,
+            &configs,
+            &queries,
+        )? {
+            println!("{}", comparison);
+        }
+        return Ok(());
+    }
+
+    let start = std::time::Instant::now();
+    let (name, oracle) = if embedded {
+        ("embedded assets".to_string(), load_embedded()?)
+    } else {
+        let path = assets.unwrap_or_else(|| "assets".into());
+        let oracle = height_oracle::inspect::load_assets(&path)
+            .with_context(|| format!("Failed to load {}", path))?;
+        (path, Box::new(oracle) as Box<dyn HeightLookup>)
+    };
+    let load_time = start.elapsed();
+
+    println!("=== Lookup benchmark ===\n");
+    println!(
+        "Oracle: {} ({} entries, loaded in {:?})",
+        name,
+        oracle.len(),
+        load_time
+    );
+    println!(
+        "Queries: {} lookups, {:.0}% random misses\n",
+        queries.len(),
+        miss_ratio * 100.0
+    );
+    println!("Cold: {}", LatencyReport::measure(&*oracle, &queries));
+    println!("Warm: {}", LatencyReport::measure(&*oracle, &queries));
+    Ok(())
+}
+
+/// The compiled-in oracle, when this binary was built with `embedded`
+#[cfg(feature = "generate")]
+fn load_embedded() -> Result<Box<dyn HeightLookup>> {
+    #[cfg(feature = "embedded")]
+    {
+        Ok(Box::new(
+            height_oracle::HeightOracleEmbedded::try_load_embedded()?,
+        ))
+    }
+    #[cfg(not(feature = "embedded"))]
+    {
+        anyhow::bail!("--embedded needs a build with the embedded feature")
+    }
+}
+
+/// Dataset listing every block hash, used to probe assets that cannot enumerate their keys
+#[cfg(feature = "generate")]
+fn load_reverse_dataset(path: Option<String>) -> Result<Dataset> {
+    let path = reverse_dataset_path(path);
+    eprintln!("📁 Loading dataset from {}...", path);
+    Dataset::from_path(&path).with_context(|| format!("Failed to load dataset {}", path))
+}
+
+/// `path`, or the dataset the build wrote to `assets/`
+#[cfg(feature = "generate")]
+fn reverse_dataset_path(path: Option<String>) -> String {
+    path.unwrap_or_else(|| {
+        if Path::new("assets/prebip34.bin").exists() {
+            "assets/prebip34.bin".into()
+        } else {
+            "assets/prebip34.txt".into()
+        }
+    })
+}
+
 #[cfg(not(feature = "generate"))]
 fn main() {
     println!("This program requires the 'generate' feature flag.");
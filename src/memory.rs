@@ -0,0 +1,284 @@
+//! Memory and on-disk size accounting, shared by the oracle types
+//!
+//! [`HeightOracle`](crate::HeightOracle), [`HeightOracleLoaded`](crate::HeightOracleLoaded)
+//! and [`HeightOracleEmbedded`](crate::HeightOracleEmbedded) all report a
+//! [`MemoryStats`], so a service can compare loaders on the same figures.
+
+use crate::cache::LookupCache;
+use crate::format;
+use crate::packing::{self, VersionFlags};
+use crate::phf::Phf;
+use crate::store::HeightStore;
+use std::time::Duration;
+
+/// Memory and on-disk size statistics for a height oracle
+///
+/// The heap fields are what loading allocates. The embedded features also put
+/// assets in the binary with `include_bytes!`; those bytes, counted in
+/// [`Self::static_asset_bytes`], cost binary size and, once touched, resident
+/// memory, whichever loader is used. Clones share their tables, so sum heap
+/// figures over distinct oracles only.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryStats {
+    /// Number of elements
+    pub num_elements: usize,
+    /// In-memory bits per element for the PtrHash structure
+    pub ptrhash_bits_per_element: f64,
+    /// In-memory bits per element for the heights: 32 when decoded, about 18
+    /// when kept packed, 0 when read in place from static or mapped data
+    pub heights_bits_per_element: f64,
+    /// Total in-memory bits per element for the PtrHash structure and the heights
+    pub total_bits_per_element: f64,
+    /// Size of the saved PtrHash asset in bytes, format header included
+    pub ptrhash_asset_bytes: usize,
+    /// Size of the saved 18-bit packed heights asset in bytes, format header included
+    pub heights_asset_bytes: usize,
+    /// Time taken to load the assets, if the oracle was loaded from disk
+    pub load_duration: Option<Duration>,
+    /// Heap bytes of the deserialized perfect hash
+    pub ptrhash_heap_bytes: usize,
+    /// Heap bytes of the heights
+    pub heights_heap_bytes: usize,
+    /// Heap bytes of the version flags, if attached
+    pub version_flags_heap_bytes: usize,
+    /// Heap bytes of the lookup cache, if enabled
+    pub cache_heap_bytes: usize,
+    /// Bytes of oracle assets compiled into the binary by the enabled
+    /// embedded features, whether or not this oracle was loaded from them
+    pub static_asset_bytes: usize,
+}
+
+impl MemoryStats {
+    /// Statistics of an oracle over `phash` and `heights`
+    pub(crate) fn new<S: HeightStore + ?Sized>(
+        phash: &Phf,
+        heights: &S,
+        version_flags: Option<&VersionFlags>,
+        cache: Option<&LookupCache>,
+        heights_header_version: u16,
+    ) -> Self {
+        let num_elements = heights.len();
+        let (pilots_bits, remap_bits) = phash.bits_per_element();
+        let ptrhash_bits = pilots_bits + remap_bits;
+        let ptrhash_asset_bytes = format::HEADER_LEN
+            + phash
+                .write(std::io::sink())
+                .expect("serializing to a sink cannot fail");
+
+        let mut stats = Self {
+            num_elements,
+            ptrhash_bits_per_element: ptrhash_bits,
+            heights_bits_per_element: 0.0,
+            total_bits_per_element: 0.0,
+            ptrhash_asset_bytes,
+            heights_asset_bytes: format::version_header_len(heights_header_version)
+                + packing::serialized_heights_len(num_elements),
+            load_duration: None,
+            ptrhash_heap_bytes: (ptrhash_bits * num_elements as f64 / 8.0).ceil() as usize,
+            heights_heap_bytes: heights.heap_bytes(),
+            version_flags_heap_bytes: version_flags.map_or(0, |flags| flags.len().div_ceil(8)),
+            cache_heap_bytes: cache.map_or(0, LookupCache::heap_bytes),
+            static_asset_bytes: static_asset_bytes(),
+        };
+        stats.heights_bits_per_element = stats.bits_per_element(stats.heights_heap_bytes);
+        stats.total_bits_per_element = ptrhash_bits + stats.heights_bits_per_element;
+        stats
+    }
+
+    fn bits_per_element(&self, bytes: usize) -> f64 {
+        if self.num_elements == 0 {
+            return 0.0;
+        }
+        (bytes * 8) as f64 / self.num_elements as f64
+    }
+
+    /// Memory usage of the PtrHash structure and the heights in bytes
+    ///
+    /// See [`Self::heap_bytes`] for everything loading allocates.
+    pub fn total_bytes(&self) -> usize {
+        ((self.total_bits_per_element * self.num_elements as f64) / 8.0).ceil() as usize
+    }
+
+    /// Total memory usage in KB
+    pub fn total_kb(&self) -> f64 {
+        self.total_bytes() as f64 / 1024.0
+    }
+
+    /// Total memory usage in MB
+    pub fn total_mb(&self) -> f64 {
+        self.total_kb() / 1024.0
+    }
+
+    /// Total heap bytes, version flags and cache included
+    pub fn heap_bytes(&self) -> usize {
+        self.ptrhash_heap_bytes
+            + self.heights_heap_bytes
+            + self.version_flags_heap_bytes
+            + self.cache_heap_bytes
+    }
+
+    /// Heap bits per element, version flags and cache included
+    pub fn heap_bits_per_element(&self) -> f64 {
+        self.bits_per_element(self.heap_bytes())
+    }
+
+    /// On-disk bits per element for the PtrHash asset
+    pub fn ptrhash_disk_bits_per_element(&self) -> f64 {
+        self.bits_per_element(self.ptrhash_asset_bytes)
+    }
+
+    /// On-disk bits per element for the packed heights asset (just over 18)
+    pub fn heights_disk_bits_per_element(&self) -> f64 {
+        self.bits_per_element(self.heights_asset_bytes)
+    }
+
+    /// Total size of both assets in bytes
+    pub fn asset_bytes(&self) -> usize {
+        self.ptrhash_asset_bytes + self.heights_asset_bytes
+    }
+
+    /// Total on-disk bits per element
+    pub fn disk_bits_per_element(&self) -> f64 {
+        self.bits_per_element(self.asset_bytes())
+    }
+
+    /// Machine-readable form, as a single JSON object
+    pub fn to_json(&self) -> String {
+        let load_ms = match self.load_duration {
+            Some(duration) => format!("{:.3}", duration.as_secs_f64() * 1000.0),
+            None => "null".to_string(),
+        };
+        format!(
+            concat!(
+                "{{\"num_elements\":{},",
+                "\"memory\":{{\"ptrhash_bits_per_element\":{:.4},\"heights_bits_per_element\":{:.4},",
+                "\"total_bits_per_element\":{:.4},\"total_bytes\":{}}},",
+                "\"heap\":{{\"ptrhash_bytes\":{},\"heights_bytes\":{},\"version_flags_bytes\":{},",
+                "\"cache_bytes\":{},\"total_bytes\":{}}},",
+                "\"disk\":{{\"ptrhash_bits_per_element\":{:.4},\"heights_bits_per_element\":{:.4},",
+                "\"total_bits_per_element\":{:.4},\"ptrhash_bytes\":{},\"heights_bytes\":{},\"total_bytes\":{}}},",
+                "\"static_asset_bytes\":{},\"load_ms\":{}}}"
+            ),
+            self.num_elements,
+            self.ptrhash_bits_per_element,
+            self.heights_bits_per_element,
+            self.total_bits_per_element,
+            self.total_bytes(),
+            self.ptrhash_heap_bytes,
+            self.heights_heap_bytes,
+            self.version_flags_heap_bytes,
+            self.cache_heap_bytes,
+            self.heap_bytes(),
+            self.ptrhash_disk_bits_per_element(),
+            self.heights_disk_bits_per_element(),
+            self.disk_bits_per_element(),
+            self.ptrhash_asset_bytes,
+            self.heights_asset_bytes,
+            self.asset_bytes(),
+            self.static_asset_bytes,
+            load_ms,
+        )
+    }
+}
+
+impl std::fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Memory Statistics:")?;
+        writeln!(f, "  Elements: {}", self.num_elements)?;
+        writeln!(
+            f,
+            "  PtrHash: {:.2} bits/element in memory, {:.2} on disk ({} bytes)",
+            self.ptrhash_bits_per_element,
+            self.ptrhash_disk_bits_per_element(),
+            self.ptrhash_asset_bytes
+        )?;
+        writeln!(
+            f,
+            "  Heights: {:.2} bits/element in memory, {:.2} on disk ({} bytes)",
+            self.heights_bits_per_element,
+            self.heights_disk_bits_per_element(),
+            self.heights_asset_bytes
+        )?;
+        if self.version_flags_heap_bytes > 0 {
+            writeln!(
+                f,
+                "  Version flags: {} bytes on the heap",
+                self.version_flags_heap_bytes
+            )?;
+        }
+        if self.cache_heap_bytes > 0 {
+            writeln!(f, "  Cache: {} bytes on the heap", self.cache_heap_bytes)?;
+        }
+        writeln!(
+            f,
+            "  Total: {:.2} bits/element in memory ({:.1} KB heap), {:.2} on disk ({:.1} KB)",
+            self.total_bits_per_element,
+            self.heap_bytes() as f64 / 1024.0,
+            self.disk_bits_per_element(),
+            self.asset_bytes() as f64 / 1024.0
+        )?;
+        if self.static_asset_bytes > 0 {
+            writeln!(
+                f,
+                "  Binary: {:.1} KB of assets compiled in",
+                self.static_asset_bytes as f64 / 1024.0
+            )?;
+        }
+        if let Some(duration) = self.load_duration {
+            writeln!(f, "  Load time: {:.3} ms", duration.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of the oracle assets compiled into the binary
+fn static_asset_bytes() -> usize {
+    #[allow(unused_mut)]
+    let mut bytes = 0;
+    #[cfg(any(
+        feature = "embedded",
+        feature = "embedded-testnet3",
+        feature = "runtime-assets"
+    ))]
+    {
+        bytes += crate::embedded::static_asset_bytes();
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_stats_disk_sizes_and_json() {
+        let stats = MemoryStats {
+            num_elements: 8,
+            ptrhash_bits_per_element: 3.0,
+            heights_bits_per_element: 32.0,
+            total_bits_per_element: 35.0,
+            ptrhash_asset_bytes: 4,
+            heights_asset_bytes: packing::serialized_heights_len(8),
+            load_duration: None,
+            ptrhash_heap_bytes: 3,
+            heights_heap_bytes: 32,
+            version_flags_heap_bytes: 1,
+            cache_heap_bytes: 0,
+            static_asset_bytes: 0,
+        };
+        assert_eq!(stats.heights_asset_bytes, 23);
+        assert_eq!(stats.asset_bytes(), 27);
+        assert_eq!(stats.ptrhash_disk_bits_per_element(), 4.0);
+        assert_eq!(stats.disk_bits_per_element(), 27.0);
+        assert_eq!(stats.heap_bytes(), 36);
+
+        let json = stats.to_json();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["disk"]["total_bytes"], 27);
+        assert_eq!(parsed["heap"]["total_bytes"], 36);
+        assert!(json.starts_with("{\"num_elements\":8,"));
+        assert!(json.contains("\"heights_bytes\":23"));
+        assert!(json.ends_with("\"load_ms\":null}"));
+    }
+}
@@ -0,0 +1,220 @@
+//! Prometheus metrics for server mode (Feature: metrics)
+//!
+//! [`Metrics`] collects lookup counts, lookup latency, batch sizes and startup
+//! time with lock-free atomics. [`Metrics::render`] produces the Prometheus
+//! text exposition format, ready to be served from a `/metrics` endpoint.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the lookup latency buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    1e-7, 2.5e-7, 5e-7, 1e-6, 2.5e-6, 5e-6, 1e-5, 2.5e-5, 5e-5, 1e-4, 1e-3, 1e-2,
+];
+
+/// Upper bounds of the batch size buckets, in lookups per stream
+const BATCH_SIZE_BUCKETS: &[f64] = &[1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0, 1_000_000.0];
+
+/// RPC method a lookup was made through, used as the `method` label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Unary `GetHeight`
+    GetHeight,
+    /// Streaming `GetHeights`
+    GetHeights,
+}
+
+impl Method {
+    const ALL: [Method; 2] = [Method::GetHeight, Method::GetHeights];
+
+    fn label(self) -> &'static str {
+        match self {
+            Method::GetHeight => "GetHeight",
+            Method::GetHeights => "GetHeights",
+        }
+    }
+}
+
+/// Cumulative histogram over fixed bucket bounds
+///
+/// Observations are integers in a base unit (nanoseconds, lookups) and are
+/// divided by `units_per_bound` when compared with the bounds and rendered.
+struct Histogram {
+    bounds: &'static [f64],
+    units_per_bound: f64,
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64], units_per_bound: f64) -> Self {
+        Self {
+            bounds,
+            units_per_bound,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: u64) {
+        let scaled = value as f64 / self.units_per_bound;
+        if let Some(i) = self.bounds.iter().position(|&bound| scaled <= bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum.load(Ordering::Relaxed) as f64 / self.units_per_bound;
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
+
+/// Lookup and startup metrics for a serving process
+///
+/// Share one instance (e.g. in an `Arc`) between the service recording
+/// lookups and the endpoint rendering them.
+pub struct Metrics {
+    /// `[ok, error]` lookup counts, indexed by `Method as usize`
+    lookups: [[AtomicU64; 2]; 2],
+    lookup_latency: Histogram,
+    batch_size: Histogram,
+    init_nanos: AtomicU64,
+    entries: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Create an empty set of metrics
+    pub fn new() -> Self {
+        Self {
+            lookups: Default::default(),
+            lookup_latency: Histogram::new(LATENCY_BUCKETS, 1e9),
+            batch_size: Histogram::new(BATCH_SIZE_BUCKETS, 1.0),
+            init_nanos: AtomicU64::new(0),
+            entries: AtomicU64::new(0),
+        }
+    }
+
+    /// Record how long loading the oracle took and how many entries it holds
+    pub fn record_init(&self, duration: Duration, entries: usize) {
+        self.init_nanos
+            .store(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.entries.store(entries as u64, Ordering::Relaxed);
+    }
+
+    /// Record one lookup; `ok` is false for rejected requests (e.g. malformed hashes)
+    pub fn record_lookup(&self, method: Method, latency: Duration, ok: bool) {
+        self.lookups[method as usize][usize::from(!ok)].fetch_add(1, Ordering::Relaxed);
+        self.lookup_latency.observe(latency.as_nanos() as u64);
+    }
+
+    /// Record the number of lookups answered by one `GetHeights` stream
+    pub fn record_batch(&self, size: usize) {
+        self.batch_size.observe(size as u64);
+    }
+
+    /// Total lookups recorded for `method`, successful or not
+    pub fn lookups(&self, method: Method) -> u64 {
+        self.lookups[method as usize]
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP height_oracle_lookups_total Block hash lookups served.\n");
+        out.push_str("# TYPE height_oracle_lookups_total counter\n");
+        for (method, counts) in Method::ALL.iter().zip(self.lookups.iter()) {
+            for (result, count) in ["ok", "error"].iter().zip(counts.iter()) {
+                let _ = writeln!(
+                    out,
+                    "height_oracle_lookups_total{{method=\"{}\",result=\"{result}\"}} {}",
+                    method.label(),
+                    count.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        out.push_str("# HELP height_oracle_lookup_duration_seconds Time to answer one lookup.\n");
+        out.push_str("# TYPE height_oracle_lookup_duration_seconds histogram\n");
+        self.lookup_latency
+            .render(&mut out, "height_oracle_lookup_duration_seconds");
+
+        out.push_str("# HELP height_oracle_batch_size Lookups per GetHeights stream.\n");
+        out.push_str("# TYPE height_oracle_batch_size histogram\n");
+        self.batch_size.render(&mut out, "height_oracle_batch_size");
+
+        out.push_str("# HELP height_oracle_init_seconds Time taken to load the oracle.\n");
+        out.push_str("# TYPE height_oracle_init_seconds gauge\n");
+        let init_seconds = self.init_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(out, "height_oracle_init_seconds {init_seconds}");
+
+        out.push_str("# HELP height_oracle_entries Number of block hashes in the oracle.\n");
+        out.push_str("# TYPE height_oracle_entries gauge\n");
+        let _ = writeln!(
+            out,
+            "height_oracle_entries {}",
+            self.entries.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.record_init(Duration::from_millis(250), 1000);
+        metrics.record_lookup(Method::GetHeight, Duration::from_nanos(800), true);
+        metrics.record_lookup(Method::GetHeights, Duration::from_micros(3), true);
+        metrics.record_lookup(Method::GetHeights, Duration::from_micros(3), false);
+        metrics.record_batch(2);
+
+        assert_eq!(metrics.lookups(Method::GetHeight), 1);
+        assert_eq!(metrics.lookups(Method::GetHeights), 2);
+
+        let text = metrics.render();
+        for line in [
+            "height_oracle_lookups_total{method=\"GetHeight\",result=\"ok\"} 1",
+            "height_oracle_lookups_total{method=\"GetHeights\",result=\"error\"} 1",
+            "height_oracle_lookup_duration_seconds_bucket{le=\"0.000001\"} 1",
+            "height_oracle_lookup_duration_seconds_bucket{le=\"0.000005\"} 3",
+            "height_oracle_lookup_duration_seconds_count 3",
+            "height_oracle_batch_size_bucket{le=\"1\"} 0",
+            "height_oracle_batch_size_bucket{le=\"10\"} 1",
+            "height_oracle_batch_size_sum 2",
+            "height_oracle_init_seconds 0.25",
+            "height_oracle_entries 1000",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line:?} in\n{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! UniFFI bindings for Kotlin and Swift (Feature: uniffi)
+//!
+//! Exports the embedded oracle to mobile SPV wallets. Generate bindings with
+//! `cargo run --features uniffi-bindgen --bin uniffi-bindgen -- generate --library <cdylib> --language kotlin`
+//! (or `swift`). Functions are exposed as `guessHeight`, `guessHeights` and
+//! `guessHeightChecked`.
+
+use crate::header::{self, BlockHeader, HEADER_SIZE};
+use crate::BlockHash;
+
+/// Errors surfaced to Kotlin/Swift as exceptions
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum OracleError {
+    /// The block hash is not 64 hex characters
+    InvalidHash { reason: String },
+    /// The header is not 80 bytes of hex
+    InvalidHeader { reason: String },
+    /// The header cannot belong to a pre-BIP34 block in the oracle
+    NotInDomain { reason: String },
+}
+
+impl std::fmt::Display for OracleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OracleError::InvalidHash { reason } => write!(f, "Invalid block hash: {reason}"),
+            OracleError::InvalidHeader { reason } => write!(f, "Invalid block header: {reason}"),
+            OracleError::NotInDomain { reason } => write!(f, "Block not in oracle: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for OracleError {}
+
+fn parse_hash(hash_hex: &str) -> Result<BlockHash, OracleError> {
+    crate::parse_block_hash(hash_hex).map_err(|reason| OracleError::InvalidHash { reason })
+}
+
+/// Look up the height of a block hash in reverse hex (unchecked)
+///
+/// Hashes outside the pre-BIP34 version-1 domain return some other block's height.
+#[uniffi::export]
+pub fn guess_height(hash_hex: String) -> Result<u32, OracleError> {
+    let block_hash = parse_hash(&hash_hex)?;
+    Ok(crate::guess_height_prebip34block_unchecked(&block_hash))
+}
+
+/// Look up heights for many block hashes in one call (unchecked)
+#[uniffi::export]
+pub fn guess_heights(hash_hexes: Vec<String>) -> Result<Vec<u32>, OracleError> {
+    hash_hexes
+        .iter()
+        .map(|hash_hex| {
+            let block_hash = parse_hash(hash_hex)?;
+            Ok(crate::guess_height_prebip34block_unchecked(&block_hash))
+        })
+        .collect()
+}
+
+/// Look up the height of a raw header in hex, rejecting headers outside the oracle's domain
+///
+/// The header must be version 1 and carry valid proof-of-work at or above
+/// mainnet minimum difficulty. After BIP34 activation, version-1 blocks are
+/// invalid, so such a header belongs to a pre-BIP34 block. Stale blocks still pass.
+#[uniffi::export]
+pub fn guess_height_checked(header_hex: String) -> Result<u32, OracleError> {
+    let raw = parse_header_hex(&header_hex)?;
+    let parsed = BlockHeader::parse(&raw);
+    if parsed.version != 1 {
+        return Err(OracleError::NotInDomain {
+            reason: format!("header version is {}, expected 1", parsed.version),
+        });
+    }
+
+    let target = parsed.target();
+    if !header::within_pow_limit(&target) {
+        return Err(OracleError::NotInDomain {
+            reason: "target is easier than the proof-of-work limit".to_string(),
+        });
+    }
+
+    let block_hash = header::block_hash(&raw);
+    if !header::hash_meets_target(&block_hash, &target) {
+        return Err(OracleError::NotInDomain {
+            reason: "header hash does not meet its target".to_string(),
+        });
+    }
+
+    Ok(crate::guess_height_prebip34block_unchecked(&block_hash))
+}
+
+/// Number of blocks in the embedded oracle
+#[uniffi::export]
+pub fn oracle_len() -> u64 {
+    crate::embedded::embedded_oracle().len() as u64
+}
+
+fn parse_header_hex(header_hex: &str) -> Result<[u8; HEADER_SIZE], OracleError> {
+    let header_hex = header_hex.trim();
+    if header_hex.len() != HEADER_SIZE * 2 {
+        return Err(OracleError::InvalidHeader {
+            reason: format!("expected {} hex characters", HEADER_SIZE * 2),
+        });
+    }
+
+    let mut raw = [0u8; HEADER_SIZE];
+    for (i, chunk) in header_hex.as_bytes().chunks(2).enumerate() {
+        raw[i] = std::str::from_utf8(chunk)
+            .ok()
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| OracleError::InvalidHeader {
+                reason: "invalid hex".to_string(),
+            })?;
+    }
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Genesis header, version 1
+    const GENESIS_HEADER_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    #[test]
+    fn test_invalid_inputs() {
+        assert!(matches!(
+            guess_height("zz".to_string()),
+            Err(OracleError::InvalidHash { .. })
+        ));
+        assert!(matches!(
+            guess_height_checked("00".to_string()),
+            Err(OracleError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn test_checked_rejects_out_of_domain_headers() {
+        // Same header with version 2
+        let v2 = format!("02{}", &GENESIS_HEADER_HEX[2..]);
+        assert!(matches!(
+            guess_height_checked(v2),
+            Err(OracleError::NotInDomain { .. })
+        ));
+
+        // Tampered nonce: no longer meets its target
+        let tampered = format!("{}00", &GENESIS_HEADER_HEX[..GENESIS_HEADER_HEX.len() - 2]);
+        assert!(matches!(
+            guess_height_checked(tampered),
+            Err(OracleError::NotInDomain { .. })
+        ));
+    }
+}
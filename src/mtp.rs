@@ -0,0 +1,101 @@
+//! Median-time-past oracle
+//!
+//! Maps pre-BIP34 block hashes to their median time past, the value Bitcoin
+//! Core reports as `mediantime`: the median timestamp of the block and its ten
+//! predecessors. BIP113 checks a transaction's locktime against the MTP of the
+//! block before it, so historical locktime checks need MTP rather than height.
+//!
+//! MTP depends on the preceding blocks, which a perfect-hash-indexed table of
+//! timestamps cannot walk, so it is stored as its own [`SideTable`], indexed
+//! like the heights. It uses the timestamp asset encoding (see
+//! [`crate::timestamps::serialize_timestamps`]) and only adds
+//! [`crate::MTP_FILE_NAME`].
+
+use crate::side_table::SideTable;
+use crate::timestamps::deserialize_timestamps;
+use crate::BlockHash;
+use std::io;
+
+/// Blocks in the median time past window
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Median time past at every height, given the header timestamps in height order from genesis
+///
+/// Near genesis the window holds fewer than [`MEDIAN_TIME_SPAN`] blocks; as in
+/// Bitcoin Core, the upper median of what is there is used.
+pub fn median_time_past(timestamps: &[u32]) -> Vec<u32> {
+    let mut window = Vec::with_capacity(MEDIAN_TIME_SPAN);
+    (0..timestamps.len())
+        .map(|height| {
+            window.clear();
+            window.extend_from_slice(
+                &timestamps[(height + 1).saturating_sub(MEDIAN_TIME_SPAN)..=height],
+            );
+            window.sort_unstable();
+            window[window.len() / 2]
+        })
+        .collect()
+}
+
+/// Block hash → median time past lookups for pre-BIP34 blocks
+pub struct MedianTimeOracle {
+    table: SideTable<u32>,
+}
+
+impl MedianTimeOracle {
+    /// Load from in-memory asset bytes (the height oracle's PtrHash asset + MTP asset)
+    pub fn from_bytes(ptrhash_data: &[u8], mtp_data: &[u8]) -> io::Result<Self> {
+        let mtps = deserialize_timestamps(io::Cursor::new(mtp_data))?;
+        let table = SideTable::from_bytes(ptrhash_data, mtps, "median time past")?;
+        Ok(Self { table })
+    }
+
+    /// Load from asset files on disk
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_files<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        mtp_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let mtp_data = std::fs::read(mtp_path)?;
+        Self::from_bytes(&ptrhash_data, &mtp_data)
+    }
+
+    /// Median time past (Unix seconds) of a block (unchecked)
+    ///
+    /// Like the height lookups, hashes outside the dataset return the MTP of
+    /// some other block.
+    pub fn mtp_for(&self, block_hash: &BlockHash) -> u32 {
+        self.table.get_unchecked(block_hash)
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median_time_past() {
+        assert_eq!(median_time_past(&[]), Vec::<u32>::new());
+        assert_eq!(median_time_past(&[100, 90, 110]), [100, 100, 100]);
+
+        // Out-of-order timestamps; once the window is full the oldest drops out
+        let timestamps: Vec<u32> = (0..12)
+            .map(|i| if i == 5 { 1000 } else { i * 10 })
+            .collect();
+        let mtps = median_time_past(&timestamps);
+        assert_eq!(mtps[1], 10);
+        assert_eq!(mtps[10], 60);
+        assert_eq!(mtps[11], 70);
+    }
+}
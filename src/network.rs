@@ -0,0 +1,85 @@
+//! Bitcoin networks with a pre-BIP34 block range
+//!
+//! Signet and regtest enforce BIP34 from genesis, so they have no blocks for an
+//! oracle to cover and are not listed.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A network whose pre-BIP34 blocks an oracle can cover
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Network {
+    /// Bitcoin mainnet
+    #[default]
+    Mainnet,
+    /// Testnet version 3
+    Testnet3,
+}
+
+impl Network {
+    /// First height at which BIP34 applies; the oracle covers the heights below it
+    pub const fn bip34_activation_height(self) -> u32 {
+        match self {
+            Network::Mainnet => crate::BIP34_ACTIVATION_HEIGHT,
+            Network::Testnet3 => 21_111,
+        }
+    }
+
+    /// Hash of the genesis block, in reverse hex as bitcoind shows it
+    pub const fn genesis_hash(self) -> &'static str {
+        match self {
+            Network::Mainnet => "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            Network::Testnet3 => "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet3 => write!(f, "testnet3"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    /// Accepts the [`Display`](fmt::Display) names and Bitcoin Core's `main` / `test`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "main" => Ok(Network::Mainnet),
+            "testnet3" | "test" => Ok(Network::Testnet3),
+            _ => Err(format!(
+                "Unknown network {s:?}, expected mainnet or testnet3"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_names() {
+        for network in [Network::Mainnet, Network::Testnet3] {
+            assert_eq!(network.to_string().parse::<Network>(), Ok(network));
+        }
+        assert_eq!("test".parse::<Network>(), Ok(Network::Testnet3));
+        assert!("signet".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn test_genesis_hashes_parse() {
+        for network in [Network::Mainnet, Network::Testnet3] {
+            assert!(crate::parse_block_hash(network.genesis_hash()).is_ok());
+        }
+    }
+}
@@ -3,10 +3,14 @@
 //! This module provides efficient packing/unpacking of u32 heights using only 18 bits.
 //! Maximum supported height is 262,143 (2^18 - 1), which covers all pre-BIP34 blocks.
 
+use std::borrow::Cow;
 use std::io::{Read, Write};
 
 pub const MAX_HEIGHT: u32 = (1 << 18) - 1; // 262,143
 
+/// Most entries a deserializer reserves up front before reading them
+const MAX_PREALLOCATED_ENTRIES: usize = 1 << 20;
+
 /// Pack 4 heights into 9 bytes (72 bits total)
 ///
 /// Each height uses 18 bits, for a total of 72 bits (9 bytes).
@@ -56,13 +60,47 @@ pub fn unpack_4_heights(bytes: &[u8; 9]) -> [u32; 4] {
     [h0, h1, h2, h3]
 }
 
+/// Groups decoded per iteration of [`unpack_heights_bulk`]
+const BULK_GROUPS: usize = 8;
+
+/// Packed bytes read at a time by [`deserialize_heights`], a whole number of bulk iterations
+const READ_BLOCK_LEN: usize = BULK_GROUPS * 9 * 2048;
+
+/// Unpack consecutive 9-byte groups, four heights per group
+///
+/// Decodes eight groups per iteration into a fixed-size buffer, which
+/// the compiler unrolls, instead of growing the output group by group. The
+/// padding slots of a last partial group come out as stored (zero when written
+/// by [`serialize_heights`]); a trailing partial group of fewer than 9 bytes is ignored.
+pub fn unpack_heights_bulk(packed: &[u8]) -> Vec<u32> {
+    let mut heights = Vec::new();
+    unpack_groups_into(packed, &mut heights);
+    heights
+}
+
+/// [`unpack_heights_bulk`], appending to `heights`
+fn unpack_groups_into(packed: &[u8], heights: &mut Vec<u32>) {
+    heights.reserve(packed.len() / 9 * 4);
+    let mut blocks = packed.chunks_exact(BULK_GROUPS * 9);
+    for block in &mut blocks {
+        let mut unpacked = [0u32; BULK_GROUPS * 4];
+        for (group, out) in block.chunks_exact(9).zip(unpacked.chunks_exact_mut(4)) {
+            out.copy_from_slice(&unpack_4_heights(group.try_into().expect("group is 9 bytes")));
+        }
+        heights.extend_from_slice(&unpacked);
+    }
+    for group in blocks.remainder().chunks_exact(9) {
+        heights.extend_from_slice(&unpack_4_heights(group.try_into().expect("group is 9 bytes")));
+    }
+}
+
 /// Serialize height arrays with metadata
 ///
 /// Format: [num_entries: u32][remainder: u8][packed_data: 9*chunks bytes]
 pub fn serialize_heights<W: Write>(heights: &[u32], mut writer: W) -> std::io::Result<()> {
     let num_entries = heights.len() as u32;
     let remainder = (num_entries % 4) as u8;
-    let chunks = (num_entries + 3) / 4; // Round up division
+    let chunks = num_entries.div_ceil(4);
 
     // Write metadata
     writer.write_all(&num_entries.to_le_bytes())?;
@@ -86,8 +124,36 @@ pub fn serialize_heights<W: Write>(heights: &[u32], mut writer: W) -> std::io::R
     Ok(())
 }
 
-/// Deserialize heights from reader
-pub fn deserialize_heights<R: Read>(mut reader: R) -> std::io::Result<Vec<u32>> {
+/// Size in bytes of `num_entries` heights in the [`serialize_heights`] format
+pub fn serialized_heights_len(num_entries: usize) -> usize {
+    4 + 1 + num_entries.div_ceil(4) * 9
+}
+
+/// How deserialization treats bytes after the last packed group
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailerMode {
+    /// Bytes after the last group are an `InvalidData` error
+    #[default]
+    Strict,
+    /// Bytes after the last group are left unread, for a caller that parses
+    /// the heights as one section of a larger stream
+    Lenient,
+}
+
+/// Deserialize heights from reader, which must end after the last packed group
+///
+/// Use [`deserialize_heights_with_mode`] to read heights followed by other data.
+pub fn deserialize_heights<R: Read>(reader: R) -> std::io::Result<Vec<u32>> {
+    deserialize_heights_with_mode(reader, TrailerMode::Strict)
+}
+
+/// Deserialize heights from reader, treating what follows them according to `mode`
+///
+/// With [`TrailerMode::Lenient`], pass `&mut reader` to keep reading after the heights.
+pub fn deserialize_heights_with_mode<R: Read>(
+    mut reader: R,
+    mode: TrailerMode,
+) -> std::io::Result<Vec<u32>> {
     // Read metadata
     let mut num_bytes = [0u8; 4];
     reader.read_exact(&mut num_bytes)?;
@@ -95,31 +161,351 @@ pub fn deserialize_heights<R: Read>(mut reader: R) -> std::io::Result<Vec<u32>>
 
     let mut remainder_bytes = [0u8; 1];
     reader.read_exact(&mut remainder_bytes)?;
-    let _remainder = remainder_bytes[0];
+    check_remainder(num_entries, remainder_bytes[0])?;
 
-    let chunks = (num_entries + 3) / 4; // Round up division
-    let mut heights = Vec::with_capacity(num_entries as usize);
+    // `num_entries` comes from the input, so grow as blocks arrive rather than
+    // trusting it for the allocation
+    let mut heights = Vec::with_capacity((num_entries as usize).min(MAX_PREALLOCATED_ENTRIES));
 
-    // Read and unpack height data
-    for chunk_idx in 0..chunks {
-        let mut packed_bytes = [0u8; 9];
-        reader.read_exact(&mut packed_bytes)?;
-        
-        let unpacked = unpack_4_heights(&packed_bytes);
-        
-        // Only take the valid heights from this chunk
-        let start = (chunk_idx * 4) as usize;
-        let end = std::cmp::min(start + 4, num_entries as usize);
-        let valid_count = end - start;
-        
-        for i in 0..valid_count {
-            heights.push(unpacked[i]);
-        }
+    // Read and unpack height data a block of groups at a time
+    let mut remaining = u64::from(num_entries.div_ceil(4)) * 9;
+    let mut block = vec![0u8; remaining.min(READ_BLOCK_LEN as u64) as usize];
+    while remaining > 0 {
+        let block_len = remaining.min(READ_BLOCK_LEN as u64) as usize;
+        reader.read_exact(&mut block[..block_len])?;
+        unpack_groups_into(&block[..block_len], &mut heights);
+        remaining -= block_len as u64;
     }
 
+    // Drop the padding slots of the last group
+    heights.truncate(num_entries as usize);
+
+    if mode == TrailerMode::Strict {
+        check_at_end(reader)?;
+    }
     Ok(heights)
 }
 
+/// Fail if `reader` has any bytes left
+fn check_at_end<R: Read>(mut reader: R) -> std::io::Result<()> {
+    let mut byte = [0u8; 1];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Unexpected bytes after the last height group",
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn check_remainder(num_entries: u32, remainder: u8) -> std::io::Result<()> {
+    if u32::from(remainder) != num_entries % 4 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Heights header is inconsistent: {num_entries} entries but remainder {remainder}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that `heights` survive every path through the [`serialize_heights`] format
+///
+/// Serializes them, checks the size against [`serialized_heights_len`], and
+/// reads them back with [`deserialize_heights`] and [`PackedHeights`], which
+/// must also write the same bytes. Heights above [`MAX_HEIGHT`] are an
+/// `InvalidInput` error rather than a panic; any disagreement is `InvalidData`.
+/// Meant for property tests, here and in crates with their own height sources.
+pub fn verify_roundtrip(heights: &[u32]) -> std::io::Result<()> {
+    let invalid = |what: String| std::io::Error::new(std::io::ErrorKind::InvalidData, what);
+    if let Some(index) = heights.iter().position(|&height| height > MAX_HEIGHT) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Height {} at {index} exceeds maximum {MAX_HEIGHT}", heights[index]),
+        ));
+    }
+
+    let mut data = Vec::new();
+    serialize_heights(heights, &mut data)?;
+    if data.len() != serialized_heights_len(heights.len()) {
+        return Err(invalid(format!(
+            "{} heights serialized to {} bytes, expected {}",
+            heights.len(),
+            data.len(),
+            serialized_heights_len(heights.len())
+        )));
+    }
+
+    let decoded = deserialize_heights(data.as_slice())?;
+    if let Some(index) = (0..heights.len().max(decoded.len()))
+        .find(|&index| heights.get(index) != decoded.get(index))
+    {
+        return Err(invalid(format!(
+            "deserialize_heights differs at {index}: {:?} != {:?} ({} vs {} entries)",
+            decoded.get(index),
+            heights.get(index),
+            decoded.len(),
+            heights.len()
+        )));
+    }
+
+    let packed = PackedHeights::from_serialized(&data)?;
+    if packed.len() != heights.len() {
+        return Err(invalid(format!(
+            "PackedHeights has {} entries, expected {}",
+            packed.len(),
+            heights.len()
+        )));
+    }
+    if let Some(index) = (0..heights.len()).find(|&index| packed.get(index) != heights[index]) {
+        return Err(invalid(format!(
+            "PackedHeights differs at {index}: {} != {}",
+            packed.get(index),
+            heights[index]
+        )));
+    }
+    let mut rewritten = Vec::with_capacity(data.len());
+    packed.write_chunked(&mut rewritten, 9)?;
+    if rewritten != data {
+        return Err(invalid("PackedHeights writes different bytes".to_string()));
+    }
+    if split_trailer(&data)? != (data.as_slice(), &[][..]) {
+        return Err(invalid("split_trailer finds bytes after the heights".to_string()));
+    }
+    Ok(())
+}
+
+/// Bytes before the packed groups in the [`serialize_heights`] format: entry count and remainder
+pub(crate) const SERIALIZED_PREFIX_LEN: usize = 5;
+
+/// Entry count and packed groups of serialized heights, without unpacking them
+///
+/// `data` must end after the last group, like [`deserialize_heights`].
+pub(crate) fn split_serialized(data: &[u8]) -> std::io::Result<(usize, &[u8])> {
+    let (num_entries, packed, trailer) = split_groups(data)?;
+    if !trailer.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} unexpected bytes after the last height group", trailer.len()),
+        ));
+    }
+    Ok((num_entries, packed))
+}
+
+/// Split `data` that starts with serialized heights into the heights and the bytes after them
+///
+/// The slice counterpart of [`TrailerMode::Lenient`], for container parsers:
+/// the first part is accepted by the strict readers, such as
+/// [`PackedHeights::from_serialized`], and the second is the caller's to parse.
+/// Checks the header but does not decode the groups.
+pub fn split_trailer(data: &[u8]) -> std::io::Result<(&[u8], &[u8])> {
+    let (_, _, trailer) = split_groups(data)?;
+    Ok(data.split_at(data.len() - trailer.len()))
+}
+
+/// Entry count, packed groups and the bytes after them
+fn split_groups(data: &[u8]) -> std::io::Result<(usize, &[u8], &[u8])> {
+    let (header, rest) = data
+        .split_first_chunk::<SERIALIZED_PREFIX_LEN>()
+        .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+    let num_entries = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    check_remainder(num_entries, header[4])?;
+    let packed_len = (num_entries as usize).div_ceil(4) * 9;
+    if rest.len() < packed_len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    let (packed, trailer) = rest.split_at(packed_len);
+    Ok((num_entries as usize, packed, trailer))
+}
+
+/// Height at `index` of packed groups, decoding only the group that holds it
+///
+/// Panics if the group is past the end of `packed`.
+#[inline]
+pub(crate) fn unpack_height(packed: &[u8], index: usize) -> u32 {
+    let start = (index / 4) * 9;
+    let group = packed[start..start + 9].try_into().expect("group is 9 bytes");
+    unpack_4_heights(group)[index % 4]
+}
+
+/// Heights kept 18-bit packed in memory, in the same layout as [`serialize_heights`]
+///
+/// Uses 2.25 bytes per entry instead of 4, for builders that fill heights in
+/// perfect-hash order without holding a `Vec<u32>`, and for loaders that
+/// decode one 9-byte group per lookup instead of all heights up front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedHeights {
+    packed: Cow<'static, [u8]>,
+    len: usize,
+}
+
+impl PackedHeights {
+    /// Zeroed heights for `len` entries
+    pub fn new(len: usize) -> Self {
+        Self {
+            packed: Cow::Owned(vec![0u8; len.div_ceil(4) * 9]),
+            len,
+        }
+    }
+
+    /// Keep serialized heights (see [`serialize_heights`]) packed, copying the packed groups
+    pub fn from_serialized(data: &[u8]) -> std::io::Result<Self> {
+        let (len, packed) = split_serialized(data)?;
+        Ok(Self { packed: Cow::Owned(packed.to_vec()), len })
+    }
+
+    /// Like [`Self::from_serialized`], but borrowing static data instead of copying it
+    pub fn from_static(data: &'static [u8]) -> std::io::Result<Self> {
+        let (len, packed) = split_serialized(data)?;
+        Ok(Self { packed: Cow::Borrowed(packed), len })
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn group(&self, index: usize) -> &[u8; 9] {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        let start = (index / 4) * 9;
+        self.packed[start..start + 9].try_into().expect("group is 9 bytes")
+    }
+
+    /// Height at `index`
+    pub fn get(&self, index: usize) -> u32 {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        unpack_height(&self.packed, index)
+    }
+
+    /// Height at `index`, or `None` if it is out of range
+    pub fn try_get(&self, index: usize) -> Option<u32> {
+        (index < self.len).then(|| self.get(index))
+    }
+
+    /// Set the height at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len()` or `height > MAX_HEIGHT`.
+    pub fn set(&mut self, index: usize, height: u32) {
+        let mut heights = unpack_4_heights(self.group(index));
+        heights[index % 4] = height;
+        let start = (index / 4) * 9;
+        self.packed.to_mut()[start..start + 9].copy_from_slice(&pack_4_heights(&heights));
+    }
+
+    /// Write in the [`serialize_heights`] format, `chunk_size` bytes at a time
+    pub fn write_chunked<W: Write>(&self, mut writer: W, chunk_size: usize) -> std::io::Result<()> {
+        writer.write_all(&(self.len as u32).to_le_bytes())?;
+        writer.write_all(&[(self.len % 4) as u8])?;
+        for chunk in self.packed.chunks(chunk_size.max(1)) {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Heap bytes of the packed groups; 0 when they are borrowed (see [`Self::from_static`])
+    pub fn heap_bytes(&self) -> usize {
+        match &self.packed {
+            Cow::Owned(packed) => packed.capacity(),
+            Cow::Borrowed(_) => 0,
+        }
+    }
+
+    /// Unpack into one `u32` per entry
+    pub fn to_vec(&self) -> Vec<u32> {
+        let mut heights = unpack_heights_bulk(&self.packed);
+        heights.truncate(self.len);
+        heights
+    }
+}
+
+/// One bit per perfect-hash index, set for blocks with header version >= 2
+///
+/// Serialized as: [num_entries: u32][bitmap: ceil(num_entries / 8) bytes, LSB first]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionFlags {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl VersionFlags {
+    /// All-clear flags for `len` entries
+    pub fn new(len: usize) -> Self {
+        Self {
+            bits: vec![0u8; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the entry at `index` is flagged as version >= 2
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len()`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        self.bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Set or clear the flag at `index`
+    pub fn set(&mut self, index: usize, v2: bool) {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        if v2 {
+            self.bits[index / 8] |= 1 << (index % 8);
+        } else {
+            self.bits[index / 8] &= !(1 << (index % 8));
+        }
+    }
+
+    /// Number of flagged entries
+    pub fn count_v2(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Serialize flags to writer
+    pub fn serialize<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(&(self.len as u32).to_le_bytes())?;
+        writer.write_all(&self.bits)
+    }
+
+    /// Deserialize flags from reader
+    pub fn deserialize<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut num_bytes = [0u8; 4];
+        reader.read_exact(&mut num_bytes)?;
+        let len = u32::from_le_bytes(num_bytes) as usize;
+
+        // Read no more than the input holds instead of allocating for `len` up front
+        let byte_len = len.div_ceil(8);
+        let mut bits = Vec::with_capacity(byte_len.min(MAX_PREALLOCATED_ENTRIES / 8));
+        reader.take(byte_len as u64).read_to_end(&mut bits)?;
+        if bits.len() != byte_len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Self { bits, len })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +580,259 @@ mod tests {
         assert_eq!(heights, deserialized);
     }
 
+    #[test]
+    fn test_serialized_heights_len() {
+        for n in [0, 1, 4, 5, 227_931] {
+            let mut buffer = Vec::new();
+            serialize_heights(&vec![0; n], &mut buffer).unwrap();
+            assert_eq!(buffer.len(), serialized_heights_len(n));
+        }
+    }
+
+    #[test]
+    fn test_packed_heights_matches_serialize_heights() {
+        let heights = vec![5, MAX_HEIGHT, 0, 227_930, 42, 7];
+        let mut packed = PackedHeights::new(heights.len());
+        for (index, &height) in heights.iter().enumerate().rev() {
+            packed.set(index, height);
+        }
+        assert_eq!(packed.to_vec(), heights);
+
+        let mut expected = Vec::new();
+        serialize_heights(&heights, &mut expected).unwrap();
+        let mut written = Vec::new();
+        packed.write_chunked(&mut written, 4).unwrap();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn test_packed_heights_from_serialized() {
+        let heights = vec![5, MAX_HEIGHT, 0, 227_930, 42];
+        let mut data = Vec::new();
+        serialize_heights(&heights, &mut data).unwrap();
+
+        let packed = PackedHeights::from_serialized(&data).unwrap();
+        assert_eq!(packed.to_vec(), heights);
+        assert_eq!(packed.try_get(4), Some(42));
+        assert_eq!(packed.try_get(5), None);
+
+        // A truncated last group or a bad remainder is caught up front
+        assert!(PackedHeights::from_serialized(&data[..data.len() - 1]).is_err());
+        data[4] = 3;
+        assert!(PackedHeights::from_serialized(&data).is_err());
+
+        let data: &'static [u8] = &[1, 0, 0, 0, 1, 7, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(PackedHeights::from_static(data).unwrap().to_vec(), [7]);
+    }
+
+    #[test]
+    fn test_version_flags_roundtrip() {
+        let mut flags = VersionFlags::new(11);
+        flags.set(0, true);
+        flags.set(9, true);
+        flags.set(10, true);
+        flags.set(10, false);
+        assert!(flags.get(0) && flags.get(9) && !flags.get(10));
+        assert_eq!(flags.count_v2(), 2);
+
+        let mut buffer = Vec::new();
+        flags.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 4 + 2);
+        assert_eq!(VersionFlags::deserialize(Cursor::new(buffer)).unwrap(), flags);
+    }
+
+    #[test]
+    fn test_deserialize_untrusted_lengths() {
+        // A huge claimed length with no data behind it fails on the read, not the allocation
+        let mut data = u32::MAX.to_le_bytes().to_vec();
+        data.push((u32::MAX % 4) as u8);
+        let err = deserialize_heights(Cursor::new(&data)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        let err = VersionFlags::deserialize(Cursor::new(&data)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // The remainder byte must agree with the entry count
+        let mut data = Vec::new();
+        serialize_heights(&[1, 2, 3], &mut data).unwrap();
+        data[4] = 0;
+        let err = deserialize_heights(Cursor::new(&data)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_unpack_heights_bulk() {
+        // Around the bulk block size, and spanning several read blocks
+        for len in [0, 1, 4, 31, 32, 33, 64, 100, READ_BLOCK_LEN / 9 * 4 * 2 + 5] {
+            let heights: Vec<u32> = (0..len as u32)
+                .map(|i| (i * 7919) % (MAX_HEIGHT + 1))
+                .collect();
+            let mut data = Vec::new();
+            serialize_heights(&heights, &mut data).unwrap();
+
+            let mut unpacked = unpack_heights_bulk(&data[SERIALIZED_PREFIX_LEN..]);
+            assert_eq!(unpacked.len(), len.div_ceil(4) * 4);
+            assert!(unpacked[len..].iter().all(|&padding| padding == 0));
+            unpacked.truncate(len);
+            assert_eq!(unpacked, heights);
+            assert_eq!(deserialize_heights(data.as_slice()).unwrap(), heights);
+            assert_eq!(PackedHeights::from_serialized(&data).unwrap().to_vec(), heights);
+        }
+
+        // A partial group at the end is not decoded
+        assert!(unpack_heights_bulk(&pack_4_heights(&[1, 2, 3, 4])[..8]).is_empty());
+    }
+
+    #[test]
+    fn test_verify_roundtrip() {
+        assert!(verify_roundtrip(&[]).is_ok());
+        assert!(verify_roundtrip(&[0, 1, 2, 3, 4, MAX_HEIGHT]).is_ok());
+        let err = verify_roundtrip(&[0, MAX_HEIGHT + 1]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_remainder_and_padding_semantics() {
+        // The remainder byte is entry count % 4 and is checked on every read path
+        for n in 0..8u32 {
+            let mut data = Vec::new();
+            serialize_heights(&vec![MAX_HEIGHT; n as usize], &mut data).unwrap();
+            assert_eq!(data[4], (n % 4) as u8);
+            for remainder in (0..=255u8).filter(|&r| u32::from(r) != n % 4) {
+                data[4] = remainder;
+                assert!(deserialize_heights(data.as_slice()).is_err());
+                assert!(PackedHeights::from_serialized(&data).is_err());
+            }
+        }
+
+        // Padding slots of the last group are written as zero and ignored on read
+        let mut data = Vec::new();
+        serialize_heights(&[7], &mut data).unwrap();
+        assert_eq!(unpack_4_heights(data[5..14].try_into().unwrap()), [7, 0, 0, 0]);
+        data[5..14].copy_from_slice(&pack_4_heights(&[7, 1, 2, 3]));
+        assert_eq!(deserialize_heights(data.as_slice()).unwrap(), [7]);
+        assert_eq!(PackedHeights::from_serialized(&data).unwrap().to_vec(), [7]);
+    }
+
+    #[test]
+    fn test_trailer_mode() {
+        let mut data = Vec::new();
+        serialize_heights(&[7, 8], &mut data).unwrap();
+        let heights_len = data.len();
+        data.extend_from_slice(b"trailer");
+
+        // Strict readers reject bytes after the last group
+        let err = deserialize_heights(data.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            TrailerMode::default(),
+            TrailerMode::Strict,
+            "strict unless asked otherwise"
+        );
+        assert!(PackedHeights::from_serialized(&data).is_err());
+        assert!(PackedHeights::from_static(data.clone().leak()).is_err());
+
+        // Lenient reading stops after the last group and leaves the rest to the caller
+        let mut reader = data.as_slice();
+        assert_eq!(
+            deserialize_heights_with_mode(&mut reader, TrailerMode::Lenient).unwrap(),
+            [7, 8]
+        );
+        assert_eq!(reader, b"trailer");
+
+        let (heights, trailer) = split_trailer(&data).unwrap();
+        assert_eq!(heights, &data[..heights_len]);
+        assert_eq!(trailer, b"trailer");
+        assert_eq!(PackedHeights::from_serialized(heights).unwrap().to_vec(), [7, 8]);
+        assert!(split_trailer(&data[..heights_len - 1]).is_err());
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Heights that fit in `bits` bits, for every width up to 18
+        fn heights_of_some_width() -> impl Strategy<Value = Vec<u32>> {
+            (0u32..=18).prop_flat_map(|bits| {
+                proptest::collection::vec(0..=(1u32 << bits) - 1, 0..64)
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn roundtrips(heights in heights_of_some_width()) {
+                prop_assert!(verify_roundtrip(&heights).is_ok());
+            }
+
+            #[test]
+            fn rejects_heights_over_18_bits(
+                mut heights in proptest::collection::vec(0..=MAX_HEIGHT, 0..16),
+                index in any::<prop::sample::Index>(),
+                excess in 1..=u32::MAX - MAX_HEIGHT,
+            ) {
+                heights.push(0);
+                let index = index.index(heights.len());
+                heights[index] = MAX_HEIGHT + excess;
+                let err = verify_roundtrip(&heights).unwrap_err();
+                prop_assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            }
+
+            #[test]
+            fn truncated_groups_are_rejected(
+                heights in proptest::collection::vec(0..=MAX_HEIGHT, 1..64),
+                cut in 1usize..=9,
+            ) {
+                let mut data = Vec::new();
+                serialize_heights(&heights, &mut data).unwrap();
+                data.truncate(data.len() - cut);
+                let err = deserialize_heights(data.as_slice()).unwrap_err();
+                prop_assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+                prop_assert!(PackedHeights::from_serialized(&data).is_err());
+            }
+
+            #[test]
+            fn trailing_bytes_are_rejected_unless_lenient(
+                heights in proptest::collection::vec(0..=MAX_HEIGHT, 0..64),
+                trailer in proptest::collection::vec(any::<u8>(), 1..16),
+            ) {
+                let mut data = Vec::new();
+                serialize_heights(&heights, &mut data).unwrap();
+                let heights_len = data.len();
+                data.extend_from_slice(&trailer);
+
+                let err = deserialize_heights(data.as_slice()).unwrap_err();
+                prop_assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+                prop_assert!(PackedHeights::from_serialized(&data).is_err());
+
+                let mut reader = data.as_slice();
+                let lenient = deserialize_heights_with_mode(&mut reader, TrailerMode::Lenient);
+                prop_assert_eq!(lenient.unwrap(), heights);
+                prop_assert_eq!(reader, trailer.as_slice());
+                prop_assert_eq!(split_trailer(&data).unwrap(), data.split_at(heights_len));
+            }
+
+            #[test]
+            fn corrupted_last_group_changes_only_its_heights(
+                heights in proptest::collection::vec(0..=MAX_HEIGHT, 1..64),
+                garbage in any::<[u8; 9]>(),
+            ) {
+                let mut data = Vec::new();
+                serialize_heights(&heights, &mut data).unwrap();
+                let last_group = data.len() - 9;
+                data[last_group..].copy_from_slice(&garbage);
+
+                let decoded = deserialize_heights(data.as_slice()).unwrap();
+                let in_last_group = (heights.len() - 1) / 4 * 4;
+                prop_assert_eq!(decoded.len(), heights.len());
+                prop_assert_eq!(&decoded[..in_last_group], &heights[..in_last_group]);
+                let garbage_heights = unpack_4_heights(&garbage);
+                prop_assert_eq!(
+                    &decoded[in_last_group..],
+                    &garbage_heights[..heights.len() - in_last_group]
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_packing_mathematics() {
         // Test the specific bit manipulation from the spec
@@ -0,0 +1,710 @@
+//! Configurable perfect hash function over block hashes
+//!
+//! [`Phf`] wraps a PtrHash instance whose bucket function and key hasher are
+//! chosen at build time through [`PhfConfig`], trading construction time for
+//! lookup speed or size. With the `fmph` feature, the `ph` crate's FMPH can be
+//! selected instead as a fallback backend. The configuration is recorded in the
+//! PtrHash asset.
+//!
+//! Asset layout: an optional header followed by the serialized function.
+//! `[magic: "HOPH"][version: u8][bucket_fn: u8][hash_fn: u8][backend: u8][lambda: f64][alpha: f64]`
+//! (little-endian, 0.0 = PtrHash default), followed in version 2 by `[remap: u8]`.
+//! The header is only written for non-default configurations, so default assets
+//! stay byte-identical to headerless ones and any headerless asset loads as
+//! [`PhfConfig::default`]. Version 1 is still written when the remap encoding
+//! is the default, so older readers load those assets too.
+
+use epserde::prelude::*;
+use ptr_hash::bucket_fn::{CubicEps, Linear};
+use ptr_hash::hash::{FxHash, Xx64};
+use ptr_hash::{DefaultPtrHash, PtrHash};
+use std::io::{self, Read, Write};
+
+/// Magic bytes of the PtrHash asset header
+pub const PHF_MAGIC: [u8; 4] = *b"HOPH";
+
+/// Current PtrHash asset header version
+pub const PHF_HEADER_VERSION: u8 = 2;
+
+/// Length of a version 1 header, which has no remap byte
+const HEADER_V1_LEN: usize = 24;
+
+const HEADER_LEN: usize = HEADER_V1_LEN + 1;
+
+/// Perfect hash implementation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum PhfBackend {
+    /// PtrHash (default), tuned by the other [`PhfConfig`] fields
+    #[default]
+    PtrHash,
+    /// FMPH from the `ph` crate (Feature: fmph); ignores the PtrHash parameters
+    #[cfg(feature = "fmph")]
+    Fmph,
+}
+
+/// Bucket function assigning keys to buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum BucketFunction {
+    /// PtrHash's default: slower to build, smaller and faster lookups
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "cubic-eps"))]
+    CubicEps,
+    /// Faster construction at a slightly larger size
+    Linear,
+}
+
+/// Hash function applied to the 32-byte keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum HashFunction {
+    /// FxHash (default): fastest, fine for uniformly distributed block hashes
+    #[default]
+    FxHash,
+    /// xxHash 64-bit: slower, more robust against adversarial key sets
+    Xx64,
+}
+
+/// Encoding of PtrHash's remap table
+///
+/// PtrHash places keys in `n / alpha` slots and remaps the keys that land past
+/// `n` back into `0..n` through this table. Only lookups of those keys read it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RemapEncoding {
+    /// Cache-line Elias-Fano (default): compact, one cache line per remapped lookup
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "cacheline-ef"))]
+    CachelineEf,
+    /// Plain `Vec<u32>`: 32 bits per remapped slot, no decoding on lookup
+    Vec,
+}
+
+impl RemapEncoding {
+    /// Every encoding, default first
+    pub const ALL: [Self; 2] = [Self::CachelineEf, Self::Vec];
+}
+
+/// Perfect hash construction parameters
+///
+/// `lambda` (average bucket size) and `alpha` (load factor) default to
+/// PtrHash's own defaults for the chosen bucket function.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct PhfConfig {
+    /// Implementation
+    pub backend: PhfBackend,
+    /// Bucket function
+    pub bucket_fn: BucketFunction,
+    /// Key hash function
+    pub hash_fn: HashFunction,
+    /// Average bucket size; higher is smaller but slower to build
+    pub lambda: Option<f64>,
+    /// Load factor of the slot array, at most 1.0
+    pub alpha: Option<f64>,
+    /// Remap table encoding
+    pub remap: RemapEncoding,
+}
+
+impl PhfConfig {
+    /// The default configuration (PtrHash with `CubicEps`, `FxHash` and default parameters)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the implementation
+    pub fn backend(mut self, backend: PhfBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the bucket function
+    pub fn bucket_fn(mut self, bucket_fn: BucketFunction) -> Self {
+        self.bucket_fn = bucket_fn;
+        self
+    }
+
+    /// Set the key hash function
+    pub fn hash_fn(mut self, hash_fn: HashFunction) -> Self {
+        self.hash_fn = hash_fn;
+        self
+    }
+
+    /// Set the average bucket size
+    pub fn lambda(mut self, lambda: f64) -> Self {
+        self.lambda = Some(lambda);
+        self
+    }
+
+    /// Set the load factor
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Set the remap table encoding
+    pub fn remap(mut self, remap: RemapEncoding) -> Self {
+        self.remap = remap;
+        self
+    }
+
+    /// Check that `lambda` is positive, `alpha` is in `(0, 1]` and the remap
+    /// encoding is the default unless the backend is PtrHash
+    ///
+    /// Builds fail with this error rather than hand PtrHash parameters it
+    /// cannot construct with; loading rejects assets that record them.
+    pub fn check_params(&self) -> io::Result<()> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        if let Some(lambda) = self.lambda {
+            if !(lambda > 0.0 && lambda.is_finite()) {
+                return Err(invalid(format!("lambda must be positive, got {lambda}")));
+            }
+        }
+        if let Some(alpha) = self.alpha {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(invalid(format!("alpha must be in (0, 1], got {alpha}")));
+            }
+        }
+        if self.backend != PhfBackend::PtrHash && self.remap != RemapEncoding::default() {
+            return Err(invalid(format!(
+                "remap encoding {:?} only applies to the PtrHash backend",
+                self.remap
+            )));
+        }
+        Ok(())
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    #[cfg(feature = "generate")]
+    fn apply<BF>(&self, mut params: ptr_hash::PtrHashParams<BF>) -> ptr_hash::PtrHashParams<BF> {
+        if let Some(lambda) = self.lambda {
+            params.lambda = lambda;
+        }
+        if let Some(alpha) = self.alpha {
+            params.alpha = alpha;
+        }
+        params
+    }
+
+    /// Header bytes, version 1 unless the remap encoding needs version 2
+    fn encode_header(&self) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[..4].copy_from_slice(&PHF_MAGIC);
+        header[4] = PHF_HEADER_VERSION;
+        header[5] = match self.bucket_fn {
+            BucketFunction::CubicEps => 0,
+            BucketFunction::Linear => 1,
+        };
+        header[6] = match self.hash_fn {
+            HashFunction::FxHash => 0,
+            HashFunction::Xx64 => 1,
+        };
+        header[7] = match self.backend {
+            PhfBackend::PtrHash => 0,
+            #[cfg(feature = "fmph")]
+            PhfBackend::Fmph => 1,
+        };
+        header[8..16].copy_from_slice(&self.lambda.unwrap_or(0.0).to_le_bytes());
+        header[16..24].copy_from_slice(&self.alpha.unwrap_or(0.0).to_le_bytes());
+        match self.remap {
+            RemapEncoding::CachelineEf => {
+                header[4] = 1;
+                header.truncate(HEADER_V1_LEN);
+            }
+            RemapEncoding::Vec => header[24] = 1,
+        }
+        header
+    }
+
+    /// Header length for `version`, or an error for unknown versions
+    fn header_len(version: u8) -> io::Result<usize> {
+        match version {
+            1 => Ok(HEADER_V1_LEN),
+            2 => Ok(HEADER_LEN),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported PtrHash header version {version} (expected at most {PHF_HEADER_VERSION})"
+                ),
+            )),
+        }
+    }
+
+    fn decode_header(header: &[u8]) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if header.len() != Self::header_len(header[4])? {
+            return Err(invalid(format!(
+                "PtrHash header version {} is {} bytes, not {}",
+                header[4],
+                Self::header_len(header[4])?,
+                header.len()
+            )));
+        }
+        let bucket_fn = match header[5] {
+            0 => BucketFunction::CubicEps,
+            1 => BucketFunction::Linear,
+            other => return Err(invalid(format!("Unknown bucket function id {other}"))),
+        };
+        let hash_fn = match header[6] {
+            0 => HashFunction::FxHash,
+            1 => HashFunction::Xx64,
+            other => return Err(invalid(format!("Unknown hash function id {other}"))),
+        };
+        let backend = match header[7] {
+            0 => PhfBackend::PtrHash,
+            #[cfg(feature = "fmph")]
+            1 => PhfBackend::Fmph,
+            #[cfg(not(feature = "fmph"))]
+            1 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Asset uses the FMPH backend; enable the `fmph` feature to load it",
+                ))
+            }
+            other => return Err(invalid(format!("Unknown backend id {other}"))),
+        };
+        let param = |bytes: &[u8]| {
+            let value = f64::from_le_bytes(bytes.try_into().unwrap());
+            (value != 0.0).then_some(value)
+        };
+        let remap = match header.get(24) {
+            None | Some(0) => RemapEncoding::CachelineEf,
+            Some(1) => RemapEncoding::Vec,
+            Some(other) => return Err(invalid(format!("Unknown remap encoding id {other}"))),
+        };
+        let config = Self {
+            backend,
+            bucket_fn,
+            hash_fn,
+            lambda: param(&header[8..16]),
+            alpha: param(&header[16..24]),
+            remap,
+        };
+        config
+            .check_params()
+            .map_err(|e| invalid(format!("PtrHash header: {e}")))?;
+        Ok(config)
+    }
+}
+
+/// PtrHash with a plain `Vec<u32>` remap table ([`RemapEncoding::Vec`])
+type VecRemapPtrHash<H, BF> = PtrHash<[u8; 32], BF, Vec<u32>, H, Vec<u8>>;
+
+enum Backend {
+    CubicEpsFx(DefaultPtrHash<FxHash, [u8; 32], CubicEps>),
+    CubicEpsXx(DefaultPtrHash<Xx64, [u8; 32], CubicEps>),
+    LinearFx(DefaultPtrHash<FxHash, [u8; 32], Linear>),
+    LinearXx(DefaultPtrHash<Xx64, [u8; 32], Linear>),
+    CubicEpsFxVec(VecRemapPtrHash<FxHash, CubicEps>),
+    CubicEpsXxVec(VecRemapPtrHash<Xx64, CubicEps>),
+    LinearFxVec(VecRemapPtrHash<FxHash, Linear>),
+    LinearXxVec(VecRemapPtrHash<Xx64, Linear>),
+    #[cfg(feature = "fmph")]
+    Fmph(ph::fmph::Function),
+}
+
+/// Match on `$backend`, running `$body` with `$phash` bound to the concrete
+/// PtrHash, or `$fmph_body` with `$fmph` bound to the FMPH function
+macro_rules! with_backend {
+    ($backend:expr, $phash:ident => $body:expr, $fmph:ident => $fmph_body:expr) => {
+        match $backend {
+            Backend::CubicEpsFx($phash) => $body,
+            Backend::CubicEpsXx($phash) => $body,
+            Backend::LinearFx($phash) => $body,
+            Backend::LinearXx($phash) => $body,
+            Backend::CubicEpsFxVec($phash) => $body,
+            Backend::CubicEpsXxVec($phash) => $body,
+            Backend::LinearFxVec($phash) => $body,
+            Backend::LinearXxVec($phash) => $body,
+            #[cfg(feature = "fmph")]
+            Backend::Fmph($fmph) => $fmph_body,
+        }
+    };
+}
+
+/// Build a PtrHash [`Backend`] for `$config`, with `$params` bound to the
+/// matching `PtrHashParams` and `$build` constructing the concrete PtrHash
+#[cfg(feature = "generate")]
+macro_rules! build_ptr_hash {
+    ($config:expr, $params:ident => $build:expr) => {{
+        use RemapEncoding::{CachelineEf, Vec as VecRemap};
+        match ($config.bucket_fn, $config.hash_fn, $config.remap) {
+            (BucketFunction::CubicEps, HashFunction::FxHash, remap) => {
+                let $params = $config.apply(ptr_hash::PtrHashParams::default());
+                match remap {
+                    CachelineEf => Backend::CubicEpsFx($build),
+                    VecRemap => Backend::CubicEpsFxVec($build),
+                }
+            }
+            (BucketFunction::CubicEps, HashFunction::Xx64, remap) => {
+                let $params = $config.apply(ptr_hash::PtrHashParams::default());
+                match remap {
+                    CachelineEf => Backend::CubicEpsXx($build),
+                    VecRemap => Backend::CubicEpsXxVec($build),
+                }
+            }
+            (BucketFunction::Linear, HashFunction::FxHash, remap) => {
+                let $params = $config.apply(ptr_hash::PtrHashParams::default_fast());
+                match remap {
+                    CachelineEf => Backend::LinearFx($build),
+                    VecRemap => Backend::LinearFxVec($build),
+                }
+            }
+            (BucketFunction::Linear, HashFunction::Xx64, remap) => {
+                let $params = $config.apply(ptr_hash::PtrHashParams::default_fast());
+                match remap {
+                    CachelineEf => Backend::LinearXx($build),
+                    VecRemap => Backend::LinearXxVec($build),
+                }
+            }
+        }
+    }};
+}
+
+/// Minimal perfect hash over 32-byte block hashes, mapping each key to `0..n`
+pub struct Phf {
+    config: PhfConfig,
+    backend: Backend,
+}
+
+impl Phf {
+    /// Build over `keys` (Feature: generate)
+    #[cfg(feature = "generate")]
+    pub fn build(keys: &[[u8; 32]], config: &PhfConfig) -> Self {
+        let backend = match config.backend {
+            PhfBackend::PtrHash => {
+                build_ptr_hash!(config, params => PtrHash::new(keys, params))
+            }
+            #[cfg(feature = "fmph")]
+            PhfBackend::Fmph => Backend::Fmph(ph::fmph::Function::from(keys)),
+        };
+        Self {
+            config: *config,
+            backend,
+        }
+    }
+
+    /// Build over `n` keys, calling `keys` for every pass over them (Feature: generate)
+    ///
+    /// Keys are never collected, so this works for key sets larger than memory.
+    #[cfg(feature = "generate")]
+    pub fn build_from_passes<F, I>(n: usize, keys: F, config: &PhfConfig) -> Self
+    where
+        F: Fn() -> I + Send + Sync,
+        I: Iterator<Item = [u8; 32]>,
+    {
+        use rayon::prelude::*;
+
+        let backend = match config.backend {
+            PhfBackend::PtrHash => {
+                // A single-item parallel iterator re-runs `keys` every time PtrHash iterates
+                let keys = (0..1usize).into_par_iter().flat_map_iter(|_| keys());
+                build_ptr_hash!(
+                    config,
+                    params => PtrHash::new_from_par_iter(n, keys, params)
+                )
+            }
+            #[cfg(feature = "fmph")]
+            PhfBackend::Fmph => Backend::Fmph(ph::fmph::Function::with_conf(
+                ph::fmph::keyset::DynamicKeySet::with_len(keys, n),
+                ph::fmph::BuildConf::default(),
+            )),
+        };
+        Self {
+            config: *config,
+            backend,
+        }
+    }
+
+    /// The configuration this function was built with
+    pub fn config(&self) -> &PhfConfig {
+        &self.config
+    }
+
+    /// Index of `key` in `0..n` (unchecked: keys outside the build set map to an arbitrary index)
+    #[inline]
+    pub fn index(&self, key: &[u8; 32]) -> usize {
+        with_backend!(
+            &self.backend,
+            phash => phash.index(key),
+            fmph => fmph.get(key).unwrap_or(0) as usize
+        )
+    }
+
+    /// Number of keys the function was built over
+    pub fn len(&self) -> usize {
+        with_backend!(
+            &self.backend,
+            phash => phash.n(),
+            fmph => fmph.len()
+        )
+    }
+
+    /// Check if the function was built over no keys
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Error unless every index fits a side table of `entries` entries
+    ///
+    /// Catches assets from different builds being loaded together, which would
+    /// otherwise panic (or return garbage) on the first lookup.
+    pub(crate) fn check_table_len(&self, entries: usize, table: &str) -> io::Result<()> {
+        if self.len() != entries {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "PtrHash asset covers {} keys but the {table} asset has {entries} entries",
+                    self.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// In-memory (pilots, remap) bits per element; FMPH reports everything as pilots
+    pub fn bits_per_element(&self) -> (f64, f64) {
+        with_backend!(
+            &self.backend,
+            phash => phash.bits_per_element(),
+            fmph => {
+                use ph::GetSize;
+                let bits = (fmph.size_bytes() * 8) as f64 / fmph.len().max(1) as f64;
+                (bits, 0.0)
+            }
+        )
+    }
+
+    /// Serialize as a PtrHash asset, returning the number of bytes written
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<usize> {
+        let mut written = 0;
+        if !self.config.is_default() {
+            let header = self.config.encode_header();
+            writer.write_all(&header)?;
+            written += header.len();
+        }
+        let serialized = with_backend!(
+            &self.backend,
+            phash => phash
+                .serialize(&mut writer)
+                .map_err(|e| io::Error::other(format!("Failed to serialize PtrHash: {e}")))?,
+            fmph => {
+                fmph.write(&mut writer)?;
+                fmph.write_bytes()
+            }
+        );
+        Ok(written + serialized)
+    }
+
+    /// Deserialize a PtrHash asset, with or without a config header
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let config = if magic == PHF_MAGIC {
+            let mut header = [0u8; HEADER_LEN];
+            header[..4].copy_from_slice(&magic);
+            reader.read_exact(&mut header[4..5])?;
+            let len = PhfConfig::header_len(header[4])?;
+            reader.read_exact(&mut header[5..len])?;
+            PhfConfig::decode_header(&header[..len])?
+        } else {
+            PhfConfig::default()
+        };
+
+        // Headerless assets start with PtrHash data: put the probed bytes back
+        let probed: &[u8] = if magic == PHF_MAGIC { &[] } else { &magic };
+        let mut reader = probed.chain(reader);
+
+        #[cfg(feature = "fmph")]
+        if config.backend == PhfBackend::Fmph {
+            let fmph = ph::fmph::Function::read(&mut reader)?;
+            return Ok(Self {
+                config,
+                backend: Backend::Fmph(fmph),
+            });
+        }
+
+        macro_rules! deserialize {
+            ($variant:ident) => {
+                Backend::$variant(
+                    Deserialize::deserialize_full(&mut reader).map_err(deserialize_error)?,
+                )
+            };
+        }
+        use RemapEncoding::{CachelineEf, Vec as VecRemap};
+        let backend = match (config.bucket_fn, config.hash_fn, config.remap) {
+            (BucketFunction::CubicEps, HashFunction::FxHash, CachelineEf) => {
+                deserialize!(CubicEpsFx)
+            }
+            (BucketFunction::CubicEps, HashFunction::Xx64, CachelineEf) => {
+                deserialize!(CubicEpsXx)
+            }
+            (BucketFunction::Linear, HashFunction::FxHash, CachelineEf) => deserialize!(LinearFx),
+            (BucketFunction::Linear, HashFunction::Xx64, CachelineEf) => deserialize!(LinearXx),
+            (BucketFunction::CubicEps, HashFunction::FxHash, VecRemap) => {
+                deserialize!(CubicEpsFxVec)
+            }
+            (BucketFunction::CubicEps, HashFunction::Xx64, VecRemap) => {
+                deserialize!(CubicEpsXxVec)
+            }
+            (BucketFunction::Linear, HashFunction::FxHash, VecRemap) => deserialize!(LinearFxVec),
+            (BucketFunction::Linear, HashFunction::Xx64, VecRemap) => deserialize!(LinearXxVec),
+        };
+        Ok(Self { config, backend })
+    }
+}
+
+fn deserialize_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Failed to deserialize PtrHash: {e}"),
+    )
+}
+
+impl From<crate::PtrHashType> for Phf {
+    fn from(phash: crate::PtrHashType) -> Self {
+        Self {
+            config: PhfConfig::default(),
+            backend: Backend::CubicEpsFx(phash),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let config = PhfConfig::new()
+            .bucket_fn(BucketFunction::Linear)
+            .hash_fn(HashFunction::Xx64)
+            .lambda(3.0);
+        let header = config.encode_header();
+        assert_eq!(&header[..4], b"HOPH");
+        assert_eq!(PhfConfig::decode_header(&header).unwrap(), config);
+
+        let mut bad = header.clone();
+        bad[5] = 9;
+        assert_eq!(
+            PhfConfig::decode_header(&bad).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_remap_header_versions() {
+        // The default remap encoding keeps writing version 1 headers
+        let v1 = PhfConfig::new().lambda(3.0).encode_header();
+        assert_eq!((v1[4], v1.len()), (1, HEADER_V1_LEN));
+
+        let config = PhfConfig::new().remap(RemapEncoding::Vec);
+        assert!(!config.is_default());
+        let v2 = config.encode_header();
+        assert_eq!((v2[4], v2.len()), (2, HEADER_LEN));
+        assert_eq!(PhfConfig::decode_header(&v2).unwrap(), config);
+
+        let mut bad = v2.clone();
+        bad[24] = 7;
+        assert!(PhfConfig::decode_header(&bad).is_err());
+        assert!(PhfConfig::decode_header(&v2[..HEADER_V1_LEN]).is_err());
+        let mut future = v2;
+        future[4] = 3;
+        assert!(PhfConfig::decode_header(&future).is_err());
+    }
+
+    #[test]
+    fn test_check_params() {
+        assert!(PhfConfig::new()
+            .lambda(3.0)
+            .alpha(1.0)
+            .check_params()
+            .is_ok());
+        for config in [
+            PhfConfig::new().lambda(0.0),
+            PhfConfig::new().lambda(-1.0),
+            PhfConfig::new().lambda(f64::NAN),
+            PhfConfig::new().lambda(f64::INFINITY),
+            PhfConfig::new().alpha(0.0),
+            PhfConfig::new().alpha(1.5),
+            PhfConfig::new().alpha(f64::NAN),
+        ] {
+            let err = config.check_params().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        // A forged header with an out-of-range load factor does not load
+        let mut header = PhfConfig::new().alpha(0.98).encode_header();
+        header[16..24].copy_from_slice(&2.0f64.to_le_bytes());
+        let err = PhfConfig::decode_header(&header).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("alpha must be in (0, 1]"));
+    }
+
+    #[test]
+    fn test_default_config_has_no_header() {
+        assert!(PhfConfig::default().is_default());
+        assert!(!PhfConfig::new().alpha(0.98).is_default());
+    }
+
+    #[test]
+    #[cfg(all(feature = "fmph", feature = "generate"))]
+    fn test_fmph_roundtrip() {
+        let keys: Vec<[u8; 32]> = (0..1000u32)
+            .map(|i| {
+                let mut key = [0u8; 32];
+                key[..4].copy_from_slice(&i.to_le_bytes());
+                key[31] = 0xAB;
+                key
+            })
+            .collect();
+        let config = PhfConfig::new().backend(PhfBackend::Fmph);
+
+        let phf = Phf::build(&keys, &config);
+        let mut indices: Vec<usize> = keys.iter().map(|key| phf.index(key)).collect();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..keys.len()).collect::<Vec<_>>());
+
+        let streamed = Phf::build_from_passes(keys.len(), || keys.iter().copied(), &config);
+        let mut asset = Vec::new();
+        let written = streamed.write(&mut asset).unwrap();
+        assert_eq!(written, asset.len());
+
+        let loaded = Phf::read(asset.as_slice()).unwrap();
+        assert_eq!(loaded.config(), &config);
+        for key in &keys {
+            assert_eq!(loaded.index(key), streamed.index(key));
+        }
+
+        // FMPH has no remap table to encode
+        let err = config.remap(RemapEncoding::Vec).check_params().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("only applies to the PtrHash backend"));
+    }
+}
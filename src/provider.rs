@@ -0,0 +1,269 @@
+//! Integration trait for block indexers
+//!
+//! Indexers (electrs, Fulcrum and the like) that want pre-BIP34 heights should
+//! code against [`BlockHeightProvider`] rather than a concrete oracle type: the
+//! oracle types keep gaining loaders, stores and options, while this trait
+//! stays small. Every oracle flavor implements it, and so do `Box` and `Arc`
+//! of one, so a `Box<dyn BlockHeightProvider>` can be picked at startup.
+//!
+//! The oracles do not keep the block hashes, so their domain check only
+//! rejects hashes whose height falls outside the recorded height range. Wrap
+//! one in a [`FilteredProvider`] with the XOR filter built by `--filter` to
+//! also reject all but about 1 in 256 hashes from outside the dataset.
+
+use crate::filter::XorFilter;
+use crate::BlockHash;
+use std::sync::Arc;
+
+/// Block hash → height lookups for indexers
+pub trait BlockHeightProvider: Send + Sync {
+    /// Height of `block_hash`, or `None` if [`Self::contains_block`] rejects it
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32>;
+
+    /// Heights of `block_hashes` into `heights`, like [`Self::block_height`] for each
+    ///
+    /// Panics if the lengths differ.
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        assert_eq!(
+            block_hashes.len(),
+            heights.len(),
+            "one output slot per block hash"
+        );
+        for (height, block_hash) in heights.iter_mut().zip(block_hashes) {
+            *height = self.block_height(block_hash);
+        }
+    }
+
+    /// Whether `block_hash` may be in the provider's domain
+    ///
+    /// Never false for a hash that is; how many others it lets through
+    /// depends on the provider. The oracle types themselves pass every hash
+    /// that maps to a height in their recorded range, which is nearly any
+    /// hash, so on a bare oracle this says almost nothing. Wrap it in a
+    /// [`FilteredProvider`] for a check that rejects foreign hashes.
+    fn contains_block(&self, block_hash: &BlockHash) -> bool {
+        self.block_height(block_hash).is_some()
+    }
+}
+
+impl<P: BlockHeightProvider + ?Sized> BlockHeightProvider for Box<P> {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        (**self).block_height(block_hash)
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        (**self).block_heights(block_hashes, heights)
+    }
+
+    fn contains_block(&self, block_hash: &BlockHash) -> bool {
+        (**self).contains_block(block_hash)
+    }
+}
+
+impl<P: BlockHeightProvider + ?Sized> BlockHeightProvider for Arc<P> {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        (**self).block_height(block_hash)
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        (**self).block_heights(block_hashes, heights)
+    }
+
+    fn contains_block(&self, block_hash: &BlockHash) -> bool {
+        (**self).contains_block(block_hash)
+    }
+}
+
+/// A provider whose domain check also asks an [`XorFilter`] over the dataset
+pub struct FilteredProvider<P> {
+    inner: P,
+    filter: XorFilter,
+}
+
+impl<P: BlockHeightProvider> FilteredProvider<P> {
+    /// Check hashes against `filter` before looking them up in `inner`
+    ///
+    /// `filter` must be built over the same dataset as `inner`, or hashes in
+    /// the dataset may be rejected.
+    pub fn new(inner: P, filter: XorFilter) -> Self {
+        Self { inner, filter }
+    }
+
+    /// The wrapped provider
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// The filter hashes are checked against
+    pub fn filter(&self) -> &XorFilter {
+        &self.filter
+    }
+}
+
+impl<P: BlockHeightProvider> BlockHeightProvider for FilteredProvider<P> {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        if !self.filter.contains(block_hash) {
+            return None;
+        }
+        self.inner.block_height(block_hash)
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        self.inner.block_heights(block_hashes, heights);
+        for (height, block_hash) in heights.iter_mut().zip(block_hashes) {
+            if !self.filter.contains(block_hash) {
+                *height = None;
+            }
+        }
+    }
+
+    fn contains_block(&self, block_hash: &BlockHash) -> bool {
+        self.filter.contains(block_hash) && self.inner.contains_block(block_hash)
+    }
+}
+
+/// `height` if it lies in the oracle's recorded height range, if any
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+fn within(range: Option<&std::ops::Range<u32>>, height: Option<u32>) -> Option<u32> {
+    height.filter(|height| range.is_none_or(|range| range.contains(height)))
+}
+
+/// Batched lookup through the oracle's [`crate::HeightLookup`] implementation
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+fn batched<O: crate::HeightLookup + ?Sized>(
+    oracle: &O,
+    range: Option<&std::ops::Range<u32>>,
+    block_hashes: &[BlockHash],
+    heights: &mut [Option<u32>],
+) {
+    assert_eq!(
+        block_hashes.len(),
+        heights.len(),
+        "one output slot per block hash"
+    );
+    let mut found = vec![0; block_hashes.len()];
+    oracle.get_heights_unchecked(block_hashes, &mut found);
+    for (height, found) in heights.iter_mut().zip(found) {
+        *height = within(range, Some(found));
+    }
+}
+
+#[cfg(feature = "generate")]
+impl BlockHeightProvider for crate::HeightOracle {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        within(
+            self.height_range().as_ref(),
+            self.get_height_opt(block_hash),
+        )
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        batched(self, self.height_range().as_ref(), block_hashes, heights)
+    }
+}
+
+#[cfg(feature = "generate")]
+impl BlockHeightProvider for crate::HeightOracleLoaded {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        within(
+            self.height_range().as_ref(),
+            self.get_height_opt(block_hash),
+        )
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        batched(self, self.height_range().as_ref(), block_hashes, heights)
+    }
+}
+
+#[cfg(any(
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+impl BlockHeightProvider for crate::HeightOracleEmbedded {
+    fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+        within(
+            self.height_range().as_ref(),
+            self.get_height_opt(block_hash),
+        )
+    }
+
+    fn block_heights(&self, block_hashes: &[BlockHash], heights: &mut [Option<u32>]) {
+        batched(self, self.height_range().as_ref(), block_hashes, heights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Knows a single block at height 7
+    struct Single(BlockHash);
+
+    impl BlockHeightProvider for Single {
+        fn block_height(&self, block_hash: &BlockHash) -> Option<u32> {
+            (*block_hash == self.0).then_some(7)
+        }
+    }
+
+    #[test]
+    fn test_filtered_provider() {
+        let known = BlockHash::from([1; 32]);
+        let block_hashes: Vec<BlockHash> = (1..=200).map(|i| BlockHash::from([i; 32])).collect();
+        let provider: Box<dyn BlockHeightProvider> = Box::new(FilteredProvider::new(
+            Single(known),
+            XorFilter::build(&block_hashes),
+        ));
+
+        assert_eq!(provider.block_height(&known), Some(7));
+        assert!(provider.contains_block(&known));
+        let mut heights = vec![Some(0); 2];
+        provider.block_heights(&[known, BlockHash::from([2; 32])], &mut heights);
+        assert_eq!(heights, [Some(7), None]);
+
+        // The filter never rejects a hash it was built over
+        let arc: Arc<dyn BlockHeightProvider> = Arc::new(FilteredProvider::new(
+            Single(block_hashes[199]),
+            XorFilter::build(&block_hashes),
+        ));
+        assert_eq!(arc.block_height(&block_hashes[199]), Some(7));
+    }
+
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_oracle_provider() {
+        use crate::generate::test_support::{test_hashes, test_oracle};
+
+        let block_hashes = test_hashes(5);
+        let heights: Vec<u32> = (10..15).collect();
+        let oracle = test_oracle(&block_hashes, &heights);
+
+        let mut found = vec![None; 5];
+        oracle.block_heights(&block_hashes, &mut found);
+        assert_eq!(found, heights.iter().copied().map(Some).collect::<Vec<_>>());
+        for (block_hash, &height) in block_hashes.iter().zip(&heights) {
+            assert_eq!(oracle.block_height(block_hash), Some(height));
+            assert!(oracle.contains_block(block_hash));
+        }
+
+        let filtered = FilteredProvider::new(oracle, XorFilter::build(&block_hashes));
+        let outside: Vec<BlockHash> = (100..=255).map(|i| BlockHash::from([i; 32])).collect();
+        let rejected = outside
+            .iter()
+            .filter(|block_hash| !filtered.contains_block(block_hash))
+            .count();
+        assert!(rejected > outside.len() / 2);
+        assert_eq!(filtered.block_height(&block_hashes[0]), Some(10));
+    }
+}
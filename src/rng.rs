@@ -0,0 +1,14 @@
+//! Small deterministic random number generator for sampling and jitter
+//!
+//! Nothing here needs cryptographic randomness, only a well-mixed sequence
+//! that repeats for the same seed, so the crate uses splitmix64 rather than
+//! pulling in a `rand` dependency.
+
+/// Advance `state` and return the next splitmix64 output
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
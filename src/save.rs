@@ -0,0 +1,92 @@
+//! Atomic asset writes
+//!
+//! Assets and fetch state are written to a temporary file next to their path
+//! and renamed into place, so readers never see a half-written file.
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Options for saving assets
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct SaveOptions {
+    fsync: bool,
+}
+
+impl SaveOptions {
+    /// Default options: atomic rename, no fsync
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flush each file and its directory to disk before returning, so a finished
+    /// save also survives power loss (slower; meant for automated pipelines)
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+}
+
+/// Write `path` atomically: write a temporary file next to it, then rename it into place
+///
+/// A crash mid-write leaves the previous file (or none) instead of a truncated
+/// asset. The temporary file is removed if `write` fails.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))
+)]
+pub fn write_atomic<P: AsRef<Path>>(
+    path: P,
+    options: &SaveOptions,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Not a file path: {}", path.display()))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let written = File::create(&tmp_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        if options.fsync {
+            file.sync_all()?;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            bytes = file.metadata()?.len(),
+            fsync = options.fsync,
+            "wrote asset"
+        );
+        drop(file);
+        std::fs::rename(&tmp_path, path)
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e).with_context(|| format!("Failed to write {}", path.display()));
+    }
+
+    // The rename itself is only durable once the directory entry is synced
+    #[cfg(unix)]
+    if options.fsync {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync directory: {}", dir.display()))?;
+    }
+    Ok(())
+}
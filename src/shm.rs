@@ -0,0 +1,326 @@
+//! Decoded heights in a named shared-memory segment (Feature: shm)
+//!
+//! Worker pools that each load the oracle decode the same 227k heights once
+//! per process. A coordinator instead calls [`SharedSegment::create`] once,
+//! which decodes the assets into a segment file under `/dev/shm` (tmpfs on
+//! Linux), and every worker calls [`SharedSegment::attach`], which maps that
+//! file read-only. The heights then live in one copy in the page cache, shared
+//! by all workers, and attaching decodes nothing. Each worker still
+//! deserializes its own PtrHash (a few bits per key) from the segment.
+//!
+//! A segment is written to a temporary file and renamed into place, so workers
+//! never attach to a half-written one. The temporary file is always created
+//! anew, never opened through an existing file or symlink, since `/dev/shm` is
+//! writable by every user. Layout, little-endian:
+//! [magic: "HOSM"][layout: u32][ptrhash_len: u64][header_len: u32][entries: u32]
+//! [PtrHash asset][heights asset header][zero padding to 4 bytes][entries × height: u32]
+
+use crate::embedded::HeightOracleEmbedded;
+use crate::format::{self, AssetKind};
+use crate::phf::Phf;
+use crate::store::HeightStore;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Magic bytes at the start of a segment
+pub const SEGMENT_MAGIC: [u8; 4] = *b"HOSM";
+
+/// Segment layout version
+const SEGMENT_LAYOUT: u32 = 1;
+
+/// Size of the fixed segment header
+const SEGMENT_HEADER_LEN: usize = 24;
+
+/// A named shared-memory segment holding an oracle
+#[derive(Debug, Clone)]
+pub struct SharedSegment {
+    path: PathBuf,
+}
+
+impl SharedSegment {
+    /// Where segments are created by default: `/dev/shm` if present, else the temp directory
+    pub fn default_dir() -> PathBuf {
+        let shm = Path::new("/dev/shm");
+        if shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        }
+    }
+
+    /// Decode a PtrHash asset and a heights asset into the segment `name`
+    ///
+    /// Replaces an existing segment of that name; workers already attached
+    /// keep the old copy until they attach again.
+    pub fn create(name: &str, ptrhash_data: &[u8], heights_data: &[u8]) -> io::Result<Self> {
+        Self::create_in(Self::default_dir(), name, ptrhash_data, heights_data)
+    }
+
+    /// Like [`Self::create`], in `dir` instead of [`Self::default_dir`]
+    pub fn create_in<P: AsRef<Path>>(
+        dir: P,
+        name: &str,
+        ptrhash_data: &[u8],
+        heights_data: &[u8],
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        let segment = Self::open_in(dir, name)?;
+
+        let payload = format::strip_header(heights_data, AssetKind::Heights)?;
+        let header = &heights_data[..heights_data.len() - payload.len()];
+        let heights = crate::packing::deserialize_heights(io::Cursor::new(payload))?;
+        Phf::read(format::strip_header(ptrhash_data, AssetKind::PtrHash)?)?
+            .check_table_len(heights.len(), "heights")?;
+
+        // Segment names never start with '.', so this cannot clash with one
+        let tmp_path = dir.join(format!(".{name}.{}.tmp", std::process::id()));
+        // Left over from a crashed run with the same pid; removing a symlink
+        // removes the link, not its target
+        match std::fs::remove_file(&tmp_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let tmp_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)?;
+        let written = (|| {
+            let mut writer = BufWriter::new(tmp_file);
+            writer.write_all(&SEGMENT_MAGIC)?;
+            writer.write_all(&SEGMENT_LAYOUT.to_le_bytes())?;
+            writer.write_all(&(ptrhash_data.len() as u64).to_le_bytes())?;
+            writer.write_all(&(header.len() as u32).to_le_bytes())?;
+            writer.write_all(&(heights.len() as u32).to_le_bytes())?;
+            writer.write_all(ptrhash_data)?;
+            writer.write_all(header)?;
+            let unaligned = SEGMENT_HEADER_LEN + ptrhash_data.len() + header.len();
+            writer.write_all(&[0; 3][..unaligned.next_multiple_of(4) - unaligned])?;
+            for height in &heights {
+                writer.write_all(&height.to_le_bytes())?;
+            }
+            writer.flush()?;
+            std::fs::rename(&tmp_path, &segment.path)
+        })();
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        written?;
+        Ok(segment)
+    }
+
+    /// The segment `name` in [`Self::default_dir`], for workers to attach to
+    ///
+    /// Does not check that it exists; [`Self::attach`] does.
+    pub fn open(name: &str) -> io::Result<Self> {
+        Self::open_in(Self::default_dir(), name)
+    }
+
+    /// Like [`Self::open`], in `dir`
+    pub fn open_in<P: AsRef<Path>>(dir: P, name: &str) -> io::Result<Self> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid segment name {name:?}: use letters, digits, '-', '_' and '.'"),
+            ));
+        }
+        Ok(Self {
+            path: dir.as_ref().join(name),
+        })
+    }
+
+    /// Path of the segment file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Map the segment and load an oracle over it
+    ///
+    /// Lookups read the heights from the shared mapping.
+    pub fn attach(&self) -> io::Result<HeightOracleEmbedded> {
+        let file = File::open(&self.path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to open segment {}: {e}", self.path.display()),
+            )
+        })?;
+        // SAFETY: segments are replaced by rename, never rewritten in place
+        let map = Arc::new(unsafe { memmap2::Mmap::map(&file)? });
+        let layout = SegmentLayout::parse(&map)?;
+        let heights = SharedHeights {
+            map: Arc::clone(&map),
+            start: layout.heights_start,
+            len: layout.entries,
+        };
+        HeightOracleEmbedded::from_store_with_header(
+            &map[layout.ptrhash],
+            &map[layout.header],
+            heights,
+        )
+    }
+
+    /// Delete the segment file; attached workers keep their mapping
+    pub fn remove(self) -> io::Result<()> {
+        std::fs::remove_file(&self.path)
+    }
+}
+
+/// Where the parts of a segment are
+struct SegmentLayout {
+    ptrhash: Range<usize>,
+    header: Range<usize>,
+    heights_start: usize,
+    entries: usize,
+}
+
+impl SegmentLayout {
+    fn parse(data: &[u8]) -> io::Result<Self> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Not a height oracle segment: {reason}"),
+            )
+        };
+        let header = data
+            .first_chunk::<SEGMENT_HEADER_LEN>()
+            .ok_or_else(|| invalid("too short"))?;
+        if header[..4] != SEGMENT_MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        let layout = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if layout != SEGMENT_LAYOUT {
+            return Err(invalid(&format!("unsupported layout {layout}")));
+        }
+        let ptrhash_len = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let header_len = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+        let entries = u32::from_le_bytes(header[20..24].try_into().unwrap()) as usize;
+
+        let ptrhash_end = usize::try_from(ptrhash_len)
+            .ok()
+            .and_then(|len| SEGMENT_HEADER_LEN.checked_add(len))
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| invalid("PtrHash asset past the end"))?;
+        let header_end = ptrhash_end
+            .checked_add(header_len)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| invalid("heights header past the end"))?;
+        let heights_start = header_end
+            .checked_next_multiple_of(4)
+            .ok_or_else(|| invalid("heights past the end"))?;
+        let heights_len = entries
+            .checked_mul(4)
+            .ok_or_else(|| invalid("too many entries"))?;
+        if heights_start.checked_add(heights_len) != Some(data.len()) {
+            return Err(invalid("truncated"));
+        }
+        Ok(Self {
+            ptrhash: SEGMENT_HEADER_LEN..ptrhash_end,
+            header: ptrhash_end..header_end,
+            heights_start,
+            entries,
+        })
+    }
+}
+
+/// Decoded heights read from a mapped segment
+struct SharedHeights {
+    map: Arc<memmap2::Mmap>,
+    start: usize,
+    len: usize,
+}
+
+impl HeightStore for SharedHeights {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        let offset = self.start + index * 4;
+        u32::from_le_bytes(self.map[offset..offset + 4].try_into().unwrap())
+    }
+}
+
+#[cfg(all(test, feature = "generate"))]
+mod tests {
+    use super::*;
+    use crate::generate::test_support::{asset_bytes, test_hashes, test_oracle_with};
+
+    #[test]
+    fn test_create_and_attach() {
+        let block_hashes = test_hashes(7);
+        let heights: Vec<u32> = (100..107).collect();
+        let options = crate::BuildOptions::new().height_range(100..200);
+        let oracle = test_oracle_with(&block_hashes, &heights, options);
+        let dir = tempfile::tempdir().unwrap();
+        let (ptrhash_data, heights_data) = asset_bytes(&oracle);
+
+        let segment =
+            SharedSegment::create_in(dir.path(), "oracle", &ptrhash_data, &heights_data).unwrap();
+        let workers = [
+            segment.attach().unwrap(),
+            SharedSegment::open_in(dir.path(), "oracle")
+                .unwrap()
+                .attach()
+                .unwrap(),
+        ];
+        for worker in &workers {
+            for (block_hash, &height) in block_hashes.iter().zip(&heights) {
+                assert_eq!(worker.get_height_opt(block_hash), Some(height));
+            }
+            assert_eq!(worker.height_range(), Some(100..200));
+        }
+
+        // A symlink planted at the temporary path is replaced, not written through
+        #[cfg(unix)]
+        {
+            let victim = dir.path().join("victim");
+            std::fs::write(&victim, b"keep").unwrap();
+            let tmp_path = dir
+                .path()
+                .join(format!(".linked.{}.tmp", std::process::id()));
+            std::os::unix::fs::symlink(&victim, &tmp_path).unwrap();
+            SharedSegment::create_in(dir.path(), "linked", &ptrhash_data, &heights_data).unwrap();
+            assert_eq!(std::fs::read(&victim).unwrap(), b"keep");
+        }
+
+        // Mismatched assets are refused, bad names and damaged segments too
+        let short_heights = {
+            let mut data = Vec::new();
+            crate::packing::serialize_heights(&heights[..6], &mut data).unwrap();
+            data
+        };
+        assert!(SharedSegment::create_in(dir.path(), "x", &ptrhash_data, &short_heights).is_err());
+        assert!(SharedSegment::open_in(dir.path(), "../oracle").is_err());
+        // Damaged under its own name: the workers map `segment`, which is never
+        // rewritten in place
+        let data = std::fs::read(segment.path()).unwrap();
+        let damaged = SharedSegment::open_in(dir.path(), "damaged").unwrap();
+        std::fs::write(damaged.path(), &data[..data.len() - 1]).unwrap();
+        assert!(damaged.attach().is_err());
+
+        // Lengths that overflow or point past the end are refused, not panicked on
+        let mut forged = data.clone();
+        forged[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(SegmentLayout::parse(&forged).is_err());
+        let mut forged = data.clone();
+        forged[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SegmentLayout::parse(&forged).is_err());
+        let mut forged = data.clone();
+        forged[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SegmentLayout::parse(&forged).is_err());
+
+        // Attached workers keep working after removal
+        segment.clone().remove().unwrap();
+        assert!(segment.attach().is_err());
+        assert_eq!(workers[0].get_height_opt(&block_hashes[0]), Some(100));
+    }
+}
@@ -0,0 +1,84 @@
+//! Per-block side tables indexed by the height oracle's perfect hash
+//!
+//! Every block in an oracle gets a distinct perfect-hash index (see
+//! `HeightOracle::index_of`), so any per-block value can be stored in a plain
+//! table by that index and share the PtrHash asset. A [`SideTable`] pairs such
+//! a table with the perfect hash it was built against; the timestamp, median
+//! time past and filter header oracles are side tables of their own values.
+
+use crate::format::{self, AssetKind};
+use crate::phf::Phf;
+use crate::BlockHash;
+use std::io;
+
+/// One value per block, looked up through the height oracle's perfect hash
+pub struct SideTable<V> {
+    phash: Phf,
+    values: Vec<V>,
+}
+
+impl<V: Copy> SideTable<V> {
+    /// Pair `values`, stored by perfect-hash index, with the perfect hash
+    ///
+    /// Fails with `InvalidData` if the two cover a different number of blocks,
+    /// naming the `table` in the error.
+    pub fn new(phash: Phf, values: Vec<V>, table: &str) -> io::Result<Self> {
+        phash.check_table_len(values.len(), table)?;
+        Ok(Self { phash, values })
+    }
+
+    /// Like [`Self::new`], reading the perfect hash from a PtrHash asset
+    pub fn from_bytes(ptrhash_data: &[u8], values: Vec<V>, table: &str) -> io::Result<Self> {
+        let phash = Phf::read(format::strip_header(ptrhash_data, AssetKind::PtrHash)?)?;
+        Self::new(phash, values, table)
+    }
+
+    /// Value stored for a block hash (unchecked)
+    ///
+    /// Like the height lookups, hashes outside the dataset return the value of
+    /// some other block.
+    pub fn get_unchecked(&self, block_hash: &BlockHash) -> V {
+        self.values[self.phash.index(block_hash.as_bytes())]
+    }
+
+    /// Get the number of blocks in the table
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(feature = "generate")]
+    fn test_side_table() {
+        use super::*;
+        use crate::generate::test_support::{asset_bytes, test_hashes, test_oracle};
+
+        let block_hashes = test_hashes(5);
+        let oracle = test_oracle(&block_hashes, &[0, 1, 2, 3, 4]);
+        let (ptrhash_data, _) = asset_bytes(&oracle);
+
+        // Squares of the heights, stored by perfect-hash index
+        let mut values = vec![0u64; 5];
+        for (height, block_hash) in block_hashes.iter().enumerate() {
+            values[oracle.index_of(block_hash)] = (height * height) as u64;
+        }
+        let table = SideTable::from_bytes(&ptrhash_data, values, "squares").unwrap();
+        assert_eq!(table.len(), 5);
+        for (height, block_hash) in block_hashes.iter().enumerate() {
+            assert_eq!(table.get_unchecked(block_hash), (height * height) as u64);
+        }
+
+        let err = SideTable::from_bytes(&ptrhash_data, vec![0u64; 4], "squares").err();
+        assert!(err
+            .unwrap()
+            .to_string()
+            .contains("squares asset has 4 entries"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Detached ed25519 signatures over asset sets (Feature: signing)
+//!
+//! [`HeightOracle::save_to_paths_signed`](crate::HeightOracle::save_to_paths_signed)
+//! signs both assets and writes the signature next to the heights asset, at
+//! [`signature_path`]. [`HeightOracle::load_verified_signed`](crate::HeightOracle::load_verified_signed)
+//! refuses assets whose signature is missing or does not verify under the
+//! given public key.
+//!
+//! The signed message is the tag `height-oracle/assets/v1`, then the SHA-256
+//! of the PtrHash asset file, then the SHA-256 of the heights asset file. A
+//! signature file is [magic: "HOSG"][signature: 64 bytes].
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// Domain tag at the start of the signed message
+pub const DOMAIN_TAG: &[u8] = b"height-oracle/assets/v1";
+
+/// Magic bytes at the start of a signature file
+pub const SIGNATURE_MAGIC: [u8; 4] = *b"HOSG";
+
+/// Size of a signature file
+pub const SIGNATURE_FILE_LEN: usize = SIGNATURE_MAGIC.len() + ed25519_dalek::SIGNATURE_LENGTH;
+
+/// Where the signature of an asset set with heights asset `heights_path` is stored
+pub fn signature_path<P: AsRef<Path>>(heights_path: P) -> PathBuf {
+    let mut path = heights_path.as_ref().as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+fn message(ptrhash_data: &[u8], heights_data: &[u8]) -> Vec<u8> {
+    let mut message = DOMAIN_TAG.to_vec();
+    message.extend_from_slice(&Sha256::digest(ptrhash_data));
+    message.extend_from_slice(&Sha256::digest(heights_data));
+    message
+}
+
+/// Sign the bytes of a PtrHash asset and a heights asset, returning the signature file
+pub fn sign_assets(
+    key: &SigningKey,
+    ptrhash_data: &[u8],
+    heights_data: &[u8],
+) -> [u8; SIGNATURE_FILE_LEN] {
+    use ed25519_dalek::Signer;
+
+    let signature = key.sign(&message(ptrhash_data, heights_data));
+    let mut file = [0u8; SIGNATURE_FILE_LEN];
+    file[..SIGNATURE_MAGIC.len()].copy_from_slice(&SIGNATURE_MAGIC);
+    file[SIGNATURE_MAGIC.len()..].copy_from_slice(&signature.to_bytes());
+    file
+}
+
+/// Check a signature file against the bytes of a PtrHash asset and a heights asset
+pub fn verify_assets(
+    key: &VerifyingKey,
+    ptrhash_data: &[u8],
+    heights_data: &[u8],
+    signature_file: &[u8],
+) -> Result<()> {
+    let signature = signature_file
+        .strip_prefix(&SIGNATURE_MAGIC)
+        .and_then(|signature| <&[u8; ed25519_dalek::SIGNATURE_LENGTH]>::try_from(signature).ok())
+        .context("Not an asset signature file")?;
+    key.verify_strict(
+        &message(ptrhash_data, heights_data),
+        &ed25519_dalek::Signature::from_bytes(signature),
+    )
+    .context("Asset signature does not verify with the given public key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = key.verifying_key();
+        let signature = sign_assets(&key, b"ptrhash", b"heights");
+        verify_assets(&public_key, b"ptrhash", b"heights", &signature).unwrap();
+
+        // Either asset changing, another key or a damaged file fails
+        assert!(verify_assets(&public_key, b"ptrhash", b"heightz", &signature).is_err());
+        assert!(verify_assets(&public_key, b"heights", b"ptrhash", &signature).is_err());
+        let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(verify_assets(&other_key, b"ptrhash", b"heights", &signature).is_err());
+        assert!(verify_assets(&public_key, b"ptrhash", b"heights", &signature[1..]).is_err());
+
+        assert_eq!(
+            signature_path("assets/heights.u18packed.dat"),
+            Path::new("assets/heights.u18packed.dat.sig")
+        );
+    }
+}
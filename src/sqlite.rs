@@ -0,0 +1,117 @@
+//! Loadable SQLite extension over the embedded oracle (Feature: sqlite-ext)
+//!
+//! Registers the scalar function `prebip34_height(hash)`, so analysts can
+//! join dumped chain data on heights in plain SQL:
+//!
+//! ```sql
+//! .load target/release/libheight_oracle
+//! SELECT txid, prebip34_height(block_hash) AS height FROM coinbases;
+//! ```
+//!
+//! The hash is either text in reverse hex, as shown by bitcoind and block
+//! explorers, or a 32-byte blob in network byte order, as stored in raw
+//! headers. NULL gives NULL; anything else that is not a block hash is an SQL
+//! error. Like [`crate::guess_height_prebip34block_unchecked`], the lookup is
+//! unchecked: hashes outside the dataset get the height of some other block.
+//!
+//! SQLite derives the entry point from the library file name, so the cdylib
+//! exports [`sqlite3_heightoracle_init`]. Renamed copies need the entry point
+//! passed explicitly, e.g. `SELECT load_extension('x.so', 'sqlite3_heightoracle_init')`.
+
+use crate::BlockHash;
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::types::ValueRef;
+use rusqlite::{ffi, Connection, Error, Result};
+use std::os::raw::{c_char, c_int};
+
+/// Name of the SQL function
+pub const FUNCTION_NAME: &str = "prebip34_height";
+
+/// SQLite entry point: register [`FUNCTION_NAME`] on `db`
+///
+/// # Safety
+///
+/// Only to be called by SQLite when loading the extension.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_heightoracle_init(
+    db: *mut ffi::sqlite3,
+    pz_err_msg: *mut *mut c_char,
+    p_api: *mut ffi::sqlite3_api_routines,
+) -> c_int {
+    Connection::extension_init2(db, pz_err_msg, p_api, register)
+}
+
+/// Load the embedded oracle and register the function on `db`
+///
+/// Returns false: the library is not loaded permanently.
+fn register(db: Connection) -> Result<bool> {
+    crate::embedded::init().map_err(|e| {
+        Error::UserFunctionError(format!("Failed to load embedded oracle: {e}").into())
+    })?;
+    db.create_scalar_function(
+        FUNCTION_NAME,
+        1,
+        FunctionFlags::SQLITE_UTF8
+            | FunctionFlags::SQLITE_DETERMINISTIC
+            | FunctionFlags::SQLITE_INNOCUOUS,
+        prebip34_height,
+    )?;
+    Ok(false)
+}
+
+/// `prebip34_height(hash)`
+fn prebip34_height(ctx: &Context<'_>) -> Result<Option<u32>> {
+    let block_hash = match parse_argument(ctx.get_raw(0)) {
+        Ok(Some(block_hash)) => block_hash,
+        Ok(None) => return Ok(None),
+        Err(reason) => {
+            return Err(Error::UserFunctionError(
+                format!("{FUNCTION_NAME}: {reason}").into(),
+            ))
+        }
+    };
+    Ok(Some(crate::guess_height_prebip34block_unchecked(
+        &block_hash,
+    )))
+}
+
+/// The block hash an argument holds, `None` for NULL
+fn parse_argument(value: ValueRef<'_>) -> Result<Option<BlockHash>, String> {
+    match value {
+        ValueRef::Null => Ok(None),
+        ValueRef::Text(hex) => {
+            let hex = std::str::from_utf8(hex).map_err(|e| e.to_string())?;
+            crate::parse_block_hash(hex).map(Some)
+        }
+        ValueRef::Blob(bytes) => BlockHash::try_from(bytes)
+            .map(Some)
+            .map_err(|_| format!("blob hashes must be 32 bytes, not {}", bytes.len())),
+        ValueRef::Integer(_) | ValueRef::Real(_) => {
+            Err("expected a hex string or a 32-byte blob".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_argument() {
+        let genesis = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let block_hash = crate::parse_block_hash(genesis).unwrap();
+        assert_eq!(
+            parse_argument(ValueRef::Text(genesis.as_bytes())),
+            Ok(Some(block_hash))
+        );
+        assert_eq!(
+            parse_argument(ValueRef::Blob(block_hash.as_bytes())),
+            Ok(Some(block_hash))
+        );
+        assert_eq!(parse_argument(ValueRef::Null), Ok(None));
+
+        assert!(parse_argument(ValueRef::Text(b"zz")).is_err());
+        assert!(parse_argument(ValueRef::Blob(&[0; 31])).is_err());
+        assert!(parse_argument(ValueRef::Integer(1)).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Per-oracle query counters (Feature: stats)
+//!
+//! With the `stats` feature every oracle counts the lookups made through it
+//! with relaxed atomics, readable through `query_stats()` and cleared with
+//! `reset_query_stats()`. Clones of an oracle share its counters, like they
+//! share its tables. Without the feature the counters are zero-sized, oracles
+//! hold them by value rather than behind an `Arc`, and the lookup paths do no
+//! extra work.
+
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of an oracle's query counters
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryStats {
+    /// Hashes looked up, one at a time or in batches
+    pub lookups: u64,
+    /// Batch lookups (`get_heights_unchecked` calls)
+    pub batches: u64,
+    /// Hashes looked up in batches, included in `lookups`
+    pub batched_lookups: u64,
+    /// Largest batch seen
+    pub max_batch_size: u64,
+    /// `get_height_opt` calls that returned `None`
+    ///
+    /// Not a count of hashes outside the dataset: lookups are unchecked, and
+    /// the perfect hash maps every hash to some entry. This only grows when
+    /// the perfect hash yields an index past the heights table, which means a
+    /// corrupt or mismatched asset, so anything but zero deserves a look.
+    pub rejections: u64,
+}
+
+#[cfg(feature = "stats")]
+impl QueryStats {
+    /// Average hashes per batch, 0 before the first batch
+    pub fn mean_batch_size(&self) -> f64 {
+        if self.batches == 0 {
+            return 0.0;
+        }
+        self.batched_lookups as f64 / self.batches as f64
+    }
+}
+
+/// Live counters behind [`QueryStats`]; zero-sized without the `stats` feature
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "stats"), derive(Clone, Copy))]
+pub(crate) struct QueryCounters {
+    #[cfg(feature = "stats")]
+    lookups: AtomicU64,
+    #[cfg(feature = "stats")]
+    batches: AtomicU64,
+    #[cfg(feature = "stats")]
+    batched_lookups: AtomicU64,
+    #[cfg(feature = "stats")]
+    max_batch_size: AtomicU64,
+    #[cfg(feature = "stats")]
+    rejections: AtomicU64,
+}
+
+impl QueryCounters {
+    /// Count one single lookup, and a rejection if it found no height
+    #[inline]
+    pub(crate) fn record_lookup(&self, found: bool) {
+        #[cfg(feature = "stats")]
+        {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            if !found {
+                self.rejections.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = found;
+    }
+
+    /// Count one batch of `size` lookups
+    #[inline]
+    pub(crate) fn record_batch(&self, size: usize) {
+        #[cfg(feature = "stats")]
+        {
+            let size = size as u64;
+            self.lookups.fetch_add(size, Ordering::Relaxed);
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            self.batched_lookups.fetch_add(size, Ordering::Relaxed);
+            self.max_batch_size.fetch_max(size, Ordering::Relaxed);
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = size;
+    }
+
+    /// Current counts
+    #[cfg(feature = "stats")]
+    pub(crate) fn snapshot(&self) -> QueryStats {
+        QueryStats {
+            lookups: self.lookups.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            batched_lookups: self.batched_lookups.load(Ordering::Relaxed),
+            max_batch_size: self.max_batch_size.load(Ordering::Relaxed),
+            rejections: self.rejections.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Set every count back to zero
+    #[cfg(feature = "stats")]
+    pub(crate) fn reset(&self) {
+        for counter in [
+            &self.lookups,
+            &self.batches,
+            &self.batched_lookups,
+            &self.max_batch_size,
+            &self.rejections,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let counters = QueryCounters::default();
+        counters.record_lookup(true);
+        counters.record_lookup(false);
+        counters.record_batch(64);
+        counters.record_batch(10);
+        let stats = counters.snapshot();
+        assert_eq!(
+            stats,
+            QueryStats {
+                lookups: 76,
+                batches: 2,
+                batched_lookups: 74,
+                max_batch_size: 64,
+                rejections: 1,
+            }
+        );
+        assert_eq!(stats.mean_batch_size(), 37.0);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), QueryStats::default());
+        assert_eq!(QueryStats::default().mean_batch_size(), 0.0);
+    }
+}
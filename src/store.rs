@@ -0,0 +1,304 @@
+//! Storage backends for the heights table
+//!
+//! Every oracle maps a block hash to a perfect-hash index and reads the height
+//! at that index from a [`HeightStore`]. The built and disk-loaded oracles use
+//! decoded `u32`s; [`HeightOracleEmbedded`](crate::HeightOracleEmbedded) picks
+//! its store at load time:
+//!
+//! - `Vec<u32>`: every height decoded up front (`from_bytes`), fastest lookups
+//! - [`PackedHeights`]: 18-bit packed groups decoded per lookup, either copied
+//!   (`from_bytes_lazy`) or borrowed from static data (`from_static`, `embedded-lazy`)
+//! - [`MmapHeights`]: packed groups read straight from a memory-mapped asset
+//!   file (Feature: mmap)
+//!
+//! Other backends implement the trait and load through
+//! [`HeightOracleEmbedded::from_store`](crate::HeightOracleEmbedded::from_store).
+//!
+//! All three oracle types look heights up through one generic path, so the
+//! built-in stores are read without dynamic dispatch; only `from_store`
+//! backends go through a trait object.
+
+use crate::packing::PackedHeights;
+use std::sync::Arc;
+
+/// Read access to a table of heights indexed by perfect-hash index
+pub trait HeightStore: Send + Sync {
+    /// Number of entries
+    fn len(&self) -> usize;
+
+    /// Height at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len()`.
+    fn at(&self, index: usize) -> u32;
+
+    /// Height at `index`, or `None` if it is out of range
+    fn get(&self, index: usize) -> Option<u32> {
+        (index < self.len()).then(|| self.at(index))
+    }
+
+    /// Check if there are no entries
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Heap bytes holding the heights, for memory accounting
+    ///
+    /// Zero for stores that read borrowed static or memory-mapped data. The
+    /// default reports zero too, so stores that allocate should override it.
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl HeightStore for [u32] {
+    fn len(&self) -> usize {
+        <[u32]>::len(self)
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        self[index]
+    }
+
+    fn get(&self, index: usize) -> Option<u32> {
+        <[u32]>::get(self, index).copied()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+impl HeightStore for Vec<u32> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        self[index]
+    }
+
+    fn get(&self, index: usize) -> Option<u32> {
+        self.as_slice().get(index).copied()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * std::mem::size_of::<u32>()
+    }
+}
+
+impl HeightStore for PackedHeights {
+    fn len(&self) -> usize {
+        PackedHeights::len(self)
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        PackedHeights::get(self, index)
+    }
+
+    fn get(&self, index: usize) -> Option<u32> {
+        self.try_get(index)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        PackedHeights::heap_bytes(self)
+    }
+}
+
+impl<S: HeightStore + ?Sized> HeightStore for Box<S> {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        (**self).at(index)
+    }
+
+    fn get(&self, index: usize) -> Option<u32> {
+        (**self).get(index)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        (**self).heap_bytes()
+    }
+}
+
+impl<S: HeightStore + ?Sized> HeightStore for Arc<S> {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        (**self).at(index)
+    }
+
+    fn get(&self, index: usize) -> Option<u32> {
+        (**self).get(index)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        (**self).heap_bytes()
+    }
+}
+
+/// Packed heights read from a memory-mapped heights asset (Feature: mmap)
+///
+/// Nothing is decoded or copied at load time: the OS pages the file in as
+/// lookups touch it, and processes mapping the same file share those pages.
+#[cfg(feature = "mmap")]
+pub struct MmapHeights {
+    map: memmap2::Mmap,
+    packed_start: usize,
+    len: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapHeights {
+    /// Map a heights asset file, with or without a format header
+    ///
+    /// The file must not be modified while it is mapped; the saving functions
+    /// replace assets by renaming a new file into place, which is safe.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: assets are replaced by rename, never rewritten in place
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let payload = crate::format::strip_header(&map, crate::format::AssetKind::Heights)?;
+        let header_len = map.len() - payload.len();
+        let (len, _) = crate::packing::split_serialized(payload)?;
+        Ok(Self {
+            map,
+            packed_start: header_len + crate::packing::SERIALIZED_PREFIX_LEN,
+            len,
+        })
+    }
+
+    /// The whole mapped asset, header included
+    pub fn asset_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl HeightStore for MmapHeights {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        assert!(index < self.len, "index {index} out of range {}", self.len);
+        crate::packing::unpack_height(&self.map[self.packed_start..], index)
+    }
+}
+
+/// The lookup path every oracle type shares: perfect hash, then heights store
+///
+/// Generic over the store, so each oracle's lookups compile to direct reads
+/// of its table, and the cache and query counters behave the same everywhere.
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+pub(crate) struct Lookup<'a, S: ?Sized> {
+    pub(crate) phash: &'a crate::phf::Phf,
+    pub(crate) heights: &'a S,
+    pub(crate) cache: Option<&'a crate::cache::LookupCache>,
+    pub(crate) query_counters: &'a crate::stats::QueryCounters,
+}
+
+#[cfg(any(
+    feature = "generate",
+    feature = "embedded",
+    feature = "embedded-testnet3",
+    feature = "runtime-assets"
+))]
+impl<S: HeightStore + ?Sized> Lookup<'_, S> {
+    /// Height at the perfect-hash index of `block_hash` (unchecked)
+    #[inline]
+    pub(crate) fn height(&self, block_hash: &crate::BlockHash) -> u32 {
+        let height = crate::cache::cached(self.cache, block_hash, || {
+            self.heights.at(self.phash.index(block_hash.as_bytes()))
+        });
+        self.query_counters.record_lookup(true);
+        height
+    }
+
+    /// Like [`Self::height`], but `None` if the index is past the store
+    #[inline]
+    pub(crate) fn height_opt(&self, block_hash: &crate::BlockHash) -> Option<u32> {
+        let height = crate::cache::cached_opt(self.cache, block_hash, || {
+            self.heights.get(self.phash.index(block_hash.as_bytes()))
+        });
+        self.query_counters.record_lookup(height.is_some());
+        height
+    }
+
+    /// Batched [`Self::height`], bypassing the cache
+    pub(crate) fn heights(&self, block_hashes: &[crate::BlockHash], heights: &mut [u32]) {
+        self.query_counters.record_batch(block_hashes.len());
+        crate::lookup_iter::get_heights_batched(self.phash, self.heights, block_hashes, heights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stores_agree() {
+        let heights: Vec<u32> = (0..11).map(|i| i * 7_919).collect();
+        let mut serialized = Vec::new();
+        crate::packing::serialize_heights(&heights, &mut serialized).unwrap();
+        let packed = PackedHeights::from_serialized(&serialized).unwrap();
+
+        let stores: [Box<dyn HeightStore>; 3] = [
+            Box::new(heights.clone()),
+            Box::new(packed),
+            Box::new(Arc::<[u32]>::from(heights.as_slice())),
+        ];
+        for store in &stores {
+            assert_eq!(store.len(), heights.len());
+            for (index, &height) in heights.iter().enumerate() {
+                assert_eq!(store.at(index), height);
+                assert_eq!(store.get(index), Some(height));
+            }
+            assert_eq!(store.get(heights.len()), None);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_mmap_heights() {
+        let heights: Vec<u32> = (0..9).map(|i| i * 1_000).collect();
+        let mut asset = Vec::new();
+        crate::format::write_header(
+            &mut asset,
+            crate::format::AssetKind::Heights,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        crate::packing::serialize_heights(&heights, &mut asset).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heights");
+        std::fs::write(&path, &asset).unwrap();
+
+        let store = MmapHeights::open(&path).unwrap();
+        assert_eq!(store.asset_bytes(), asset.as_slice());
+        assert_eq!(store.len(), 9);
+        assert_eq!((0..9).map(|i| store.at(i)).collect::<Vec<_>>(), heights);
+        assert_eq!(store.get(9), None);
+
+        std::fs::write(&path, &asset[..asset.len() - 1]).unwrap();
+        assert!(MmapHeights::open(&path).is_err());
+    }
+}
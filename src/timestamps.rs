@@ -0,0 +1,172 @@
+//! Block timestamp oracle
+//!
+//! Maps pre-BIP34 block hashes to their header timestamps. The timestamps are a
+//! [`SideTable`] indexed by the height oracle's perfect hash, so a
+//! [`TimestampOracle`] reuses the PtrHash asset and only adds
+//! [`crate::TIMESTAMPS_FILE_NAME`].
+//!
+//! Asset format: [num_entries: u32][base: u32][bits: u8][offsets: ceil(num_entries * bits / 8) bytes]
+//!
+//! Each entry is stored as its offset from `base` (the earliest timestamp) in
+//! `bits` bits, LSB first. Pre-BIP34 timestamps span about four years, so that
+//! is 27 bits per entry instead of 32.
+
+use crate::side_table::SideTable;
+use crate::BlockHash;
+use std::io::{self, Read, Write};
+#[cfg(feature = "embedded-timestamps")]
+use std::sync::OnceLock;
+
+// Embedded timestamps asset at compile time
+#[cfg(feature = "embedded-timestamps")]
+const TIMESTAMPS_DATA: &[u8] = include_bytes!(concat!(
+    env!("HEIGHT_ORACLE_ASSET_DIR"),
+    "/timestamps.bitpacked.dat"
+));
+
+/// Serialize timestamps as bit-packed offsets from the earliest one
+pub fn serialize_timestamps<W: Write>(timestamps: &[u32], mut writer: W) -> io::Result<()> {
+    let base = timestamps.iter().copied().min().unwrap_or(0);
+    let max_offset = timestamps.iter().map(|&t| t - base).max().unwrap_or(0);
+    let bits = u32::BITS - max_offset.leading_zeros();
+
+    writer.write_all(&(timestamps.len() as u32).to_le_bytes())?;
+    writer.write_all(&base.to_le_bytes())?;
+    writer.write_all(&[bits as u8])?;
+
+    let mut packed = vec![0u8; (timestamps.len() * bits as usize).div_ceil(8)];
+    for (i, &timestamp) in timestamps.iter().enumerate() {
+        let offset = u64::from(timestamp - base);
+        let bit = i * bits as usize;
+        for (j, byte) in packed[bit / 8..].iter_mut().take(5).enumerate() {
+            *byte |= ((offset << (bit % 8)) >> (8 * j)) as u8;
+        }
+    }
+    writer.write_all(&packed)
+}
+
+/// Deserialize timestamps written by [`serialize_timestamps`]
+pub fn deserialize_timestamps<R: Read>(mut reader: R) -> io::Result<Vec<u32>> {
+    let mut header = [0u8; 9];
+    reader.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let base = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let bits = header[8] as usize;
+    if bits > 32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Timestamp width of {bits} bits exceeds 32"),
+        ));
+    }
+
+    let mut packed = vec![0u8; (len * bits).div_ceil(8)];
+    reader.read_exact(&mut packed)?;
+
+    let mask = (1u64 << bits) - 1;
+    let timestamps = (0..len)
+        .map(|i| {
+            let bit = i * bits;
+            let mut window = [0u8; 8];
+            let bytes = &packed[bit / 8..packed.len().min(bit / 8 + 5)];
+            window[..bytes.len()].copy_from_slice(bytes);
+            let offset = (u64::from_le_bytes(window) >> (bit % 8)) & mask;
+            base.wrapping_add(offset as u32)
+        })
+        .collect();
+    Ok(timestamps)
+}
+
+/// Block hash → header timestamp lookups for pre-BIP34 blocks
+pub struct TimestampOracle {
+    table: SideTable<u32>,
+}
+
+impl TimestampOracle {
+    /// Load from in-memory asset bytes (the height oracle's PtrHash asset + timestamps asset)
+    pub fn from_bytes(ptrhash_data: &[u8], timestamps_data: &[u8]) -> io::Result<Self> {
+        let timestamps = deserialize_timestamps(io::Cursor::new(timestamps_data))?;
+        let table = SideTable::from_bytes(ptrhash_data, timestamps, "timestamps")?;
+        Ok(Self { table })
+    }
+
+    /// Load from asset files on disk
+    #[cfg(feature = "runtime-assets")]
+    pub fn from_files<P1: AsRef<std::path::Path>, P2: AsRef<std::path::Path>>(
+        ptrhash_path: P1,
+        timestamps_path: P2,
+    ) -> io::Result<Self> {
+        let ptrhash_data = std::fs::read(ptrhash_path)?;
+        let timestamps_data = std::fs::read(timestamps_path)?;
+        Self::from_bytes(&ptrhash_data, &timestamps_data)
+    }
+
+    /// Load from the embedded PtrHash and timestamps assets (Feature: embedded-timestamps)
+    #[cfg(feature = "embedded-timestamps")]
+    pub fn try_load_embedded() -> io::Result<Self> {
+        let phash = crate::embedded::embedded_phf()?;
+        let timestamps = deserialize_timestamps(io::Cursor::new(TIMESTAMPS_DATA))?;
+        let table = SideTable::new(phash, timestamps, "timestamps")?;
+        Ok(Self { table })
+    }
+
+    /// Look up the header timestamp (Unix seconds) for a block hash (unchecked)
+    ///
+    /// Like the height lookups, hashes outside the dataset return the timestamp
+    /// of some other block.
+    pub fn get_timestamp_unchecked(&self, block_hash: &BlockHash) -> u32 {
+        self.table.get_unchecked(block_hash)
+    }
+
+    /// Get the number of blocks in the oracle
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Check if the oracle is empty
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+/// Global singleton for the embedded timestamp oracle
+#[cfg(feature = "embedded-timestamps")]
+static EMBEDDED_TIMESTAMPS: OnceLock<TimestampOracle> = OnceLock::new();
+
+/// Global timestamp lookup for the embedded oracle (Feature: embedded-timestamps)
+#[cfg(feature = "embedded-timestamps")]
+pub fn guess_timestamp_prebip34block_unchecked(block_hash: &BlockHash) -> u32 {
+    EMBEDDED_TIMESTAMPS
+        .get_or_init(|| {
+            TimestampOracle::try_load_embedded().expect("Failed to load embedded timestamps")
+        })
+        .get_timestamp_unchecked(block_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_roundtrip() {
+        // Genesis, block 1 and the last pre-BIP34 block's era
+        let timestamps = vec![1_231_006_505, 1_231_469_665, 1_364_100_000, 1_231_006_505];
+        let mut data = Vec::new();
+        serialize_timestamps(&timestamps, &mut data).unwrap();
+
+        assert_eq!(data[8], 27);
+        assert_eq!(data.len(), 9 + (4 * 27usize).div_ceil(8));
+        assert_eq!(
+            deserialize_timestamps(io::Cursor::new(&data)).unwrap(),
+            timestamps
+        );
+
+        for timestamps in [vec![], vec![7], vec![0, u32::MAX, 1]] {
+            let mut data = Vec::new();
+            serialize_timestamps(&timestamps, &mut data).unwrap();
+            assert_eq!(
+                deserialize_timestamps(io::Cursor::new(&data)).unwrap(),
+                timestamps
+            );
+        }
+    }
+}
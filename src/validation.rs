@@ -0,0 +1,512 @@
+//! Oracle validation against a dataset (Feature: generate)
+//!
+//! Looks up every dataset entry and compares the returned height, plus a few
+//! spot checks on well-known blocks. Intended for CI pipelines validating
+//! freshly built assets. With the `fetch` feature,
+//! [`spot_check_against_rpc`] checks a random sample of heights against a
+//! live bitcoind instead, which needs no dataset on hand.
+
+use crate::dataset::Dataset;
+use crate::{BlockHash, HeightLookup};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Default cap on individually recorded mismatches
+const DEFAULT_MAX_RECORDED_MISMATCHES: usize = 100;
+
+/// A dataset entry the oracle got wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mismatch {
+    pub block_hash: BlockHash,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// Result of looking up a known block
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpotCheck {
+    pub name: String,
+    pub block_hash: BlockHash,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl SpotCheck {
+    /// Whether the oracle returned the expected height
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Overall quality rating of a validation run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Verdict {
+    /// Every entry validated
+    Perfect,
+    /// Less than 0.1% errors
+    Excellent,
+    /// Less than 1% errors
+    Good,
+    /// 1% errors or more
+    Poor,
+}
+
+/// Outcome of validating an oracle against a dataset
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+    /// Number of dataset entries looked up
+    pub total_entries: usize,
+    /// Entries for which the oracle returned the expected height
+    pub correct_lookups: usize,
+    /// Entries for which it did not
+    pub incorrect_heights: usize,
+    /// The first mismatches, up to the validator's recording cap
+    pub mismatches: Vec<Mismatch>,
+    /// Number of entries in the oracle
+    pub oracle_len: usize,
+    /// Time spent on the dataset lookups
+    pub duration: Duration,
+    /// Known-block spot checks
+    pub spot_checks: Vec<SpotCheck>,
+}
+
+impl ValidationReport {
+    /// Whether all entries and spot checks passed
+    pub fn is_perfect(&self) -> bool {
+        self.incorrect_heights == 0 && self.spot_checks.iter().all(SpotCheck::passed)
+    }
+
+    /// Dataset lookups per second
+    pub fn lookups_per_second(&self) -> f64 {
+        self.total_entries as f64 / self.duration.as_secs_f64()
+    }
+
+    /// Percentage of oracle entries confirmed correct by the dataset
+    pub fn coverage(&self) -> f64 {
+        (self.correct_lookups as f64 / self.oracle_len as f64) * 100.0
+    }
+
+    /// Rating based on the error rate
+    pub fn verdict(&self) -> Verdict {
+        if self.incorrect_heights == 0 {
+            Verdict::Perfect
+        } else if self.incorrect_heights < self.total_entries / 1000 {
+            Verdict::Excellent
+        } else if self.incorrect_heights < self.total_entries / 100 {
+            Verdict::Good
+        } else {
+            Verdict::Poor
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |n: usize| (n as f64 / self.total_entries as f64) * 100.0;
+        writeln!(f, "Validation Report:")?;
+        writeln!(f, "  Total entries:     {}", self.total_entries)?;
+        writeln!(
+            f,
+            "  Correct lookups:   {} ({:.2}%)",
+            self.correct_lookups,
+            percent(self.correct_lookups)
+        )?;
+        writeln!(
+            f,
+            "  Incorrect heights: {} ({:.2}%)",
+            self.incorrect_heights,
+            percent(self.incorrect_heights)
+        )?;
+        writeln!(f, "  Oracle size:       {} entries", self.oracle_len)?;
+        writeln!(f, "  Coverage:          {:.2}%", self.coverage())?;
+        writeln!(
+            f,
+            "  Validation time:   {:.3}s ({:.0} lookups/s)",
+            self.duration.as_secs_f64(),
+            self.lookups_per_second()
+        )?;
+        for check in &self.spot_checks {
+            writeln!(
+                f,
+                "  Spot check {}: expected {}, got {} ({})",
+                check.name,
+                check.expected,
+                check.actual,
+                if check.passed() { "ok" } else { "FAILED" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Configurable validator
+#[derive(Debug, Clone)]
+pub struct OracleValidator {
+    spot_checks: Vec<(String, BlockHash, u32)>,
+    max_recorded_mismatches: usize,
+}
+
+impl Default for OracleValidator {
+    /// Validator with spot checks for the genesis block, block 1 and block 100
+    fn default() -> Self {
+        let known = [
+            (
+                "Genesis Block",
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+                0,
+            ),
+            (
+                "Block 1",
+                "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+                1,
+            ),
+            (
+                "Block 100",
+                "000000007bc154e0fa7ea32218a72fe2c1bb9f86cf8c9ebf9a715ed27fdb229a",
+                100,
+            ),
+        ];
+        known
+            .iter()
+            .fold(Self::new(), |validator, &(name, hex, height)| {
+                let hash = crate::parse_block_hash(hex).expect("valid built-in hash");
+                validator.spot_check(name, hash, height)
+            })
+    }
+}
+
+impl OracleValidator {
+    /// Validator without any spot checks
+    pub fn new() -> Self {
+        Self {
+            spot_checks: Vec::new(),
+            max_recorded_mismatches: DEFAULT_MAX_RECORDED_MISMATCHES,
+        }
+    }
+
+    /// Add a known (hash, height) pair to check
+    pub fn spot_check(
+        mut self,
+        name: impl Into<String>,
+        block_hash: BlockHash,
+        height: u32,
+    ) -> Self {
+        self.spot_checks.push((name.into(), block_hash, height));
+        self
+    }
+
+    /// Cap on how many mismatches are recorded individually in the report
+    pub fn max_recorded_mismatches(mut self, max: usize) -> Self {
+        self.max_recorded_mismatches = max;
+        self
+    }
+
+    /// Look up every dataset entry and run the spot checks
+    pub fn validate<O: HeightLookup + ?Sized>(
+        &self,
+        oracle: &O,
+        dataset: &Dataset,
+    ) -> ValidationReport {
+        let mut correct_lookups = 0;
+        let mut incorrect_heights = 0;
+        let mut mismatches = Vec::new();
+
+        let start = Instant::now();
+        for (block_hash, expected) in dataset.iter() {
+            let actual = oracle.get_height_unchecked(block_hash);
+            if actual == expected {
+                correct_lookups += 1;
+            } else {
+                incorrect_heights += 1;
+                if mismatches.len() < self.max_recorded_mismatches {
+                    mismatches.push(Mismatch {
+                        block_hash: *block_hash,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        let duration = start.elapsed();
+
+        let spot_checks = self
+            .spot_checks
+            .iter()
+            .map(|(name, block_hash, expected)| SpotCheck {
+                name: name.clone(),
+                block_hash: *block_hash,
+                expected: *expected,
+                actual: oracle.get_height_unchecked(block_hash),
+            })
+            .collect();
+
+        ValidationReport {
+            total_entries: dataset.len(),
+            correct_lookups,
+            incorrect_heights,
+            mismatches,
+            oracle_len: oracle.len(),
+            duration,
+            spot_checks,
+        }
+    }
+}
+
+/// Validate `oracle` against `dataset` with the default spot checks
+pub fn validate<O: HeightLookup + ?Sized>(oracle: &O, dataset: &Dataset) -> ValidationReport {
+    OracleValidator::default().validate(oracle, dataset)
+}
+
+/// Blocks per RPC batch when spot checking against a node
+#[cfg(feature = "fetch")]
+const RPC_SPOT_CHECK_BATCH: usize = 500;
+
+/// Outcome of [`spot_check_against_rpc`]
+#[cfg(feature = "fetch")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RpcSpotCheckReport {
+    /// Number of heights sampled
+    pub sampled: usize,
+    /// Sampled blocks the oracle mapped back to their height
+    pub correct_lookups: usize,
+    /// Sampled blocks it did not; `expected` is the node's height
+    pub mismatches: Vec<Mismatch>,
+    /// Sampled version-2 blocks, which are not in the oracle and are not looked up
+    pub skipped_v2: usize,
+    /// Heights the node failed to return
+    pub fetch_failures: Vec<crate::fetch::FetchFailure>,
+    /// Time spent on the whole check, including RPC round trips
+    pub duration: Duration,
+}
+
+#[cfg(feature = "fetch")]
+impl RpcSpotCheckReport {
+    /// Whether every sampled block was fetched and looked up correctly
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty() && self.fetch_failures.is_empty()
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl fmt::Display for RpcSpotCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "RPC Spot Check Report:")?;
+        writeln!(f, "  Sampled heights:   {}", self.sampled)?;
+        writeln!(f, "  Correct lookups:   {}", self.correct_lookups)?;
+        writeln!(f, "  Incorrect heights: {}", self.mismatches.len())?;
+        writeln!(f, "  Skipped (v2):      {}", self.skipped_v2)?;
+        writeln!(f, "  Fetch failures:    {}", self.fetch_failures.len())?;
+        writeln!(
+            f,
+            "  Check time:        {:.3}s",
+            self.duration.as_secs_f64()
+        )?;
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "  Mismatch {}: node says {}, oracle says {}",
+                mismatch.block_hash, mismatch.expected, mismatch.actual
+            )?;
+        }
+        for failure in &self.fetch_failures {
+            writeln!(f, "  Fetch failed at {}: {}", failure.height, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check `sample_size` random pre-BIP34 heights against a live bitcoind
+///
+/// Each sampled height is resolved to its block hash by the node, and the
+/// oracle must map that hash back to the same height. Version-2 blocks are
+/// excluded from the oracle, so they are counted but not looked up.
+#[cfg(feature = "fetch")]
+pub async fn spot_check_against_rpc<O: HeightLookup + ?Sized>(
+    oracle: &O,
+    rpc_config: &crate::fetch::rpc::RpcConfig,
+    sample_size: usize,
+) -> anyhow::Result<RpcSpotCheckReport> {
+    use std::hash::{BuildHasher, RandomState};
+
+    let start = Instant::now();
+    let client = rpc_config.client();
+    let seed = RandomState::new().hash_one(std::process::id());
+    let heights = sample_heights(crate::BIP34_ACTIVATION_HEIGHT, sample_size, seed);
+
+    let mut report = RpcSpotCheckReport {
+        sampled: heights.len(),
+        correct_lookups: 0,
+        mismatches: Vec::new(),
+        skipped_v2: 0,
+        fetch_failures: Vec::new(),
+        duration: Duration::ZERO,
+    };
+    for chunk in heights.chunks(RPC_SPOT_CHECK_BATCH) {
+        let results = client.fetch_blocks_batch(chunk).await?;
+        for (&height, result) in chunk.iter().zip(results) {
+            match result {
+                Ok(block) => check_fetched_block(oracle, &block, &mut report)?,
+                Err(e) => report.fetch_failures.push(crate::fetch::FetchFailure {
+                    height,
+                    error: format!("{e:#}"),
+                }),
+            }
+        }
+    }
+    report.duration = start.elapsed();
+    Ok(report)
+}
+
+/// Look up one block returned by the node and record the outcome
+#[cfg(feature = "fetch")]
+fn check_fetched_block<O: HeightLookup + ?Sized>(
+    oracle: &O,
+    block: &crate::fetch::FetchedBlock,
+    report: &mut RpcSpotCheckReport,
+) -> anyhow::Result<()> {
+    if block.version == 2 {
+        report.skipped_v2 += 1;
+        return Ok(());
+    }
+    let block_hash = crate::parse_block_hash(&block.hash)
+        .map_err(|e| anyhow::anyhow!("Invalid hash for block {}: {e}", block.height))?;
+    let actual = oracle.get_height_unchecked(&block_hash);
+    if actual == block.height {
+        report.correct_lookups += 1;
+    } else {
+        report.mismatches.push(Mismatch {
+            block_hash,
+            expected: block.height,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Up to `count` distinct heights below `end`, in ascending order
+///
+/// Uses a splitmix64 sequence from `seed`; asking for more heights than the
+/// range holds returns the whole range.
+#[cfg(feature = "fetch")]
+fn sample_heights(end: u32, count: usize, seed: u64) -> Vec<u32> {
+    if count >= end as usize {
+        return (0..end).collect();
+    }
+    let mut state = seed;
+    let mut next = || crate::rng::splitmix64(&mut state);
+    let mut heights = std::collections::BTreeSet::new();
+    while heights.len() < count {
+        heights.insert((next() % end as u64) as u32);
+    }
+    heights.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lookup table standing in for an oracle
+    struct FakeOracle(Vec<(BlockHash, u32)>);
+
+    impl HeightLookup for FakeOracle {
+        fn get_height_unchecked(&self, block_hash: &BlockHash) -> u32 {
+            self.0
+                .iter()
+                .find(|(hash, _)| hash == block_hash)
+                .map_or(0, |(_, height)| *height)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    fn hash(byte: u8) -> BlockHash {
+        BlockHash::from([byte; 32])
+    }
+
+    #[test]
+    fn test_validate_reports_mismatches() {
+        let oracle = FakeOracle(vec![(hash(1), 10), (hash(2), 20), (hash(3), 99)]);
+        let dataset = Dataset {
+            block_hashes: vec![hash(1), hash(2), hash(3)],
+            heights: vec![10, 20, 30],
+            v2_flags: vec![false; 3],
+            placeholders: 0,
+        };
+
+        let report = OracleValidator::new()
+            .spot_check("first", hash(1), 10)
+            .validate(&oracle, &dataset);
+
+        assert_eq!(report.total_entries, 3);
+        assert_eq!(report.correct_lookups, 2);
+        assert_eq!(report.incorrect_heights, 1);
+        assert_eq!(
+            report.mismatches,
+            vec![Mismatch {
+                block_hash: hash(3),
+                expected: 30,
+                actual: 99
+            }]
+        );
+        assert!(report.spot_checks[0].passed());
+        assert!(!report.is_perfect());
+        assert_eq!(report.verdict(), Verdict::Poor);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn test_sample_heights() {
+        let heights = sample_heights(1_000, 50, 7);
+        assert_eq!(heights.len(), 50);
+        assert!(heights.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(heights.iter().all(|&height| height < 1_000));
+        assert_eq!(heights, sample_heights(1_000, 50, 7));
+
+        assert_eq!(sample_heights(5, 10, 7), vec![0, 1, 2, 3, 4]);
+        assert!(sample_heights(5, 0, 7).is_empty());
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn test_check_fetched_block() {
+        use crate::fetch::FetchedBlock;
+
+        let block = |height: u32, tag: u8, version: u8| {
+            let mut header = [0u8; crate::header::HEADER_SIZE];
+            header[0] = version;
+            FetchedBlock::from_header(height, hash(tag).to_string(), header)
+        };
+        let oracle = FakeOracle(vec![(hash(1), 10), (hash(2), 99)]);
+        let mut report = RpcSpotCheckReport {
+            sampled: 3,
+            correct_lookups: 0,
+            mismatches: Vec::new(),
+            skipped_v2: 0,
+            fetch_failures: Vec::new(),
+            duration: Duration::ZERO,
+        };
+
+        check_fetched_block(&oracle, &block(10, 1, 1), &mut report).unwrap();
+        check_fetched_block(&oracle, &block(20, 2, 1), &mut report).unwrap();
+        check_fetched_block(&oracle, &block(30, 3, 2), &mut report).unwrap();
+
+        assert_eq!(report.correct_lookups, 1);
+        assert_eq!(report.skipped_v2, 1);
+        assert_eq!(
+            report.mismatches,
+            vec![Mismatch {
+                block_hash: hash(2),
+                expected: 20,
+                actual: 99
+            }]
+        );
+        assert!(!report.passed());
+    }
+}
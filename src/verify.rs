@@ -0,0 +1,241 @@
+//! Cross-checking oracle heights against bitcoind (Feature: verify)
+//!
+//! Lookups are unchecked: a hash outside the oracle's domain (a version-2 or
+//! post-BIP34 block, a made-up hash) still gets a height, and it is wrong.
+//! [`RpcCrossChecker`] asks a node for the block at the looked-up height with
+//! `getblockhash` and reports whether it is the queried block.
+//!
+//! Node hashes are cached by height. The pre-BIP34 chain is buried far too
+//! deep to reorg, so cached answers stay valid; heights past the node's tip
+//! are never cached, since the node may catch up.
+//!
+//! This is what delphi's `--verify` and the gRPC server's `GetVerifiedHeight`
+//! use, so the RPC handling lives in one place.
+
+use crate::fetch::rpc::{RpcClient, RpcConfig, RpcError, RPC_INVALID_PARAMETER};
+use crate::BlockHash;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Default number of node hashes kept, about 2.5 MB
+pub const DEFAULT_CACHE_CAPACITY: usize = 65_536;
+
+/// Most `getblockhash` calls sent in one JSON-RPC batch
+const MAX_BATCH: usize = 1_000;
+
+/// What the node says about a looked-up height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Confirmation {
+    /// The node has the queried block at that height
+    Confirmed,
+    /// The node has another block at that height, so the queried hash is
+    /// outside the oracle's domain
+    Mismatch {
+        /// The node's block at that height
+        node_hash: BlockHash,
+    },
+    /// The height is past the node's tip, e.g. while it is still syncing
+    BeyondTip,
+}
+
+impl Confirmation {
+    /// Whether the node confirmed the height
+    pub fn is_confirmed(&self) -> bool {
+        *self == Confirmation::Confirmed
+    }
+
+    /// Compare the queried hash with the node's hash at the height, if it has one
+    fn classify(block_hash: &BlockHash, node_hash: Option<BlockHash>) -> Self {
+        match node_hash {
+            Some(node_hash) if node_hash == *block_hash => Confirmation::Confirmed,
+            Some(node_hash) => Confirmation::Mismatch { node_hash },
+            None => Confirmation::BeyondTip,
+        }
+    }
+}
+
+/// Confirms oracle heights with a bitcoind node over JSON-RPC
+///
+/// Safe to share between tasks; concurrent calls share the cache.
+pub struct RpcCrossChecker {
+    client: RpcClient,
+    cache: Mutex<HashMap<u32, BlockHash>>,
+    cache_capacity: usize,
+}
+
+impl RpcCrossChecker {
+    /// Check against the node `client` talks to
+    pub fn new(client: RpcClient) -> Self {
+        Self {
+            client,
+            cache: Mutex::default(),
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+        }
+    }
+
+    /// Check against the node at `config`
+    pub fn from_config(config: &RpcConfig) -> Self {
+        Self::new(config.client())
+    }
+
+    /// Keep at most `entries` node hashes (default [`DEFAULT_CACHE_CAPACITY`]); 0 disables the cache
+    ///
+    /// The cache is emptied when it fills up.
+    pub fn with_cache_capacity(mut self, entries: usize) -> Self {
+        self.cache_capacity = entries;
+        self
+    }
+
+    /// Ask the node whether `block_hash` is at `oracle_height`
+    ///
+    /// Errors are RPC or transport failures, never a verdict on the hash.
+    pub async fn confirm(
+        &self,
+        block_hash: &BlockHash,
+        oracle_height: u32,
+    ) -> Result<Confirmation> {
+        let mut confirmations = self.confirm_batch(&[(*block_hash, oracle_height)]).await?;
+        Ok(confirmations.remove(0))
+    }
+
+    /// [`Self::confirm`] for many `(block hash, oracle height)` pairs, in order
+    ///
+    /// Heights missing from the cache are fetched in JSON-RPC batches.
+    pub async fn confirm_batch(&self, lookups: &[(BlockHash, u32)]) -> Result<Vec<Confirmation>> {
+        let mut node_hashes: Vec<Option<BlockHash>> = lookups
+            .iter()
+            .map(|&(_, height)| self.cached(height))
+            .collect();
+        let mut missing: Vec<u32> = lookups
+            .iter()
+            .zip(&node_hashes)
+            .filter(|(_, node_hash)| node_hash.is_none())
+            .map(|(&(_, height), _)| height)
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        let mut fetched = HashMap::with_capacity(missing.len());
+        for heights in missing.chunks(MAX_BATCH) {
+            let params = heights
+                .iter()
+                .map(|&height| vec![serde_json::Value::from(height)])
+                .collect();
+            let results = self
+                .client
+                .call_batch::<String>("getblockhash", params)
+                .await?;
+            for (&height, result) in heights.iter().zip(results) {
+                if let Some(node_hash) = node_hash_from(result)? {
+                    self.remember(height, node_hash);
+                    fetched.insert(height, node_hash);
+                }
+            }
+        }
+
+        for (node_hash, &(_, height)) in node_hashes.iter_mut().zip(lookups) {
+            if node_hash.is_none() {
+                *node_hash = fetched.get(&height).copied();
+            }
+        }
+        Ok(lookups
+            .iter()
+            .zip(node_hashes)
+            .map(|((block_hash, _), node_hash)| Confirmation::classify(block_hash, node_hash))
+            .collect())
+    }
+
+    /// Number of node hashes currently cached
+    pub fn cached_len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    fn cached(&self, height: u32) -> Option<BlockHash> {
+        self.cache.lock().unwrap().get(&height).copied()
+    }
+
+    fn remember(&self, height: u32, node_hash: BlockHash) {
+        if self.cache_capacity == 0 {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.cache_capacity {
+            cache.clear();
+        }
+        cache.insert(height, node_hash);
+    }
+}
+
+/// The node's hash from one `getblockhash` result, `None` past its tip
+fn node_hash_from(result: Result<String>) -> Result<Option<BlockHash>> {
+    match result {
+        Ok(hex) => crate::parse_block_hash(&hex)
+            .map(Some)
+            .map_err(|e| anyhow!("Node returned invalid block hash {hex:?}: {e}")),
+        Err(err)
+            if err
+                .downcast_ref::<RpcError>()
+                .is_some_and(|err| err.code == RPC_INVALID_PARAMETER) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation() {
+        let block_hash = BlockHash::from([1; 32]);
+        let other = BlockHash::from([2; 32]);
+        assert!(Confirmation::classify(&block_hash, Some(block_hash)).is_confirmed());
+        assert_eq!(
+            Confirmation::classify(&block_hash, Some(other)),
+            Confirmation::Mismatch { node_hash: other }
+        );
+        assert_eq!(
+            Confirmation::classify(&block_hash, None),
+            Confirmation::BeyondTip
+        );
+
+        let genesis = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        assert_eq!(
+            node_hash_from(Ok(genesis.to_string())).unwrap(),
+            Some(crate::parse_block_hash(genesis).unwrap())
+        );
+        let beyond_tip = RpcError {
+            method: "getblockhash".to_string(),
+            code: RPC_INVALID_PARAMETER,
+            message: "Block height out of range".to_string(),
+        };
+        assert_eq!(node_hash_from(Err(beyond_tip.into())).unwrap(), None);
+        assert!(node_hash_from(Err(anyhow!("connection refused"))).is_err());
+        assert!(node_hash_from(Ok("zz".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_cache() {
+        let checker = RpcCrossChecker::new(RpcClient::new("http://127.0.0.1:1", "", ""))
+            .with_cache_capacity(2);
+        let node_hash = BlockHash::from([3; 32]);
+        checker.remember(1, node_hash);
+        checker.remember(2, node_hash);
+        assert_eq!(checker.cached(1), Some(node_hash));
+        assert_eq!(checker.cached_len(), 2);
+
+        // A full cache starts over
+        checker.remember(3, node_hash);
+        assert_eq!(checker.cached_len(), 1);
+        assert_eq!(checker.cached(1), None);
+
+        let uncached = RpcCrossChecker::new(RpcClient::new("http://127.0.0.1:1", "", ""))
+            .with_cache_capacity(0);
+        uncached.remember(1, node_hash);
+        assert_eq!(uncached.cached_len(), 0);
+    }
+}